@@ -5,12 +5,14 @@ pub const SC2_GENOME: &str = "SARS-CoV-2";
 // Refs for RSV heatmaps
 pub const RSV_GENOME: &str = "RSV";
 
+use crate::io::virus_plugins::{VirusPlugin, virus_plugin};
+
 // Function to obtain reference based on virus
 pub fn get_references_for_virus(virus: &str) -> Vec<String> {
     match virus.to_lowercase().as_str() {
         "flu" => FLU_SEGMENTS.iter().map(ToString::to_string).collect(),
         "sc2-wgs" | "sc2-spike" => vec![SC2_GENOME.to_string()],
         "rsv" => vec![RSV_GENOME.to_string()],
-        _ => vec![],
+        _ => virus_plugin(virus).map_or_else(Vec::new, VirusPlugin::segment_names),
     }
 }
@@ -1 +1,2 @@
 pub mod heatmap_ref;
+pub mod status_palette;
@@ -0,0 +1,61 @@
+// Shared QC status palette used by all HTML and Plotly outputs.
+// Defaults are drawn from the Okabe-Ito colorblind-safe palette so that
+// pass/fail and the steps of graded scales stay distinguishable for the common
+// forms of color blindness.
+
+pub const PASS_COLOR: &str = "#56B4E9"; // sky blue
+pub const WARN_COLOR: &str = "#E69F00"; // orange
+pub const FAIL_COLOR: &str = "#D55E00"; // vermillion
+pub const NO_ASSEMBLY_COLOR: &str = "#000000"; // black
+pub const NEUTRAL_COLOR: &str = "#999999"; // grey, for nodes/cells with no QC meaning
+
+// Categorical colors shared by the sankey nodes and the segment traces
+pub const CATEGORY_BLUE: &str = "#3366CC";
+pub const CATEGORY_CYAN: &str = "#0099C6";
+
+// Colorscale for the pass/fail heatmap, ordered from pass to no assembly
+pub const PASS_FAIL_COLORSCALE: [(f64, &str); 5] = [
+    (0.0, PASS_COLOR),
+    (0.25, "#F0E442"), // yellow
+    (0.5, WARN_COLOR),
+    (0.75, FAIL_COLOR),
+    (1.0, NO_ASSEMBLY_COLOR),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QcStatus {
+    Pass,
+    Fail,
+    NoAssembly,
+}
+
+impl QcStatus {
+    /// Classify a MIRA pass/fail reason string. Any failed criteria is a failure.
+    #[must_use]
+    pub fn from_reason(reason: &str) -> Self {
+        match reason.trim() {
+            "Pass" => QcStatus::Pass,
+            "No assembly" => QcStatus::NoAssembly,
+            _ => QcStatus::Fail,
+        }
+    }
+
+    #[must_use]
+    pub fn color(self) -> &'static str {
+        match self {
+            QcStatus::Pass => PASS_COLOR,
+            QcStatus::Fail => FAIL_COLOR,
+            QcStatus::NoAssembly => NO_ASSEMBLY_COLOR,
+        }
+    }
+}
+
+/// Wrap a pass/fail reason in a colored HTML badge for the static report tables.
+#[must_use]
+pub fn status_badge_html(reason: &str) -> String {
+    if reason.is_empty() {
+        return String::new();
+    }
+    let color = QcStatus::from_reason(reason).color();
+    format!("<span style=\"border-left: 6px solid {color}; padding-left: 6px;\">{reason}</span>")
+}
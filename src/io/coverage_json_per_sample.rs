@@ -1,4 +1,6 @@
+use crate::io::coverage_thumbnails::write_coverage_thumbnail;
 use crate::io::data_ingest::CoverageData;
+use crate::status;
 use plotly::{
     Plot, Scatter,
     common::{Fill, Line, Mode, Title},
@@ -10,6 +12,9 @@ use std::error::Error;
 pub struct SampleCoverageJson {
     pub sample_id: String,
     pub json: serde_json::Value,
+    /// File name of the coverage thumbnail PNG, relative to the output directory
+    #[serde(default)]
+    pub thumbnail: Option<String>,
 }
 
 #[allow(clippy::too_many_lines, clippy::double_must_use)]
@@ -265,7 +270,7 @@ pub fn create_coverage_plot(
         .into_iter()
         .collect();
 
-    println!(
+    status!(
         "Building coverage plots for {} samples as JSONs",
         samples.len()
     );
@@ -278,10 +283,12 @@ pub fn create_coverage_plot(
         let json_value = serde_json::to_value(&coverage_fig)?;
         let json_output = serde_json::to_string_pretty(&json_value)?;
         std::fs::write(&file_name, json_output)?;
-        println!("  -> saved {file_name}");
+        status!("  -> saved {file_name}");
+        let thumbnail = write_coverage_thumbnail(&sample, data, output_file)?;
         json_vec.push(SampleCoverageJson {
             sample_id: sample,
             json: json_value,
+            thumbnail,
         });
     }
 
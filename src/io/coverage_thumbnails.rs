@@ -0,0 +1,148 @@
+#![allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+use crate::io::data_ingest::CoverageData;
+use crate::processes::plotter::get_segment_color;
+use crate::status;
+use flate2::{Compression, Crc, write::ZlibEncoder};
+use std::{collections::BTreeMap, error::Error, io::Write};
+
+const THUMB_WIDTH: usize = 240;
+const THUMB_HEIGHT: usize = 48;
+/// Pixels left blank between segments
+const SEGMENT_GAP: usize = 2;
+const BACKGROUND: [u8; 3] = [255, 255, 255];
+const BASELINE: [u8; 3] = [200, 200, 200];
+
+fn hex_to_rgb(hex: &str) -> [u8; 3] {
+    let hex = hex.trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .unwrap_or(0)
+    };
+    [channel(0), channel(2), channel(4)]
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: [u8; 4], data: &[u8]) {
+    png.extend_from_slice(&u32::try_from(data.len()).unwrap_or(u32::MAX).to_be_bytes());
+    let mut crc = Crc::new();
+    crc.update(&kind);
+    crc.update(data);
+    png.extend_from_slice(&kind);
+    png.extend_from_slice(data);
+    png.extend_from_slice(&crc.sum().to_be_bytes());
+}
+
+/// Encodes 8-bit RGB pixels, row by row from the top, as a PNG
+fn encode_png(width: usize, height: usize, pixels: &[u8]) -> std::io::Result<Vec<u8>> {
+    // Every scanline starts with its filter type, 0 for none
+    let mut raw = Vec::with_capacity((width * 3 + 1) * height);
+    for row in pixels.chunks(width * 3) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&raw)?;
+    let image_data = encoder.finish()?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&u32::try_from(width).unwrap_or(u32::MAX).to_be_bytes());
+    header.extend_from_slice(&u32::try_from(height).unwrap_or(u32::MAX).to_be_bytes());
+    // 8-bit depth, truecolor, default compression/filter, no interlace
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png_chunk(&mut png, *b"IHDR", &header);
+    png_chunk(&mut png, *b"IDAT", &image_data);
+    png_chunk(&mut png, *b"IEND", &[]);
+    Ok(png)
+}
+
+/// Draws the whole genome coverage of one sample: segments side by side, in reference name
+/// order with widths proportional to their length, each filled in its segment color on a log
+/// depth scale. Uncovered stretches show as a grey baseline.
+fn draw_coverage_thumbnail(sample_data: &[&CoverageData]) -> Vec<u8> {
+    let mut segments: BTreeMap<&str, Vec<i32>> = BTreeMap::new();
+    for entry in sample_data {
+        let depths = segments.entry(entry.reference_name.as_str()).or_default();
+        let index = usize::try_from(entry.position.max(1) - 1).unwrap_or(0);
+        if depths.len() <= index {
+            depths.resize(index + 1, 0);
+        }
+        depths[index] = entry.coverage_depth;
+    }
+
+    let mut pixels = BACKGROUND.repeat(THUMB_WIDTH * THUMB_HEIGHT);
+    let total_len: usize = segments.values().map(Vec::len).sum();
+    if total_len == 0 {
+        return pixels;
+    }
+    let max_depth = segments
+        .values()
+        .flatten()
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let log_max = f64::from(max_depth).ln_1p();
+
+    let gaps = SEGMENT_GAP * (segments.len() - 1);
+    let drawable = THUMB_WIDTH.saturating_sub(gaps).max(segments.len());
+    let mut set_pixel = |x: usize, y: usize, color: [u8; 3]| {
+        let offset = ((THUMB_HEIGHT - 1 - y) * THUMB_WIDTH + x) * 3;
+        pixels[offset..offset + 3].copy_from_slice(&color);
+    };
+
+    let mut cumulative = 0;
+    for (i, (segment, depths)) in segments.iter().enumerate() {
+        let x_start = cumulative * drawable / total_len + SEGMENT_GAP * i;
+        cumulative += depths.len();
+        let x_end = (cumulative * drawable / total_len + SEGMENT_GAP * i)
+            .max(x_start + 1)
+            .min(THUMB_WIDTH);
+        let color = hex_to_rgb(get_segment_color(segment));
+        let columns = x_end.saturating_sub(x_start).max(1);
+
+        for x in x_start..x_end {
+            let from = (x - x_start) * depths.len() / columns;
+            let to = ((x - x_start + 1) * depths.len() / columns).max(from + 1);
+            let window = &depths[from..to.min(depths.len())];
+            let mean = window.iter().map(|&d| f64::from(d)).sum::<f64>() / window.len() as f64;
+
+            set_pixel(x, 0, BASELINE);
+            let height = (mean.ln_1p() / log_max * (THUMB_HEIGHT - 1) as f64).round() as usize;
+            for y in 0..height.min(THUMB_HEIGHT) {
+                set_pixel(x, y, color);
+            }
+        }
+    }
+    pixels
+}
+
+/// Writes a small PNG of the sample's coverage trace to `{output_prefix}mira_{sample}_coverage_thumb.png`,
+/// returning the file name so reports can reference it relative to the output directory.
+/// Returns None when the sample has no coverage data.
+pub fn write_coverage_thumbnail(
+    sample: &str,
+    data: &[CoverageData],
+    output_prefix: &str,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let sample_data: Vec<&CoverageData> = data
+        .iter()
+        .filter(|d| d.sample_id.as_deref() == Some(sample))
+        .collect();
+    if sample_data.is_empty() {
+        return Ok(None);
+    }
+
+    let pixels = draw_coverage_thumbnail(&sample_data);
+    let png = encode_png(THUMB_WIDTH, THUMB_HEIGHT, &pixels)?;
+    let file_name = format!("mira_{sample}_coverage_thumb.png");
+    let path = format!("{output_prefix}{file_name}");
+    std::fs::write(&path, png)?;
+    status!("  -> saved {path}");
+    Ok(Some(file_name))
+}
@@ -1,4 +1,6 @@
+use super::data_ingest::PlotFormat;
 use crate::constants::heatmap_ref::get_references_for_virus;
+use crate::status;
 use crate::utils::data_processing::TransformedData;
 use serde_json::json;
 
@@ -93,22 +95,28 @@ fn build_heatmap_json(
     y_values: &[String],
     z_values: &[u32],
     colorscale: &[(f64, &str)],
+    plot_format: &PlotFormat,
 ) -> serde_json::Value {
+    let default_hover = format!(
+        "%{{y}} = %{{z:{}}}x <extra>%{{x}}</extra>",
+        plot_format.number_format()
+    );
     json!({
         "type": "heatmap",
         "x": x_values,
         "y": y_values,
         "z": z_values,
         "colorscale": colorscale,
-        "hovertemplate": "%{y} = %{z:,.0f}x <extra>%{x}</extra>",
+        "hovertemplate": plot_format.hover_template("coverage_heatmap", &default_hover),
         "zmin": 0,
         "zmid": 100,
         "zmax": 1000
     })
 }
 
-fn build_layout_json(colorscale: &[(f64, &str)]) -> serde_json::Value {
+fn build_layout_json(colorscale: &[(f64, &str)], plot_format: &PlotFormat) -> serde_json::Value {
     json!({
+        "separators": plot_format.separators,
         "template": {
             "data": {
                 "heatmap": [{
@@ -138,15 +146,16 @@ pub fn coverage_to_heatmap_json(
     sample_list: &[String],
     virus: &str,
     output_file: &str,
+    plot_format: &PlotFormat,
 ) -> serde_json::Value {
-    println!("Building coverage heatmap as JSON");
+    status!("Building coverage heatmap as JSON");
     let filtered_data = normalize_rsv_segments(coverage_data, virus);
     let references = get_references_for_virus(virus);
     let completed_data = complete_data_for_samples(&filtered_data, sample_list, &references);
     let (x_values, y_values, z_values) = prepare_heatmap_axes(&completed_data);
     let colorscale = get_colorscale();
-    let heatmap = build_heatmap_json(&x_values, &y_values, &z_values, &colorscale);
-    let layout = build_layout_json(&colorscale);
+    let heatmap = build_heatmap_json(&x_values, &y_values, &z_values, &colorscale, plot_format);
+    let layout = build_layout_json(&colorscale, plot_format);
 
     let plot_json = json!({
         "data": [heatmap],
@@ -155,7 +164,7 @@ pub fn coverage_to_heatmap_json(
 
     let file_path = format!("{output_file}heatmap.json");
     std::fs::write(&file_path, plot_json.to_string()).expect("Failed to write heatmap JSON");
-    println!("  -> coverage heatmap json saved to {file_path}");
+    status!("  -> coverage heatmap json saved to {file_path}");
 
     // Return the JSON object
     plot_json
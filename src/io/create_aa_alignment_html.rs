@@ -0,0 +1,257 @@
+#![allow(clippy::format_push_string)]
+use super::data_ingest::DaisSeqData;
+use crate::status;
+use crate::utils::markup::escape_markup;
+use std::fs::write;
+use std::path::{Path, PathBuf};
+
+// Amino acid colors for substitutions, grouped by side chain chemistry
+fn aa_color(aa: char) -> &'static str {
+    match aa.to_ascii_uppercase() {
+        'A' | 'V' | 'L' | 'I' | 'M' | 'F' | 'W' => "#80A0F0", // hydrophobic
+        'S' | 'T' | 'N' | 'Q' => "#15C015",                   // polar
+        'K' | 'R' | 'H' => "#F01505",                         // positive
+        'D' | 'E' => "#C048C0",                               // negative
+        'C' => "#F08080",
+        'G' => "#F09048",
+        'P' => "#C0C000",
+        'Y' => "#15A4A4",
+        '*' => "#000000",
+        _ => "#BBBBBB", // gaps, X and anything else
+    }
+}
+
+// Residues come straight from the DAIS output, so they are escaped like any other text
+fn escape_residue(aa: char) -> String {
+    escape_markup(aa.encode_utf8(&mut [0; 4]))
+}
+
+// One row of the alignment for positions [start, end). Matches to the reference are shown as dots.
+// Passing no reference writes the sequence out as is (used for the reference row itself).
+fn alignment_row(
+    name: &str,
+    seq: &[char],
+    ref_seq: Option<&[char]>,
+    start: usize,
+    end: usize,
+) -> String {
+    let mut row = format!("<tr><th>{}</th>", escape_markup(name));
+    for pos in start..end {
+        let aa = seq.get(pos).copied().unwrap_or('-');
+        let Some(ref_seq) = ref_seq else {
+            row.push_str(&format!("<td>{}</td>", escape_residue(aa)));
+            continue;
+        };
+        let ref_aa = ref_seq.get(pos).copied().unwrap_or('-');
+        if aa == ref_aa {
+            row.push_str("<td class=\"match\">.</td>");
+        } else {
+            let (aa_html, ref_html) = (escape_residue(aa), escape_residue(ref_aa));
+            row.push_str(&format!(
+                "<td class=\"diff\" style=\"background:{}\" title=\"{ref_html}{}{aa_html}\">{aa_html}</td>",
+                aa_color(aa),
+                pos + 1
+            ));
+        }
+    }
+    row.push_str("</tr>\n");
+    row
+}
+
+// The position ruler above each page, labelling every tenth position
+fn position_header(start: usize, end: usize) -> String {
+    let mut header = String::from("<tr><th></th>");
+    for pos in start..end {
+        let label = if (pos + 1) % 10 == 0 {
+            (pos + 1).to_string()
+        } else {
+            String::new()
+        };
+        header.push_str(&format!("<td class=\"pos\">{label}</td>"));
+    }
+    header.push_str("</tr>\n");
+    header
+}
+
+// The pages for the samples aligned to one reference, `page_width` positions each
+fn reference_pages(
+    protein: &str,
+    ref_entry: &DaisSeqData,
+    sample_seqs: &[DaisSeqData],
+    page_width: usize,
+) -> Vec<String> {
+    let ref_aln: Vec<char> = ref_entry.aa_aln.chars().collect();
+
+    let mut samples: Vec<(&str, Vec<char>)> = sample_seqs
+        .iter()
+        .filter(|s| s.protein == protein && s.reference == ref_entry.reference)
+        .map(|s| (s.sample_id.as_str(), s.aa_aln.chars().collect()))
+        .collect();
+    samples.sort_by(|a, b| a.0.cmp(b.0));
+
+    let aln_len = samples
+        .iter()
+        .map(|(_, seq)| seq.len())
+        .chain(std::iter::once(ref_aln.len()))
+        .max()
+        .unwrap_or(0);
+
+    let mut pages = Vec::new();
+    for start in (0..aln_len).step_by(page_width) {
+        let end = (start + page_width).min(aln_len);
+        let mut table = format!(
+            "<h3>{} | {} | positions {}-{}</h3>\n<table>\n",
+            escape_markup(protein),
+            escape_markup(&ref_entry.reference),
+            start + 1,
+            end
+        );
+        table.push_str(&position_header(start, end));
+        table.push_str(&alignment_row(
+            &ref_entry.sample_id,
+            &ref_aln,
+            None,
+            start,
+            end,
+        ));
+        for (name, seq) in &samples {
+            table.push_str(&alignment_row(
+                name,
+                seq,
+                Some(ref_aln.as_slice()),
+                start,
+                end,
+            ));
+        }
+        table.push_str("</table>\n");
+        pages.push(table);
+    }
+    pages
+}
+
+/// Writes a paged, color-coded amino acid alignment of all samples for one protein, with
+/// differences highlighted relative to the DAIS reference each sample was aligned to.
+pub fn write_aa_alignment_html(
+    output_path: &Path,
+    runid: &str,
+    protein: &str,
+    sample_seqs: &[DaisSeqData],
+    ref_seqs: &[DaisSeqData],
+    page_width: usize,
+) -> std::io::Result<PathBuf> {
+    let page_width = page_width.max(1);
+
+    // Group samples by the reference they were aligned to so every block has one coordinate system
+    let mut references: Vec<&str> = sample_seqs
+        .iter()
+        .filter(|s| s.protein == protein)
+        .map(|s| s.reference.as_str())
+        .collect();
+    references.sort_unstable();
+    references.dedup();
+
+    let mut pages = Vec::new();
+    for reference in references {
+        let Some(ref_entry) = ref_seqs
+            .iter()
+            .find(|r| r.reference == reference && r.protein == protein)
+        else {
+            eprintln!("No DAIS reference found for {reference} {protein}, skipping");
+            continue;
+        };
+        pages.extend(reference_pages(protein, ref_entry, sample_seqs, page_width));
+    }
+
+    let out_path = output_path.join(format!("mira_{runid}_{protein}_aa_alignment.html"));
+    write(&out_path, alignment_page_html(runid, protein, &pages))?;
+
+    status!(
+        "  -> {protein} AA alignment HTML saved to {:?}",
+        out_path.display()
+    );
+
+    Ok(out_path)
+}
+
+// The full HTML document, showing one page at a time with previous/next buttons
+fn alignment_page_html(runid: &str, protein: &str, pages: &[String]) -> String {
+    let mut pages_html = String::new();
+    for (i, page) in pages.iter().enumerate() {
+        let display = if i == 0 { "block" } else { "none" };
+        pages_html.push_str(&format!(
+            "<div class=\"page\" style=\"display:{display}\">\n{page}</div>\n"
+        ));
+    }
+    let n_pages = pages.len();
+
+    format!(
+        r#"
+<html>
+<head>
+    <style>
+        body {{
+            font-family: Helvetica;
+            margin-bottom: 20px;
+            margin-left: 100px;
+            margin-right: 100px;
+        }}
+        table {{
+            border-collapse: collapse;
+            font-family: monospace;
+            font-size: 13px;
+        }}
+        th {{
+            text-align: right;
+            padding-right: 10px;
+            white-space: nowrap;
+        }}
+        td {{
+            width: 12px;
+            text-align: center;
+        }}
+        td.pos {{
+            font-size: 10px;
+            color: #666666;
+        }}
+        td.match {{
+            color: #BBBBBB;
+        }}
+        td.diff {{
+            color: white;
+            font-weight: bold;
+        }}
+        .pager {{
+            text-align: center;
+            margin: 20px 0;
+        }}
+    </style>
+    <title>{protein} Amino Acid Alignment</title>
+</head>
+<body>
+    <h2>{protein} Amino Acid Alignment | {runid}</h2>
+    <p>Positions matching the reference are shown as dots. Hover over a substitution to see it in reference coordinates.</p>
+    <div class="pager">
+        <button onclick="showPage(-1)">Previous</button>
+        <span id="page-label"></span>
+        <button onclick="showPage(1)">Next</button>
+    </div>
+    {pages_html}
+<script type="text/javascript">
+var current = 0;
+var pages = document.getElementsByClassName('page');
+function showPage(step) {{
+    if (pages.length === 0) {{ return; }}
+    pages[current].style.display = 'none';
+    current = Math.min(Math.max(current + step, 0), pages.length - 1);
+    pages[current].style.display = 'block';
+    document.getElementById('page-label').innerHTML = 'Page ' + (current + 1) + ' of {n_pages}';
+}}
+showPage(0);
+</script>
+</body>
+</html>
+"#,
+        protein = escape_markup(protein),
+        runid = escape_markup(runid),
+    )
+}
@@ -0,0 +1,114 @@
+use crate::constants::status_palette::{NEUTRAL_COLOR, QcStatus};
+use crate::status;
+use crate::utils::{data_processing::IRMASummary, markup::escape_markup};
+use std::{collections::BTreeMap, error::Error, fs, path::Path};
+
+/// Background of the label (left) half of every badge
+const LABEL_COLOR: &str = "#555555";
+
+// Rough width of a character of 11px Verdana, enough to size the badge halves
+const CHAR_WIDTH: usize = 7;
+const PADDING: usize = 10;
+
+fn text_width(text: &str) -> usize {
+    text.chars().count() * CHAR_WIDTH + PADDING
+}
+
+/// A flat two part badge: grey label on the left, colored value on the right
+fn badge_svg(label: &str, value: &str, color: &str) -> String {
+    let label_width = text_width(label);
+    let value_width = text_width(value);
+    let width = label_width + value_width;
+    let label_x = label_width / 2;
+    let value_x = label_width + value_width / 2;
+    let (label, value) = (escape_markup(label), escape_markup(value));
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {value}">
+<title>{label}: {value}</title>
+<rect width="{label_width}" height="20" fill="{LABEL_COLOR}"/>
+<rect x="{label_width}" width="{value_width}" height="20" fill="{color}"/>
+<g fill="#fff" text-anchor="middle" font-family="Verdana,DejaVu Sans,sans-serif" font-size="11">
+<text x="{label_x}" y="14">{label}</text>
+<text x="{value_x}" y="14">{value}</text>
+</g>
+</svg>
+"##
+    )
+}
+
+// The worst status over a sample's references. A sample is only "no assembly" if none of its
+// references assembled
+fn sample_status(rows: &[&IRMASummary]) -> QcStatus {
+    let statuses: Vec<QcStatus> = rows
+        .iter()
+        .map(|r| QcStatus::from_reason(r.pass_fail_reason.as_deref().unwrap_or("No assembly")))
+        .collect();
+    if statuses.iter().all(|s| *s == QcStatus::NoAssembly) {
+        QcStatus::NoAssembly
+    } else if statuses.contains(&QcStatus::Fail) {
+        QcStatus::Fail
+    } else {
+        QcStatus::Pass
+    }
+}
+
+// Median of the references' median coverages
+fn sample_median_coverage(rows: &[&IRMASummary]) -> Option<i32> {
+    let mut medians: Vec<i32> = rows
+        .iter()
+        .filter_map(|r| r.median_coverage.map(i32::from))
+        .collect();
+    if medians.is_empty() {
+        return None;
+    }
+    medians.sort_unstable();
+    Some(medians[medians.len() / 2])
+}
+
+/// Writes QC status, median coverage and subtype SVG badges for every sample to
+/// `{output_path}/badges/mira_{sample}_{qc,coverage,subtype}.svg`, for hot-linking from wikis
+/// and LIMS pages.
+pub fn write_sample_badges(
+    irma_summary: &[IRMASummary],
+    output_path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let badge_dir = output_path.join("badges");
+    fs::create_dir_all(&badge_dir)?;
+
+    let mut samples: BTreeMap<&str, Vec<&IRMASummary>> = BTreeMap::new();
+    for row in irma_summary {
+        samples.entry(row.sample_id.as_str()).or_default().push(row);
+    }
+
+    status!(
+        "Building summary badges for {} samples as SVGs",
+        samples.len()
+    );
+    for (sample, rows) in &samples {
+        let status = sample_status(rows);
+        let status_text = match status {
+            QcStatus::Pass => "PASS",
+            QcStatus::Fail => "FAIL",
+            QcStatus::NoAssembly => "NO ASSEMBLY",
+        };
+        let coverage = sample_median_coverage(rows).map_or("n/a".to_string(), |m| format!("{m}×"));
+        let subtype = rows
+            .iter()
+            .find_map(|r| r.subtype.as_deref().filter(|s| !s.is_empty()))
+            .unwrap_or("undetermined");
+
+        for (kind, svg) in [
+            ("qc", badge_svg("QC", status_text, status.color())),
+            (
+                "coverage",
+                badge_svg("Median cov", &coverage, NEUTRAL_COLOR),
+            ),
+            ("subtype", badge_svg("Subtype", subtype, NEUTRAL_COLOR)),
+        ] {
+            fs::write(badge_dir.join(format!("mira_{sample}_{kind}.svg")), svg)?;
+        }
+    }
+    status!("  -> badges saved to {}", badge_dir.display());
+
+    Ok(())
+}
@@ -1,4 +1,8 @@
+use super::data_ingest::PlotFormat;
 use crate::constants::heatmap_ref::get_references_for_virus;
+use crate::constants::status_palette::PASS_FAIL_COLORSCALE;
+use crate::io::virus_plugins::reference_segment;
+use crate::status;
 use crate::utils::data_processing::IRMASummary;
 use serde_json::json;
 use std::fs::File;
@@ -17,25 +21,6 @@ fn assign_number(reason: &str) -> i32 {
     }
 }
 
-fn normalize_reference(reference: &str, virus: &str) -> String {
-    match virus {
-        "flu" => {
-            let parts: Vec<&str> = reference.split('_').collect();
-            if parts.len() >= 2 {
-                parts[1].to_string()
-            } else {
-                reference.to_string()
-            }
-        }
-        "rsv" => reference
-            .replace("_AD", "")
-            .replace("_BD", "")
-            .replace("_A", "")
-            .replace("_B", ""),
-        _ => reference.to_string(),
-    }
-}
-
 fn remove_brace_content(s: &str) -> String {
     let mut result = String::new();
     let mut in_brace = false;
@@ -70,13 +55,13 @@ fn build_records(
             // Try to find a summary for this sample/reference
             if let Some(summary) = summaries.iter().find(|s| {
                 &s.sample_id == sample
-                    && normalize_reference(
+                    && reference_segment(
                         &s.reference.clone().unwrap_or_else(|| "Unknown".to_string()),
                         virus,
                     ) == *reference
             }) {
                 let sample = &summary.sample_id;
-                let reference = normalize_reference(
+                let reference = reference_segment(
                     &summary
                         .reference
                         .clone()
@@ -292,16 +277,11 @@ pub fn create_passfail_heatmap(
     sample_list: &[String],
     virus: &str,
     output_path: &str,
+    plot_format: &PlotFormat,
 ) -> serde_json::Value {
-    println!("Building pass_fail_heatmap as JSON");
+    status!("Building pass_fail_heatmap as JSON");
 
-    let colorscale = vec![
-        (0.0, "rgb(184, 212, 237)"),
-        (0.25, "rgb(252, 235, 201)"),
-        (0.5, "rgb(251, 126, 56)"),
-        (0.75, "rgb(204, 27, 34)"),
-        (1.0, "rgb(0,0,0)"),
-    ];
+    let colorscale = PASS_FAIL_COLORSCALE.to_vec();
 
     let references = get_references_for_virus(virus);
     let records = build_records(summaries, &references, sample_list, virus);
@@ -314,7 +294,8 @@ pub fn create_passfail_heatmap(
         "z": z,
         "customdata": customdata,
         "colorscale": colorscale,
-        "hovertemplate": "%{x} %{customdata} <extra>%{y}</extra>",
+        "hovertemplate": plot_format
+            .hover_template("pass_fail_heatmap", "%{x} %{customdata} <extra>%{y}</extra>"),
         "zmin": -4,
         "zmid": 1,
         "zmax": 6,
@@ -323,6 +304,7 @@ pub fn create_passfail_heatmap(
 
     let layout = json!({
         "template": plotly_template(&colorscale),
+        "separators": plot_format.separators,
         "xaxis": {"side": "top"},
         "paper_bgcolor": "white",
         "plot_bgcolor": "white"
@@ -338,7 +320,7 @@ pub fn create_passfail_heatmap(
     file.write_all(plot_json.to_string().as_bytes())
         .expect("Unable to write data");
 
-    println!("  -> pass_fail heatmap json saved to {file_path}");
+    status!("  -> pass_fail heatmap json saved to {file_path}");
 
     // Return the JSON object
     plot_json
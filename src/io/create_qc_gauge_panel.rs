@@ -0,0 +1,126 @@
+use super::data_ingest::{PlotFormat, QCSettings};
+use crate::constants::status_palette::{FAIL_COLOR, PASS_COLOR};
+use crate::status;
+use crate::utils::data_processing::IRMASummary;
+use serde_json::json;
+use std::fs::File;
+use std::io::Write;
+
+const ROW_HEIGHT: usize = 60;
+
+// Bullet chart for a single metric, with the bar colored by whether it meets the QC threshold
+fn bullet_indicator(
+    title: &str,
+    value: f64,
+    threshold: f64,
+    axis_max: f64,
+    x_domain: [f64; 2],
+    y_domain: [f64; 2],
+    plot_format: &PlotFormat,
+) -> serde_json::Value {
+    let bar_color = if value >= threshold {
+        PASS_COLOR
+    } else {
+        FAIL_COLOR
+    };
+
+    json!({
+        "type": "indicator",
+        "mode": "number+gauge",
+        "value": value,
+        "domain": {"x": x_domain, "y": y_domain},
+        "title": {"text": title, "font": {"size": 12}},
+        "number": {"font": {"size": 14}, "valueformat": plot_format.number_format()},
+        "gauge": {
+            "shape": "bullet",
+            "axis": {"range": [0, axis_max]},
+            "bar": {"color": bar_color, "thickness": 0.6},
+            "threshold": {
+                "line": {"color": "black", "width": 2},
+                "thickness": 0.9,
+                "value": threshold
+            }
+        }
+    })
+}
+
+/// Creates a panel of bullet charts showing % reference covered and median coverage for every
+/// sample/reference in the IRMA summary, colored against the QC thresholds in use.
+/// Writes it to a file and returns the JSON object.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn create_qc_gauge_panel(
+    summaries: &[IRMASummary],
+    qc_values: &QCSettings,
+    output_path: &str,
+    plot_format: &PlotFormat,
+) -> serde_json::Value {
+    status!("Building qc_gauge_panel as JSON");
+
+    // Samples without an assembly have nothing to plot
+    let rows: Vec<&IRMASummary> = summaries
+        .iter()
+        .filter(|s| s.percent_reference_coverage.is_some() || s.median_coverage.is_some())
+        .collect();
+
+    let cov_threshold = f64::from(qc_values.med_cov);
+    let max_median = rows
+        .iter()
+        .filter_map(|s| s.median_coverage)
+        .max()
+        .map_or(cov_threshold, f64::from)
+        .max(cov_threshold * 2.0);
+
+    let n = rows.len().max(1) as f64;
+    let mut traces = Vec::new();
+    for (i, summary) in rows.iter().enumerate() {
+        let y_top = 1.0 - (i as f64) / n;
+        let y_domain = [y_top - 0.8 / n, y_top];
+        let label = format!(
+            "{}<br>{}",
+            summary.sample_id,
+            summary.reference.as_deref().unwrap_or("")
+        );
+
+        traces.push(bullet_indicator(
+            &format!("{label}<br>% Ref Covered"),
+            summary.percent_reference_coverage.map_or(0.0, f64::from),
+            f64::from(qc_values.perc_ref_covered),
+            100.0,
+            [0.12, 0.45],
+            y_domain,
+            plot_format,
+        ));
+        traces.push(bullet_indicator(
+            &format!("{label}<br>Median Coverage"),
+            summary.median_coverage.map_or(0.0, f64::from),
+            cov_threshold,
+            max_median,
+            [0.62, 0.95],
+            y_domain,
+            plot_format,
+        ));
+    }
+
+    let layout = json!({
+        "height": ROW_HEIGHT * rows.len().max(1) + 100,
+        "margin": {"l": 180, "r": 40, "t": 40, "b": 40},
+        "separators": plot_format.separators,
+        "paper_bgcolor": "white",
+        "plot_bgcolor": "white"
+    });
+
+    let plot_json = json!({
+        "data": traces,
+        "layout": layout
+    });
+
+    let file_path = format!("{output_path}qc_gauge_panel.json");
+    let mut file = File::create(&file_path).expect("Unable to create file");
+    file.write_all(plot_json.to_string().as_bytes())
+        .expect("Unable to write data");
+
+    status!("  -> qc gauge panel json saved to {file_path}");
+
+    plot_json
+}
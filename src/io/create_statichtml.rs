@@ -2,8 +2,13 @@
 use super::coverage_json_per_sample::SampleCoverageJson;
 use super::data_ingest::{IndelsData, MinorVariantsData};
 use super::reads_to_sankey_json::SampleSankeyJson;
+use super::timeline_json_per_sample::SampleTimelineJson;
+use super::watchlist_alerts::WatchlistAlert;
+use crate::constants::status_palette::{FAIL_COLOR, NEUTRAL_COLOR, PASS_COLOR, status_badge_html};
 use crate::processes::summary_report_update::UpdatedIRMASummary;
-use crate::utils::data_processing::{DaisVarsData, IRMASummary};
+use crate::status;
+use crate::utils::data_processing::{DaisVarsData, IRMASummary, QCCriterion};
+use crate::utils::markup::escape_markup;
 use glob::glob;
 use serde_json::json;
 use std::fs::{self, read, write};
@@ -72,7 +77,18 @@ fn write_sample_plot_html(
     sample: &str,
     coverage_json: &serde_json::Value,
     sankey_json: &serde_json::Value,
+    timeline_json: Option<&serde_json::Value>,
 ) -> std::io::Result<()> {
+    let timeline_html = timeline_json.map_or(String::new(), |timeline_json| {
+        format!(
+            r#"
+    <h2>Pipeline Timeline - {sample}</h2>
+    <div id="timeline_plot" class="plot-container"></div>
+    <script>
+        Plotly.newPlot('timeline_plot', {timeline_json}.data, {timeline_json}.layout);
+    </script>"#
+        )
+    });
     let html = format!(
         r#"
 <html>
@@ -84,6 +100,13 @@ fn write_sample_plot_html(
             margin-left: 100px;
             margin-right: 100px;
         }}
+        .lookup {{
+            text-align: center;
+            margin: 10px 0;
+        }}
+        .lookup input {{
+            width: 80px;
+        }}
     </style>
     <title>Coverage & Sankey</title>
     <script src="https://cdn.plot.ly/plotly-latest.min.js"></script>
@@ -93,18 +116,66 @@ fn write_sample_plot_html(
     <div id="sankey_plot" class="plot-container"></div>
 
     <h2>Coverage Plot - {sample}</h2>
+    <div class="lookup">
+        <label>Position <input id="lookup_pos" type="number" min="1"></label>
+        <label>in <select id="lookup_protein"><option value="">nucleotide coordinates</option></select></label>
+        <label>Window (nt) <input id="lookup_window" type="number" value="50" min="1"></label>
+        <button onclick="lookupPosition()">Go</button>
+        <button onclick="resetZoom()">Reset</button>
+    </div>
     <div id="coverage_plot" class="plot-container"></div>
+    {timeline_html}
 
     <script>
         Plotly.newPlot('sankey_plot', {sankey_json}.data, {sankey_json}.layout);
         Plotly.newPlot('coverage_plot', {coverage_json}.data, {coverage_json}.layout);
+
+        // ORF boxes are the filled traces; their first two x values are the ORF start and end
+        var coverageDiv = document.getElementById('coverage_plot');
+        var orfs = {{}};
+        coverageDiv.data.forEach(function(trace) {{
+            if (trace.fill === 'toself' && trace.name) {{
+                orfs[trace.name] = trace.x[0];
+                var opt = document.createElement('option');
+                opt.value = trace.name;
+                opt.innerHTML = trace.name + ' amino acid coordinates';
+                document.getElementById('lookup_protein').appendChild(opt);
+            }}
+        }});
+
+        function xAxes() {{
+            var axes = Object.keys(coverageDiv.layout).filter(function(k) {{ return /^xaxis\d*$/.test(k); }});
+            return axes.length > 0 ? axes : ['xaxis'];
+        }}
+
+        // Zoom every x axis to the requested position (amino acid positions map to the first base of the codon)
+        function lookupPosition() {{
+            var pos = parseInt(document.getElementById('lookup_pos').value, 10);
+            var win = parseInt(document.getElementById('lookup_window').value, 10) || 50;
+            var protein = document.getElementById('lookup_protein').value;
+            if (isNaN(pos) || pos < 1) {{ return; }}
+            var nt = protein ? orfs[protein] + (pos - 1) * 3 : pos;
+            var update = {{}};
+            xAxes().forEach(function(k) {{ update[k + '.range'] = [nt - win, nt + win]; }});
+            Plotly.relayout(coverageDiv, update);
+        }}
+
+        function resetZoom() {{
+            var update = {{}};
+            xAxes().forEach(function(k) {{ update[k + '.autorange'] = true; }});
+            Plotly.relayout(coverageDiv, update);
+        }}
+
+        document.getElementById('lookup_pos').addEventListener('keydown', function(e) {{
+            if (e.key === 'Enter') {{ lookupPosition(); }}
+        }});
     </script>
 </body>
 </html>
 "#
     );
     let out_path = output_path.join(format!("mira_{sample}_coverage.html"));
-    println!(
+    status!(
         "  -> coverge and sankey HTML saved to {:?}",
         out_path.display()
     );
@@ -212,7 +283,7 @@ pub fn plotly_table_script(div_id: &str, table_json: &str, table_title: &str) ->
 #[allow(clippy::must_use_candidate)]
 pub fn irma_summary_to_plotly_json(summary: &[IRMASummary], virus: &str) -> String {
     // Determine headers dynamically based on virus type
-    let headers: Vec<&str> = match virus {
+    let mut headers: Vec<&str> = match virus {
         "sc2-wgs" => vec![
             "Sample",
             "Total Reads",
@@ -263,6 +334,12 @@ pub fn irma_summary_to_plotly_json(summary: &[IRMASummary], virus: &str) -> Stri
         ],
     };
 
+    // Passthrough columns from --metadata
+    let metadata_start = headers.len();
+    if let Some(row) = summary.first() {
+        headers.extend(row.metadata.columns());
+    }
+
     let mut columns: Vec<Vec<String>> = vec![Vec::new(); headers.len()];
 
     for row in summary {
@@ -308,7 +385,9 @@ pub fn irma_summary_to_plotly_json(summary: &[IRMASummary], virus: &str) -> Stri
         }
 
         // Common columns after spike
-        columns[col_index].push(row.pass_fail_reason.as_deref().unwrap_or("").to_string());
+        columns[col_index].push(status_badge_html(
+            row.pass_fail_reason.as_deref().unwrap_or(""),
+        ));
         col_index += 1;
 
         columns[col_index].push(row.subtype.as_deref().unwrap_or("").to_string());
@@ -321,6 +400,10 @@ pub fn irma_summary_to_plotly_json(summary: &[IRMASummary], virus: &str) -> Stri
         col_index += 1;
 
         columns[col_index].push(row.instrument.as_deref().unwrap_or("").to_string());
+
+        for (column, (_, value)) in columns[metadata_start..].iter_mut().zip(&row.metadata.0) {
+            column.push(value.clone());
+        }
     }
 
     json!({
@@ -355,6 +438,78 @@ fn dais_vars_to_plotly_json(vars: &[DaisVarsData]) -> String {
     .to_string()
 }
 
+/// Observed value against the threshold, e.g. `43 / ≥50 (-7)`, colored by the criterion's
+/// status. The difference is only shown when both sides are numbers.
+fn qc_criterion_cell(criterion: &QCCriterion) -> String {
+    let threshold = criterion
+        .threshold
+        .replacen(">= ", "≥", 1)
+        .replacen("<= ", "≤", 1);
+    let observed = criterion.observed_value.as_deref().unwrap_or("NA");
+    let threshold_number = criterion
+        .threshold
+        .trim_start_matches(['>', '<', '='])
+        .trim()
+        .parse::<f64>();
+    let delta = match (observed.parse::<f64>(), threshold_number) {
+        (Ok(observed), Ok(threshold)) => {
+            let delta = observed - threshold;
+            let delta = if delta.fract() == 0.0 {
+                format!("{delta:+}")
+            } else {
+                format!("{delta:+.2}")
+            };
+            format!(" ({delta})")
+        }
+        _ => String::new(),
+    };
+    let color = match criterion.status.as_str() {
+        "pass" => PASS_COLOR,
+        "fail" => FAIL_COLOR,
+        _ => NEUTRAL_COLOR,
+    };
+    format!(r#"<span style="color:{color}">{observed} / {threshold}{delta}</span>"#)
+}
+
+/// One row per sample and reference, one column per QC criterion
+fn qc_criteria_to_plotly_json(criteria: &[QCCriterion]) -> String {
+    let mut criterion_names: Vec<&str> = Vec::new();
+    let mut rows: Vec<(&str, &str)> = Vec::new();
+    for criterion in criteria {
+        if !criterion_names.contains(&criterion.criterion.as_str()) {
+            criterion_names.push(&criterion.criterion);
+        }
+        let row = (criterion.sample_id.as_str(), criterion.reference.as_str());
+        if !rows.contains(&row) {
+            rows.push(row);
+        }
+    }
+
+    let mut headers = vec!["Sample".to_string(), "Reference".to_string()];
+    headers.extend(criterion_names.iter().map(|name| name.replace('_', " ")));
+    let mut columns: Vec<Vec<String>> = vec![Vec::new(); headers.len()];
+
+    for (sample, reference) in &rows {
+        columns[0].push((*sample).to_string());
+        columns[1].push((*reference).to_string());
+        for (col, name) in criterion_names.iter().enumerate() {
+            let cell = criteria
+                .iter()
+                .find(|c| {
+                    c.sample_id == *sample && c.reference == *reference && c.criterion == *name
+                })
+                .map_or(String::new(), qc_criterion_cell);
+            columns[col + 2].push(cell);
+        }
+    }
+
+    serde_json::json!({
+        "header": headers,
+        "columns": columns
+    })
+    .to_string()
+}
+
 fn alleles_to_plotly_json(data: &[MinorVariantsData], virus: &str) -> String {
     let headers = if virus == "sc2-spike" {
         [
@@ -505,7 +660,7 @@ fn indels_to_plotly_json(data: &[IndelsData], virus: &str) -> String {
 
 // helper funciton for creating html for each plotly fig
 // `div_id` is used as both the DOM id and, by default, part of the file name.
-fn write_plot_html(
+pub(crate) fn write_plot_html(
     output_path: &Path,
     file_stem: &str,
     div_id: &str,
@@ -552,7 +707,7 @@ fn write_plot_html(
 
     let out_path = output_path.join(format!("{file_stem}.html"));
     write(&out_path, html)?;
-    println!("  -> {title} HTML saved to {:?}", out_path.display());
+    status!("  -> {title} HTML saved to {:?}", out_path.display());
     Ok(out_path)
 }
 
@@ -564,7 +719,8 @@ fn write_summary_plots_html(
     barcode_distribution_json: &serde_json::Value,
     pass_fail_heatmap_json: &serde_json::Value,
     cov_heatmap_json: &serde_json::Value,
-) -> std::io::Result<(PathBuf, PathBuf, PathBuf)> {
+    qc_gauge_panel_json: &serde_json::Value,
+) -> std::io::Result<(PathBuf, PathBuf, PathBuf, PathBuf)> {
     let bdp_path = write_plot_html(
         output_path,
         &format!("mira_{runid}_barcode_distribution"),
@@ -589,12 +745,21 @@ fn write_summary_plots_html(
         cov_heatmap_json,
     )?;
 
-    Ok((bdp_path, pfhm_path, chm_path))
+    let qcgp_path = write_plot_html(
+        output_path,
+        &format!("mira_{runid}_qc_gauge_panel"),
+        "qc_gauge_panel_plot",
+        "Reference Coverage QC",
+        qc_gauge_panel_json,
+    )?;
+
+    Ok((bdp_path, pfhm_path, chm_path, qcgp_path))
 }
 
 #[allow(clippy::too_many_arguments)]
 pub fn generate_html_report(
     output_path: &Path,
+    alerts: &[WatchlistAlert],
     irma_summary: &[IRMASummary],
     dais_vars_data: &[DaisVarsData],
     minor_variants: &[MinorVariantsData],
@@ -602,13 +767,16 @@ pub fn generate_html_report(
     barcode_distribution_json: &serde_json::Value,
     pass_fail_heatmap_json: &serde_json::Value,
     cov_heatmap_json: &serde_json::Value,
+    qc_gauge_panel_json: &serde_json::Value,
     coverage_json_per_sample: &[SampleCoverageJson],
     sankey_json_per_sample: &[SampleSankeyJson],
+    timeline_json_per_sample: &[SampleTimelineJson],
+    qc_criteria: Option<&[QCCriterion]>,
     runid: &str,
     logo_path: Option<&Path>,
     virus: &str,
 ) -> std::io::Result<()> {
-    println!("Building static HTML file");
+    status!("Building static HTML file");
 
     // Set up asset paths
     let (mira_logo, favicon, excel_logo) = if let Some(logo_path) = logo_path {
@@ -635,12 +803,16 @@ pub fn generate_html_report(
     let cov_heatmap_json_str = cov_heatmap_json.to_string();
     let chm_html = plotly_json_script("cov_heatmap_plot", &cov_heatmap_json_str);
 
+    let qc_gauge_panel_json_str = qc_gauge_panel_json.to_string();
+    let qcgp_html = plotly_json_script("qc_gauge_panel_plot", &qc_gauge_panel_json_str);
+
     write_summary_plots_html(
         output_path,
         runid,
         barcode_distribution_json,
         pass_fail_heatmap_json,
         cov_heatmap_json,
+        qc_gauge_panel_json,
     )?;
 
     // Pull in data tables for htmls
@@ -661,6 +833,50 @@ pub fn generate_html_report(
     let indels_json = indels_to_plotly_json(indels, virus);
     let indels_table_html = plotly_table_script("indels_table", &indels_json, "Minor Indels Table");
 
+    let qc_values_html = qc_criteria.map_or(String::new(), |criteria| {
+        let qc_values_json = qc_criteria_to_plotly_json(criteria);
+        format!(
+            r#"{}
+        <p class="info-paragraph">
+            Each segment's observed value against the QC threshold in use, with the difference in 
+            brackets. Failing values are colored as failures.
+        </p>"#,
+            plotly_table_script(
+                "qc_values_table",
+                &qc_values_json,
+                "QC Values vs Thresholds"
+            )
+        )
+    });
+
+    // Watchlist alerts, listed before everything else when there are any
+    let alerts_html = if alerts.is_empty() {
+        String::new()
+    } else {
+        let mut rows = String::new();
+        for alert in alerts {
+            rows.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape_markup(&alert.sample),
+                escape_markup(&alert.subtype),
+                escape_markup(&alert.protein),
+                escape_markup(&alert.aa_mutation),
+                escape_markup(&alert.description),
+            ));
+        }
+        format!(
+            r#"<div class="alerts">
+            <h2>Watchlist Alerts ({count})</h2>
+            <table>
+                <tr><th>Sample</th><th>Subtype</th><th>Protein</th><th>Mutation</th><th>Description</th></tr>
+                {rows}
+            </table>
+        </div>
+        <hr>"#,
+            count = alerts.len()
+        )
+    };
+
     // Coverage links
 
     let mut coverage_links_html = String::from(
@@ -677,11 +893,27 @@ pub fn generate_html_report(
             .find(|s| s.sample_id == *sample)
         {
             // Write the per-sample HTML file
-            write_sample_plot_html(output_path, sample, &coverage_json.json, &sankey_json.json)?;
-
-            // Add the link to the main HTML (relative path)
+            let timeline_json = timeline_json_per_sample
+                .iter()
+                .find(|t| t.sample_id == *sample)
+                .map(|t| &t.json);
+            write_sample_plot_html(
+                output_path,
+                sample,
+                &coverage_json.json,
+                &sankey_json.json,
+                timeline_json,
+            )?;
+
+            // Add the link to the main HTML (relative path), led by the coverage thumbnail
+            let thumbnail = coverage_json
+                .thumbnail
+                .as_deref()
+                .map_or(String::new(), |t| {
+                    format!(r#"<img src="{t}" alt="{sample} coverage" width="120" height="24"> "#)
+                });
             let link = format!(
-                r#"<a href="mira_{sample}_coverage.html" target="_blank">{sample}</a><br>"#
+                r#"<a href="mira_{sample}_coverage.html" target="_blank">{thumbnail}{sample}</a><br>"#
             );
             coverage_links_html.push_str(&link);
         }
@@ -758,6 +990,14 @@ pub fn generate_html_report(
             a {{
                 display: inline-block;
             }}
+            .alerts {{
+                border: 3px solid {FAIL_COLOR};
+                padding: 10px;
+                margin-bottom: 20px;
+            }}
+            .alerts h2 {{
+                color: {FAIL_COLOR};
+            }}
         </style>
         <title>MIRA Summary</title>
         <link rel="icon" type="image/x-icon" href="data:image/png;base64,{base64_favicon}">
@@ -770,6 +1010,7 @@ pub fn generate_html_report(
             <h2>{runid}</h2>
         </div>
         <hr>
+        {alerts_html}
         <hr>
         <h2>Barcode Assignment</h2>
         {bdp_html}
@@ -792,6 +1033,7 @@ pub fn generate_html_report(
             Outside of those genes, premature stop codons near the end of the gene may be ok for 
             submission. Hover your mouse over the figure to see individual results.
         </p>
+        {qc_values_html}
         <hr>
         <h2>Median Coverage</h2>
         {chm_html}
@@ -799,6 +1041,13 @@ pub fn generate_html_report(
             The heatmap summarizes the mean coverage per sample per reference.
         </p>
         <hr>
+        <h2>Reference Coverage QC</h2>
+        {qcgp_html}
+        <p class="info-paragraph">
+            Percent of reference covered and median coverage for each sample and reference. The black 
+            line marks the QC threshold in use; bars meeting it are colored as passing.
+        </p>
+        <hr>
         <!-- START_IRMA_SUMMARY -->
         {irma_sum_html}
         <div class="centered-link">
@@ -845,7 +1094,7 @@ pub fn generate_html_report(
     let out_path = output_path.join(format!("mira_{runid}_summary.html"));
     write(&out_path, html_string)?;
 
-    println!("  -> static HTML saved to {:?}", out_path.display());
+    status!("  -> static HTML saved to {:?}", out_path.display());
 
     Ok(())
 }
@@ -875,7 +1124,7 @@ pub fn update_summary_in_html(
     updated_html.push_str(&html[end_marker.len() + html[..end].rfind(end_marker).unwrap()..]);
 
     fs::write(html_path, updated_html)?;
-    println!(" -> HTML written to {}", html_path.display());
+    status!(" -> HTML written to {}", html_path.display());
     Ok(())
 }
 
@@ -994,7 +1243,9 @@ pub fn update_irma_summary_to_plotly_json(summary: &[UpdatedIRMASummary], virus:
             col += 1;
         }
 
-        columns[col].push(row.pass_fail_reason.as_deref().unwrap_or("").to_string());
+        columns[col].push(status_badge_html(
+            row.pass_fail_reason.as_deref().unwrap_or(""),
+        ));
         col += 1;
         columns[col].push(row.subtype.as_deref().unwrap_or("").to_string());
         col += 1;
@@ -1,33 +1,52 @@
+use crate::io::virus_plugins::strip_reference_suffix;
+use crate::utils::coordinates::CoordinateSystem;
+use crate::utils::units::{Depth, Frequency, Percent};
 use csv::ReaderBuilder;
 use either::Either;
 use glob::glob;
 use serde::{self, Deserialize, Deserializer, Serialize, de::DeserializeOwned};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     error::Error,
     fs::{File, OpenOptions},
-    io::{self, BufRead, BufReader, Read, Stdin},
+    io::{self, BufRead, BufReader, Cursor, Read, Stdin},
     path::{Path, PathBuf},
+    sync::Mutex,
     time::{SystemTime, UNIX_EPOCH},
 };
 
+/// Count of invalid UTF-8 sequences replaced per input file, collected while reading so they can
+/// be reported once ingestion is done
+static DECODE_WARNINGS: Mutex<BTreeMap<PathBuf, usize>> = Mutex::new(BTreeMap::new());
+
 /////////////// Structs to hold IRMA data ///////////////
 ///
 ///QC structs
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct QCSettings {
-    pub med_cov: u32,
+    pub med_cov: Depth,
     pub minor_vars: u32,
     pub stop_codon_restricted_proteins: String,
-    pub perc_ref_covered: u32,
-    pub negative_control_perc: u32,
-    pub negative_control_perc_exception: u32,
+    pub perc_ref_covered: Percent,
+    pub negative_control_perc: Percent,
+    pub negative_control_perc_exception: Percent,
     pub positive_control_minimum: u32,
     pub padded_consensus: bool,
     #[serde(default)]
-    pub med_spike_cov: Option<u32>,
+    pub med_spike_cov: Option<Depth>,
+    #[serde(default)]
+    pub perc_ref_spike_covered: Option<Percent>,
+    /// Translation QC flags that fail a segment, comma separated (`missing_start`,
+    /// `internal_stop`, `length_not_multiple_of_3`, `short_cds`)
+    #[serde(default)]
+    pub translation_qc_fail: String,
+    /// CDS shorter than this percent of the reference CDS are flagged `short_cds`. Default is 90
+    #[serde(default)]
+    pub min_cds_perc_of_reference: Option<Percent>,
+    /// Segments whose reference changed at more positions than this between IRMA's last two
+    /// iterations fail QC. Only checked when the iteration references are ingested
     #[serde(default)]
-    pub perc_ref_spike_covered: Option<u32>,
+    pub max_consensus_iteration_changes: Option<u32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -85,12 +104,38 @@ pub struct CoverageData {
     pub consensus_avg_quality: f64,
     #[serde(rename = "HMM_Position")]
     pub hmm_position: Option<i32>,
+    /// Coordinates `position` is in. IRMA's tables are raw
+    #[serde(rename = "Coordinate_System", default)]
+    pub coordinate_system: CoordinateSystem,
     #[serde(rename = "Run_ID")]
     pub run_id: Option<String>,
     #[serde(rename = "Instrument")]
     pub instrument: Option<String>,
 }
 
+impl CoverageData {
+    /// Position in the reference HMM, whichever coordinates `position` is in
+    #[must_use]
+    pub fn hmm_coordinate(&self) -> Option<i32> {
+        if self.coordinate_system == CoordinateSystem::Hmm {
+            Some(self.position)
+        } else {
+            self.hmm_position
+        }
+    }
+
+    /// Position to compare with reference bounds: `position` as IRMA reported it, or the HMM
+    /// position once the rows were converted to other coordinates
+    #[must_use]
+    pub fn region_position(&self) -> Option<i32> {
+        if self.coordinate_system == CoordinateSystem::Raw {
+            Some(self.position)
+        } else {
+            self.hmm_coordinate()
+        }
+    }
+}
+
 /// Reads struct
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ReadsData {
@@ -136,7 +181,7 @@ pub struct MinorVariantsData {
     #[serde(rename = "Minority_Count")]
     pub minority_count: i32,
     #[serde(rename = "Minority_Frequency")]
-    pub minority_frequency: f64,
+    pub minority_frequency: Frequency,
     #[serde(rename = "Run_ID")]
     pub run_id: Option<String>,
     #[serde(rename = "Instrument")]
@@ -247,6 +292,15 @@ pub struct SeqData {
     pub sequence: String,
 }
 
+/// Reference of one IRMA iteration (round) for a sample and segment
+#[derive(Debug)]
+pub struct IterationReference {
+    pub sample_id: String,
+    pub reference: String,
+    pub round: u32,
+    pub sequence: String,
+}
+
 /////////////// Structs to hold dais-ribosome data ///////////////
 /// Dais Sequence Data
 #[derive(Deserialize, Debug)]
@@ -316,6 +370,20 @@ pub struct NextcladeData {
     pub sample_id: Option<String>,
 }
 
+/////////////// Structs to hold Nextflow data ///////////////
+/// A task of the Nextflow trace file (`-with-trace`). Other trace fields are ignored
+#[derive(Deserialize, Debug)]
+pub struct NfTraceData {
+    /// Process and tag, e.g. `MIRA:IRMA (sample_1)`
+    pub name: String,
+    pub status: String,
+    pub submit: String,
+    /// Submission to completion, human readable (`1m 3s`) or milliseconds in raw traces
+    pub duration: String,
+    /// Run time without queueing, in the same format as `duration`
+    pub realtime: String,
+}
+
 /////////////// Imp for the process_txt_with_sample_function ///////////////
 /// Define a trait for structs that have a `sample_id` field
 trait GetSampleId {
@@ -375,6 +443,48 @@ pub fn create_reader(path: &PathBuf) -> io::Result<BufReader<Either<File, Stdin>
     }
 }
 
+/// Reads a whole file, replacing any invalid UTF-8 with U+FFFD instead of failing deserialization.
+/// Replacements are counted per file and can be collected with [`take_decode_warnings`].
+pub fn open_lossy(path: &Path) -> io::Result<Cursor<Vec<u8>>> {
+    let bytes = std::fs::read(path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Could not open file '{}': {}", path.display(), e),
+        )
+    })?;
+
+    let invalid = bytes
+        .utf8_chunks()
+        .filter(|chunk| !chunk.invalid().is_empty())
+        .count();
+    if invalid == 0 {
+        return Ok(Cursor::new(bytes));
+    }
+
+    eprintln!(
+        "Warning: replaced {invalid} invalid UTF-8 sequence(s) in {}",
+        path.display()
+    );
+    *DECODE_WARNINGS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .entry(path.to_path_buf())
+        .or_insert(0) += invalid;
+
+    Ok(Cursor::new(
+        String::from_utf8_lossy(&bytes).into_owned().into_bytes(),
+    ))
+}
+
+/// Returns the invalid UTF-8 counts per file seen so far and clears them
+pub fn take_decode_warnings() -> BTreeMap<PathBuf, usize> {
+    std::mem::take(
+        &mut *DECODE_WARNINGS
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner),
+    )
+}
+
 /// Reads in csv file - currently only used for samplesheet
 pub fn read_csv<T: DeserializeOwned, R: std::io::Read>(
     reader: R,
@@ -394,7 +504,65 @@ pub fn read_csv<T: DeserializeOwned, R: std::io::Read>(
     Ok(records)
 }
 
-/// Reads in yaml file - currently only used for qc yaml
+/// Plot formatting overrides so partner labs can adjust plot annotations without recompiling.
+/// Every field is optional in the yaml, e.g.
+/// ```yaml
+/// separators: ",."
+/// decimal_places: 1
+/// hover_templates:
+///   barcode_distribution: "Probe=%{label}<br>Lectures=%{value:,}<extra></extra>"
+/// ```
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct PlotFormat {
+    /// Plotly separators: the decimal separator followed by the thousands separator
+    pub separators: String,
+    /// Decimal places used for non-integer values in hover text and indicators
+    pub decimal_places: usize,
+    /// Hover template overrides keyed by plot: `coverage_heatmap`, `pass_fail_heatmap`,
+    /// `barcode_distribution` and `read_sankey`
+    pub hover_templates: HashMap<String, String>,
+}
+
+impl Default for PlotFormat {
+    fn default() -> Self {
+        PlotFormat {
+            separators: ".,".to_string(),
+            decimal_places: 0,
+            hover_templates: HashMap::new(),
+        }
+    }
+}
+
+impl PlotFormat {
+    /// The configured hover template for a plot, falling back to the built in one
+    #[must_use]
+    pub fn hover_template(&self, plot: &str, default: &str) -> String {
+        self.hover_templates
+            .get(plot)
+            .cloned()
+            .unwrap_or_else(|| default.to_string())
+    }
+
+    /// d3 number format with thousands separators and the configured decimal places
+    #[must_use]
+    pub fn number_format(&self) -> String {
+        format!(",.{}f", self.decimal_places)
+    }
+}
+
+/// Reads in the plot format yaml
+pub fn read_plot_format_yaml<R: std::io::Read>(
+    reader: R,
+) -> Result<PlotFormat, Box<dyn std::error::Error>> {
+    let mut contents = String::new();
+    let mut buf_reader = BufReader::new(reader);
+    buf_reader.read_to_string(&mut contents)?;
+    let plot_format: PlotFormat = serde_yaml_ng::from_str(&contents)?;
+    Ok(plot_format)
+}
+
+/// Reads in the qc yaml
 pub fn read_yaml<R: std::io::Read>(reader: R) -> Result<QCConfig, Box<dyn std::error::Error>> {
     let mut contents = String::new();
     let mut buf_reader = BufReader::new(reader);
@@ -480,13 +648,55 @@ pub fn split_by_comma(input: &str) -> Vec<String> {
         .collect()
 }
 
-/// Read in the coverage files made by IRMA and save to a vector of `CoverageData`
-pub fn coverage_data_collection(
+/// Streams the rows of tab-delimited IRMA tables one at a time, adding the sample name from each
+/// file's path. Only one table is held in memory at a time, so consumers don't need whole-run
+/// vectors.
+fn iter_txt_with_sample<T>(paths: Vec<PathBuf>) -> impl Iterator<Item = Result<T, Box<dyn Error>>>
+where
+    T: DeserializeOwned + GetSampleId + 'static,
+{
+    paths.into_iter().flat_map(
+        |path| -> Box<dyn Iterator<Item = Result<T, Box<dyn Error>>>> {
+            let sample = match extract_sample_name(&path) {
+                Ok(sample) => sample,
+                Err(e) => return Box::new(std::iter::once(Err(e))),
+            };
+            let reader = match open_lossy(&path) {
+                Ok(reader) => reader,
+                Err(e) => return Box::new(std::iter::once(Err(e.into()))),
+            };
+            let rdr = ReaderBuilder::new()
+                .has_headers(true)
+                .delimiter(b'\t')
+                .from_reader(reader);
+            Box::new(rdr.into_deserialize::<T>().map(move |result| {
+                let mut record = result?;
+                record.set_sample_id(sample.clone());
+                Ok(record)
+            }))
+        },
+    )
+}
+
+/// Paths matching a glob pattern, reporting and skipping unreadable entries
+fn glob_paths(pattern: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut paths = Vec::new();
+    for entry in glob(pattern)? {
+        match entry {
+            Ok(path) => paths.push(path),
+            Err(e) => eprintln!("Error reading file: {e}"),
+        }
+    }
+    Ok(paths)
+}
+
+/// Streams the coverage files made by IRMA as `CoverageData` rows, one at a time
+pub fn iter_coverage_data(
     irma_path: impl AsRef<Path>,
     platform: &str,
     runid: &str,
     virus: &str,
-) -> Result<Vec<CoverageData>, Box<dyn std::error::Error>> {
+) -> Result<impl Iterator<Item = Result<CoverageData, Box<dyn Error>>>, Box<dyn Error>> {
     let pattern = if virus.to_lowercase() == "sc2-spike" {
         format!(
             "{}/*/IRMA/*/tables/*coverage.a2m.txt",
@@ -499,38 +709,33 @@ pub fn coverage_data_collection(
         )
     };
 
-    let mut cov_data: Vec<CoverageData> = Vec::new();
-
-    // Iterate over all files matching the pattern and get the sample name from file
-    for entry in glob(&pattern).expect("Failed to read glob pattern") {
-        match entry {
-            Ok(path) => {
-                let sample = extract_sample_name(&path)?;
-                let file = File::open(&path)?;
-                let reader = BufReader::new(file);
-
-                // Read the data from the file and include the sample name
-                let mut records: Vec<CoverageData> =
-                    process_txt_with_sample(reader, true, &sample)?;
-
-                // If virus is "sc2-spike", replace position with hmm_position
-                if virus == "sc2-spike" {
-                    for line in &mut records {
-                        line.position = line.hmm_position.unwrap_or(0);
-                    }
-                }
-
-                for line in &mut records {
-                    line.run_id = Some(runid.to_string());
-                    line.instrument = Some(platform.to_string());
-                }
-                cov_data.append(&mut records);
+    let platform = platform.to_string();
+    let runid = runid.to_string();
+    let is_spike = virus == "sc2-spike";
+
+    Ok(
+        iter_txt_with_sample::<CoverageData>(glob_paths(&pattern)?).map(move |result| {
+            let mut line = result?;
+            // If virus is "sc2-spike", replace position with hmm_position
+            if is_spike {
+                line.position = line.hmm_position.unwrap_or(0);
+                line.coordinate_system = CoordinateSystem::Hmm;
             }
-            Err(e) => println!("Error reading file: {e}"),
-        }
-    }
+            line.run_id = Some(runid.clone());
+            line.instrument = Some(platform.clone());
+            Ok(line)
+        }),
+    )
+}
 
-    Ok(cov_data)
+/// Read in the coverage files made by IRMA and save to a vector of `CoverageData`
+pub fn coverage_data_collection(
+    irma_path: impl AsRef<Path>,
+    platform: &str,
+    runid: &str,
+    virus: &str,
+) -> Result<Vec<CoverageData>, Box<dyn std::error::Error>> {
+    iter_coverage_data(irma_path, platform, runid, virus)?.collect()
 }
 
 ///  Collect read data created by IRMA and save to vector of `ReadsData`
@@ -551,8 +756,7 @@ pub fn reads_data_collection(
         match entry {
             Ok(path) => {
                 let sample = extract_sample_name(&path)?;
-                let file = File::open(&path)?;
-                let reader = BufReader::new(file);
+                let reader = open_lossy(&path)?;
 
                 // Read the data from the file and include the sample name
                 let mut records: Vec<ReadsData> = process_txt_with_sample(reader, true, &sample)?;
@@ -566,58 +770,57 @@ pub fn reads_data_collection(
 
                 reads_data.append(&mut records);
             }
-            Err(e) => println!("Error reading file: {e}"),
+            Err(e) => eprintln!("Error reading file: {e}"),
         }
     }
     Ok(reads_data)
 }
-/// Collecting minor vairant data created by IRMA and save to two vectors of `MinorVariantsData`
-/// One vector contains filtered minor variants (frequency >= 0.05), and the other contains all minor variants.
-pub fn minor_variant_data_collection(
+/// Streams the minor variant files made by IRMA as `MinorVariantsData` rows, one at a time
+pub fn iter_minor_variant_data(
     irma_path: &Path,
     platform: &str,
     runid: &str,
-) -> Result<MinorVariantDataCollection, Box<dyn std::error::Error>> {
+) -> Result<impl Iterator<Item = Result<MinorVariantsData, Box<dyn Error>>>, Box<dyn Error>> {
     let pattern = format!(
         "{}/*/IRMA/*/tables/*variants.txt",
         irma_path.to_string_lossy()
     );
 
+    let platform = platform.to_string();
+    let runid = runid.to_string();
+
+    Ok(
+        iter_txt_with_sample::<MinorVariantsData>(glob_paths(&pattern)?).map(move |result| {
+            let mut record = result?;
+            // Add platform and runid to each record
+            record.instrument = Some(platform.clone());
+            record.run_id = Some(runid.clone());
+
+            // Round minority_frequency to 3 decimal places
+            record.minority_frequency =
+                Frequency((record.minority_frequency.0 * 1000.0).round() / 1000.0);
+            Ok(record)
+        }),
+    )
+}
+
+/// Collecting minor vairant data created by IRMA and save to two vectors of `MinorVariantsData`
+/// One vector contains filtered minor variants (frequency >= 0.05), and the other contains all minor variants.
+pub fn minor_variant_data_collection(
+    irma_path: &Path,
+    platform: &str,
+    runid: &str,
+) -> Result<MinorVariantDataCollection, Box<dyn std::error::Error>> {
     let mut filtered_minor_variants: Vec<MinorVariantsData> = Vec::new();
     let mut all_minor_variants: Vec<MinorVariantsData> = Vec::new();
 
-    // Iterate over all files matching the pattern and get the sample name from file
-    for entry in glob(&pattern).expect("Failed to read glob pattern") {
-        match entry {
-            Ok(path) => {
-                let sample = extract_sample_name(&path)?;
-                let file = File::open(&path)?;
-                let reader = BufReader::new(file);
-
-                // Read the data from the file and include the sample name
-                let mut records: Vec<MinorVariantsData> =
-                    process_txt_with_sample(reader, true, &sample)?;
-
-                // Add platform and runid to each record
-                for record in &mut records {
-                    record.instrument = Some(platform.to_string());
-                    record.run_id = Some(runid.to_string());
-
-                    // Round minority_frequency to 3 decimal places
-                    record.minority_frequency =
-                        (record.minority_frequency * 1000.0).round() / 1000.0;
-                }
-
-                // Separate records into filtered and unfiltered vectors
-                for record in records {
-                    if record.minority_frequency >= 0.05 {
-                        filtered_minor_variants.push(record.clone());
-                    }
-                    all_minor_variants.push(record);
-                }
-            }
-            Err(e) => println!("Error reading file: {e}"),
+    // Separate records into filtered and unfiltered vectors
+    for record in iter_minor_variant_data(irma_path, platform, runid)? {
+        let record = record?;
+        if record.minority_frequency >= Frequency::MINOR_VARIANT {
+            filtered_minor_variants.push(record.clone());
         }
+        all_minor_variants.push(record);
     }
 
     Ok(MinorVariantDataCollection {
@@ -649,8 +852,7 @@ pub fn indels_data_collection(
         match entry {
             Ok(path) => {
                 let sample = extract_sample_name(&path)?;
-                let file = File::open(&path)?;
-                let reader = BufReader::new(file);
+                let reader = open_lossy(&path)?;
 
                 // Read the data from the file and include the sample name
                 let mut records: Vec<IndelsData> = process_txt_with_sample(reader, true, &sample)?;
@@ -661,7 +863,7 @@ pub fn indels_data_collection(
                 }
                 indels_data.append(&mut records);
             }
-            Err(e) => println!("Error reading file: {e}"),
+            Err(e) => eprintln!("Error reading file: {e}"),
         }
     }
 
@@ -670,8 +872,7 @@ pub fn indels_data_collection(
         match entry {
             Ok(path) => {
                 let sample = extract_sample_name(&path)?;
-                let file = File::open(&path)?;
-                let reader = BufReader::new(file);
+                let reader = open_lossy(&path)?;
 
                 // Read the data from the file and include the sample name
                 let mut records: Vec<IndelsData> = process_txt_with_sample(reader, true, &sample)?;
@@ -682,7 +883,7 @@ pub fn indels_data_collection(
                 }
                 indels_data.append(&mut records);
             }
-            Err(e) => println!("Error reading file: {e}"),
+            Err(e) => eprintln!("Error reading file: {e}"),
         }
     }
     Ok(indels_data)
@@ -706,8 +907,7 @@ pub fn all_alleles_data_collection(
         match entry {
             Ok(path) => {
                 let sample = extract_sample_name(&path)?;
-                let file = File::open(&path)?;
-                let reader = BufReader::new(file);
+                let reader = open_lossy(&path)?;
 
                 // Read the data from the file and include the sample name
                 let mut records: Vec<AllAllelesData> =
@@ -722,7 +922,7 @@ pub fn all_alleles_data_collection(
                 // Add the records to all_alleles
                 all_alleles.extend(records);
             }
-            Err(e) => println!("Error reading file: {e}"),
+            Err(e) => eprintln!("Error reading file: {e}"),
         }
     }
 
@@ -735,14 +935,14 @@ pub fn amended_consensus_data_collection(
     organism: &str,
 ) -> Result<Vec<SeqData>, Box<dyn std::error::Error>> {
     // Determine the glob pattern based on the organism
-    let pattern = if organism == "flu" || organism == "sc2-spike" {
+    let pattern = if CoordinateSystem::of_consensus(organism) == CoordinateSystem::Padded {
         format!(
-            "{}/*/IRMA/*/amended_consensus/*fa",
+            "{}/*/IRMA/*/amended_consensus/*pad.fa",
             irma_path.as_ref().display()
         )
     } else {
         format!(
-            "{}/*/IRMA/*/amended_consensus/*pad.fa",
+            "{}/*/IRMA/*/amended_consensus/*fa",
             irma_path.as_ref().display()
         )
     };
@@ -753,8 +953,7 @@ pub fn amended_consensus_data_collection(
     for entry in glob(&pattern).expect("Failed to read glob pattern") {
         match entry {
             Ok(path) => {
-                let file = File::open(&path)?;
-                let reader = BufReader::new(file);
+                let reader = open_lossy(&path)?;
 
                 // Parse the file line by line (assuming FASTA format)
                 let mut current_name = String::new();
@@ -787,7 +986,7 @@ pub fn amended_consensus_data_collection(
                     });
                 }
             }
-            Err(e) => println!("Error reading file: {e}"),
+            Err(e) => eprintln!("Error reading file: {e}"),
         }
     }
 
@@ -808,8 +1007,7 @@ pub fn get_reference_lens(
     for entry in glob(&pattern)? {
         match entry {
             Ok(path) => {
-                let file = File::open(&path)?;
-                let reader = BufReader::new(file);
+                let reader = open_lossy(&path)?;
 
                 let mut ref_name = String::new();
                 let mut current_sequence = String::new();
@@ -818,11 +1016,11 @@ pub fn get_reference_lens(
                     let line = line?;
                     if let Some(line) = line.strip_prefix('>') {
                         if !ref_name.is_empty() {
-                            // Remove "{S1}" suffix if present - sc2 situations
-                            if ref_name.ends_with("{S1}") {
-                                ref_name.truncate(ref_name.len() - 4);
-                            }
-                            ref_len_map.insert(ref_name.clone(), current_sequence.len());
+                            // Remove "{S1}" and other bracketed tags - sc2 situations
+                            ref_len_map.insert(
+                                strip_reference_suffix(&ref_name).to_string(),
+                                current_sequence.len(),
+                            );
                         }
                         ref_name = line.to_string();
                         current_sequence.clear();
@@ -832,20 +1030,88 @@ pub fn get_reference_lens(
                 }
 
                 if !ref_name.is_empty() {
-                    // Remove "{S1}" suffix if present - sc2 situations
-                    if ref_name.ends_with("{S1}") {
-                        ref_name.truncate(ref_name.len() - 4);
-                    }
-                    ref_len_map.insert(ref_name, current_sequence.len());
+                    ref_len_map.insert(
+                        strip_reference_suffix(&ref_name).to_string(),
+                        current_sequence.len(),
+                    );
                 }
             }
-            Err(e) => println!("Error reading file: {e}"),
+            Err(e) => eprintln!("Error reading file: {e}"),
         }
     }
 
     Ok(ref_len_map)
 }
 
+/// Read in the references of every IRMA iteration (round) kept in
+/// `intermediate/0-ITERATIVE-REFERENCES` to `IterationReference` structs
+pub fn iteration_references_collection(
+    irma_path: impl AsRef<Path>,
+) -> Result<Vec<IterationReference>, Box<dyn std::error::Error>> {
+    let pattern = format!(
+        "{}/*/IRMA/*/intermediate/0-ITERATIVE-REFERENCES/R*ref",
+        irma_path.as_ref().display()
+    );
+
+    let mut iteration_refs: Vec<IterationReference> = Vec::new();
+
+    for path in glob_paths(&pattern)? {
+        // R3.ref -> round 3
+        let round = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix('R'))
+            .map(|name| {
+                name.chars()
+                    .take_while(char::is_ascii_digit)
+                    .collect::<String>()
+            })
+            .and_then(|digits| digits.parse::<u32>().ok());
+        let Some(round) = round else {
+            continue;
+        };
+        // <sample>/intermediate/0-ITERATIVE-REFERENCES/R3.ref
+        let sample_id = path
+            .ancestors()
+            .nth(3)
+            .and_then(|dir| dir.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| format!("No sample directory for {}", path.display()))?;
+
+        let reader = open_lossy(&path)?;
+        let mut reference = String::new();
+        let mut sequence = String::new();
+        for line in reader.lines() {
+            let line = line?;
+            if let Some(header) = line.strip_prefix('>') {
+                if !reference.is_empty() {
+                    iteration_refs.push(IterationReference {
+                        sample_id: sample_id.clone(),
+                        reference: reference.clone(),
+                        round,
+                        sequence: sequence.clone(),
+                    });
+                }
+                // Remove "{S1}" and other bracketed tags - sc2 situations
+                reference = strip_reference_suffix(header).to_string();
+                sequence.clear();
+            } else {
+                sequence.push_str(line.trim());
+            }
+        }
+        if !reference.is_empty() {
+            iteration_refs.push(IterationReference {
+                sample_id,
+                reference,
+                round,
+                sequence,
+            });
+        }
+    }
+
+    Ok(iteration_refs)
+}
+
 // Function to get the current timestamp in the desired format
 // For irma_config file - ingest of run_info.txt
 fn get_current_timestamp() -> String {
@@ -946,8 +1212,7 @@ pub fn run_info_collection(
     for entry in glob(&pattern).expect("Failed to read glob pattern") {
         match entry {
             Ok(path) => {
-                let file = File::open(&path)?;
-                let reader = BufReader::new(file);
+                let reader = open_lossy(&path)?;
 
                 // Read the data from the file
                 let mut records: Vec<RunInfo> = process_txt_without_sample(reader, true);
@@ -961,7 +1226,7 @@ pub fn run_info_collection(
                 // Break after processing the first valid file
                 break;
             }
-            Err(e) => println!("Error reading file: {e}"),
+            Err(e) => eprintln!("Error reading file: {e}"),
         }
     }
 
@@ -1003,8 +1268,7 @@ pub fn dais_sequence_data_collection(
     for entry in glob(&pattern)? {
         match entry {
             Ok(path) => {
-                let file = File::open(&path)?;
-                let reader = BufReader::new(file);
+                let reader = open_lossy(&path)?;
                 let mut records: Vec<DaisSeqData> = process_txt(reader, false)?;
                 dais_seq_data.append(&mut records);
             }
@@ -1035,8 +1299,7 @@ pub fn dais_ref_seq_data_collection(
     for entry in glob(&pattern)? {
         match entry {
             Ok(path) => {
-                let file = File::open(&path)?;
-                let reader = BufReader::new(file);
+                let reader = open_lossy(&path)?;
                 let mut records: Vec<DaisSeqData> = process_txt(reader, false)?;
                 dais_seq_data.append(&mut records);
             }
@@ -1060,11 +1323,9 @@ pub fn di_stat_data_collection(
 
     // Use the glob crate to find all matching files
     for entry in glob(&pattern)? {
-        println!("{entry:#?}");
         match entry {
             Ok(path) => {
-                let file = File::open(&path)?;
-                let reader = BufReader::new(file);
+                let reader = open_lossy(&path)?;
                 let mut records: Vec<DIStatData> = process_txt(reader, true)?;
                 di_stats_data.append(&mut records);
             }
@@ -1077,6 +1338,68 @@ pub fn di_stat_data_collection(
     Ok(di_stats_data)
 }
 
+/////////////// Data reading functions for a single IRMA directory ///////////////
+/// Segment of an IRMA table, the part of its file name before the first `-`
+/// (`A_HA_H3-coverage.txt` -> `A_HA_H3`)
+#[must_use]
+pub fn table_segment_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_str()
+        .unwrap_or("Unknown")
+        .split('-')
+        .next()
+        .unwrap_or("Unknown")
+        .to_string()
+}
+
+/// IRMA coverage tables, each with its segment name
+pub type CoverageTables = Vec<(String, Vec<CoverageData>)>;
+
+/// Read in the coverage tables of one IRMA output directory (`<irma_dir>/tables/*coverage.txt`)
+/// as (segment, `CoverageData` rows), one entry per table
+pub fn irma_dir_coverage_tables(irma_dir: &Path) -> Result<CoverageTables, Box<dyn Error>> {
+    glob_paths(&format!("{}/tables/*coverage.txt", irma_dir.display()))?
+        .into_iter()
+        .map(|path| {
+            let segment = table_segment_name(&path);
+            let rows =
+                iter_txt_with_sample::<CoverageData>(vec![path]).collect::<Result<Vec<_>, _>>()?;
+            Ok((segment, rows))
+        })
+        .collect()
+}
+
+/// Read in the minor variants of one IRMA output directory (`<irma_dir>/tables/*variants.txt`)
+pub fn irma_dir_minor_variants(irma_dir: &Path) -> Result<Vec<MinorVariantsData>, Box<dyn Error>> {
+    iter_txt_with_sample::<MinorVariantsData>(glob_paths(&format!(
+        "{}/tables/*variants.txt",
+        irma_dir.display()
+    ))?)
+    .collect()
+}
+
+/// Read in the read counts of one IRMA output directory (`<irma_dir>/tables/READ_COUNTS.txt`)
+pub fn irma_dir_read_counts(irma_dir: &Path) -> Result<Vec<ReadsData>, Box<dyn Error>> {
+    let path = irma_dir.join("tables").join("READ_COUNTS.txt");
+    if !path.exists() {
+        return Err(format!("READ_COUNTS.txt not found at {}", path.display()).into());
+    }
+    process_txt(open_lossy(&path)?, true)
+}
+
+/////////////// Data reading functions for Nextflow ///////////////
+/// Read the tasks of a Nextflow trace file
+pub fn nf_trace_data_collection(
+    trace_path: impl AsRef<Path>,
+) -> Result<Vec<NfTraceData>, Box<dyn std::error::Error>> {
+    let path = trace_path.as_ref();
+    if !path.exists() {
+        return Err(format!("Nextflow trace file not found at {}", path.display()).into());
+    }
+    Ok(process_txt_without_sample(open_lossy(path)?, true))
+}
+
 /////////////// Data reading functions for Nextclade ///////////////
 /// Collect read data created by IRMA and save to vector of `NextcladeData`
 pub fn nextclade_data_collection(
@@ -1105,8 +1428,7 @@ pub fn nextclade_data_collection(
                         .ok_or("Failed to get dataset name from file")?
                         .to_string();
 
-                    let file = File::open(&path)?;
-                    let reader = BufReader::new(file);
+                    let reader = open_lossy(&path)?;
 
                     let mut records: Vec<NextcladeData> = process_txt_without_sample(reader, true);
 
@@ -0,0 +1,146 @@
+use csv::ReaderBuilder;
+use serde::Deserialize;
+use std::{error::Error, fs, path::Path};
+
+/// Version of the built-in flu subtype references
+pub const BUILTIN_VERSION: &str = "builtin-1";
+
+/// The protein, DAIS reference ID and subtype of each flu reference strain MIRA knows out of
+/// the box
+const BUILTIN_REFERENCES: [(&str, &str, &str); 17] = [
+    ("HA", "CALI07", "H1"),
+    ("HA", "ANNARBOR60", "H2"),
+    ("HA", "HK4801", "H3"),
+    ("HA", "VT1203", "H5"),
+    ("HA", "ANHUI01", "H7"),
+    ("HA", "BGD0994", "H9"),
+    ("HA", "BRISBANE60", "BVIC"),
+    ("HA", "PHUKET3073", "BYAM"),
+    ("NA", "CALI07", "N1"),
+    ("NA", "HK4801", "N2"),
+    ("NA", "ONTARIO6118", "N4"),
+    ("NA", "RU1526", "N5"),
+    ("NA", "ALASKA4733", "N5"),
+    ("NA", "SICHUAN26221", "N6"),
+    ("NA", "NL219", "N7"),
+    ("NA", "ASTRAKHAN3212", "N8"),
+    ("NA", "ANHUI01", "N9"),
+];
+
+/// A row of a `--flu-subtype-map` file
+#[derive(Deserialize, Debug)]
+struct SubtypeReferenceRow {
+    protein: String,
+    reference_id: String,
+    subtype: String,
+}
+
+/// The subtype an HA or NA matched to each DAIS reference calls
+#[derive(Debug)]
+pub struct FluSubtypeMap {
+    /// The `# version:` of the file the references were read from, or [`BUILTIN_VERSION`]
+    pub version: String,
+    /// Protein, DAIS reference ID and subtype
+    references: Vec<(String, String, String)>,
+}
+
+impl Default for FluSubtypeMap {
+    fn default() -> Self {
+        FluSubtypeMap {
+            version: BUILTIN_VERSION.to_string(),
+            references: BUILTIN_REFERENCES
+                .iter()
+                .map(|&(protein, reference_id, subtype)| {
+                    (
+                        protein.to_string(),
+                        reference_id.to_string(),
+                        subtype.to_string(),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl FluSubtypeMap {
+    /// The subtype a protein matched to a DAIS reference ID calls
+    #[must_use]
+    pub fn subtype_of(&self, protein: &str, reference_id: &str) -> Option<&str> {
+        self.references
+            .iter()
+            .find(|(p, r, _)| p == protein && r == reference_id)
+            .map(|(_, _, subtype)| subtype.as_str())
+    }
+}
+
+/// Reads a flu subtype reference CSV (TSV for `.tsv` and `.txt` files) with `protein`,
+/// `reference_id` and `subtype` columns, headed by a `# version: <version>` line so the
+/// mapping a run used can be traced. It replaces the built-in references entirely
+pub fn read_flu_subtype_map(path: &Path) -> Result<FluSubtypeMap, Box<dyn Error>> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Could not open file '{}': {e}", path.display()))?;
+    let version = text
+        .lines()
+        .next()
+        .and_then(|line| line.trim().strip_prefix('#'))
+        .and_then(|line| line.trim().strip_prefix("version:"))
+        .map(str::trim)
+        .filter(|version| !version.is_empty())
+        .ok_or_else(|| {
+            format!(
+                "{} must start with a '# version: <version>' line",
+                path.display()
+            )
+        })?
+        .to_string();
+
+    let delimiter = if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("tsv") || ext.eq_ignore_ascii_case("txt"))
+    {
+        b'\t'
+    } else {
+        b','
+    };
+    let mut reader = ReaderBuilder::new()
+        .delimiter(delimiter)
+        .comment(Some(b'#'))
+        .trim(csv::Trim::All)
+        .from_reader(text.as_bytes());
+
+    let mut references: Vec<(String, String, String)> = Vec::new();
+    for row in reader.deserialize() {
+        let row: SubtypeReferenceRow =
+            row.map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+        let protein = row.protein.to_uppercase();
+        if protein != "HA" && protein != "NA" {
+            return Err(format!(
+                "Protein '{}' in {} is not HA or NA",
+                row.protein,
+                path.display()
+            )
+            .into());
+        }
+        if let Some((_, _, subtype)) = references
+            .iter()
+            .find(|(p, r, _)| *p == protein && *r == row.reference_id)
+        {
+            return Err(format!(
+                "{protein} {} is listed twice in {} ({subtype} and {})",
+                row.reference_id,
+                path.display(),
+                row.subtype
+            )
+            .into());
+        }
+        references.push((protein, row.reference_id, row.subtype));
+    }
+    if references.is_empty() {
+        return Err(format!("{} has no subtype references", path.display()).into());
+    }
+
+    Ok(FluSubtypeMap {
+        version,
+        references,
+    })
+}
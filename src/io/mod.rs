@@ -1,11 +1,23 @@
 pub mod coverage_json_per_sample;
+pub mod coverage_thumbnails;
 pub mod coverage_to_heatmap;
+pub mod create_aa_alignment_html;
+pub mod create_badges;
 pub mod create_passfail_heatmap;
+pub mod create_qc_gauge_panel;
 pub mod create_statichtml;
 pub mod data_ingest;
+pub mod flu_subtype_references;
 pub mod reads_to_piechart;
 pub mod reads_to_sankey_json;
+pub mod sample_metadata;
+pub mod segment_completeness;
+pub mod timeline_json_per_sample;
+pub mod virus_plugins;
+pub mod watchlist_alerts;
 pub mod write_csv_files;
 pub mod write_fasta_files;
 pub mod write_json_files;
 pub mod write_parquet_files;
+pub mod write_quarantine;
+pub mod write_xlsx_files;
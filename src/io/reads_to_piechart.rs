@@ -1,4 +1,5 @@
-use super::data_ingest::ReadsData;
+use super::data_ingest::{PlotFormat, ReadsData};
+use crate::status;
 use serde_json::json;
 
 /// Creates a barcode distribution figure - writes it to a file and returns the JSON object.
@@ -6,8 +7,9 @@ use serde_json::json;
 pub fn create_barcode_distribution_figure(
     summaries: &[ReadsData],
     output_path: &str,
+    plot_format: &PlotFormat,
 ) -> serde_json::Value {
-    println!("Building barcode distribution pie figure as JSON");
+    status!("Building barcode distribution pie figure as JSON");
 
     // Prepare vectors for samples and reads
     let mut samples = Vec::new();
@@ -44,7 +46,10 @@ pub fn create_barcode_distribution_figure(
     // Build pie chart JSON
     let pie_data = json!({
         "domain": { "x": [0.0, 1.0], "y": [0.0, 1.0] },
-        "hovertemplate": "Sample=%{label}<br>Reads=%{value}<extra></extra>",
+        "hovertemplate": plot_format.hover_template(
+            "barcode_distribution",
+            "Sample=%{label}<br>Reads=%{value:,}<extra></extra>",
+        ),
         "labels": samples,
         "legendgroup": "",
         "name": "",
@@ -58,7 +63,7 @@ pub fn create_barcode_distribution_figure(
 
     let plot_json = json!({
         "data": [pie_data],
-        "layout": { "margin": { "t": 60 } }
+        "layout": { "margin": { "t": 60 }, "separators": plot_format.separators }
     });
 
     // Save to file
@@ -66,7 +71,7 @@ pub fn create_barcode_distribution_figure(
     std::fs::write(&file_path, plot_json.to_string())
         .expect("Failed to write barcode distribution JSON");
 
-    println!("  -> barcode distribution pie figure saved to {file_path}");
+    status!("  -> barcode distribution pie figure saved to {file_path}");
 
     plot_json
 }
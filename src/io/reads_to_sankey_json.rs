@@ -1,4 +1,5 @@
-use crate::io::data_ingest::ReadsData;
+use crate::io::data_ingest::{PlotFormat, ReadsData};
+use crate::status;
 use serde_json::{Value, json};
 use std::collections::HashMap;
 
@@ -19,7 +20,7 @@ fn seg(label: &str) -> std::string::String {
     label.to_string()
 }
 #[allow(clippy::too_many_lines)]
-fn dash_reads_to_sankey(data: &[ReadsData], virus: &str) -> Value {
+fn dash_reads_to_sankey(data: &[ReadsData], virus: &str, plot_format: &PlotFormat) -> Value {
     // Filter out rows where "Stage" is None or "Stage" is 0 or 5
     let filtered_data: Vec<_> = data
         .iter()
@@ -152,7 +153,8 @@ fn dash_reads_to_sankey(data: &[ReadsData], virus: &str) -> Value {
                 "x": x_pos,
                 "y": y_pos,
                 "color": color,
-                "hovertemplate": "%{label} %{value} reads <extra></extra>"
+                "hovertemplate": plot_format
+                    .hover_template("read_sankey", "%{label} %{value:,} reads <extra></extra>")
             },
             "link": {
                 "source": source,
@@ -161,7 +163,10 @@ fn dash_reads_to_sankey(data: &[ReadsData], virus: &str) -> Value {
                 "color": "#DBE8F7".to_string(),
                 "hovertemplate": "<extra></extra>"
             }
-        }]
+        }],
+        "layout": {
+            "separators": plot_format.separators
+        }
     })
 }
 
@@ -170,8 +175,9 @@ pub fn reads_to_sankey_json(
     data: &[ReadsData],
     virus: &str,
     output_file: &str,
+    plot_format: &PlotFormat,
 ) -> Vec<SampleSankeyJson> {
-    println!("Building read sankey plots as JSON");
+    status!("Building read sankey plots as JSON");
 
     let unique_samples: Vec<_> = data
         .iter()
@@ -189,11 +195,11 @@ pub fn reads_to_sankey_json(
             .cloned()
             .collect();
 
-        let sankeyfig = dash_reads_to_sankey(&sample_data, virus);
+        let sankeyfig = dash_reads_to_sankey(&sample_data, virus, plot_format);
 
         let file_path = format!("{output_file}readsfig_{sample}.json");
         std::fs::write(file_path.clone(), sankeyfig.to_string()).expect("Unable to write file");
-        println!("  -> read sankey plot json saved to {file_path}");
+        status!("  -> read sankey plot json saved to {file_path}");
 
         json_vec.push(SampleSankeyJson {
             sample_id: sample,
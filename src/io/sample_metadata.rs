@@ -0,0 +1,199 @@
+use crate::status;
+use crate::utils::data_processing::IRMASummary;
+use csv::ReaderBuilder;
+use serde::{Serialize, Serializer, ser::SerializeMap};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    path::Path,
+};
+
+/// Columns joined onto a summary row from the `--metadata` CSV, in the CSV's column order.
+/// Serialized as a map so they flatten into the row's own columns
+#[derive(Debug, Clone, Default)]
+pub struct SampleMetadata(pub Vec<(String, String)>);
+
+impl Serialize for SampleMetadata {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (column, value) in &self.0 {
+            map.serialize_entry(column, value)?;
+        }
+        map.end()
+    }
+}
+
+impl SampleMetadata {
+    /// Names of the metadata columns
+    pub fn columns(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|(column, _)| column.as_str())
+    }
+}
+
+/// Headers the summary tables of the HTML report and Excel workbook show for their own columns
+const SUMMARY_DISPLAY_HEADERS: &[&str] = &[
+    "Sample",
+    "Total Reads",
+    "Pass QC",
+    "Reads Mapped",
+    "Reference",
+    "% Reference Covered",
+    "Median Coverage",
+    "Count of Minor SNVs >= 0.05",
+    "Minor SNVs >= 5%",
+    "Spike % Coverage",
+    "Spike Median Coverage",
+    "DI Ratios 5'/3'",
+    "Pass/Fail Reason",
+    "Subtype",
+    "MIRA module",
+    "Run ID",
+    "Instrument",
+    "Coverage",
+];
+
+/// A `--metadata` CSV: its columns besides `sample_id` and each sample's values
+#[derive(Debug)]
+pub struct MetadataTable {
+    pub columns: Vec<String>,
+    pub rows: HashMap<String, Vec<String>>,
+}
+
+/// Reads a metadata CSV (TSV for `.tsv` and `.txt` files) with a `sample_id` (or `sample`)
+/// column. The other columns are passed through, so they can't share a name with a summary
+/// column, as stored or as shown in the report tables, or with each other
+pub fn read_sample_metadata(path: &Path) -> Result<MetadataTable, Box<dyn Error>> {
+    let delimiter = if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("tsv") || ext.eq_ignore_ascii_case("txt"))
+    {
+        b'\t'
+    } else {
+        b','
+    };
+    let mut reader = ReaderBuilder::new()
+        .delimiter(delimiter)
+        .from_path(path)
+        .map_err(|e| format!("Could not open file '{}': {e}", path.display()))?;
+
+    let headers: Vec<String> = reader
+        .headers()?
+        .iter()
+        .map(|h| h.trim().to_string())
+        .collect();
+    let sample_index = headers
+        .iter()
+        .position(|h| h == "sample_id")
+        .or_else(|| headers.iter().position(|h| h == "sample"))
+        .ok_or_else(|| format!("{} has no sample_id column", path.display()))?;
+
+    let mut seen = HashSet::new();
+    for (index, column) in headers.iter().enumerate() {
+        if index == sample_index {
+            continue;
+        }
+        if column.is_empty() {
+            return Err(format!("Column {} of {} has no name", index + 1, path.display()).into());
+        }
+        if IRMASummary::column_doc(&column.to_lowercase()).is_some()
+            || SUMMARY_DISPLAY_HEADERS
+                .iter()
+                .any(|header| header.eq_ignore_ascii_case(column))
+        {
+            return Err(format!(
+                "Metadata column '{column}' has the name of a summary column, rename it in {}",
+                path.display()
+            )
+            .into());
+        }
+        if !seen.insert(column.to_lowercase()) {
+            return Err(format!(
+                "Metadata column '{column}' is in {} more than once",
+                path.display()
+            )
+            .into());
+        }
+    }
+
+    let mut rows = HashMap::new();
+    for record in reader.records() {
+        let record = record?;
+        let sample = record
+            .get(sample_index)
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        if sample.is_empty() {
+            continue;
+        }
+        let values: Vec<String> = (0..headers.len())
+            .filter(|&index| index != sample_index)
+            .map(|index| record.get(index).unwrap_or_default().trim().to_string())
+            .collect();
+        if rows.insert(sample.clone(), values).is_some() {
+            return Err(format!("Sample {sample} is in {} more than once", path.display()).into());
+        }
+    }
+
+    Ok(MetadataTable {
+        columns: headers
+            .into_iter()
+            .enumerate()
+            .filter(|&(index, _)| index != sample_index)
+            .map(|(_, column)| column)
+            .collect(),
+        rows,
+    })
+}
+
+/// Joins the metadata onto every summary row by sample. Samples without metadata get empty
+/// values, and both they and metadata rows for samples not in the run are warned about
+pub fn add_sample_metadata(irma_summary: &mut [IRMASummary], metadata: &MetadataTable) {
+    let mut missing: Vec<&str> = Vec::new();
+    for row in irma_summary.iter_mut() {
+        let values = metadata.rows.get(&row.sample_id);
+        if values.is_none() && !missing.contains(&row.sample_id.as_str()) {
+            missing.push(&row.sample_id);
+        }
+        row.metadata = SampleMetadata(
+            metadata
+                .columns
+                .iter()
+                .enumerate()
+                .map(|(index, column)| {
+                    let value = values
+                        .and_then(|values| values.get(index))
+                        .cloned()
+                        .unwrap_or_default();
+                    (column.clone(), value)
+                })
+                .collect(),
+        );
+    }
+
+    if !missing.is_empty() {
+        eprintln!(
+            "Warning: no metadata for {} samples: {}",
+            missing.len(),
+            missing.join(", ")
+        );
+    }
+    let mut unused: Vec<&str> = metadata
+        .rows
+        .keys()
+        .filter(|sample| !irma_summary.iter().any(|row| &row.sample_id == *sample))
+        .map(String::as_str)
+        .collect();
+    if !unused.is_empty() {
+        unused.sort_unstable();
+        eprintln!(
+            "Warning: metadata for {} samples not in this run: {}",
+            unused.len(),
+            unused.join(", ")
+        );
+    }
+    status!(
+        " -> Joined {} metadata columns onto the summary",
+        metadata.columns.len()
+    );
+}
@@ -0,0 +1,111 @@
+use crate::constants::heatmap_ref::get_references_for_virus;
+use crate::constants::status_palette::{FAIL_COLOR, PASS_COLOR, WARN_COLOR};
+use crate::io::create_statichtml::write_plot_html;
+use crate::io::virus_plugins::reference_segment;
+use crate::status;
+use crate::utils::data_processing::IRMASummary;
+use serde_json::json;
+use std::{collections::HashMap, error::Error, fs::File, io::Write, path::Path};
+
+/// Percent of the reference covered for every sample (rows) and segment (columns)
+pub struct CompletenessMatrix {
+    pub segments: Vec<String>,
+    pub rows: Vec<(String, Vec<f64>)>,
+}
+
+/// Samples without an assembly for a segment get 0. Spike-only SC2 uses the spike coverage.
+#[must_use]
+pub fn segment_completeness_matrix(
+    irma_summary: &[IRMASummary],
+    sample_list: &[String],
+    virus: &str,
+) -> CompletenessMatrix {
+    let is_spike = virus.eq_ignore_ascii_case("sc2-spike");
+    let mut coverage: HashMap<(&str, String), f64> = HashMap::new();
+    for row in irma_summary {
+        let Some(reference) = &row.reference else {
+            continue;
+        };
+        let percent = if is_spike {
+            row.spike_percent_coverage
+        } else {
+            row.percent_reference_coverage
+        };
+        if let Some(percent) = percent.map(f64::from) {
+            // Keep the best reference when a sample has more than one for a segment
+            let entry = coverage
+                .entry((row.sample_id.as_str(), reference_segment(reference, virus)))
+                .or_insert(0.0);
+            *entry = entry.max(percent);
+        }
+    }
+
+    let segments = get_references_for_virus(virus);
+    let rows = sample_list
+        .iter()
+        .map(|sample| {
+            let values = segments
+                .iter()
+                .map(|segment| {
+                    coverage
+                        .get(&(sample.as_str(), segment.clone()))
+                        .copied()
+                        .unwrap_or(0.0)
+                })
+                .collect();
+            (sample.clone(), values)
+        })
+        .collect();
+
+    CompletenessMatrix { segments, rows }
+}
+
+/// Writes the samples x segments percent covered matrix to
+/// `mira_{runid}_segment_completeness.csv`, and with `heatmap` also as a heatmap HTML.
+pub fn write_segment_completeness(
+    irma_summary: &[IRMASummary],
+    sample_list: &[String],
+    virus: &str,
+    output_path: &Path,
+    runid: &str,
+    heatmap: bool,
+) -> Result<(), Box<dyn Error>> {
+    let matrix = segment_completeness_matrix(irma_summary, sample_list, virus);
+
+    let file_path = output_path.join(format!("mira_{runid}_segment_completeness.csv"));
+    let mut file = File::create(&file_path)?;
+    writeln!(file, "sample_id,{}", matrix.segments.join(","))?;
+    for (sample, values) in &matrix.rows {
+        let values: Vec<String> = values.iter().map(|v| format!("{v:.2}")).collect();
+        writeln!(file, "{sample},{}", values.join(","))?;
+    }
+    status!(" -> CSV written to {}", file_path.display());
+
+    if heatmap {
+        let plot_json = json!({
+            "data": [{
+                "type": "heatmap",
+                "x": matrix.segments,
+                "y": matrix.rows.iter().map(|(sample, _)| sample).collect::<Vec<_>>(),
+                "z": matrix.rows.iter().map(|(_, values)| values).collect::<Vec<_>>(),
+                "colorscale": [[0.0, FAIL_COLOR], [0.9, WARN_COLOR], [1.0, PASS_COLOR]],
+                "zmin": 0,
+                "zmax": 100,
+                "hovertemplate": "%{y} %{x}: %{z:.1f}% covered<extra></extra>"
+            }],
+            "layout": {
+                "xaxis": {"side": "top"},
+                "yaxis": {"autorange": "reversed"}
+            }
+        });
+        write_plot_html(
+            output_path,
+            &format!("mira_{runid}_segment_completeness"),
+            "segment_completeness",
+            "Segment Completeness",
+            &plot_json,
+        )?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,204 @@
+use crate::constants::status_palette::{FAIL_COLOR, PASS_COLOR, WARN_COLOR};
+use crate::io::data_ingest::NfTraceData;
+use crate::status;
+use serde_json::{Value, json};
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct SampleTimelineJson {
+    pub sample_id: String,
+    pub json: serde_json::Value,
+}
+
+/// A task of one sample, with times in seconds
+#[derive(Debug)]
+struct TimelineTask {
+    process: String,
+    status: String,
+    submit: f64,
+    duration: f64,
+    realtime: Option<f64>,
+}
+
+// `NFCORE_MIRA:MIRA:IRMA (sample_1)` -> (`IRMA`, `sample_1`)
+fn split_task_name(name: &str) -> Option<(&str, &str)> {
+    let (process, tag) = name.trim().split_once(" (")?;
+    let process = process.rsplit(':').next().unwrap_or(process);
+    Some((process, tag.strip_suffix(')')?))
+}
+
+// Days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Seconds since the epoch of a trace timestamp, `2024-05-01 10:11:12.345` or epoch
+/// milliseconds in raw traces
+#[allow(clippy::cast_precision_loss)]
+fn parse_timestamp(timestamp: &str) -> Option<f64> {
+    let timestamp = timestamp.trim();
+    if let Ok(millis) = timestamp.parse::<f64>() {
+        return Some(millis / 1000.0);
+    }
+    let (date, time) = timestamp.split_once(' ')?;
+    let mut date = date.split('-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    let mut time = time.split(':');
+    let hours = time.next()?.parse::<f64>().ok()?;
+    let minutes = time.next()?.parse::<f64>().ok()?;
+    let seconds = time.next()?.parse::<f64>().ok()?;
+    Some(
+        days_from_civil(year, month, day) as f64 * 86_400.0
+            + hours * 3600.0
+            + minutes * 60.0
+            + seconds,
+    )
+}
+
+/// Seconds of a trace duration, `1h 2m 3s`, `4.5s`, `120ms` or milliseconds in raw traces
+fn parse_duration(duration: &str) -> Option<f64> {
+    let duration = duration.trim();
+    if duration.is_empty() {
+        return None;
+    }
+    if let Ok(millis) = duration.parse::<f64>() {
+        return Some(millis / 1000.0);
+    }
+    let mut seconds = 0.0;
+    for part in duration.split_whitespace() {
+        let unit_start = part.find(|c: char| c.is_ascii_alphabetic())?;
+        let (value, unit) = part.split_at(unit_start);
+        let value = value.parse::<f64>().ok()?;
+        seconds += value
+            * match unit {
+                "ms" => 0.001,
+                "s" => 1.0,
+                "m" => 60.0,
+                "h" => 3600.0,
+                "d" => 86_400.0,
+                _ => return None,
+            };
+    }
+    Some(seconds)
+}
+
+// Whole seconds are plenty for a pipeline timeline
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.max(0.0).round() as u64;
+    match (seconds / 3600, seconds % 3600 / 60, seconds % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, s) => format!("{m}m {s}s"),
+        (h, m, s) => format!("{h}h {m}m {s}s"),
+    }
+}
+
+fn status_color(status: &str) -> &'static str {
+    match status {
+        "COMPLETED" | "CACHED" => PASS_COLOR,
+        "FAILED" | "ABORTED" => FAIL_COLOR,
+        _ => WARN_COLOR,
+    }
+}
+
+/// Horizontal bars from each task's submission to its completion, in minutes since the
+/// sample's first task was submitted
+fn timeline_figure(sample: &str, tasks: &[TimelineTask]) -> Value {
+    let first_submit = tasks.iter().map(|t| t.submit).fold(f64::INFINITY, f64::min);
+    let last_end = tasks
+        .iter()
+        .map(|t| t.submit + t.duration)
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    let labels: Vec<String> = tasks
+        .iter()
+        .enumerate()
+        .map(|(i, t)| format!("{}. {}", i + 1, t.process))
+        .collect();
+    let bases: Vec<f64> = tasks
+        .iter()
+        .map(|t| (t.submit - first_submit) / 60.0)
+        .collect();
+    let lengths: Vec<f64> = tasks.iter().map(|t| t.duration / 60.0).collect();
+    let colors: Vec<&str> = tasks.iter().map(|t| status_color(&t.status)).collect();
+    let hover: Vec<String> = tasks
+        .iter()
+        .map(|t| {
+            let realtime = t.realtime.map_or(String::new(), |r| {
+                format!("<br>Running: {}", format_duration(r))
+            });
+            format!(
+                "{}<br>Submit to completion: {}{realtime}<br>Status: {}",
+                t.process,
+                format_duration(t.duration),
+                t.status
+            )
+        })
+        .collect();
+
+    json!({
+        "data": [{
+            "type": "bar",
+            "orientation": "h",
+            "y": labels,
+            "x": lengths,
+            "base": bases,
+            "marker": {"color": colors},
+            "text": hover,
+            "hoverinfo": "text",
+            "textposition": "none"
+        }],
+        "layout": {
+            "title": {"text": format!("{sample} took {}", format_duration(last_end - first_submit))},
+            "xaxis": {"title": {"text": "Minutes since the sample's first task was submitted"}},
+            "yaxis": {"autorange": "reversed", "automargin": true},
+            "height": 150 + 30 * tasks.len(),
+            "showlegend": false
+        }
+    })
+}
+
+/// Builds each sample's pipeline timeline from the Nextflow trace. Tasks are assigned to a
+/// sample by their tag, and tasks without a submit time or duration are skipped.
+#[must_use]
+pub fn create_sample_timelines(
+    trace: &[NfTraceData],
+    sample_list: &[String],
+) -> Vec<SampleTimelineJson> {
+    status!("Building sample pipeline timelines as JSON");
+
+    let mut json_vec = Vec::new();
+    for sample in sample_list {
+        let mut tasks: Vec<TimelineTask> = trace
+            .iter()
+            .filter_map(|task| {
+                let (process, tag) = split_task_name(&task.name)?;
+                if tag != sample {
+                    return None;
+                }
+                Some(TimelineTask {
+                    process: process.to_string(),
+                    status: task.status.clone(),
+                    submit: parse_timestamp(&task.submit)?,
+                    duration: parse_duration(&task.duration)?,
+                    realtime: parse_duration(&task.realtime),
+                })
+            })
+            .collect();
+        if tasks.is_empty() {
+            continue;
+        }
+        tasks.sort_by(|a, b| a.submit.total_cmp(&b.submit));
+
+        json_vec.push(SampleTimelineJson {
+            sample_id: sample.clone(),
+            json: timeline_figure(sample, &tasks),
+        });
+    }
+
+    json_vec
+}
@@ -0,0 +1,292 @@
+use crate::io::data_ingest::{QCSettings, create_reader, process_txt};
+use crate::status;
+use serde::Deserialize;
+use std::{error::Error, fs, io::Read, path::Path, sync::OnceLock};
+
+/// Viruses MIRA supports out of the box
+pub const BUILTIN_VIRUSES: [&str; 4] = ["flu", "sc2-wgs", "sc2-spike", "rsv"];
+
+/// Viruses loaded from `--virus-config-dir`, set once before any data is read
+static VIRUS_PLUGINS: OnceLock<Vec<VirusPlugin>> = OnceLock::new();
+
+/// A row of a plugin's segments.tsv
+#[derive(Deserialize, Debug)]
+struct SegmentRow {
+    reference_name: String,
+    segment: String,
+}
+
+/// A row of a plugin's subtypes.tsv
+#[derive(Deserialize, Debug)]
+struct SubtypeRow {
+    reference_name: String,
+    subtype: String,
+}
+
+/// The QC settings of a plugin's qc.yaml, one section per platform
+#[derive(Deserialize, Debug)]
+struct PluginQC {
+    illumina: QCSettings,
+    ont: QCSettings,
+}
+
+/// A virus added at runtime from its own directory of config files
+#[derive(Debug)]
+pub struct VirusPlugin {
+    /// The `--virus` value, the name of the directory
+    pub name: String,
+    /// IRMA reference names and the segment each is shown as, in display order
+    pub segments: Vec<(String, String)>,
+    /// IRMA reference names and the subtype an assembly to them calls
+    pub subtypes: Vec<(String, String)>,
+    illumina_qc: QCSettings,
+    ont_qc: QCSettings,
+}
+
+impl VirusPlugin {
+    /// Reads a plugin directory: segments.tsv and qc.yaml are required, subtypes.tsv is
+    /// optional
+    fn read(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let name = dir
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| format!("Invalid virus config directory {}", dir.display()))?
+            .to_lowercase();
+
+        let segments: Vec<SegmentRow> =
+            process_txt(create_reader(&dir.join("segments.tsv"))?, true)?;
+        let subtypes_path = dir.join("subtypes.tsv");
+        let subtypes: Vec<SubtypeRow> = if subtypes_path.exists() {
+            process_txt(create_reader(&subtypes_path)?, true)?
+        } else {
+            Vec::new()
+        };
+
+        let mut qc_yaml = String::new();
+        create_reader(&dir.join("qc.yaml"))?.read_to_string(&mut qc_yaml)?;
+        let qc: PluginQC = serde_yaml_ng::from_str(&qc_yaml)
+            .map_err(|e| format!("Could not read {}: {e}", dir.join("qc.yaml").display()))?;
+
+        Ok(VirusPlugin {
+            name,
+            segments: segments
+                .into_iter()
+                .map(|row| (row.reference_name, row.segment))
+                .collect(),
+            subtypes: subtypes
+                .into_iter()
+                .map(|row| (row.reference_name, row.subtype))
+                .collect(),
+            illumina_qc: qc.illumina,
+            ont_qc: qc.ont,
+        })
+    }
+
+    /// Segment names in display order, each once
+    #[must_use]
+    pub fn segment_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for (_, segment) in &self.segments {
+            if !names.contains(segment) {
+                names.push(segment.clone());
+            }
+        }
+        names
+    }
+
+    /// The segment an IRMA reference is shown as
+    #[must_use]
+    pub fn segment_of(&self, reference: &str) -> Option<&str> {
+        let reference = strip_reference_suffix(reference);
+        self.segments
+            .iter()
+            .find(|(name, _)| name == reference)
+            .map(|(_, segment)| segment.as_str())
+    }
+
+    /// The subtype an assembly to an IRMA reference calls
+    #[must_use]
+    pub fn subtype_of(&self, reference: &str) -> Option<&str> {
+        let reference = strip_reference_suffix(reference);
+        self.subtypes
+            .iter()
+            .find(|(name, _)| name == reference)
+            .map(|(_, subtype)| subtype.as_str())
+    }
+
+    #[must_use]
+    pub fn qc_settings(&self, platform: &str) -> &QCSettings {
+        if platform.eq_ignore_ascii_case("illumina") {
+            &self.illumina_qc
+        } else {
+            &self.ont_qc
+        }
+    }
+}
+
+/// Loads every subdirectory of `config_dir` with a segments.tsv as a virus, named after the
+/// subdirectory. Can only be done once per run.
+pub fn load_virus_plugins(config_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let mut plugins = Vec::new();
+    for entry in fs::read_dir(config_dir)
+        .map_err(|e| format!("Could not read {}: {e}", config_dir.display()))?
+    {
+        let path = entry?.path();
+        if !path.join("segments.tsv").is_file() {
+            continue;
+        }
+        let plugin = VirusPlugin::read(&path)?;
+        if BUILTIN_VIRUSES.contains(&plugin.name.as_str()) {
+            return Err(format!(
+                "{} would replace the built-in virus {}, rename the directory",
+                path.display(),
+                plugin.name
+            )
+            .into());
+        }
+        status!(" -> Loaded virus config {}", plugin.name);
+        plugins.push(plugin);
+    }
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+
+    VIRUS_PLUGINS
+        .set(plugins)
+        .map_err(|_| "Virus configs were already loaded".into())
+}
+
+/// The loaded plugin for a `--virus` value, if it isn't a built-in virus
+#[must_use]
+pub fn virus_plugin(virus: &str) -> Option<&'static VirusPlugin> {
+    VIRUS_PLUGINS
+        .get()?
+        .iter()
+        .find(|plugin| plugin.name.eq_ignore_ascii_case(virus))
+}
+
+/// Checks that `--virus` is a built-in virus or a loaded plugin
+pub fn validate_virus(virus: &str) -> Result<(), Box<dyn Error>> {
+    if BUILTIN_VIRUSES.contains(&virus.to_lowercase().as_str()) || virus_plugin(virus).is_some() {
+        return Ok(());
+    }
+    let mut options: Vec<&str> = BUILTIN_VIRUSES.to_vec();
+    options.extend(
+        VIRUS_PLUGINS
+            .get()
+            .into_iter()
+            .flatten()
+            .map(|plugin| plugin.name.as_str()),
+    );
+    Err(format!(
+        "Unsupported virus '{virus}'. Options: {}",
+        options.join(", ")
+    )
+    .into())
+}
+
+/// An IRMA reference name without the bracketed tags IRMA and MIRA append to it, like the
+/// `{S1}` of SC2 references or the `[2]` of a duplicated reference: `A_HA_H3{S1}` -> `A_HA_H3`
+#[must_use]
+pub fn strip_reference_suffix(reference: &str) -> &str {
+    let mut name = reference.trim();
+    loop {
+        let stripped = [('{', '}'), ('[', ']')]
+            .into_iter()
+            .find_map(|(open, close)| {
+                let rest = name.strip_suffix(close)?;
+                rest.rfind(open).map(|start| &rest[..start])
+            })
+            .map(str::trim_end);
+        match stripped {
+            Some(stripped) if !stripped.is_empty() => name = stripped,
+            _ => return name,
+        }
+    }
+}
+
+/// The segment an IRMA reference is plotted and tabulated as: the part after the type for flu
+/// (`A_HA_H3` -> `HA`, `B_MP` -> `MP`), `RSV` for RSV, the segments.tsv entry for plugin
+/// viruses and otherwise the reference itself, always without bracketed tags
+#[must_use]
+pub fn reference_segment(reference: &str, virus: &str) -> String {
+    let reference = strip_reference_suffix(reference);
+    if virus.eq_ignore_ascii_case("rsv") {
+        return "RSV".to_string();
+    }
+    if let Some(segment) = virus_plugin(virus).and_then(|plugin| plugin.segment_of(reference)) {
+        return segment.to_string();
+    }
+    reference
+        .split('_')
+        .nth(1)
+        .filter(|segment| !segment.is_empty())
+        .unwrap_or(reference)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_reference_tags() {
+        assert_eq!(strip_reference_suffix("A_HA_H3{S1}"), "A_HA_H3");
+        assert_eq!(strip_reference_suffix("X[2]"), "X");
+        assert_eq!(strip_reference_suffix("A_HA_H3{S1}[2]"), "A_HA_H3");
+        assert_eq!(strip_reference_suffix("A_HA_H3 {S1} "), "A_HA_H3");
+        assert_eq!(strip_reference_suffix("SARS-CoV-2{S1}"), "SARS-CoV-2");
+        assert_eq!(strip_reference_suffix("RSV_AD"), "RSV_AD");
+        assert_eq!(strip_reference_suffix("B_MP"), "B_MP");
+    }
+
+    #[test]
+    fn keeps_a_name_that_is_only_a_tag() {
+        assert_eq!(strip_reference_suffix("{S1}"), "{S1}");
+        assert_eq!(strip_reference_suffix("[2]"), "[2]");
+    }
+
+    #[test]
+    fn finds_builtin_segments() {
+        assert_eq!(reference_segment("A_HA_H3{S1}", "flu"), "HA");
+        assert_eq!(reference_segment("B_MP", "flu"), "MP");
+        assert_eq!(reference_segment("A_NA_N2[2]", "flu"), "NA");
+        assert_eq!(reference_segment("RSV_AD", "rsv"), "RSV");
+        assert_eq!(reference_segment("RSV_BD{S1}", "RSV"), "RSV");
+        assert_eq!(reference_segment("SARS-CoV-2{S1}", "sc2-wgs"), "SARS-CoV-2");
+    }
+
+    // The only test loading plugins, as they can be loaded once per process
+    #[test]
+    fn finds_plugin_segments() {
+        let config_dir =
+            std::env::temp_dir().join(format!("mira-oxide-plugins-{}", std::process::id()));
+        let plugin_dir = config_dir.join("mpox");
+        fs::create_dir_all(&plugin_dir).unwrap();
+        fs::write(
+            plugin_dir.join("segments.tsv"),
+            "reference_name\tsegment\nMPXV_CLADE_I\tgenome\nMPXV_CLADE_II\tgenome\n",
+        )
+        .unwrap();
+        let qc = "med_cov: 50\nminor_vars: 10\nstop_codon_restricted_proteins: \"\"\n\
+            perc_ref_covered: 90\nnegative_control_perc: 10\n\
+            negative_control_perc_exception: 5\npositive_control_minimum: 5\n\
+            padded_consensus: false\n";
+        fs::write(
+            plugin_dir.join("qc.yaml"),
+            format!("illumina:\n{}ont:\n{}", indent(qc), indent(qc)),
+        )
+        .unwrap();
+
+        load_virus_plugins(&config_dir).unwrap();
+        let plugin = virus_plugin("MPOX").unwrap();
+        assert_eq!(plugin.segment_of("MPXV_CLADE_II{S1}"), Some("genome"));
+        assert_eq!(plugin.segment_of("MPXV_CLADE_III"), None);
+        assert_eq!(reference_segment("MPXV_CLADE_I[2]", "mpox"), "genome");
+        // References missing from segments.tsv fall back to the name
+        assert_eq!(reference_segment("MPXV_OTHER", "mpox"), "OTHER");
+        fs::remove_dir_all(&config_dir).unwrap();
+    }
+
+    fn indent(yaml: &str) -> String {
+        yaml.lines().flat_map(|line| ["  ", line, "\n"]).collect()
+    }
+}
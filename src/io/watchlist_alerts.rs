@@ -0,0 +1,62 @@
+use crate::status;
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+/// A watchlist mutation found in a sample
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchlistAlert {
+    pub sample: String,
+    pub subtype: String,
+    pub protein: String,
+    pub aa_mutation: String,
+    pub description: String,
+}
+
+/// The alerts JSON written by variants-of-interest `--alerts-json`
+#[derive(Serialize, Deserialize)]
+struct AlertsFile {
+    alerts: Vec<WatchlistAlert>,
+}
+
+/// Lists the alerts prominently on stderr, where they are never mixed into a table written
+/// to stdout
+pub fn print_alerts(alerts: &[WatchlistAlert]) {
+    if alerts.is_empty() {
+        return;
+    }
+    eprintln!("!!! {} WATCHLIST ALERT(S) DETECTED !!!", alerts.len());
+    for alert in alerts {
+        eprintln!(
+            "  ALERT {}: {} {} ({}) {}",
+            alert.sample, alert.protein, alert.aa_mutation, alert.subtype, alert.description
+        );
+    }
+}
+
+/// Writes the alerts as an `alerts` array, for posting to a notification service
+pub fn write_alerts_json(path: &PathBuf, alerts: &[WatchlistAlert]) -> Result<(), Box<dyn Error>> {
+    let file = File::create(path)?;
+    let alerts = AlertsFile {
+        alerts: alerts.to_vec(),
+    };
+    serde_json::to_writer_pretty(file, &alerts)?;
+    status!(" -> JSON written to {}", path.display());
+    Ok(())
+}
+
+/// Reads the alerts of a variants-of-interest `--alerts-json` file
+pub fn read_alerts_json(path: &Path) -> Result<Vec<WatchlistAlert>, Box<dyn Error>> {
+    let file = File::open(path).map_err(|e| {
+        std::io::Error::new(
+            e.kind(),
+            format!("Could not open file '{}': {e}", path.display()),
+        )
+    })?;
+    let alerts: AlertsFile = serde_json::from_reader(BufReader::new(file))?;
+    Ok(alerts.alerts)
+}
@@ -1,7 +1,15 @@
-use csv::Writer;
+use crate::status;
+use csv::{ReaderBuilder, StringRecord, Writer, WriterBuilder};
 use serde::Serialize;
 use serde_json::Value;
-use std::{error::Error, path::Path};
+use std::{
+    error::Error,
+    fs::OpenOptions,
+    io::{Seek, SeekFrom},
+    path::Path,
+};
+
+use super::write_json_files::write_summary_columns_json;
 
 use crate::{
     processes::summary_report_update::UpdatedIRMASummary,
@@ -42,8 +50,61 @@ pub fn write_structs_to_csv_file<T: Serialize>(
     }
 
     csv_writer.flush()?;
-    println!(" -> CSV written to {file_path}");
+    status!(" -> CSV written to {file_path}");
+
+    Ok(())
+}
+
+//////////////// Function to append rows to a CSV shared between processes ///////////////
+/// Appends `rows` to the CSV at `file_path` while holding an exclusive advisory lock on it, so
+/// parallel pipeline tasks can each contribute their rows to one run level table. The header is
+/// written only by whichever task creates the file; later tasks must bring the same header.
+pub fn append_records_to_shared_csv(
+    file_path: &Path,
+    header: &StringRecord,
+    rows: &[StringRecord],
+    delimiter: u8,
+) -> Result<(), Box<dyn Error>> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(file_path)
+        .map_err(|e| format!("Could not open file '{}': {e}", file_path.display()))?;
+    // Blocks until any other writer is done. Released when `file` is dropped
+    file.lock()?;
+
+    if file.metadata()?.len() == 0 {
+        let mut csv_writer = WriterBuilder::new().delimiter(delimiter).from_writer(&file);
+        csv_writer.write_record(header)?;
+        csv_writer.flush()?;
+    } else {
+        file.seek(SeekFrom::Start(0))?;
+        let existing = ReaderBuilder::new()
+            .delimiter(delimiter)
+            .from_reader(&file)
+            .headers()?
+            .clone();
+        if &existing != header {
+            return Err(format!(
+                "Header of {} ({}) does not match the rows being appended ({})",
+                file_path.display(),
+                existing.iter().collect::<Vec<_>>().join(","),
+                header.iter().collect::<Vec<_>>().join(",")
+            )
+            .into());
+        }
+    }
 
+    // Append mode writes at the end of the file whatever was read above
+    let mut csv_writer = WriterBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .from_writer(&file);
+    for row in rows {
+        csv_writer.write_record(row)?;
+    }
+    csv_writer.flush()?;
     Ok(())
 }
 
@@ -74,6 +135,7 @@ pub fn write_out_all_csv_mira_reports(
         "Ambiguous",
         "Consensus_Count",
         "Consensus_Average_Quality",
+        "Coordinate_System",
         "Run_ID",
         "Instrument",
     ];
@@ -88,6 +150,7 @@ pub fn write_out_all_csv_mira_reports(
         "ambiguous",
         "consensus_count",
         "consensus_average_quality",
+        "coordinate_system",
         "run_id",
         "instrument",
     ];
@@ -236,6 +299,8 @@ pub fn write_out_all_csv_mira_reports(
         "protein",
         "aa_variant_count",
         "aa_variants",
+        "hgvs_protein",
+        "hgvs_coding",
     ];
 
     write_structs_to_csv_file(
@@ -246,7 +311,7 @@ pub fn write_out_all_csv_mira_reports(
     )?;
 
     // write out the mira_{runid}_summary.csv
-    let summary_struct_values: Vec<&str> = if virus == "sc2-wgs" {
+    let mut summary_struct_values: Vec<&str> = if virus == "sc2-wgs" {
         vec![
             "sample_id",
             "total_reads",
@@ -299,7 +364,7 @@ pub fn write_out_all_csv_mira_reports(
         ]
     };
 
-    let summary_columns: Vec<&str> = if virus == "sc2-wgs" {
+    let mut summary_columns: Vec<&str> = if virus == "sc2-wgs" {
         vec![
             "sample_id",
             "total_reads",
@@ -352,12 +417,39 @@ pub fn write_out_all_csv_mira_reports(
         ]
     };
 
+    // Secondary assembly subtypes are only listed when they were ingested
+    if irma_summary
+        .iter()
+        .any(|row| row.secondary_subtypes.is_some())
+    {
+        for columns in [&mut summary_struct_values, &mut summary_columns] {
+            let index = columns
+                .iter()
+                .position(|column| *column == "subtype")
+                .map_or(columns.len(), |i| i + 1);
+            columns.insert(index, "secondary_subtypes");
+        }
+    }
+
+    // Passthrough columns from --metadata, after the MIRA columns
+    if let Some(row) = irma_summary.first() {
+        summary_struct_values.extend(row.metadata.columns());
+        summary_columns.extend(row.metadata.columns());
+    }
+
     write_structs_to_csv_file(
         &format!("{}/mira_{runid}_summary.csv", output_path.display()),
         irma_summary,
         &summary_columns,
         &summary_struct_values,
     )?;
+    write_summary_columns_json(
+        &format!(
+            "{}/mira_{runid}_summary_columns.json",
+            output_path.display()
+        ),
+        &summary_columns,
+    )?;
 
     // write out the amended_consensus
     let seq_columns = vec![
@@ -1,3 +1,4 @@
+use crate::status;
 use std::{error::Error, fs::File, io::Write, path::Path};
 
 use crate::{io::data_ingest::SeqData, utils::data_processing::NextcladeSequences};
@@ -11,7 +12,7 @@ pub fn write_to_fasta(output_file: &str, seq_data_vec: &[SeqData]) -> Result<(),
         writeln!(file, "{}", seq_data.sequence)?;
     }
 
-    println!(" -> FASTA written to {output_file}");
+    status!(" -> FASTA written to {output_file}");
 
     Ok(())
 }
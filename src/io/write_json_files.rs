@@ -1,17 +1,26 @@
-use std::{collections::HashMap, error::Error, fs::File, io::Write, path::Path};
+use crate::status;
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fs::{File, rename},
+    io::Write,
+    path::Path,
+};
 
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 use crate::{
+    constants::status_palette::QcStatus,
     processes::summary_report_update::UpdatedIRMASummary,
     utils::data_processing::{
-        DaisVarsData, IRMASummary, NTSequences, ProcessedRecord, filter_struct_by_ids,
+        ColumnDoc, DaisVarsData, IRMASummary, NTSequences, ProcessedRecord, filter_struct_by_ids,
     },
 };
 use std::collections::HashSet;
 
 use super::data_ingest::{CoverageData, IndelsData, MinorVariantDataCollection, ReadsData};
+use super::watchlist_alerts::WatchlistAlert;
 
 //////////////// Function to collection and write out all JSON files ///////////////
 /////////////// Structs ///////////////
@@ -22,6 +31,28 @@ pub struct ReadQC {
     pub percent_mapping: f64,
 }
 
+/// Writes the description and unit of each summary column as a `columns.json` style sidecar, so
+/// BI tools can show tooltips without a hand-maintained data dictionary
+pub fn write_summary_columns_json(file_path: &str, columns: &[&str]) -> Result<(), Box<dyn Error>> {
+    let column_docs: Vec<ColumnDoc> = columns
+        .iter()
+        .map(|column| {
+            IRMASummary::column_doc(column).unwrap_or_else(|| ColumnDoc {
+                name: (*column).to_string(),
+                description: String::new(),
+                unit: None,
+            })
+        })
+        .collect();
+    let output = json!({ "table": "summary", "columns": column_docs });
+
+    let mut file = File::create(file_path)?;
+    file.write_all(serde_json::to_string_pretty(&output)?.as_bytes())?;
+
+    status!(" -> JSON written to {file_path}");
+    Ok(())
+}
+
 /// Function to serialize a vector of structs into split-oriented JSON with precision and indexing
 pub fn write_structs_to_split_json_file<T: Serialize>(
     file_path: &str,
@@ -55,7 +86,7 @@ pub fn write_structs_to_split_json_file<T: Serialize>(
 
     std::fs::write(file_path, serde_json::to_string_pretty(&split_json)?)?;
 
-    println!(" -> JSON written to {file_path}");
+    status!(" -> JSON written to {file_path}");
 
     Ok(())
 }
@@ -116,16 +147,13 @@ pub fn write_irma_summary_to_pass_fail_json_file(
     // Write the JSON to the specified file
     std::fs::write(file_path, serde_json::to_string_pretty(&split_json)?)?;
 
-    println!(" -> JSON written to {file_path}");
+    status!(" -> JSON written to {file_path}");
 
     Ok(())
 }
 
-pub fn negative_qc_statement(
-    output_file: &str,
-    reads_data: &[ReadsData],
-    neg_control_list: &[String],
-) -> Result<(), Box<dyn Error>> {
+// Percent of reads mapping for each negative control
+fn negative_control_mapping(reads_data: &[ReadsData], neg_control_list: &[String]) -> Vec<ReadQC> {
     let filtered_reads_data = filter_struct_by_ids(reads_data, neg_control_list);
 
     let mut results = Vec::new();
@@ -158,6 +186,31 @@ pub fn negative_qc_statement(
         }
     }
 
+    results
+}
+
+/// Negative controls with 1% or more of reads mapping fail QC.
+#[must_use]
+pub fn failed_negative_controls(
+    reads_data: &[ReadsData],
+    neg_control_list: &[String],
+) -> Vec<String> {
+    let mut failed: Vec<String> = negative_control_mapping(reads_data, neg_control_list)
+        .into_iter()
+        .filter(|qc| qc.percent_mapping >= 1.0)
+        .map(|qc| qc.sample_id)
+        .collect();
+    failed.sort();
+    failed
+}
+
+pub fn negative_qc_statement(
+    output_file: &str,
+    reads_data: &[ReadsData],
+    neg_control_list: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let results = negative_control_mapping(reads_data, neg_control_list);
+
     // Categorize results into "passes QC" and "FAILS QC"
     let mut passes_qc = HashMap::new();
     let mut fails_qc = HashMap::new();
@@ -180,7 +233,7 @@ pub fn negative_qc_statement(
     let mut file = File::create(output_file)?;
     file.write_all(json_output.to_string().as_bytes())?;
 
-    println!(" -> JSON written to {output_file}");
+    status!(" -> JSON written to {output_file}");
     Ok(())
 }
 
@@ -213,6 +266,7 @@ pub fn write_out_all_json_files(
         "Ambiguous",
         "Consensus_Count",
         "Consensus_Average_Quality",
+        "Coordinate_System",
     ];
 
     let coverage_columns = vec![
@@ -225,6 +279,7 @@ pub fn write_out_all_json_files(
         "ambiguous",
         "consensus_count",
         "consensus_average_quality",
+        "coordinate_system",
     ];
 
     write_structs_to_split_json_file(
@@ -374,6 +429,8 @@ pub fn write_out_all_json_files(
         "protein",
         "aa_variant_count",
         "aa_variants",
+        "hgvs_protein",
+        "hgvs_coding",
     ];
 
     write_structs_to_split_json_file(
@@ -390,7 +447,7 @@ pub fn write_out_all_json_files(
     )?;
 
     // write out the summary.json
-    let summary_struct_values: Vec<&str> = if virus == "sc2-wgs" {
+    let mut summary_struct_values: Vec<&str> = if virus == "sc2-wgs" {
         vec![
             "sample_id",
             "total_reads",
@@ -443,7 +500,7 @@ pub fn write_out_all_json_files(
         ]
     };
 
-    let summary_columns: Vec<&str> = if virus == "sc2-wgs" {
+    let mut summary_columns: Vec<&str> = if virus == "sc2-wgs" {
         vec![
             "sample_id",
             "total_reads",
@@ -496,6 +553,26 @@ pub fn write_out_all_json_files(
         ]
     };
 
+    // Secondary assembly subtypes are only listed when they were ingested
+    if irma_summary
+        .iter()
+        .any(|row| row.secondary_subtypes.is_some())
+    {
+        for columns in [&mut summary_struct_values, &mut summary_columns] {
+            let index = columns
+                .iter()
+                .position(|column| *column == "subtype")
+                .map_or(columns.len(), |i| i + 1);
+            columns.insert(index, "secondary_subtypes");
+        }
+    }
+
+    // Passthrough columns from --metadata, after the MIRA columns
+    if let Some(row) = irma_summary.first() {
+        summary_struct_values.extend(row.metadata.columns());
+        summary_columns.extend(row.metadata.columns());
+    }
+
     write_structs_to_split_json_file(
         &format!("{}/irma_summary.json", output_path.display()),
         irma_summary,
@@ -665,3 +742,87 @@ pub fn write_out_updated_json_files(
 
     Ok(())
 }
+
+//////////////// Run status file for workflow completion hooks ///////////////
+#[derive(Serialize, Debug)]
+pub struct RunStatus {
+    pub runid: String,
+    /// One of "pass", "fail" or "controls-failed"
+    pub verdict: String,
+    pub status_counts: BTreeMap<String, usize>,
+    pub failed_negative_controls: Vec<String>,
+    /// Watchlist mutations detected in the run, from the `--alerts` file
+    pub alerts: Vec<WatchlistAlert>,
+    pub artifacts: BTreeMap<String, String>,
+}
+
+/// Builds the run status from the QC evaluated summary. A failing negative control fails the
+/// whole run, otherwise the run passes as long as at least one test sample reference passed.
+#[must_use]
+pub fn build_run_status(
+    runid: &str,
+    irma_summary: &[IRMASummary],
+    reads_data: &[ReadsData],
+    neg_control_list: &[String],
+    artifacts: BTreeMap<String, String>,
+    alerts: Vec<WatchlistAlert>,
+) -> RunStatus {
+    let mut status_counts = BTreeMap::new();
+    for key in ["pass", "fail", "no_assembly"] {
+        status_counts.insert(key.to_string(), 0);
+    }
+
+    let mut any_pass = false;
+    for summary in irma_summary {
+        let status =
+            QcStatus::from_reason(summary.pass_fail_reason.as_deref().unwrap_or("No assembly"));
+        let key = match status {
+            QcStatus::Pass => "pass",
+            QcStatus::Fail => "fail",
+            QcStatus::NoAssembly => "no_assembly",
+        };
+        *status_counts.entry(key.to_string()).or_insert(0) += 1;
+        if status == QcStatus::Pass && !neg_control_list.contains(&summary.sample_id) {
+            any_pass = true;
+        }
+    }
+
+    let failed_negative_controls = failed_negative_controls(reads_data, neg_control_list);
+
+    let verdict = if !failed_negative_controls.is_empty() {
+        "controls-failed"
+    } else if any_pass {
+        "pass"
+    } else {
+        "fail"
+    };
+
+    RunStatus {
+        runid: runid.to_string(),
+        verdict: verdict.to_string(),
+        status_counts,
+        failed_negative_controls,
+        alerts,
+        artifacts,
+    }
+}
+
+/// Writes the run status JSON atomically (temp file + rename) so a watcher never reads a
+/// partially written file.
+pub fn write_run_status_json(
+    status_file: &Path,
+    run_status: &RunStatus,
+) -> Result<(), Box<dyn Error>> {
+    let mut tmp_name = status_file.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = Path::new(&tmp_name);
+
+    let mut file = File::create(tmp_path)?;
+    file.write_all(serde_json::to_string_pretty(run_status)?.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+    rename(tmp_path, status_file)?;
+
+    status!(" -> JSON written to {}", status_file.display());
+    Ok(())
+}
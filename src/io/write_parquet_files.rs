@@ -1,20 +1,114 @@
 use crate::io::data_ingest::{AllAllelesData, ReadsData};
+use crate::processes::di_stats::DIRecord;
 use crate::processes::prepare_mira_reports::Samplesheet;
 use crate::processes::summary_report_update::UpdatedIRMASummary;
-use crate::utils::data_processing::{AASequences, IRMASummary, NTSequences, extract_field};
-use arrow::array::Float64Array;
+use crate::processes::variants_of_interest::MutationMatrix;
+use crate::status;
+use crate::utils::cancel::{PartialFile, check_cancelled};
+use crate::utils::data_processing::{
+    AASequences, IRMASummary, NTSequences, QCCriterion, ReadFateData, SecondaryAssembly,
+    extract_field,
+};
+use arrow::array::{BooleanArray, Float64Array};
 use arrow::{
     array::{ArrayRef, Float32Array, Int32Array, StringArray},
     datatypes::{DataType, Field, Schema},
+    error::ArrowError,
     record_batch::RecordBatch,
 };
 use parquet::arrow::ArrowWriter;
-use std::{error::Error, fs::File, sync::Arc};
+use std::{collections::HashMap, error::Error, path::Path, sync::Arc};
 
 use super::data_ingest::{CoverageData, IndelsData, MinorVariantsData, RunInfo};
 
 /////////////// Functions to write parquet files out ///////////////
 
+/// Columns to keep and rename in the parquet files, applied to the Arrow schema before writing
+/// so the files can match a downstream contract.
+#[derive(Debug, Clone, Default)]
+pub struct ParquetColumns {
+    /// Columns to keep, in this order. Empty keeps every column.
+    pub columns: Vec<String>,
+    /// Old and new column names. Renaming happens after selecting, by the original names.
+    pub rename: Vec<(String, String)>,
+}
+
+/// Parses a `--rename` value, `column=new_name`.
+pub fn parse_column_rename(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((old, new)) if !old.trim().is_empty() && !new.trim().is_empty() => {
+            Ok((old.trim().to_string(), new.trim().to_string()))
+        }
+        _ => Err(format!("expected column=new_name, got {value}")),
+    }
+}
+
+impl ParquetColumns {
+    /// Selects and renames the columns of a batch. The listed columns a file doesn't have are
+    /// skipped, and a file with none of them is kept whole rather than written without columns.
+    pub fn apply(&self, record_batch: &RecordBatch) -> Result<RecordBatch, ArrowError> {
+        let schema = record_batch.schema();
+        let record_batch = if self.columns.is_empty() {
+            record_batch.clone()
+        } else {
+            let indices: Vec<usize> = self
+                .columns
+                .iter()
+                .filter_map(|column| schema.index_of(column).ok())
+                .collect();
+            if indices.is_empty() {
+                eprintln!(
+                    "Warning: none of the --columns are in this parquet file, keeping all of its columns"
+                );
+                record_batch.clone()
+            } else {
+                record_batch.project(&indices)?
+            }
+        };
+        if self.rename.is_empty() {
+            return Ok(record_batch);
+        }
+
+        // Field metadata (descriptions and units) stays with the renamed column
+        let schema = record_batch.schema();
+        let fields: Vec<Field> = schema
+            .fields()
+            .iter()
+            .map(
+                |field| match self.rename.iter().find(|(old, _)| old == field.name()) {
+                    Some((_, new)) => field.as_ref().clone().with_name(new),
+                    None => field.as_ref().clone(),
+                },
+            )
+            .collect();
+        RecordBatch::try_new(
+            Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone())),
+            record_batch.columns().to_vec(),
+        )
+    }
+}
+
+/// Applies the column options and writes the batch to a parquet file. The file is written
+/// next to its path and moved into place once closed, so a run stopped partway never leaves a
+/// half-written file. After a termination signal no more files are started
+fn write_record_batch(
+    record_batch: &RecordBatch,
+    columns: &ParquetColumns,
+    output_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    check_cancelled()?;
+    let record_batch = columns.apply(record_batch)?;
+    let (partial, file) = PartialFile::create(Path::new(output_file))?;
+    let mut writer = ArrowWriter::try_new(file, record_batch.schema(), None)?;
+    writer.write(&record_batch)?;
+    writer.close()?;
+    partial.finish()?;
+
+    status!(" -> PARQUET written to {output_file}");
+
+    Ok(())
+}
+
 pub fn extract_string_fields_as_int<V, T, F>(data: V, extractor: F) -> Vec<Option<i32>>
 where
     V: AsRef<[T]>,
@@ -106,12 +200,15 @@ where
 pub fn write_coverage_to_parquet(
     coverage_data: &[CoverageData],
     output_file: &str,
+    columns: &ParquetColumns,
 ) -> Result<(), Box<dyn Error>> {
     // Convert values in struct to vector of values
     let sample_ids_vec: Vec<Option<String>> =
         extract_field(coverage_data, |item| item.sample_id.clone());
     let ref_name_vec = extract_field(coverage_data, |item| item.reference_name.clone());
     let position_vec = extract_field(coverage_data, |item| item.position);
+    let coordinate_system_vec =
+        extract_field(coverage_data, |item| item.coordinate_system.to_string());
     let coverage_depth_vec = extract_field(coverage_data, |item| item.coverage_depth);
     let consensus_vec = extract_field(coverage_data, |item| item.consensus.clone());
     let deletions_vec = extract_field(coverage_data, |item| item.deletions);
@@ -125,6 +222,7 @@ pub fn write_coverage_to_parquet(
     let sample_array: ArrayRef = Arc::new(StringArray::from(sample_ids_vec));
     let ref_name_array: ArrayRef = Arc::new(StringArray::from(ref_name_vec));
     let positions_array: ArrayRef = Arc::new(Int32Array::from(position_vec));
+    let coordinate_system_array: ArrayRef = Arc::new(StringArray::from(coordinate_system_vec));
     let coverage_depth_array: ArrayRef = Arc::new(Int32Array::from(coverage_depth_vec));
     let consensus_array: ArrayRef = Arc::new(StringArray::from(consensus_vec));
     let deletions_array: ArrayRef = Arc::new(Int32Array::from(deletions_vec));
@@ -140,6 +238,7 @@ pub fn write_coverage_to_parquet(
         Field::new("sample_id", DataType::Utf8, true),
         Field::new("reference_name", DataType::Utf8, true),
         Field::new("position", DataType::Int32, true),
+        Field::new("coordinate_system", DataType::Utf8, true),
         Field::new("coverage_depth", DataType::Int32, true),
         Field::new("consensus", DataType::Utf8, true),
         Field::new("deletions", DataType::Int32, true),
@@ -158,6 +257,7 @@ pub fn write_coverage_to_parquet(
             sample_array,
             ref_name_array,
             positions_array,
+            coordinate_system_array,
             coverage_depth_array,
             consensus_array,
             deletions_array,
@@ -170,12 +270,7 @@ pub fn write_coverage_to_parquet(
     )?;
 
     // Write the RecordBatch to a Parquet file
-    let file = File::create(output_file)?;
-    let mut writer = ArrowWriter::try_new(file, schema.clone(), None)?;
-    writer.write(&record_batch)?;
-    writer.close()?;
-
-    println!(" -> PARQUET written to {output_file}");
+    write_record_batch(&record_batch, columns, output_file)?;
 
     Ok(())
 }
@@ -184,6 +279,7 @@ pub fn write_coverage_to_parquet(
 pub fn write_reads_to_parquet(
     reads_data: &[ReadsData],
     output_file: &str,
+    columns: &ParquetColumns,
 ) -> Result<(), Box<dyn Error>> {
     // Convert values in struct to vector of values
     let sample_ids_vec: Vec<Option<String>> =
@@ -237,12 +333,263 @@ pub fn write_reads_to_parquet(
     )?;
 
     // Write the RecordBatch to a Parquet file
-    let file = File::create(output_file)?;
-    let mut writer = ArrowWriter::try_new(file, schema.clone(), None)?;
-    writer.write(&record_batch)?;
-    writer.close()?;
+    write_record_batch(&record_batch, columns, output_file)?;
+
+    Ok(())
+}
+
+/// Write the per-segment read fate data to parquet file.
+pub fn write_read_fate_to_parquet(
+    read_fate_data: &[ReadFateData],
+    output_file: &str,
+    columns: &ParquetColumns,
+) -> Result<(), Box<dyn Error>> {
+    // Convert values in struct to vector of values
+    let sample_ids_vec = extract_field(read_fate_data, |item| item.sample_id.clone());
+    let assignment_vec = extract_field(read_fate_data, |item| item.assignment.clone());
+    let reads_vec = extract_field(read_fate_data, |item| item.reads);
+    let percent_vec = extract_field(read_fate_data, |item| item.percent_of_pass_qc);
+    let runid_vec = extract_field(read_fate_data, |item| item.runid.clone());
+    let instrument_vec = extract_field(read_fate_data, |item| item.instrument.clone());
+
+    // Convert the vectors into Arrow columns
+    let sample_array: ArrayRef = Arc::new(StringArray::from(sample_ids_vec));
+    let assignment_array: ArrayRef = Arc::new(StringArray::from(assignment_vec));
+    let reads_array: ArrayRef = Arc::new(Int32Array::from(reads_vec));
+    let percent_array: ArrayRef = Arc::new(Float64Array::from(percent_vec));
+    let runid_array: ArrayRef = Arc::new(StringArray::from(runid_vec));
+    let instrument_array: ArrayRef = Arc::new(StringArray::from(instrument_vec));
+
+    // Define the schema for the Arrow IPC file
+    let fields = vec![
+        Field::new("sample_id", DataType::Utf8, true),
+        Field::new("assignment", DataType::Utf8, true),
+        Field::new("readcount", DataType::Int32, true),
+        Field::new("percent_of_pass_qc", DataType::Float64, true),
+        Field::new("runid", DataType::Utf8, true),
+        Field::new("machine", DataType::Utf8, true),
+    ];
+    let schema = Arc::new(Schema::new(fields));
+
+    // Create a RecordBatch
+    let record_batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            sample_array,
+            assignment_array,
+            reads_array,
+            percent_array,
+            runid_array,
+            instrument_array,
+        ],
+    )?;
+
+    // Write the RecordBatch to a Parquet file
+    write_record_batch(&record_batch, columns, output_file)?;
+
+    Ok(())
+}
+
+/// Write the secondary assemblies found by IRMA to parquet file.
+pub fn write_secondary_assemblies_to_parquet(
+    secondary_data: &[SecondaryAssembly],
+    output_file: &str,
+    columns: &ParquetColumns,
+) -> Result<(), Box<dyn Error>> {
+    // Convert values in struct to vector of values
+    let sample_ids_vec = extract_field(secondary_data, |item| item.sample_id.clone());
+    let reference_vec = extract_field(secondary_data, |item| item.reference.clone());
+    let subtype_vec = extract_field(secondary_data, |item| item.secondary_subtype.clone());
+    let reads_vec = extract_field(secondary_data, |item| item.reads);
+    let percent_vec = extract_field(secondary_data, |item| item.percent_of_pass_qc);
+    let runid_vec = extract_field(secondary_data, |item| item.runid.clone());
+    let instrument_vec = extract_field(secondary_data, |item| item.instrument.clone());
+
+    // Convert the vectors into Arrow columns
+    let sample_array: ArrayRef = Arc::new(StringArray::from(sample_ids_vec));
+    let reference_array: ArrayRef = Arc::new(StringArray::from(reference_vec));
+    let subtype_array: ArrayRef = Arc::new(StringArray::from(subtype_vec));
+    let reads_array: ArrayRef = Arc::new(Int32Array::from(reads_vec));
+    let percent_array: ArrayRef = Arc::new(Float64Array::from(percent_vec));
+    let runid_array: ArrayRef = Arc::new(StringArray::from(runid_vec));
+    let instrument_array: ArrayRef = Arc::new(StringArray::from(instrument_vec));
+
+    // Define the schema for the Arrow IPC file
+    let fields = vec![
+        Field::new("sample_id", DataType::Utf8, true),
+        Field::new("reference", DataType::Utf8, true),
+        Field::new("secondary_subtype", DataType::Utf8, true),
+        Field::new("readcount", DataType::Int32, true),
+        Field::new("percent_of_pass_qc", DataType::Float64, true),
+        Field::new("runid", DataType::Utf8, true),
+        Field::new("machine", DataType::Utf8, true),
+    ];
+    let schema = Arc::new(Schema::new(fields));
+
+    // Create a RecordBatch
+    let record_batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            sample_array,
+            reference_array,
+            subtype_array,
+            reads_array,
+            percent_array,
+            runid_array,
+            instrument_array,
+        ],
+    )?;
+
+    // Write the RecordBatch to a Parquet file
+    write_record_batch(&record_batch, columns, output_file)?;
+
+    Ok(())
+}
+
+/// Write the sample by mutation matrix to parquet file, a text column per mutation with empty
+/// cells as nulls.
+pub fn write_mutation_matrix_to_parquet(
+    matrix: &MutationMatrix,
+    output_file: &str,
+    columns: &ParquetColumns,
+) -> Result<(), Box<dyn Error>> {
+    let sample_vec: Vec<&str> = matrix
+        .rows
+        .iter()
+        .map(|(sample, _)| sample.as_str())
+        .collect();
+    let mut fields = vec![Field::new("sample", DataType::Utf8, false)];
+    let mut arrays: Vec<ArrayRef> = vec![Arc::new(StringArray::from(sample_vec))];
+    for (index, column) in matrix.columns.iter().enumerate() {
+        let cells: Vec<Option<&str>> = matrix
+            .rows
+            .iter()
+            .map(|(_, cells)| cells.get(index).and_then(Option::as_deref))
+            .collect();
+        fields.push(Field::new(column, DataType::Utf8, true));
+        arrays.push(Arc::new(StringArray::from(cells)));
+    }
 
-    println!(" -> PARQUET written to {output_file}");
+    let record_batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)?;
+    write_record_batch(&record_batch, columns, output_file)?;
+
+    Ok(())
+}
+
+/// Write the long form QC criteria to parquet file.
+pub fn write_qc_criteria_to_parquet(
+    qc_criteria: &[QCCriterion],
+    output_file: &str,
+    columns: &ParquetColumns,
+) -> Result<(), Box<dyn Error>> {
+    // Convert values in struct to vector of values
+    let sample_ids_vec = extract_field(qc_criteria, |item| item.sample_id.clone());
+    let reference_vec = extract_field(qc_criteria, |item| item.reference.clone());
+    let criterion_vec = extract_field(qc_criteria, |item| item.criterion.clone());
+    let observed_vec = extract_field(qc_criteria, |item| item.observed_value.clone());
+    let threshold_vec = extract_field(qc_criteria, |item| item.threshold.clone());
+    let status_vec = extract_field(qc_criteria, |item| item.status.clone());
+    let runid_vec = extract_field(qc_criteria, |item| item.runid.clone());
+    let instrument_vec = extract_field(qc_criteria, |item| item.instrument.clone());
+
+    // Convert the vectors into Arrow columns
+    let sample_array: ArrayRef = Arc::new(StringArray::from(sample_ids_vec));
+    let reference_array: ArrayRef = Arc::new(StringArray::from(reference_vec));
+    let criterion_array: ArrayRef = Arc::new(StringArray::from(criterion_vec));
+    let observed_array: ArrayRef = Arc::new(StringArray::from(observed_vec));
+    let threshold_array: ArrayRef = Arc::new(StringArray::from(threshold_vec));
+    let status_array: ArrayRef = Arc::new(StringArray::from(status_vec));
+    let runid_array: ArrayRef = Arc::new(StringArray::from(runid_vec));
+    let instrument_array: ArrayRef = Arc::new(StringArray::from(instrument_vec));
+
+    // Define the schema for the Arrow IPC file
+    let fields = vec![
+        Field::new("sample_id", DataType::Utf8, true),
+        Field::new("reference", DataType::Utf8, true),
+        Field::new("criterion", DataType::Utf8, true),
+        Field::new("observed_value", DataType::Utf8, true),
+        Field::new("threshold", DataType::Utf8, true),
+        Field::new("status", DataType::Utf8, true),
+        Field::new("runid", DataType::Utf8, true),
+        Field::new("instrument", DataType::Utf8, true),
+    ];
+    let schema = Arc::new(Schema::new(fields));
+
+    // Create a RecordBatch
+    let record_batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            sample_array,
+            reference_array,
+            criterion_array,
+            observed_array,
+            threshold_array,
+            status_array,
+            runid_array,
+            instrument_array,
+        ],
+    )?;
+
+    // Write the RecordBatch to a Parquet file
+    write_record_batch(&record_batch, columns, output_file)?;
+
+    Ok(())
+}
+
+/// Write the DI stats to parquet file. DI stats have no instrument column of their own, so the
+/// machine is given for the whole run.
+pub fn write_di_stats_to_parquet(
+    di_data: &[DIRecord],
+    machine: Option<&str>,
+    output_file: &str,
+    columns: &ParquetColumns,
+) -> Result<(), Box<dyn Error>> {
+    // Convert values in struct to vector of values
+    let sample_ids_vec = extract_field(di_data, |item| item.sample_id.clone());
+    let reference_vec = extract_field(di_data, |item| item.segment.clone());
+    let prime5_vec = extract_field(di_data, |item| item.prime5);
+    let prime3_vec = extract_field(di_data, |item| item.prime3);
+    let di_flag_vec = extract_field(di_data, |item| item.di_flag);
+    let runid_vec = extract_field(di_data, |item| item.run_id.clone());
+    let instrument_vec = extract_field(di_data, |_| machine.map(str::to_string));
+
+    // Convert the vectors into Arrow columns
+    let sample_array: ArrayRef = Arc::new(StringArray::from(sample_ids_vec));
+    let reference_array: ArrayRef = Arc::new(StringArray::from(reference_vec));
+    let prime5_array: ArrayRef = Arc::new(Float64Array::from(prime5_vec));
+    let prime3_array: ArrayRef = Arc::new(Float64Array::from(prime3_vec));
+    let di_flag_array: ArrayRef = Arc::new(BooleanArray::from(di_flag_vec));
+    let runid_array: ArrayRef = Arc::new(StringArray::from(runid_vec));
+    let instrument_array: ArrayRef = Arc::new(StringArray::from(instrument_vec));
+
+    // Define the schema for the Arrow IPC file
+    let fields = vec![
+        Field::new("sample_id", DataType::Utf8, true),
+        Field::new("reference", DataType::Utf8, true),
+        Field::new("di_ratio_5prime", DataType::Float64, true),
+        Field::new("di_ratio_3prime", DataType::Float64, true),
+        Field::new("di_flag", DataType::Boolean, true),
+        Field::new("runid", DataType::Utf8, true),
+        Field::new("machine", DataType::Utf8, true),
+    ];
+    let schema = Arc::new(Schema::new(fields));
+
+    // Create a RecordBatch
+    let record_batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            sample_array,
+            reference_array,
+            prime5_array,
+            prime3_array,
+            di_flag_array,
+            runid_array,
+            instrument_array,
+        ],
+    )?;
+
+    // Write the RecordBatch to a Parquet file
+    write_record_batch(&record_batch, columns, output_file)?;
 
     Ok(())
 }
@@ -252,6 +599,7 @@ pub fn write_reads_to_parquet(
 pub fn write_alleles_to_parquet(
     alleles_data: &[AllAllelesData],
     output_file: &str,
+    columns: &ParquetColumns,
 ) -> Result<(), Box<dyn Error>> {
     // Convert values in struct to vector of values
     let sample_ids_vec: Vec<Option<String>> =
@@ -329,12 +677,7 @@ pub fn write_alleles_to_parquet(
     )?;
 
     // Write the RecordBatch to a Parquet file
-    let file = File::create(output_file)?;
-    let mut writer = ArrowWriter::try_new(file, schema.clone(), None)?;
-    writer.write(&record_batch)?;
-    writer.close()?;
-
-    println!(" -> PARQUET written to {output_file}");
+    write_record_batch(&record_batch, columns, output_file)?;
 
     Ok(())
 }
@@ -343,6 +686,7 @@ pub fn write_alleles_to_parquet(
 pub fn write_indels_to_parquet(
     indels_data: &[IndelsData],
     output_file: &str,
+    columns: &ParquetColumns,
 ) -> Result<(), Box<dyn Error>> {
     // Convert values in struct to vector of values
     let sample_ids_vec: Vec<Option<String>> =
@@ -411,12 +755,7 @@ pub fn write_indels_to_parquet(
     )?;
 
     // Write the RecordBatch to a Parquet file
-    let file = File::create(output_file)?;
-    let mut writer = ArrowWriter::try_new(file, schema.clone(), None)?;
-    writer.write(&record_batch)?;
-    writer.close()?;
-
-    println!(" -> PARQUET written to {output_file}");
+    write_record_batch(&record_batch, columns, output_file)?;
 
     Ok(())
 }
@@ -425,6 +764,7 @@ pub fn write_indels_to_parquet(
 pub fn write_minor_vars_to_parquet(
     minor_vars_data: &[MinorVariantsData],
     output_file: &str,
+    columns: &ParquetColumns,
 ) -> Result<(), Box<dyn Error>> {
     // Convert values in struct to vector of values
     let sample_ids_vec: Vec<Option<String>> =
@@ -436,7 +776,7 @@ pub fn write_minor_vars_to_parquet(
     let minority_allele_vec = extract_field(minor_vars_data, |item| item.minority_allele.clone());
     let consensus_count_vec = extract_field(minor_vars_data, |item| item.consensus_count);
     let minority_count_vec = extract_field(minor_vars_data, |item| item.minority_count);
-    let minority_frequency_vec = extract_field(minor_vars_data, |item| item.minority_frequency);
+    let minority_frequency_vec = extract_field(minor_vars_data, |item| item.minority_frequency.0);
     let runid_vec = extract_field(minor_vars_data, |item| item.run_id.clone());
     let instrument_vec = extract_field(minor_vars_data, |item| item.instrument.clone());
 
@@ -488,12 +828,7 @@ pub fn write_minor_vars_to_parquet(
     )?;
 
     // Write the RecordBatch to a Parquet file
-    let file = File::create(output_file)?;
-    let mut writer = ArrowWriter::try_new(file, schema.clone(), None)?;
-    writer.write(&record_batch)?;
-    writer.close()?;
-
-    println!(" -> PARQUET written to {output_file}");
+    write_record_batch(&record_batch, columns, output_file)?;
 
     Ok(())
 }
@@ -503,6 +838,7 @@ pub fn write_irma_summary_to_parquet(
     irma_summary_data: &[IRMASummary],
     virus: &str,
     output_file: &str,
+    columns: &ParquetColumns,
 ) -> Result<(), Box<dyn Error>> {
     if irma_summary_data.is_empty() {
         return Err("Input data is empty".into());
@@ -513,16 +849,21 @@ pub fn write_irma_summary_to_parquet(
     let pass_qc_vec = extract_field(irma_summary_data, |item| item.pass_qc);
     let reads_mapped_vec = extract_field(irma_summary_data, |item| item.reads_mapped);
     let reference_vec = extract_field(irma_summary_data, |item| item.reference.clone());
-    let percent_reference_coverage_vec =
-        extract_field(irma_summary_data, |item| item.percent_reference_coverage);
-    let median_coverage_vec = extract_field(irma_summary_data, |item| item.median_coverage);
+    let percent_reference_coverage_vec = extract_field(irma_summary_data, |item| {
+        item.percent_reference_coverage.map(f64::from)
+    });
+    let median_coverage_vec = extract_field(irma_summary_data, |item| {
+        item.median_coverage.map(i32::from)
+    });
     let count_minor_snv_at_or_over_5_pct_vec = extract_field(irma_summary_data, |item| {
         item.count_minor_snv_at_or_over_5_pct
     });
-    let spike_percent_coverage_vec =
-        extract_field(irma_summary_data, |item| item.spike_percent_coverage);
-    let spike_median_coverage_vec =
-        extract_field(irma_summary_data, |item| item.spike_median_coverage);
+    let spike_percent_coverage_vec = extract_field(irma_summary_data, |item| {
+        item.spike_percent_coverage.map(f64::from)
+    });
+    let spike_median_coverage_vec = extract_field(irma_summary_data, |item| {
+        item.spike_median_coverage.map(i32::from)
+    });
     let pass_fail_reason_vec =
         extract_field(irma_summary_data, |item| item.pass_fail_reason.clone());
     let subtype_vec = extract_field(irma_summary_data, |item| item.subtype.clone());
@@ -609,15 +950,51 @@ pub fn write_irma_summary_to_parquet(
         arrays.insert(insert_idx, Arc::new(StringArray::from(di_ratios_vec)));
     }
 
+    // Secondary assembly subtypes, when they were ingested
+    if irma_summary_data
+        .iter()
+        .any(|item| item.secondary_subtypes.is_some())
+    {
+        let secondary_subtypes_vec =
+            extract_field(irma_summary_data, |item| item.secondary_subtypes.clone());
+        fields.push(Field::new("secondary_subtypes", DataType::Utf8, true));
+        arrays.push(Arc::new(StringArray::from(secondary_subtypes_vec)));
+    }
+
+    // Passthrough columns from --metadata, empty values as nulls
+    if let Some(first) = irma_summary_data.first() {
+        for (index, column) in first.metadata.columns().enumerate() {
+            let values = extract_field(irma_summary_data, |item| {
+                item.metadata
+                    .0
+                    .get(index)
+                    .map(|(_, value)| value.clone())
+                    .filter(|value| !value.is_empty())
+            });
+            fields.push(Field::new(column, DataType::Utf8, true));
+            arrays.push(Arc::new(StringArray::from(values)));
+        }
+    }
+
+    // Column descriptions and units travel with the file as field metadata
+    let fields: Vec<Field> = fields
+        .into_iter()
+        .map(|field| match IRMASummary::column_doc(field.name()) {
+            Some(doc) => {
+                let mut metadata = HashMap::from([("description".to_string(), doc.description)]);
+                if let Some(unit) = doc.unit {
+                    metadata.insert("unit".to_string(), unit);
+                }
+                field.with_metadata(metadata)
+            }
+            None => field,
+        })
+        .collect();
+
     let schema = Arc::new(Schema::new(fields));
     let record_batch = RecordBatch::try_new(schema.clone(), arrays)?;
 
-    let file = File::create(output_file)?;
-    let mut writer = ArrowWriter::try_new(file, schema.clone(), None)?;
-    writer.write(&record_batch)?;
-    writer.close()?;
-
-    println!(" -> PARQUET written to {output_file}");
+    write_record_batch(&record_batch, columns, output_file)?;
 
     Ok(())
 }
@@ -626,6 +1003,7 @@ pub fn write_irma_summary_to_parquet(
 pub fn write_nt_seq_to_parquet(
     nt_seq_data: &[NTSequences],
     output_file: &str,
+    columns: &ParquetColumns,
 ) -> Result<(), Box<dyn Error>> {
     // Convert values in struct to vector of values
     let sample_ids_vec: Vec<String> = extract_field(nt_seq_data, |item| item.sample_id.clone());
@@ -668,12 +1046,7 @@ pub fn write_nt_seq_to_parquet(
     )?;
 
     // Write the RecordBatch to a Parquet file
-    let file = File::create(output_file)?;
-    let mut writer = ArrowWriter::try_new(file, schema.clone(), None)?;
-    writer.write(&record_batch)?;
-    writer.close()?;
-
-    println!(" -> PARQUET written to {output_file}");
+    write_record_batch(&record_batch, columns, output_file)?;
 
     Ok(())
 }
@@ -682,6 +1055,7 @@ pub fn write_nt_seq_to_parquet(
 pub fn write_aa_seq_to_parquet(
     aa_seq_data: &[AASequences],
     output_file: &str,
+    columns: &ParquetColumns,
 ) -> Result<(), Box<dyn Error>> {
     // Convert values in struct to vector of values
     let sample_ids_vec: Vec<String> = extract_field(aa_seq_data, |item| item.sample_id.clone());
@@ -724,12 +1098,7 @@ pub fn write_aa_seq_to_parquet(
     )?;
 
     // Write the RecordBatch to a Parquet file
-    let file = File::create(output_file)?;
-    let mut writer = ArrowWriter::try_new(file, schema.clone(), None)?;
-    writer.write(&record_batch)?;
-    writer.close()?;
-
-    println!(" -> PARQUET written to {output_file}");
+    write_record_batch(&record_batch, columns, output_file)?;
 
     Ok(())
 }
@@ -738,6 +1107,7 @@ pub fn write_aa_seq_to_parquet(
 pub fn write_run_info_to_parquet(
     run_info_data: &[RunInfo],
     output_file: &str,
+    columns: &ParquetColumns,
 ) -> Result<(), Box<dyn Error>> {
     // Convert values in struct to vector of values
     let program_name_vec: Vec<Option<String>> =
@@ -781,12 +1151,7 @@ pub fn write_run_info_to_parquet(
     )?;
 
     // Write the RecordBatch to a Parquet file
-    let file = File::create(output_file)?;
-    let mut writer = ArrowWriter::try_new(file, schema.clone(), None)?;
-    writer.write(&record_batch)?;
-    writer.close()?;
-
-    println!(" -> PARQUET written to {output_file}");
+    write_record_batch(&record_batch, columns, output_file)?;
 
     Ok(())
 }
@@ -795,6 +1160,7 @@ pub fn write_run_info_to_parquet(
 pub fn write_samplesheet_to_parquet(
     samplesheet: Samplesheet,
     output_file: &str,
+    columns: &ParquetColumns,
     runid: &str,
     instrument: &str,
 ) -> Result<(), Box<dyn Error>> {
@@ -836,12 +1202,7 @@ pub fn write_samplesheet_to_parquet(
             )?;
 
             // Write the RecordBatch to a Parquet file
-            let file = File::create(output_file)?;
-            let mut writer = ArrowWriter::try_new(file, schema.clone(), None)?;
-            writer.write(&record_batch)?;
-            writer.close()?;
-
-            println!(" -> PARQUET written to {output_file}");
+            write_record_batch(&record_batch, columns, output_file)?;
         }
         Samplesheet::ONT(data) => {
             // Extract fields from SamplesheetO
@@ -884,12 +1245,7 @@ pub fn write_samplesheet_to_parquet(
             )?;
 
             // Write the RecordBatch to a Parquet file
-            let file = File::create(output_file)?;
-            let mut writer = ArrowWriter::try_new(file, schema.clone(), None)?;
-            writer.write(&record_batch)?;
-            writer.close()?;
-
-            println!(" -> PARQUET written to {output_file}");
+            write_record_batch(&record_batch, columns, output_file)?;
         }
     }
 
@@ -902,6 +1258,7 @@ pub fn write_updated_irma_summary_to_parquet(
     summary_data: &[UpdatedIRMASummary],
     virus: &str,
     output_file: &str,
+    columns: &ParquetColumns,
 ) -> Result<(), Box<dyn Error>> {
     if summary_data.is_empty() {
         return Err("Input data is empty".into());
@@ -913,9 +1270,10 @@ pub fn write_updated_irma_summary_to_parquet(
     let pass_qc_vec = extract_field(summary_data, |i| i.pass_qc);
     let reads_mapped_vec = extract_field(summary_data, |i| i.reads_mapped);
     let reference_vec = extract_field(summary_data, |i| i.reference.clone());
-    let percent_reference_coverage_vec =
-        extract_field(summary_data, |i| i.percent_reference_coverage);
-    let median_coverage_vec = extract_field(summary_data, |i| i.median_coverage);
+    let percent_reference_coverage_vec = extract_field(summary_data, |i| {
+        i.percent_reference_coverage.map(f64::from)
+    });
+    let median_coverage_vec = extract_field(summary_data, |i| i.median_coverage.map(i32::from));
     let count_minor_snv_at_or_over_5_pct_vec =
         extract_field(summary_data, |i| i.count_minor_snv_at_or_over_5_pct);
     let pass_fail_reason_vec = extract_field(summary_data, |i| i.pass_fail_reason.clone());
@@ -949,8 +1307,10 @@ pub fn write_updated_irma_summary_to_parquet(
 
     // SC2 spike fields
     if virus == "sc2-wgs" {
-        let spike_percent_coverage_vec = extract_field(summary_data, |i| i.spike_percent_coverage);
-        let spike_median_coverage_vec = extract_field(summary_data, |i| i.spike_median_coverage);
+        let spike_percent_coverage_vec =
+            extract_field(summary_data, |i| i.spike_percent_coverage.map(f64::from));
+        let spike_median_coverage_vec =
+            extract_field(summary_data, |i| i.spike_median_coverage.map(i32::from));
 
         fields.push(Field::new(
             "spike_percent_coverage",
@@ -1047,11 +1407,6 @@ pub fn write_updated_irma_summary_to_parquet(
     let schema = Arc::new(Schema::new(fields));
     let record_batch = RecordBatch::try_new(schema.clone(), arrays)?;
 
-    let file = File::create(output_file)?;
-    let mut writer = ArrowWriter::try_new(file, schema, None)?;
-    writer.write(&record_batch)?;
-    writer.close()?;
-
-    println!(" -> PARQUET written to {output_file}");
+    write_record_batch(&record_batch, columns, output_file)?;
     Ok(())
 }
@@ -0,0 +1,180 @@
+use crate::io::data_ingest::SeqData;
+use crate::io::write_fasta_files::write_to_fasta;
+use crate::status;
+use glob::{Pattern, glob};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// How the IRMA artifacts of failed samples get into the quarantine tree
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum QuarantineMode {
+    /// Copy the files
+    Copy,
+    /// Symlink the files, to save space
+    Symlink,
+}
+
+/// A failed segment or protein of a quarantined sample and why it failed
+#[derive(Serialize, Debug)]
+pub struct QuarantinedSequence {
+    pub name: String,
+    pub pass_fail_reason: String,
+}
+
+/// The README.json of a quarantined sample
+#[derive(Serialize, Debug)]
+pub struct QuarantineRecord {
+    pub sample_id: String,
+    pub runid: String,
+    pub failed_segments: Vec<QuarantinedSequence>,
+    pub failed_proteins: Vec<QuarantinedSequence>,
+    /// Files in the sample's quarantine directory
+    pub artifacts: Vec<String>,
+}
+
+// Failed FASTA headers are `<sample> | <segment or protein> | <reason>`
+fn split_failed_header(header: &str) -> (&str, &str, &str) {
+    let mut parts = header.splitn(3, " | ");
+    let sample = parts.next().unwrap_or(header);
+    let name = parts.next().unwrap_or_default();
+    let reason = parts.next().unwrap_or_default();
+    (sample, name, reason)
+}
+
+fn group_by_sample(failed_seqs: &[SeqData]) -> BTreeMap<&str, Vec<&SeqData>> {
+    let mut by_sample: BTreeMap<&str, Vec<&SeqData>> = BTreeMap::new();
+    for seq in failed_seqs {
+        let (sample, _, _) = split_failed_header(&seq.name);
+        by_sample.entry(sample).or_default().push(seq);
+    }
+    by_sample
+}
+
+fn quarantined_sequences(seqs: Option<&Vec<&SeqData>>) -> Vec<QuarantinedSequence> {
+    seqs.into_iter()
+        .flatten()
+        .map(|seq| {
+            let (_, name, reason) = split_failed_header(&seq.name);
+            QuarantinedSequence {
+                name: name.to_string(),
+                pass_fail_reason: reason.to_string(),
+            }
+        })
+        .collect()
+}
+
+fn place_artifact(source: &Path, target: &Path, mode: QuarantineMode) -> std::io::Result<()> {
+    if target.exists() || target.is_symlink() {
+        fs::remove_file(target)?;
+    }
+    match mode {
+        QuarantineMode::Copy => fs::copy(source, target).map(|_| ()),
+        // Absolute, so the link survives the quarantine tree being read from elsewhere
+        QuarantineMode::Symlink => std::os::unix::fs::symlink(fs::canonicalize(source)?, target),
+    }
+}
+
+/// Moves everything downstream submission could pick up for a failed sample into
+/// `quarantine/<sample_id>/`: its failed nucleotide and amino acid consensus, its IRMA amended
+/// consensus files (copied or symlinked) and a README.json with the failure reasons. The
+/// run level failed consensus FASTAs are moved into `quarantine/` as well.
+pub fn write_quarantine(
+    output_path: &Path,
+    irma_path: &Path,
+    runid: &str,
+    failed_nt_seqs: &[SeqData],
+    failed_aa_seqs: &[SeqData],
+    mode: QuarantineMode,
+) -> Result<(), Box<dyn Error>> {
+    let quarantine_path = output_path.join("quarantine");
+    fs::create_dir_all(&quarantine_path)?;
+
+    for failed_fasta in [
+        format!("mira_{runid}_failed_amended_consensus.fasta"),
+        format!("mira_{runid}_failed_amino_acid_consensus.fasta"),
+    ] {
+        let source = output_path.join(&failed_fasta);
+        if source.exists() {
+            fs::rename(&source, quarantine_path.join(&failed_fasta))?;
+        }
+    }
+
+    let nt_by_sample = group_by_sample(failed_nt_seqs);
+    let aa_by_sample = group_by_sample(failed_aa_seqs);
+    let mut samples: Vec<&str> = nt_by_sample
+        .keys()
+        .chain(aa_by_sample.keys())
+        .copied()
+        .collect();
+    samples.sort_unstable();
+    samples.dedup();
+
+    for sample in &samples {
+        let sample_path = quarantine_path.join(sample);
+        fs::create_dir_all(&sample_path)?;
+        let mut artifacts = Vec::new();
+
+        for (file_name, seqs) in [
+            ("failed_amended_consensus.fasta", nt_by_sample.get(sample)),
+            (
+                "failed_amino_acid_consensus.fasta",
+                aa_by_sample.get(sample),
+            ),
+        ] {
+            if let Some(seqs) = seqs {
+                let seqs: Vec<SeqData> = seqs
+                    .iter()
+                    .map(|seq| SeqData {
+                        name: seq.name.clone(),
+                        sequence: seq.sequence.clone(),
+                    })
+                    .collect();
+                write_to_fasta(&sample_path.join(file_name).display().to_string(), &seqs)?;
+                artifacts.push(file_name.to_string());
+            }
+        }
+
+        // The amended consensus IRMA wrote for the sample
+        let pattern = format!(
+            "{}/{}/IRMA/*/amended_consensus/*",
+            Pattern::escape(&irma_path.display().to_string()),
+            Pattern::escape(sample)
+        );
+        let irma_files: Vec<PathBuf> = glob(&pattern)?.filter_map(Result::ok).collect();
+        if !irma_files.is_empty() {
+            let irma_target = sample_path.join("amended_consensus");
+            fs::create_dir_all(&irma_target)?;
+            for source in irma_files.iter().filter(|path| path.is_file()) {
+                let Some(file_name) = source.file_name() else {
+                    continue;
+                };
+                place_artifact(source, &irma_target.join(file_name), mode)?;
+                artifacts.push(format!("amended_consensus/{}", file_name.to_string_lossy()));
+            }
+        }
+
+        let record = QuarantineRecord {
+            sample_id: (*sample).to_string(),
+            runid: runid.to_string(),
+            failed_segments: quarantined_sequences(nt_by_sample.get(sample)),
+            failed_proteins: quarantined_sequences(aa_by_sample.get(sample)),
+            artifacts,
+        };
+        let mut file = File::create(sample_path.join("README.json"))?;
+        file.write_all(serde_json::to_string_pretty(&record)?.as_bytes())?;
+    }
+
+    status!(
+        " -> {} failed samples quarantined in {}",
+        samples.len(),
+        quarantine_path.display()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use crate::io::data_ingest::CoverageData;
+use crate::status;
+use crate::utils::data_processing::IRMASummary;
+use rust_xlsxwriter::{Format, Sparkline, SparklineType, Workbook, Worksheet, XlsxError};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Number of points each coverage sparkline is reduced to
+const SPARKLINE_BINS: usize = 100;
+const COVERAGE_SHEET: &str = "Coverage";
+
+// Mean depth in equal width bins along the segment, which is all a sparkline needs to show the
+// shape of the assembly
+#[allow(clippy::cast_precision_loss)]
+fn binned_depths(depths: &[i32]) -> Vec<f64> {
+    if depths.is_empty() {
+        return Vec::new();
+    }
+    let bins = SPARKLINE_BINS.min(depths.len());
+    (0..bins)
+        .map(|bin| {
+            let start = bin * depths.len() / bins;
+            let end = ((bin + 1) * depths.len() / bins).max(start + 1);
+            let window = &depths[start..end];
+            window.iter().map(|&d| f64::from(d)).sum::<f64>() / window.len() as f64
+        })
+        .collect()
+}
+
+/// Writes the run summary as an Excel workbook with a coverage sparkline per sample/segment,
+/// so the assembly shape can be scanned without opening the HTML reports. The binned depths
+/// behind the sparklines are kept on a hidden sheet.
+pub fn write_summary_xlsx(
+    output_path: &Path,
+    runid: &str,
+    irma_summary: &[IRMASummary],
+    coverage_data: &[CoverageData],
+) -> Result<PathBuf, XlsxError> {
+    // Depths in position order for every sample/segment
+    let mut depths: HashMap<(&str, &str), Vec<(i32, i32)>> = HashMap::new();
+    for entry in coverage_data {
+        if let Some(sample) = entry.sample_id.as_deref() {
+            depths
+                .entry((sample, entry.reference_name.as_str()))
+                .or_default()
+                .push((entry.position, entry.coverage_depth));
+        }
+    }
+
+    let mut summary_sheet = Worksheet::new();
+    summary_sheet.set_name("Summary")?;
+    let mut coverage_sheet = Worksheet::new();
+    coverage_sheet.set_name(COVERAGE_SHEET)?;
+    coverage_sheet.set_hidden(true);
+
+    let bold = Format::new().set_bold();
+    let headers = [
+        "Sample",
+        "Reference",
+        "Subtype",
+        "% Reference Covered",
+        "Median Coverage",
+        "Minor SNVs >= 5%",
+        "Pass/Fail Reason",
+        "Coverage",
+    ];
+    for (col, header) in (0u16..).zip(headers) {
+        summary_sheet.write_with_format(0, col, header, &bold)?;
+    }
+    let sparkline_col = u16::try_from(headers.len() - 1).unwrap_or(u16::MAX);
+    // Passthrough columns from --metadata, after the sparklines
+    if let Some(first) = irma_summary.first() {
+        for (col, column) in (sparkline_col + 1..).zip(first.metadata.columns()) {
+            summary_sheet.write_with_format(0, col, column, &bold)?;
+        }
+    }
+    summary_sheet.set_column_width(0, 20)?;
+    summary_sheet.set_column_width(1, 18)?;
+    summary_sheet.set_column_width(6, 30)?;
+    summary_sheet.set_column_width(sparkline_col, 30)?;
+
+    let mut data_col: u16 = 0;
+    for (row, summary) in (1u32..).zip(irma_summary) {
+        let reference = summary.reference.as_deref().unwrap_or("");
+        summary_sheet.write(row, 0, summary.sample_id.as_str())?;
+        summary_sheet.write(row, 1, reference)?;
+        summary_sheet.write(row, 2, summary.subtype.as_deref().unwrap_or(""))?;
+        if let Some(coverage) = summary.percent_reference_coverage {
+            summary_sheet.write(row, 3, f64::from(coverage))?;
+        }
+        if let Some(median) = summary.median_coverage {
+            summary_sheet.write(row, 4, i32::from(median))?;
+        }
+        if let Some(minor_snvs) = summary.count_minor_snv_at_or_over_5_pct {
+            summary_sheet.write(row, 5, minor_snvs)?;
+        }
+        summary_sheet.write(row, 6, summary.pass_fail_reason.as_deref().unwrap_or(""))?;
+        for (col, (_, value)) in (sparkline_col + 1..).zip(&summary.metadata.0) {
+            summary_sheet.write(row, col, value.as_str())?;
+        }
+
+        let Some(segment_depths) = depths.get_mut(&(summary.sample_id.as_str(), reference)) else {
+            continue;
+        };
+        segment_depths.sort_unstable_by_key(|&(position, _)| position);
+        let binned = binned_depths(
+            &segment_depths
+                .iter()
+                .map(|&(_, depth)| depth)
+                .collect::<Vec<_>>(),
+        );
+        if binned.is_empty() {
+            continue;
+        }
+
+        coverage_sheet.write(0, data_col, format!("{} {reference}", summary.sample_id))?;
+        for (data_row, depth) in (1u32..).zip(&binned) {
+            coverage_sheet.write(data_row, data_col, *depth)?;
+        }
+        let last_row = u32::try_from(binned.len()).unwrap_or(u32::MAX);
+        let sparkline = Sparkline::new()
+            .set_range((COVERAGE_SHEET, 1, data_col, last_row, data_col))
+            .set_type(SparklineType::Line)
+            .show_low_point(true);
+        summary_sheet.add_sparkline(row, sparkline_col, &sparkline)?;
+        data_col += 1;
+    }
+
+    let mut workbook = Workbook::new();
+    workbook.push_worksheet(summary_sheet);
+    workbook.push_worksheet(coverage_sheet);
+
+    let out_path = output_path.join(format!("mira_{runid}_summary.xlsx"));
+    workbook.save(&out_path)?;
+    status!(" -> XLSX written to {}", out_path.display());
+
+    Ok(out_path)
+}
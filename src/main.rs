@@ -7,17 +7,32 @@
 use crate::processes::{
     all_sample_hd::{HammingArgs, all_sample_hd_process},
     all_sample_nt_diffs::{NTDiffsArgs, all_sample_nt_diffs_process},
+    append_csv::{AppendCsvArgs, append_csv_process},
     check_mira_version::{MiraVersionArgs, check_mira_version},
     create_nextflow_samplesheet::{SamplesheetArgs, create_nextflow_samplesheet},
     di_stats::{DIStatArgs, di_stats_process},
+    fastq_stats::{FastqStatsArgs, fastq_stats_process},
     find_chemistry::{FindChemArgs, find_chemistry_process},
+    ont_yield::{OntYieldArgs, ont_yield_process},
+    parquet_compact::{CompactArgs, parquet_compact_process},
     plotter::{PlotterArgs, plotter_process},
     positions_of_interest::{PositionsArgs, positions_of_interest_process},
     prepare_mira_reports::{ReportsArgs, prepare_mira_reports_process},
+    qc_config::{QcConfigArgs, qc_config_process},
+    qc_sweep::{QcSweepArgs, qc_sweep_process},
+    read_trace::{ReadTraceArgs, read_trace_process},
+    reference_panel::{ReferencePanelArgs, reference_panel_process},
+    report_index::{ReportIndexArgs, report_index_process},
     samplesheet_check::{SamplesheetCheckArgs, samplesheet_check},
+    snp_clusters::{SnpClustersArgs, snp_clusters_process},
     summary_report_update::{SummaryUpdateArgs, summary_report_update_process},
+    swap_check::{SwapCheckArgs, swap_check_process},
     variants_of_interest::{VariantsArgs, variants_of_interest_process},
 };
+use crate::utils::{
+    cancel::{PREEMPTED_EXIT_CODE, is_cancellation},
+    output::set_quiet,
+};
 use clap::{Parser, Subcommand};
 use zoe::prelude::OrFail;
 
@@ -27,6 +42,10 @@ use zoe::prelude::OrFail;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Only write data to stdout and errors/warnings to stderr, no progress messages
+    #[arg(long, global = true, visible_alias = "json-only")]
+    quiet: bool,
 }
 
 #[derive(Subcommand, Debug)]
@@ -55,11 +74,34 @@ enum Commands {
     SamplesheetCheck(SamplesheetCheckArgs),
     /// DI Stats
     DIStats(DIStatArgs),
+    /// Read trace at a position (debug)
+    ReadTrace(ReadTraceArgs),
+    /// HTML report index
+    ReportIndex(ReportIndexArgs),
+    /// QC threshold sweep
+    QcSweep(QcSweepArgs),
+    /// QC config tools
+    QcConfig(QcConfigArgs),
+    /// Compact run level parquet files
+    Compact(CompactArgs),
+    /// FASTQ read and quality statistics
+    FastqStats(FastqStatsArgs),
+    /// Build the variants of interest reference panel
+    ReferencePanel(ReferencePanelArgs),
+    /// Append rows to a shared run CSV
+    AppendCsv(AppendCsvArgs),
+    /// Flag possible sample swaps from HA and NA distances
+    SwapCheck(SwapCheckArgs),
+    /// Cluster samples within a SNP distance threshold
+    SnpClusters(SnpClustersArgs),
+    /// Per-barcode ONT read yield over time
+    OntYield(OntYieldArgs),
 }
 
 fn main() {
     let args = Cli::parse();
     let module = module_path!();
+    set_quiet(args.quiet);
 
     match args.command {
         Commands::VariantsOfInterest(cmd_args) => {
@@ -84,8 +126,12 @@ fn main() {
             check_mira_version(&cmd_args).unwrap_or_die(&format!("{module}::CheckMiraVersion"));
         }
         Commands::PrepareMiraReports(cmd_args) => {
-            prepare_mira_reports_process(&cmd_args)
-                .unwrap_or_else(|e| panic!("{module}::PrepareMiraReports: {e}"));
+            if let Err(e) = prepare_mira_reports_process(&cmd_args) {
+                if is_cancellation(e.as_ref()) {
+                    std::process::exit(PREEMPTED_EXIT_CODE);
+                }
+                panic!("{module}::PrepareMiraReports: {e}");
+            }
         }
         Commands::SummaryReportUpdate(cmd_args) => {
             summary_report_update_process(&cmd_args)
@@ -101,6 +147,45 @@ fn main() {
         Commands::DIStats(cmd_args) => {
             di_stats_process(&cmd_args).unwrap_or_die(&format!("{module}::DIStats"));
         }
+        Commands::ReadTrace(cmd_args) => {
+            read_trace_process(&cmd_args).unwrap_or_die(&format!("{module}::ReadTrace"));
+        }
+        Commands::ReportIndex(cmd_args) => {
+            report_index_process(&cmd_args).unwrap_or_die(&format!("{module}::ReportIndex"));
+        }
+        Commands::QcSweep(cmd_args) => {
+            qc_sweep_process(&cmd_args).unwrap_or_else(|e| panic!("{module}::QcSweep: {e}"));
+        }
+        Commands::QcConfig(cmd_args) => {
+            qc_config_process(&cmd_args).unwrap_or_else(|e| panic!("{module}::QcConfig: {e}"));
+        }
+        Commands::Compact(cmd_args) => {
+            if let Err(e) = parquet_compact_process(&cmd_args) {
+                if is_cancellation(e.as_ref()) {
+                    std::process::exit(PREEMPTED_EXIT_CODE);
+                }
+                panic!("{module}::Compact: {e}");
+            }
+        }
+        Commands::FastqStats(cmd_args) => {
+            fastq_stats_process(&cmd_args).unwrap_or_else(|e| panic!("{module}::FastqStats: {e}"));
+        }
+        Commands::ReferencePanel(cmd_args) => {
+            reference_panel_process(&cmd_args)
+                .unwrap_or_else(|e| panic!("{module}::ReferencePanel: {e}"));
+        }
+        Commands::AppendCsv(cmd_args) => {
+            append_csv_process(&cmd_args).unwrap_or_else(|e| panic!("{module}::AppendCsv: {e}"));
+        }
+        Commands::SwapCheck(cmd_args) => {
+            swap_check_process(&cmd_args).unwrap_or_die(&format!("{module}::SwapCheck"));
+        }
+        Commands::SnpClusters(cmd_args) => {
+            snp_clusters_process(&cmd_args).unwrap_or_die(&format!("{module}::SnpClusters"));
+        }
+        Commands::OntYield(cmd_args) => {
+            ont_yield_process(&cmd_args).unwrap_or_else(|e| panic!("{module}::OntYield: {e}"));
+        }
     }
 }
 
@@ -1,12 +1,33 @@
-use clap::Parser;
+use crate::constants::heatmap_ref::FLU_SEGMENTS;
+use crate::constants::status_palette::NEUTRAL_COLOR;
+use crate::io::virus_plugins::strip_reference_suffix;
+use crate::status;
+use crate::utils::{
+    alignment::{AlignMode, align_sequences, project_onto_reference},
+    completeness::{completeness, is_missing, terminal_trimmed_span, write_excluded_report},
+    coordinates::CoordinateSystem,
+    coverage_depths::{CoverageDepths, is_masked, read_coverage_depths, sequence_depths},
+    iupac::iupac_bases,
+    output::{Progress, output_writer},
+};
+use clap::{Parser, ValueEnum};
 use either::Either;
+use plotly::{
+    HeatMap, Layout, Plot, Scatter,
+    common::{ColorBar, ColorScale, ColorScalePalette, HoverInfo, Line, Mode, Title},
+    configuration::{ImageButtonFormats, ToImageButtonOptions},
+    layout::Axis,
+};
+use rayon::prelude::*;
 use std::{
     fs::OpenOptions,
-    io::{BufReader, BufWriter, Write, stdin, stdout},
-    path::PathBuf,
+    io::{BufReader, Write, stdin},
+    path::{Path, PathBuf},
 };
 use zoe::{data::fasta::FastaNT, distance::dna::NucleotidesDistance, prelude::*};
 
+// Each bool is an independent --flag of the subcommand, not state to fold into an enum
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Debug, Parser)]
 #[command(
     about = "Tool for calculating hamming distances between all samples within a given fasta file"
@@ -23,12 +44,740 @@ pub struct HammingArgs {
     #[arg(short = 'd', long)]
     /// Use the provider delimiter for separating fields. Default is ','
     output_delimiter: Option<char>,
+
+    #[arg(short = 'm', long, value_enum, default_value_t = MatrixFormat::Square)]
+    /// Layout of the distances. `phylip` ignores the delimiter
+    matrix_format: MatrixFormat,
+
+    #[arg(long)]
+    /// Count IUPAC ambiguity codes as matching any base they could stand for (R matches A and
+    /// G), instead of as differences
+    ambiguity_compatible: bool,
+
+    #[arg(long)]
+    /// Skip positions with an N or a gap in either sequence, instead of comparing them
+    ignore_missing: bool,
+
+    #[arg(long)]
+    /// Report p-distances (differences / compared positions) in the matrix. The long format
+    /// then has the difference count, compared positions and p-distance columns
+    p_distance: bool,
+
+    #[arg(long)]
+    /// Also report each pair's compared sites, percent identity and positions differing only
+    /// by an ambiguity code. Columns in the long format, otherwise extra matrices written next
+    /// to the output (`<output>_compared_sites.csv`, ...)
+    pair_stats: bool,
+
+    #[arg(long, value_enum, default_value_t = AlignMode::None)]
+    /// Align the sequences before comparing them, for unaligned or unequal length input.
+    /// `pairwise` aligns every pair, `reference` aligns every sequence to one of them
+    align: AlignMode,
+
+    #[arg(long)]
+    /// Name of the input sequence to align to with `--align reference`. Default is the first
+    align_reference: Option<String>,
+
+    #[arg(long)]
+    /// Optional Newick file for a tree built from the distances (p-distances with
+    /// `--p-distance`)
+    tree: Option<PathBuf>,
+
+    #[arg(long, value_enum, default_value_t = TreeMethod::Nj, requires = "tree")]
+    /// How the `--tree` is built
+    tree_method: TreeMethod,
+
+    #[arg(long, requires = "output_xsv", conflicts_with = "align_reference")]
+    /// Split flu sequences by the segment in their names (`s1 | A_HA_H3`, `s1_HA` or IRMA's
+    /// `s1_4`) and write one matrix per segment, named `<output>_<segment>`
+    by_segment: bool,
+
+    #[arg(long)]
+    /// Optional HTML heat map of the distances, with the sequences ordered by a UPGMA
+    /// dendrogram drawn next to it
+    heatmap: Option<PathBuf>,
+
+    #[arg(long, default_value_t = 20_000_000)]
+    /// Refuse inputs with more pairs of sequences than this, instead of running out of memory
+    /// or time. 20 million pairs is about 6,300 sequences
+    max_pairs: usize,
+
+    #[arg(long, conflicts_with = "align")]
+    /// Optional MIRA coverage parquet file (mira_<runid>_coverage.parq) or IRMA run directory
+    /// with the coverage tables. Differences at positions where either sample has low depth
+    /// are down-weighted
+    coverage: Option<PathBuf>,
+
+    #[arg(long, requires = "coverage")]
+    /// Only use coverage of references containing this, for samples with several references in
+    /// the coverage file (e.g. HA)
+    coverage_segment: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = CoordinateSystem::Raw, requires = "coverage")]
+    /// Coordinates of the input sequences, which the coverage positions are put in: raw (IRMA
+    /// consensus), padded (padded consensus, as reported for SARS-CoV-2 and RSV) or hmm
+    coverage_coordinates: CoordinateSystem,
+
+    #[arg(long, default_value_t = 50, requires = "coverage")]
+    /// Depth at which a position counts fully with `--coverage`. Below it a position counts
+    /// as the lower depth of the pair divided by this
+    full_weight_depth: u32,
+
+    #[arg(long, requires = "coverage")]
+    /// Leave positions where either sample's depth is below this out of the comparison, so
+    /// low coverage miscalls aren't counted as differences
+    mask_depth: Option<u32>,
+
+    #[arg(long)]
+    /// Leave the leading and trailing Ns and gaps of each sequence out of its comparisons
+    trim_terminal_n: bool,
+
+    #[arg(long)]
+    /// Exclude sequences with a smaller fraction of base calls (not Ns or gaps) than this,
+    /// out of the length of the longest input sequence
+    min_completeness: Option<f64>,
+
+    #[arg(long, requires = "min_completeness")]
+    /// Optional delimited file listing the sequences excluded by `--min-completeness`
+    excluded_report: Option<PathBuf>,
+}
+
+/// Pairs compared between writes of the matrix
+const CHUNK_PAIRS: usize = 1 << 20;
+
+/// Flu segments by IRMA's segment number, 1 to 8
+const FLU_SEGMENT_NUMBERS: [&str; 8] = ["PB2", "PB1", "PA", "HA", "NP", "NA", "MP", "NS"];
+
+/// Layouts the distance matrix can be written in
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum MatrixFormat {
+    /// Full matrix with a header row of sequence names
+    Square,
+    /// Lower triangle without the diagonal, as MEGA reads
+    Lower,
+    /// Square PHYLIP distance matrix, as IQ-TREE and PHYLIP read
+    Phylip,
+    /// One row per pair of sequences
+    Long,
+}
+
+/// Values the matrix layouts can hold, the distance and with `--pair-stats` the statistics
+/// of each pair
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Layer {
+    Distance,
+    ComparedSites,
+    PercentIdentity,
+    AmbiguityDifferences,
+}
+
+impl Layer {
+    /// Suffix of the file a statistic's matrix is written to
+    fn suffix(self) -> &'static str {
+        match self {
+            Layer::Distance => "distance",
+            Layer::ComparedSites => "compared_sites",
+            Layer::PercentIdentity => "percent_identity",
+            Layer::AmbiguityDifferences => "ambiguity_differences",
+        }
+    }
+}
+
+/// Tree building methods for `--tree`
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum TreeMethod {
+    /// Neighbor-joining, an unrooted tree that allows for different rates along branches
+    Nj,
+    /// UPGMA, a rooted tree that assumes a molecular clock
+    Upgma,
 }
 
 #[derive(Debug)]
 struct ValidSeq {
     name: String,
     sequence: Nucleotides,
+    /// Uppercased sequence as read, keeping the ambiguity codes
+    raw: Vec<u8>,
+    /// Depth at each position of `raw` with `--coverage`. Gaps and positions without
+    /// coverage are `None`
+    depths: Option<Vec<Option<u32>>>,
+    /// Start and end of the positions of `raw` to compare, without the terminal Ns with
+    /// `--trim-terminal-n`
+    span: (usize, usize),
+}
+
+/// Differences between two sequences and the number of positions compared. The weighted
+/// values are the same sums with every position counted by its coverage weight, and equal the
+/// counts without `--coverage`
+#[derive(Debug, Clone, Copy)]
+struct PairDistance {
+    differences: usize,
+    compared: usize,
+    /// Positions where the sequences differ but are compatible through an ambiguity code (R
+    /// against A), whether or not they count as differences
+    ambiguity_differences: usize,
+    weighted_differences: f64,
+    weighted_compared: f64,
+}
+
+impl PairDistance {
+    #[allow(clippy::cast_precision_loss)]
+    fn p_distance(self) -> f64 {
+        if self.compared == 0 {
+            0.0
+        } else {
+            self.differences as f64 / self.compared as f64
+        }
+    }
+
+    fn percent_identity(self) -> f64 {
+        (1.0 - self.p_distance()) * 100.0
+    }
+
+    fn weighted_p_distance(self) -> f64 {
+        if self.weighted_compared == 0.0 {
+            0.0
+        } else {
+            self.weighted_differences / self.weighted_compared
+        }
+    }
+}
+
+/// Compares aligned sequences position by position, up to the length of the shorter one.
+/// `weight` gives how much each position counts towards the weighted distance, and `None` for
+/// masked positions, which aren't compared
+fn pair_distance(
+    seq1: &[u8],
+    seq2: &[u8],
+    ambiguity_compatible: bool,
+    ignore_missing: bool,
+    weight: impl Fn(usize) -> Option<f64>,
+) -> PairDistance {
+    let mut distance = PairDistance {
+        differences: 0,
+        compared: 0,
+        ambiguity_differences: 0,
+        weighted_differences: 0.0,
+        weighted_compared: 0.0,
+    };
+    for (position, (&a, &b)) in seq1.iter().zip(seq2).enumerate() {
+        if ignore_missing && (is_missing(a) || is_missing(b)) {
+            continue;
+        }
+        let Some(weight) = weight(position) else {
+            continue;
+        };
+        distance.compared += 1;
+        distance.weighted_compared += weight;
+        let (bases_a, bases_b) = (iupac_bases(a), iupac_bases(b));
+        let compatible = bases_a.iter().any(|base| bases_b.contains(base));
+        if a != b && compatible && (bases_a.len() > 1 || bases_b.len() > 1) {
+            distance.ambiguity_differences += 1;
+        }
+        let differs = a != b && !(ambiguity_compatible && compatible);
+        if differs {
+            distance.differences += 1;
+            distance.weighted_differences += weight;
+        }
+    }
+    distance
+}
+
+/// Weight of a position from the depths of the two samples: 1 at or above `full_weight_depth`,
+/// the lower depth over `full_weight_depth` below it. Positions without a depth count fully
+fn depth_weight(depth1: Option<u32>, depth2: Option<u32>, full_weight_depth: u32) -> f64 {
+    match depth1.into_iter().chain(depth2).min() {
+        Some(depth) if depth < full_weight_depth => f64::from(depth) / f64::from(full_weight_depth),
+        _ => 1.0,
+    }
+}
+
+/// Distances between every pair of sequences, stored as the upper triangle with the diagonal
+struct DistanceMatrix {
+    names: Vec<String>,
+    upper: Vec<PairDistance>,
+    p_distance: bool,
+    coverage_weighted: bool,
+    pair_stats: bool,
+    /// Entries at the start of the upper triangle that were written and let go
+    released: usize,
+}
+
+impl DistanceMatrix {
+    fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    fn pair(&self, r: usize, c: usize) -> PairDistance {
+        let n = self.len();
+        let index = if r <= c {
+            r * n - r * (r + 1) / 2 + c
+        } else {
+            c * n - c * (c + 1) / 2 + r
+        };
+        self.upper[index - self.released]
+    }
+
+    /// Lets go of the rows before `row`, which can't be read afterwards
+    fn release_rows(&mut self, row: usize) {
+        let n = self.len();
+        let row_start = row * n - row * (row + 1) / 2 + row;
+        self.upper.drain(..row_start - self.released);
+        self.released = row_start;
+    }
+
+    /// The distance a tree is built on, the difference count or with `p_distance` the
+    /// p-distance, coverage weighted with `coverage_weighted`
+    #[allow(clippy::cast_precision_loss)]
+    fn value(&self, r: usize, c: usize) -> f64 {
+        let pair = self.pair(r, c);
+        match (self.p_distance, self.coverage_weighted) {
+            (true, true) => pair.weighted_p_distance(),
+            (true, false) => pair.p_distance(),
+            (false, true) => pair.weighted_differences,
+            (false, false) => pair.differences as f64,
+        }
+    }
+
+    fn full(&self) -> Vec<Vec<f64>> {
+        (0..self.len())
+            .map(|r| (0..self.len()).map(|c| self.value(r, c)).collect())
+            .collect()
+    }
+
+    /// The matrix cell of a layer. The distance is the difference count or with `p_distance`
+    /// the p-distance, and weighted counts have 2 decimals
+    fn get(&self, r: usize, c: usize, layer: Layer) -> String {
+        let pair = self.pair(r, c);
+        match layer {
+            Layer::Distance if self.p_distance => format!("{:.6}", self.value(r, c)),
+            Layer::Distance if self.coverage_weighted => format!("{:.2}", self.value(r, c)),
+            Layer::Distance => pair.differences.to_string(),
+            Layer::ComparedSites => pair.compared.to_string(),
+            Layer::PercentIdentity => format!("{:.4}", pair.percent_identity()),
+            Layer::AmbiguityDifferences => pair.ambiguity_differences.to_string(),
+        }
+    }
+}
+
+// Newick labels can't contain its punctuation or whitespace unquoted
+fn newick_label(name: &str) -> String {
+    if name
+        .chars()
+        .any(|c| c.is_whitespace() || "()[]':;,".contains(c))
+    {
+        format!("'{}'", name.replace('\'', "''"))
+    } else {
+        name.to_string()
+    }
+}
+
+// Branch lengths without trailing zeros. Negative lengths from neighbor-joining are set to 0
+fn branch_length(length: f64) -> String {
+    let length = format!("{:.6}", length.max(0.0));
+    length
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// Neighbor-joining (Saitou and Nei) over a full distance matrix
+#[allow(clippy::cast_precision_loss)]
+fn neighbor_joining(names: &[String], distances: Vec<Vec<f64>>) -> String {
+    let mut nodes: Vec<String> = names.iter().map(|name| newick_label(name)).collect();
+    let mut d = distances;
+    match nodes.len() {
+        0 => return ";".to_string(),
+        1 => return format!("{};", nodes[0]),
+        2 => {
+            let half = branch_length(d[0][1] / 2.0);
+            return format!("({}:{half},{}:{half});", nodes[0], nodes[1]);
+        }
+        _ => {}
+    }
+
+    while nodes.len() > 3 {
+        let n = nodes.len();
+        let totals: Vec<f64> = d.iter().map(|row| row.iter().sum()).collect();
+        let mut closest = (0, 1);
+        let mut min_q = f64::INFINITY;
+        for (i, row) in d.iter().enumerate() {
+            for (j, distance) in row.iter().enumerate().skip(i + 1) {
+                let q = (n - 2) as f64 * distance - totals[i] - totals[j];
+                if q < min_q {
+                    min_q = q;
+                    closest = (i, j);
+                }
+            }
+        }
+        let (i, j) = closest;
+        let length_i = d[i][j] / 2.0 + (totals[i] - totals[j]) / (2.0 * (n - 2) as f64);
+        let length_j = d[i][j] - length_i;
+        let joined = format!(
+            "({}:{},{}:{})",
+            nodes[i],
+            branch_length(length_i),
+            nodes[j],
+            branch_length(length_j)
+        );
+        let joined_distances: Vec<f64> = (0..n)
+            .filter(|&k| k != i && k != j)
+            .map(|k| (d[i][k] + d[j][k] - d[i][j]) / 2.0)
+            .collect();
+
+        // j > i, so removing j first keeps i in place
+        for index in [j, i] {
+            nodes.remove(index);
+            d.remove(index);
+            for row in &mut d {
+                row.remove(index);
+            }
+        }
+        for (row, distance) in d.iter_mut().zip(&joined_distances) {
+            row.push(*distance);
+        }
+        let mut joined_row = joined_distances;
+        joined_row.push(0.0);
+        d.push(joined_row);
+        nodes.push(joined);
+    }
+
+    // The last three nodes meet at the unrooted center
+    let length_0 = (d[0][1] + d[0][2] - d[1][2]) / 2.0;
+    let length_1 = d[0][1] - length_0;
+    let length_2 = d[0][2] - length_0;
+    format!(
+        "({}:{},{}:{},{}:{});",
+        nodes[0],
+        branch_length(length_0),
+        nodes[1],
+        branch_length(length_1),
+        nodes[2],
+        branch_length(length_2)
+    )
+}
+
+/// A join of two clusters by UPGMA. Clusters are numbered with the sequences first and then
+/// the joins in order
+#[derive(Debug, Clone, Copy)]
+struct Join {
+    left: usize,
+    right: usize,
+    height: f64,
+}
+
+/// UPGMA over a full distance matrix, the joins from the closest clusters to the root
+#[allow(clippy::cast_precision_loss)]
+fn upgma_joins(distances: Vec<Vec<f64>>) -> Vec<Join> {
+    let sequences = distances.len();
+    // Cluster and number of sequences in it
+    let mut nodes: Vec<(usize, usize)> = (0..sequences).map(|i| (i, 1)).collect();
+    let mut d = distances;
+    let mut joins = Vec::new();
+
+    while nodes.len() > 1 {
+        let n = nodes.len();
+        let mut closest = (0, 1);
+        let mut min_distance = f64::INFINITY;
+        for (i, row) in d.iter().enumerate() {
+            for (j, &distance) in row.iter().enumerate().skip(i + 1) {
+                if distance < min_distance {
+                    min_distance = distance;
+                    closest = (i, j);
+                }
+            }
+        }
+        let (i, j) = closest;
+        let (size_i, size_j) = (nodes[i].1, nodes[j].1);
+        joins.push(Join {
+            left: nodes[i].0,
+            right: nodes[j].0,
+            height: d[i][j] / 2.0,
+        });
+        let joined = (sequences + joins.len() - 1, size_i + size_j);
+        let joined_distances: Vec<f64> = (0..n)
+            .filter(|&k| k != i && k != j)
+            .map(|k| (size_i as f64 * d[i][k] + size_j as f64 * d[j][k]) / (size_i + size_j) as f64)
+            .collect();
+
+        for index in [j, i] {
+            nodes.remove(index);
+            d.remove(index);
+            for row in &mut d {
+                row.remove(index);
+            }
+        }
+        for (row, distance) in d.iter_mut().zip(&joined_distances) {
+            row.push(*distance);
+        }
+        let mut joined_row = joined_distances;
+        joined_row.push(0.0);
+        d.push(joined_row);
+        nodes.push(joined);
+    }
+    joins
+}
+
+/// Height of a cluster in the UPGMA tree, 0 for a sequence
+fn join_height(joins: &[Join], sequences: usize, cluster: usize) -> f64 {
+    cluster
+        .checked_sub(sequences)
+        .map_or(0.0, |join| joins[join].height)
+}
+
+/// UPGMA tree over a full distance matrix
+fn upgma(names: &[String], distances: Vec<Vec<f64>>) -> String {
+    if names.is_empty() {
+        return ";".to_string();
+    }
+    let joins = upgma_joins(distances);
+    let mut nodes: Vec<String> = names.iter().map(|name| newick_label(name)).collect();
+    for join in &joins {
+        let length =
+            |cluster| branch_length(join.height - join_height(&joins, names.len(), cluster));
+        nodes.push(format!(
+            "({}:{},{}:{})",
+            nodes[join.left],
+            length(join.left),
+            nodes[join.right],
+            length(join.right)
+        ));
+    }
+    format!("{};", nodes[nodes.len() - 1])
+}
+
+/// Sequences in the order of the leaves of their UPGMA tree, so similar sequences are next to
+/// each other
+fn leaf_order(joins: &[Join], sequences: usize) -> Vec<usize> {
+    let mut order = Vec::with_capacity(sequences);
+    if sequences == 0 {
+        return order;
+    }
+    let mut stack = vec![sequences + joins.len() - 1];
+    while let Some(cluster) = stack.pop() {
+        match cluster.checked_sub(sequences) {
+            Some(join) => stack.extend([joins[join].right, joins[join].left]),
+            None => order.push(cluster),
+        }
+    }
+    order
+}
+
+/// Writes the distances as a heat map with the sequences in UPGMA order, and the UPGMA
+/// dendrogram to its right
+#[allow(clippy::cast_precision_loss)]
+fn write_heatmap(matrix: &DistanceMatrix, path: &Path) {
+    let n = matrix.len();
+    let joins = upgma_joins(matrix.full());
+    let order = leaf_order(&joins, n);
+    let names: Vec<String> = order.iter().map(|&i| matrix.names[i].clone()).collect();
+    let z: Vec<Vec<f64>> = order
+        .iter()
+        .map(|&r| order.iter().map(|&c| matrix.value(r, c)).collect())
+        .collect();
+    let distance = match (matrix.p_distance, matrix.coverage_weighted) {
+        (true, _) => "p-distance",
+        (false, true) => "Weighted differences",
+        (false, false) => "Differences",
+    };
+
+    // Sequences sit at their row, joins halfway between the two clusters they join
+    let mut rows = vec![0.0; n + joins.len()];
+    for (row, &i) in order.iter().enumerate() {
+        rows[i] = row as f64;
+    }
+    let (mut x, mut y) = (Vec::new(), Vec::new());
+    for (index, join) in joins.iter().enumerate() {
+        let (left, right) = (rows[join.left], rows[join.right]);
+        rows[n + index] = f64::midpoint(left, right);
+        // A bracket from each cluster's height out to the join, and a gap before the next
+        x.extend([
+            Some(join_height(&joins, n, join.left)),
+            Some(join.height),
+            Some(join.height),
+            Some(join_height(&joins, n, join.right)),
+            None,
+        ]);
+        y.extend([Some(left), Some(left), Some(right), Some(right), None]);
+    }
+
+    let mut plot = Plot::new();
+    plot.add_trace(
+        HeatMap::new(names.clone(), names, z)
+            .color_scale(ColorScale::Palette(ColorScalePalette::Viridis))
+            .color_bar(ColorBar::new().title(distance))
+            .hover_template(format!(
+                "%{{y}}<br>%{{x}}<br>{distance}: %{{z}}<extra></extra>"
+            )),
+    );
+    plot.add_trace(
+        Scatter::new(x, y)
+            .mode(Mode::Lines)
+            .line(Line::new().color(NEUTRAL_COLOR))
+            .hover_info(HoverInfo::Skip)
+            .x_axis("x2")
+            .y_axis("y2"),
+    );
+    plot.set_layout(
+        Layout::new()
+            .title(Title::with_text(format!("{distance} between sequences")))
+            .show_legend(false)
+            .x_axis(Axis::new().domain(&[0.0, 0.84]).tick_angle(-45.0))
+            .y_axis(Axis::new().auto_margin(true))
+            .x_axis2(Axis::new().domain(&[0.86, 1.0]).anchor("y2").visible(false))
+            .y_axis2(
+                Axis::new()
+                    .anchor("x2")
+                    .range(vec![-0.5, n as f64 - 0.5])
+                    .visible(false),
+            ),
+    );
+    plot.set_configuration(
+        plotly::Configuration::new()
+            .responsive(true)
+            .display_logo(false)
+            .fill_frame(true)
+            .to_image_button_options(
+                ToImageButtonOptions::new()
+                    .format(ImageButtonFormats::Svg)
+                    .filename("hamming_heatmap"),
+            ),
+    );
+    plot.write_html(path);
+}
+
+fn write_header<W: Write>(
+    writer: &mut W,
+    matrix: &DistanceMatrix,
+    format: MatrixFormat,
+    delim: char,
+) -> std::io::Result<()> {
+    match format {
+        MatrixFormat::Square | MatrixFormat::Lower => {
+            write!(writer, "sequences")?;
+            for name in &matrix.names {
+                write!(writer, "{delim}{name}")?;
+            }
+            writeln!(writer)?;
+        }
+        // Relaxed PHYLIP: whitespace separated, so names can't contain any
+        MatrixFormat::Phylip => writeln!(writer, "{}", matrix.len())?,
+        MatrixFormat::Long => {
+            write!(writer, "sequence_1{delim}sequence_2{delim}distance")?;
+            if matrix.coverage_weighted {
+                write!(writer, "{delim}weighted_distance")?;
+            }
+            if matrix.p_distance {
+                write!(writer, "{delim}compared_sites{delim}p_distance")?;
+            } else if matrix.pair_stats {
+                write!(writer, "{delim}compared_sites")?;
+            }
+            if matrix.pair_stats {
+                write!(
+                    writer,
+                    "{delim}percent_identity{delim}ambiguity_differences"
+                )?;
+            }
+            writeln!(writer)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes row `r` of a layer of the matrix, which needs the upper triangle rows up to `r`. The
+/// long format has every layer in its columns
+fn write_row<W: Write>(
+    writer: &mut W,
+    matrix: &DistanceMatrix,
+    r: usize,
+    format: MatrixFormat,
+    delim: char,
+    layer: Layer,
+) -> std::io::Result<()> {
+    let n = matrix.len();
+    let name = &matrix.names[r];
+    match format {
+        MatrixFormat::Square => {
+            write!(writer, "{name}")?;
+            for c in 0..n {
+                // This space was in the original
+                write!(writer, "{delim} {dist}", dist = matrix.get(r, c, layer))?;
+            }
+            writeln!(writer)?;
+        }
+        MatrixFormat::Lower => {
+            write!(writer, "{name}")?;
+            for c in 0..r {
+                write!(writer, "{delim}{dist}", dist = matrix.get(r, c, layer))?;
+            }
+            writeln!(writer)?;
+        }
+        MatrixFormat::Phylip => {
+            write!(writer, "{}", name.replace(char::is_whitespace, "_"))?;
+            for c in 0..n {
+                write!(writer, " {dist}", dist = matrix.get(r, c, layer))?;
+            }
+            writeln!(writer)?;
+        }
+        MatrixFormat::Long => {
+            for c in r + 1..n {
+                let pair = matrix.pair(r, c);
+                write!(
+                    writer,
+                    "{name}{delim}{}{delim}{}",
+                    matrix.names[c], pair.differences
+                )?;
+                if matrix.coverage_weighted {
+                    write!(writer, "{delim}{:.2}", pair.weighted_differences)?;
+                }
+                if matrix.p_distance {
+                    write!(
+                        writer,
+                        "{delim}{}{delim}{:.6}",
+                        pair.compared,
+                        matrix.value(r, c)
+                    )?;
+                } else if matrix.pair_stats {
+                    write!(writer, "{delim}{}", pair.compared)?;
+                }
+                if matrix.pair_stats {
+                    write!(
+                        writer,
+                        "{delim}{:.4}{delim}{}",
+                        pair.percent_identity(),
+                        pair.ambiguity_differences
+                    )?;
+                }
+                writeln!(writer)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `<output stem>_<suffix>.<output extension>`, next to the output
+fn suffixed_path(output: &Path, suffix: &str) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(extension) => format!("{stem}_{suffix}.{}", extension.to_string_lossy()),
+        None => format!("{stem}_{suffix}"),
+    };
+    output.with_file_name(name)
+}
+
+/// Flu segment a FASTA header is for, from a segment or reference name in it (`s1 | A_HA_H3`,
+/// `s1_HA`) or else IRMA's segment number suffix (`s1_4` is HA)
+fn header_segment(name: &str) -> Option<&'static str> {
+    let name = strip_reference_suffix(name);
+    let named = name
+        .split(|c: char| c == '|' || c == '_' || c.is_whitespace())
+        .rev()
+        .find_map(|token| FLU_SEGMENTS.into_iter().find(|&segment| segment == token));
+    if let Some(segment) = named {
+        return Some(segment);
+    }
+    let number: usize = name.rsplit('_').next()?.trim().parse().ok()?;
+    FLU_SEGMENT_NUMBERS.get(number.checked_sub(1)?).copied()
 }
 
 pub fn all_sample_hd_process(args: &HammingArgs) -> Result<(), std::io::Error> {
@@ -47,60 +796,316 @@ pub fn all_sample_hd_process(args: &HammingArgs) -> Result<(), std::io::Error> {
         FastaReader::new(BufReader::new(Either::Right(stdin())))
     };
 
-    //output
-    let mut writer = if let Some(ref file_path) = args.output_xsv {
-        BufWriter::new(Either::Left(
-            OpenOptions::new()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(file_path)
-                .expect("File write error"),
-        ))
-    } else {
-        BufWriter::new(Either::Right(stdout()))
-    };
-
-    let all_sequences = reader
+    let mut all_sequences = reader
         .map(|record|
             // TODO: don't translate, instead defer until later
             record.map(|r| {
+                let raw = r.sequence.to_ascii_uppercase();
                 let FastaNT { name, sequence } = r.recode_to_dna();
                 ValidSeq {
                     name,
                     sequence,
+                    raw,
+                    depths: None,
+                    span: (0, 0),
                 }
               }))
         .collect::<Result<Vec<_>, _>>()?;
 
-    write!(&mut writer, "sequences")?;
-    for query_header in all_sequences.iter().map(|f| f.name.as_str()) {
-        write!(&mut writer, "{delim}{query_header}")?;
+    if let Some(min_completeness) = args.min_completeness {
+        let length = all_sequences.iter().map(|s| s.raw.len()).max().unwrap_or(0);
+        let mut excluded = Vec::new();
+        all_sequences.retain(|s| {
+            let completeness = completeness(&s.raw, length);
+            if completeness < min_completeness {
+                excluded.push((s.name.clone(), completeness));
+            }
+            completeness >= min_completeness
+        });
+        write_excluded_report(
+            args.excluded_report.as_deref(),
+            &excluded,
+            min_completeness,
+            delim,
+        )?;
     }
-    writeln!(&mut writer)?;
 
-    let n = all_sequences.len();
-    let mut matrix_cache = Vec::with_capacity(n * (n + 1) / 2);
-    for (r, sequence) in all_sequences.iter().map(|v| &v.sequence).enumerate() {
-        for (c, seq2) in all_sequences.iter().map(|v| &v.sequence).enumerate() {
-            if r <= c {
-                matrix_cache.push(sequence.distance_hamming(seq2));
+    let coverage = args
+        .coverage
+        .as_deref()
+        .map(|path| read_coverage_depths(path, args.coverage_coordinates))
+        .transpose()?;
+
+    if !args.by_segment {
+        return compare_sequences(args, all_sequences, coverage.as_ref(), None);
+    }
+
+    // One matrix per flu segment, in the usual segment order
+    let mut groups: Vec<(&str, Vec<ValidSeq>)> = FLU_SEGMENTS
+        .iter()
+        .map(|&segment| (segment, Vec::new()))
+        .collect();
+    let mut unassigned = Vec::new();
+    for seq in all_sequences {
+        let group = header_segment(&seq.name)
+            .and_then(|segment| groups.iter_mut().find(|(name, _)| *name == segment));
+        match group {
+            Some((_, group)) => group.push(seq),
+            None => unassigned.push(seq.name),
+        }
+    }
+    if !unassigned.is_empty() {
+        eprintln!(
+            "Warning: no flu segment found in the names of {} sequences, they are left out: {}",
+            unassigned.len(),
+            unassigned.join(", ")
+        );
+    }
+    groups.retain(|(_, group)| !group.is_empty());
+    if groups.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "No sequence names with a flu segment for --by-segment",
+        ));
+    }
+    for (segment, group) in groups {
+        status!(" -> {segment}: {} sequences", group.len());
+        compare_sequences(args, group, coverage.as_ref(), Some(segment))?;
+    }
+    Ok(())
+}
+
+/// Computes and writes the distances between a set of sequences. With a `segment` the outputs
+/// are named after it, next to the given ones
+#[allow(clippy::too_many_lines)]
+fn compare_sequences(
+    args: &HammingArgs,
+    mut all_sequences: Vec<ValidSeq>,
+    coverage: Option<&CoverageDepths>,
+    segment: Option<&str>,
+) -> Result<(), std::io::Error> {
+    let delim = args.output_delimiter.unwrap_or(',');
+    let segment_path = |path: &Path| match segment {
+        Some(segment) => suffixed_path(path, segment),
+        None => path.to_path_buf(),
+    };
+    let output = args.output_xsv.as_deref().map(segment_path);
+    let output = output.as_deref();
+
+    //output
+    let mut writer = output_writer(output)?;
+
+    if let Some(coverage) = coverage {
+        // Each segment's sequences take the coverage of that segment
+        let coverage_segment = args.coverage_segment.as_deref().or(segment);
+        for seq in &mut all_sequences {
+            seq.depths = sequence_depths(&seq.name, &seq.raw, coverage, coverage_segment)?;
+            if seq.depths.is_none() {
+                eprintln!(
+                    "Warning: no coverage found for {}, its differences are not down-weighted \
+                     or masked",
+                    seq.name
+                );
             }
         }
     }
 
-    for (r, sequence_name) in all_sequences.iter().map(|v| &v.name).enumerate() {
-        write!(&mut writer, "{sequence_name}")?;
-        for c in 0..n {
-            let index = if r <= c {
-                r * n - r * (r + 1) / 2 + c
-            } else {
-                c * n - c * (c + 1) / 2 + r
+    // In reference coordinates the sequences are aligned with each other, gaps where they don't
+    // reach the reference
+    if args.align == AlignMode::Reference {
+        let reference = match &args.align_reference {
+            Some(name) => all_sequences
+                .iter()
+                .find(|s| &s.name == name)
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("--align-reference {name} is not in the input"),
+                    )
+                })?,
+            None => all_sequences.first().ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, "No input sequences")
+            })?,
+        }
+        .raw
+        .clone();
+        all_sequences
+            .par_iter_mut()
+            .for_each(|s| s.raw = project_onto_reference(&s.raw, &reference));
+    }
+
+    for seq in &mut all_sequences {
+        seq.span = if args.trim_terminal_n {
+            terminal_trimmed_span(&seq.raw)
+        } else {
+            (0, seq.raw.len())
+        };
+    }
+
+    // The plain Hamming distance unless positions need to be compared one by one
+    let per_position = args.trim_terminal_n
+        || args.ambiguity_compatible
+        || args.ignore_missing
+        || args.p_distance
+        || args.pair_stats
+        || args.align != AlignMode::None
+        || args.coverage.is_some();
+    let distance = |seq1: &ValidSeq, seq2: &ValidSeq| {
+        if args.align == AlignMode::Pairwise {
+            // Only the locally aligned region is compared
+            let (aligned1, aligned2) = align_sequences(
+                &seq2.raw[seq2.span.0..seq2.span.1],
+                &seq1.raw[seq1.span.0..seq1.span.1],
+            );
+            pair_distance(
+                &aligned1,
+                &aligned2,
+                args.ambiguity_compatible,
+                args.ignore_missing,
+                |_| Some(1.0),
+            )
+        } else if per_position {
+            let depth_at = |seq: &ValidSeq, position: usize| {
+                seq.depths
+                    .as_ref()
+                    .and_then(|depths| depths.get(position).copied().flatten())
             };
-            // This space was in the original
-            write!(&mut writer, "{delim} {dist}", dist = matrix_cache[index])?;
+            // Only where both sequences are compared
+            let start = seq1.span.0.max(seq2.span.0);
+            let end = seq1.span.1.min(seq2.span.1).max(start);
+            pair_distance(
+                &seq1.raw[start..end],
+                &seq2.raw[start..end],
+                args.ambiguity_compatible,
+                args.ignore_missing,
+                |position| {
+                    let (depth1, depth2) = (
+                        depth_at(seq1, start + position),
+                        depth_at(seq2, start + position),
+                    );
+                    (!is_masked(depth1, depth2, args.mask_depth))
+                        .then(|| depth_weight(depth1, depth2, args.full_weight_depth))
+                },
+            )
+        } else {
+            let differences = seq1.sequence.distance_hamming(&seq2.sequence);
+            let compared = seq1.raw.len().min(seq2.raw.len());
+            #[allow(clippy::cast_precision_loss)]
+            let (weighted_differences, weighted_compared) = (differences as f64, compared as f64);
+            PairDistance {
+                differences,
+                compared,
+                ambiguity_differences: 0,
+                weighted_differences,
+                weighted_compared,
+            }
+        }
+    };
+
+    let n = all_sequences.len();
+    let pair_count = n * n.saturating_sub(1) / 2;
+    if pair_count > args.max_pairs {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "{n} sequences make {pair_count} pairs, more than --max-pairs {}. Split the input \
+                 or raise --max-pairs; unless the output is `-m long` without `--tree` or \
+                 `--heatmap`, every distance is kept in memory (about 40 bytes per pair)",
+                args.max_pairs
+            ),
+        ));
+    }
+
+    let mut matrix = DistanceMatrix {
+        names: all_sequences.iter().map(|s| s.name.clone()).collect(),
+        upper: Vec::new(),
+        p_distance: args.p_distance,
+        coverage_weighted: args.coverage.is_some(),
+        pair_stats: args.pair_stats,
+        released: 0,
+    };
+    // The long layout only reads a row's own distances, so written rows can be let go
+    let release_rows =
+        args.matrix_format == MatrixFormat::Long && args.tree.is_none() && args.heatmap.is_none();
+    write_header(&mut writer, &matrix, args.matrix_format, delim)?;
+
+    // The matrix layouts hold one value, so each pair statistic gets its own file
+    let mut layer_writers = Vec::new();
+    if args.pair_stats && args.matrix_format != MatrixFormat::Long {
+        let output = output.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "--pair-stats with a matrix layout needs -o, the statistics are written next to it",
+            )
+        })?;
+        for layer in [
+            Layer::ComparedSites,
+            Layer::PercentIdentity,
+            Layer::AmbiguityDifferences,
+        ] {
+            let mut layer_writer = output_writer(Some(&suffixed_path(output, layer.suffix())))?;
+            write_header(&mut layer_writer, &matrix, args.matrix_format, delim)?;
+            layer_writers.push((layer, layer_writer));
         }
-        writeln!(&mut writer)?;
+    }
+
+    // Only the upper triangle is computed, one row per task, and mirrored when writing. Rows
+    // are computed in chunks and written as soon as their chunk is done
+    let mut progress = Progress::new("pairs compared", pair_count);
+    let mut row = 0;
+    while row < n {
+        let mut end = row;
+        let mut chunk_pairs = 0;
+        while end < n && chunk_pairs < CHUNK_PAIRS {
+            chunk_pairs += n - end - 1;
+            end += 1;
+        }
+        let chunk: Vec<_> = (row..end)
+            .into_par_iter()
+            .flat_map_iter(|r| {
+                let seq1 = &all_sequences[r];
+                all_sequences[r..]
+                    .iter()
+                    .map(move |seq2| distance(seq1, seq2))
+            })
+            .collect();
+        matrix.upper.extend(chunk);
+
+        for r in row..end {
+            write_row(
+                &mut writer,
+                &matrix,
+                r,
+                args.matrix_format,
+                delim,
+                Layer::Distance,
+            )?;
+            for (layer, layer_writer) in &mut layer_writers {
+                write_row(layer_writer, &matrix, r, args.matrix_format, delim, *layer)?;
+            }
+        }
+        writer.flush()?;
+        for (_, layer_writer) in &mut layer_writers {
+            layer_writer.flush()?;
+        }
+        if release_rows {
+            matrix.release_rows(end);
+        }
+        progress.add(chunk_pairs);
+        row = end;
+    }
+    progress.finish();
+
+    if let Some(tree_path) = args.tree.as_deref().map(segment_path) {
+        let newick = match args.tree_method {
+            TreeMethod::Nj => neighbor_joining(&matrix.names, matrix.full()),
+            TreeMethod::Upgma => upgma(&matrix.names, matrix.full()),
+        };
+        std::fs::write(tree_path, format!("{newick}\n"))?;
+    }
+
+    if let Some(heatmap_path) = args.heatmap.as_deref().map(segment_path) {
+        write_heatmap(&matrix, &heatmap_path);
     }
 
     Ok(())
@@ -1,12 +1,19 @@
-use clap::Parser;
+use crate::utils::{
+    alignment::{AlignMode, align_with_offset, project_onto_reference},
+    completeness::{completeness, terminal_trimmed_span, write_excluded_report},
+    coordinates::CoordinateSystem,
+    coverage_depths::{is_masked, read_coverage_depths, sequence_depths},
+    output::output_writer,
+};
+use clap::{ArgAction, Parser};
 use either::Either;
 use std::{
-    fs::OpenOptions,
-    io::{BufReader, BufWriter, Write, stdin, stdout},
-    path::PathBuf,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write, stdin},
+    path::{Path, PathBuf},
 };
 use zoe::{
-    data::fasta::FastaNT,
+    data::{fasta::FastaNT, mappings::StdGeneticCode},
     prelude::*,
 };
 
@@ -26,18 +33,239 @@ pub struct NTDiffsArgs {
     #[arg(short = 'd', long)]
     /// Use the provider delimiter for separating fields. Default is ','
     output_delimiter: Option<char>,
+
+    #[arg(short = 'r', long)]
+    /// Only compare every sequence against this one instead of all against all: the name of an
+    /// input sequence, or a FASTA file whose first sequence is used
+    reference: Option<String>,
+
+    #[arg(short = 'c', long)]
+    /// Optional BED or GFF3 (.gff/.gff3) file of the CDSs in `sequence_1` coordinates, to
+    /// annotate every difference with its codon and amino acid change
+    cds: Option<PathBuf>,
+
+    #[arg(long, value_enum, default_value_t = AlignMode::None)]
+    /// Align the sequences before comparing them, for unaligned or unequal length input.
+    /// `pairwise` aligns every pair, `reference` aligns every sequence to one of them
+    align: AlignMode,
+
+    #[arg(long)]
+    /// Name of the input sequence to align to with `--align reference`. Default is the first
+    align_reference: Option<String>,
+
+    #[arg(long, default_value_t = true, action = ArgAction::Set)]
+    /// Compare every unordered pair once and skip comparing a sequence with itself. Set to
+    /// false to report both A vs B and B vs A
+    unique_pairs: bool,
+
+    #[arg(long)]
+    /// Leave the leading and trailing Ns and gaps of each sequence out of its comparisons
+    trim_terminal_n: bool,
+
+    #[arg(long)]
+    /// Exclude sequences with a smaller fraction of base calls (not Ns or gaps) than this,
+    /// out of the length of the longest input sequence. The `--reference` is always kept
+    min_completeness: Option<f64>,
+
+    #[arg(long, requires = "min_completeness")]
+    /// Optional delimited file listing the sequences excluded by `--min-completeness`
+    excluded_report: Option<PathBuf>,
+
+    #[arg(long, conflicts_with = "align", requires = "mask_depth")]
+    /// Optional MIRA coverage parquet file (mira_<runid>_coverage.parq) or IRMA run directory
+    /// with the coverage tables, for `--mask-depth`
+    coverage: Option<PathBuf>,
+
+    #[arg(long, requires = "coverage")]
+    /// Only use coverage of references containing this, for samples with several references in
+    /// the coverage file (e.g. HA)
+    coverage_segment: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = CoordinateSystem::Raw, requires = "coverage")]
+    /// Coordinates of the input sequences, which the coverage positions are put in: raw (IRMA
+    /// consensus), padded (padded consensus, as reported for SARS-CoV-2 and RSV) or hmm
+    coverage_coordinates: CoordinateSystem,
+
+    #[arg(long, requires = "coverage")]
+    /// Don't report differences at positions where either sample's depth is below this, so
+    /// low coverage miscalls aren't reported
+    mask_depth: Option<u32>,
 }
 
 #[derive(Debug)]
 struct ValidSeq {
     name: String,
     sequence: Nucleotides,
+    /// Start and end of the positions to compare, without the terminal Ns with
+    /// `--trim-terminal-n`
+    span: (usize, usize),
+    /// Depth at each position with `--coverage`. Gaps and positions without coverage are
+    /// `None`
+    depths: Option<Vec<Option<u32>>>,
 }
 
-pub fn all_sample_nt_diffs_process(args: &NTDiffsArgs) {
-    //let args = NTDiffsArgs::parse();
-    let delim = args.output_delimiter.unwrap_or(',');
+/// A coding sequence, as 0-based half-open parts in order so spliced CDSs (M2, NS2) work
+#[derive(Debug)]
+struct Cds {
+    name: String,
+    parts: Vec<(usize, usize)>,
+}
+
+impl Cds {
+    /// Offset of a position in the spliced CDS
+    fn offset(&self, position: usize) -> Option<usize> {
+        let mut offset = 0;
+        for &(start, end) in &self.parts {
+            if (start..end).contains(&position) {
+                return Some(offset + position - start);
+            }
+            offset += end - start;
+        }
+        None
+    }
+
+    /// Position of an offset in the spliced CDS
+    fn position(&self, mut offset: usize) -> Option<usize> {
+        for &(start, end) in &self.parts {
+            if offset < end - start {
+                return Some(start + offset);
+            }
+            offset -= end - start;
+        }
+        None
+    }
+}
 
+/// Reads the forward strand CDSs of a BED file, or the `CDS` features of a GFF3 file. Rows with
+/// the same name are the parts of one spliced CDS
+fn read_cds(path: &Path) -> Result<Vec<Cds>, std::io::Error> {
+    let is_gff = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gff") || ext.eq_ignore_ascii_case("gff3"));
+    let mut cds_list: Vec<Cds> = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.starts_with('#') || line.starts_with("track") || line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        let parsed = if is_gff {
+            // seqid, source, type, start, end, score, strand, phase, attributes. 1-based, inclusive
+            if fields.len() < 9 || fields[2] != "CDS" {
+                continue;
+            }
+            let name = fields[8]
+                .split(';')
+                .filter_map(|attribute| attribute.split_once('='))
+                .find(|(key, _)| matches!(*key, "Name" | "gene" | "ID"))
+                .map_or(fields[8], |(_, value)| value);
+            fields[3]
+                .parse::<usize>()
+                .ok()
+                .zip(fields[4].parse::<usize>().ok())
+                .map(|(start, end)| (name, start.saturating_sub(1), end, fields[6]))
+        } else {
+            // chrom, start, end, name, score, strand. 0-based, half-open
+            if fields.len() < 3 {
+                continue;
+            }
+            fields[1]
+                .parse::<usize>()
+                .ok()
+                .zip(fields[2].parse::<usize>().ok())
+                .map(|(start, end)| {
+                    let name = fields.get(3).copied().unwrap_or(fields[0]);
+                    (name, start, end, fields.get(5).copied().unwrap_or("+"))
+                })
+        };
+        let Some((name, start, end, strand)) = parsed else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Could not read the CDS coordinates in '{line}'"),
+            ));
+        };
+        if strand == "-" {
+            eprintln!("Warning: skipping {name}, only forward strand CDSs are annotated");
+            continue;
+        }
+        match cds_list.iter_mut().find(|cds| cds.name == name) {
+            Some(cds) => cds.parts.push((start, end)),
+            None => cds_list.push(Cds {
+                name: name.to_string(),
+                parts: vec![(start, end)],
+            }),
+        }
+    }
+    for cds in &mut cds_list {
+        cds.parts.sort_unstable();
+    }
+    Ok(cds_list)
+}
+
+/// Codon and amino acid change of a difference at `position` of `seq1`, one set of columns per
+/// CDS the position is in (or one empty set outside them). The alternate codon is read from
+/// `seq2` when it is in the coordinates of `seq1`, otherwise only `alt_base` is substituted
+fn codon_consequences(
+    cds_list: &[Cds],
+    seq1: &[u8],
+    seq2: Option<&[u8]>,
+    position: usize,
+    alt_base: u8,
+    delim: char,
+) -> Vec<String> {
+    let consequences: Vec<String> = cds_list
+        .iter()
+        .filter_map(|cds| {
+            let offset = cds.offset(position)?;
+            let codon_start = offset - offset % 3;
+            let positions = [
+                cds.position(codon_start)?,
+                cds.position(codon_start + 1)?,
+                cds.position(codon_start + 2)?,
+            ];
+            let mut ref_codon = [b'N'; 3];
+            let mut alt_codon = [b'N'; 3];
+            for (i, &p) in positions.iter().enumerate() {
+                ref_codon[i] = seq1.get(p).copied().unwrap_or(b'-').to_ascii_uppercase();
+                alt_codon[i] = match seq2 {
+                    Some(seq2) => seq2.get(p).copied().unwrap_or(b'-'),
+                    None if p == position => alt_base,
+                    None => ref_codon[i],
+                }
+                .to_ascii_uppercase();
+            }
+            let ref_aa = StdGeneticCode::translate_codon(&ref_codon);
+            let alt_aa = StdGeneticCode::translate_codon(&alt_codon);
+            let consequence = if ref_codon.contains(&b'-') || alt_codon.contains(&b'-') {
+                "indel"
+            } else if !ref_codon.iter().chain(&alt_codon).all(|b| b"ACGT".contains(b)) {
+                "ambiguous"
+            } else if ref_aa == alt_aa {
+                "synonymous"
+            } else {
+                "non-synonymous"
+            };
+            Some(format!(
+                "{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{consequence}",
+                cds.name,
+                codon_start / 3 + 1,
+                offset % 3 + 1,
+                String::from_utf8_lossy(&ref_codon),
+                String::from_utf8_lossy(&alt_codon),
+                char::from(ref_aa),
+                char::from(alt_aa)
+            ))
+        })
+        .collect();
+    if consequences.is_empty() {
+        vec![delim.to_string().repeat(8)]
+    } else {
+        consequences
+    }
+}
+
+// The input sequences, from the --input-fasta or stdin
+fn read_sequences(args: &NTDiffsArgs) -> Vec<ValidSeq> {
     //read in fasta file
     let reader = if let Some(ref file_path) = args.input_fasta {
         FastaReader::new(BufReader::new(Either::Left(
@@ -50,57 +278,247 @@ pub fn all_sample_nt_diffs_process(args: &NTDiffsArgs) {
         FastaReader::new(BufReader::new(Either::Right(stdin())))
     };
 
-    //output
-    let mut writer = if let Some(ref file_path) = args.output_xsv {
-        BufWriter::new(Either::Left(
-            OpenOptions::new()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(file_path)
-                .expect("File write error"),
-        ))
-    } else {
-        BufWriter::new(Either::Right(stdout()))
-    };
-
-    let all_sequences = reader
+    reader
         .map(|record|
             // TODO: don't translate, instead defer until later
             record.map(|r| {
                 let FastaNT { name, sequence } = r.recode_to_dna();
                 ValidSeq {
-                    name, 
+                    name,
                     sequence,
+                    span: (0, 0),
+                    depths: None,
                 }
               }))
         .collect::<Result<Vec<_>, _>>()
-        .unwrap_or_die("Could not process other data.");
-    
-        writeln!(
-            &mut writer,
-            "sequence_1{delim}sequence_2{delim}nt_sequence_1{delim}position{delim}nt_sequence_2"
+        .unwrap_or_die("Could not process other data.")
+}
+
+// Takes the --reference out of the sequences and prepares them for comparing: coverage,
+// alignment to the reference, completeness filter and the span compared. Gives the reference
+fn prepare_sequences(args: &NTDiffsArgs, all_sequences: &mut Vec<ValidSeq>) -> Option<ValidSeq> {
+    let delim = args.output_delimiter.unwrap_or(',');
+
+    // The sequence everything is compared against, taken out of the input so it isn't also
+    // compared against itself
+    let mut reference = args.reference.as_ref().map(|reference| {
+        if Path::new(reference).is_file() {
+            FastaReader::new(BufReader::new(
+                File::open(reference).expect("File opening error"),
+            ))
+            .next()
+            .expect("The --reference FASTA is empty")
+            .map(|r| {
+                let FastaNT { name, sequence } = r.recode_to_dna();
+                ValidSeq {
+                    name,
+                    sequence,
+                    span: (0, 0),
+                    depths: None,
+                }
+            })
+            .unwrap_or_die("Could not process the --reference FASTA.")
+        } else {
+            let index = all_sequences
+                .iter()
+                .position(|s| &s.name == reference)
+                .expect("The --reference sequence is not in the input");
+            all_sequences.remove(index)
+        }
+    });
+
+    if let Some(coverage_path) = &args.coverage {
+        let coverage = read_coverage_depths(coverage_path, args.coverage_coordinates)
+            .unwrap_or_die("Could not read the --coverage file.");
+        for s in all_sequences.iter_mut().chain(reference.as_mut()) {
+            let bases: Vec<u8> = s.sequence.iter().copied().collect();
+            s.depths =
+                sequence_depths(&s.name, &bases, &coverage, args.coverage_segment.as_deref())
+                    .unwrap_or_die("Could not match the sequences to the --coverage file.");
+            if s.depths.is_none() {
+                eprintln!(
+                    "Warning: no coverage found for {}, its differences are not masked",
+                    s.name
+                );
+            }
+        }
+    }
+
+    // Positions are then in the coordinates of the reference
+    if args.align == AlignMode::Reference {
+        let align_to: Vec<u8> = match &args.align_reference {
+            Some(name) => all_sequences
+                .iter()
+                .chain(reference.as_ref())
+                .find(|s| &s.name == name),
+            None => reference.as_ref().or(all_sequences.first()),
+        }
+        .expect("The --align-reference sequence is not in the input")
+        .sequence
+        .iter()
+        .copied()
+        .collect();
+        for s in all_sequences.iter_mut().chain(reference.as_mut()) {
+            let bases: Vec<u8> = s.sequence.iter().copied().collect();
+            s.sequence = Nucleotides::from(project_onto_reference(&bases, &align_to));
+        }
+    }
+
+    if let Some(min_completeness) = args.min_completeness {
+        let length = all_sequences
+            .iter()
+            .map(|s| s.sequence.len())
+            .max()
+            .unwrap_or(0);
+        let mut excluded = Vec::new();
+        all_sequences.retain(|s| {
+            let bases: Vec<u8> = s.sequence.iter().copied().collect();
+            let completeness = completeness(&bases, length);
+            if completeness < min_completeness {
+                excluded.push((s.name.clone(), completeness));
+            }
+            completeness >= min_completeness
+        });
+        write_excluded_report(
+            args.excluded_report.as_deref(),
+            &excluded,
+            min_completeness,
+            delim,
+        )
+        .unwrap_or_die("Could not write the --excluded-report.");
+    }
+
+    for s in all_sequences.iter_mut().chain(reference.as_mut()) {
+        s.span = if args.trim_terminal_n {
+            let bases: Vec<u8> = s.sequence.iter().copied().collect();
+            terminal_trimmed_span(&bases)
+        } else {
+            (0, s.sequence.len())
+        };
+    }
+
+    reference
+}
+
+// The codon columns are only there with --cds
+fn write_header(writer: &mut impl Write, delim: char, with_cds: bool) {
+    write!(
+        writer,
+        "sequence_1{delim}sequence_2{delim}nt_sequence_1{delim}position{delim}nt_sequence_2"
+    )
+    .unwrap();
+    if with_cds {
+        write!(
+            writer,
+            "{delim}cds{delim}codon{delim}codon_position{delim}ref_codon{delim}alt_codon{delim}ref_aa{delim}alt_aa{delim}consequence"
         ).unwrap();
-#[allow(clippy::needless_for_each)]
-        all_sequences.iter().for_each(|f| {
-            let name_1 = &f.name;
-            let seq1 = &f.sequence;
-            all_sequences.iter().for_each(|f| {
-                let name_2 = &f.name;
-                let seq2 = &f.sequence;
-                for (i, (nt1, nt2)) in seq1.iter().zip(seq2.iter()).enumerate() {
+    }
+    writeln!(writer).unwrap();
+}
+
+pub fn all_sample_nt_diffs_process(args: &NTDiffsArgs) {
+    //let args = NTDiffsArgs::parse();
+    let delim = args.output_delimiter.unwrap_or(',');
+
+    //output
+    let mut writer = output_writer(args.output_xsv.as_deref()).expect("File write error");
+
+    let mut all_sequences = read_sequences(args);
+    let reference = prepare_sequences(args, &mut all_sequences);
+
+    let cds_list = args
+        .cds
+        .as_deref()
+        .map(read_cds)
+        .transpose()
+        .unwrap_or_die("Could not read the --cds file.");
+
+    write_header(&mut writer, delim, cds_list.is_some());
+    let compared_to: Vec<&ValidSeq> = match &reference {
+        Some(reference) => vec![reference],
+        None => all_sequences.iter().collect(),
+    };
+    // All against all, each pair once: sequence_2 comes after sequence_1 in the input
+    let skip_to = |index: usize| {
+        if reference.is_none() && args.unique_pairs {
+            index + 1
+        } else {
+            0
+        }
+    };
+    #[allow(clippy::needless_for_each)]
+    compared_to.iter().enumerate().for_each(|(index, compared)| {
+        let name_1 = &compared.name;
+        let seq1 = &compared.sequence;
+        all_sequences.iter().skip(skip_to(index)).for_each(|f| {
+            let name_2 = &f.name;
+            let seq2 = &f.sequence;
+            let (span1, span2) = (compared.span, f.span);
+            if args.align == AlignMode::Pairwise {
+                let bases1: Vec<u8> = seq1.iter().copied().collect();
+                let bases2: Vec<u8> = seq2.iter().copied().collect();
+                let Some((start, aligned1, aligned2)) = align_with_offset(
+                    &bases2[span2.0..span2.1],
+                    &bases1[span1.0..span1.1],
+                ) else {
+                    return;
+                };
+                // Positions are in sequence_1; an insertion in sequence_2 is reported at the
+                // next sequence_1 position
+                let mut i = span1.0 + start;
+                for (nt1, nt2) in aligned1.into_iter().zip(aligned2) {
                     if nt1 != nt2 {
-                        let nucleotide1 = char::from(*nt1);
-                        let nucleotide2 = char::from(*nt2);
+                        let nucleotide1 = char::from(nt1);
+                        let nucleotide2 = char::from(nt2);
+                        let annotations = match &cds_list {
+                            Some(cds_list) => codon_consequences(cds_list, &bases1, None, i, nt2, delim),
+                            None => vec![String::new()],
+                        };
+                        for annotation in annotations {
+                            writeln!(
+                                &mut writer,
+                                "{name_1}{delim}{name_2}{delim}{nucleotide1}{delim}{i}{delim}{nucleotide2}{annotation}"
+                            )
+                            .unwrap();
+                        }
+                    }
+                    if nt1 != b'-' {
+                        i += 1;
+                    }
+                }
+                return;
+            }
+            // Only needed to read the codons around a difference
+            let (bases1, bases2): (Vec<u8>, Vec<u8>) = if cds_list.is_some() {
+                (seq1.iter().copied().collect(), seq2.iter().copied().collect())
+            } else {
+                (Vec::new(), Vec::new())
+            };
+            // Only where both sequences are compared
+            let start = span1.0.max(span2.0);
+            let end = span1.1.min(span2.1);
+            let depth_at = |seq: &ValidSeq, position: usize| {
+                seq.depths
+                    .as_ref()
+                    .and_then(|depths| depths.get(position).copied().flatten())
+            };
+            for (i, (nt1, nt2)) in seq1.iter().zip(seq2.iter()).enumerate().take(end).skip(start) {
+                if nt1 != nt2 && !is_masked(depth_at(compared, i), depth_at(f, i), args.mask_depth) {
+                    let nucleotide1 = char::from(*nt1);
+                    let nucleotide2 = char::from(*nt2);
+                    let annotations = match &cds_list {
+                        Some(cds_list) => codon_consequences(cds_list, &bases1, Some(&bases2), i, *nt2, delim),
+                        None => vec![String::new()],
+                    };
+                    for annotation in annotations {
                         writeln!(
                             &mut writer,
-                            "{name_1}{delim}{name_2}{delim}{nucleotide1}{delim}{i}{delim}{nucleotide2}"
+                            "{name_1}{delim}{name_2}{delim}{nucleotide1}{delim}{i}{delim}{nucleotide2}{annotation}"
                         )
                         .unwrap();
                     }
                 }
-            });
+            }
         });
-        
-
-}
\ No newline at end of file
+    });
+}
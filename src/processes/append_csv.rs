@@ -0,0 +1,50 @@
+use crate::io::write_csv_files::append_records_to_shared_csv;
+use crate::status;
+use clap::Parser;
+use csv::{ReaderBuilder, StringRecord};
+use std::{error::Error, path::PathBuf};
+
+#[derive(Debug, Parser)]
+#[command(
+    about = "Append per-sample CSV rows to a run level CSV shared by parallel tasks, writing its header once"
+)]
+pub struct AppendCsvArgs {
+    #[arg(short = 't', long)]
+    /// Shared run level CSV. Created with the inputs' header if it doesn't exist yet
+    target: PathBuf,
+
+    #[arg(short = 'i', long, num_args = 1.., required = true)]
+    /// Per-sample CSV files to append, each with a header row matching the target's
+    inputs: Vec<PathBuf>,
+
+    #[arg(short = 'd', long, default_value_t = ',')]
+    /// Field delimiter of the inputs and target
+    delimiter: char,
+}
+
+pub fn append_csv_process(args: &AppendCsvArgs) -> Result<(), Box<dyn Error>> {
+    let delimiter = u8::try_from(args.delimiter).map_err(|_| {
+        format!(
+            "Delimiter must be a single byte character, not '{}'",
+            args.delimiter
+        )
+    })?;
+
+    for input in &args.inputs {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(delimiter)
+            .from_path(input)
+            .map_err(|e| format!("Could not open file '{}': {e}", input.display()))?;
+        let header = reader.headers()?.clone();
+        let rows = reader.records().collect::<Result<Vec<StringRecord>, _>>()?;
+
+        append_records_to_shared_csv(&args.target, &header, &rows, delimiter)?;
+        status!(
+            " -> {} rows of {} appended to {}",
+            rows.len(),
+            input.display(),
+            args.target.display()
+        );
+    }
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use crate::status;
 use clap::Parser;
 use std::{
     fs,
@@ -39,9 +40,9 @@ pub fn check_mira_version(args: &MiraVersionArgs) -> io::Result<()> {
     })?;
 
     if current >= available {
-        println!("MIRA-NF version up to date!");
+        status!("MIRA-NF version up to date!");
     } else {
-        println!("MIRA-NF {available} is now available!");
+        status!("MIRA-NF {available} is now available!");
     }
 
     Ok(())
@@ -1,6 +1,12 @@
-use clap::Parser;
+use crate::io::write_parquet_files::{
+    ParquetColumns, parse_column_rename, write_di_stats_to_parquet,
+};
+use crate::status;
+use crate::utils::rng::{DEFAULT_SEED, SeededRng};
+use clap::{Parser, ValueEnum};
 use glob::glob;
-use serde::Deserialize;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fs::File;
 use std::io::{BufWriter, Write};
@@ -14,9 +20,168 @@ pub struct DIStatArgs {
     #[arg(short = 'a', long)]
     assemblies_dir: PathBuf,
 
-    /// Run ID to include in output
+    /// Run ID to include in output. Default is the name of the assemblies directory
     #[arg(short = 'r', long)]
-    run_id: String,
+    run_id: Option<String>,
+
+    /// Glob, relative to the assemblies directory, matching each sample's IRMA output directory
+    /// (the one holding `tables/*coverage.txt`). The sample ID is the matched directory's name
+    #[arg(short = 'g', long, default_value = "*/IRMA/*")]
+    sample_glob: String,
+
+    /// Use the coverage files in HMM positions (`tables/*coverage.a2m.txt`), as MIRA does for
+    /// SC2 spike-only assemblies, instead of `tables/*coverage.txt`
+    #[arg(long)]
+    a2m: bool,
+
+    /// Length in bp of the 5', 3' and middle windows compared
+    #[arg(short = 'l', long, default_value_t = 300)]
+    length: usize,
+
+    /// Output file. prepare-mira-reports reads the default TSV
+    #[arg(short = 'o', long, default_value = "di_stats.txt")]
+    output: PathBuf,
+
+    /// Output format
+    #[arg(short = 'f', long, value_enum, default_value_t = DIFormat::Tsv)]
+    format: DIFormat,
+
+    /// 5' ratio at or above which a segment is flagged as a suspected DI
+    #[arg(long, default_value_t = 2.0)]
+    threshold_5prime: f64,
+
+    /// 3' ratio at or above which a segment is flagged as a suspected DI
+    #[arg(long, default_value_t = 2.0)]
+    threshold_3prime: f64,
+
+    /// (Optional) Also write the number of flagged segments per sample to this file, in the
+    /// same format
+    #[arg(short = 's', long)]
+    sample_summary: Option<PathBuf>,
+
+    /// (Optional) Also write the sliding window coverage profile of every segment to this
+    /// file, in the same format: the ratio of each --length window's mean to the middle window
+    /// mean, by window center
+    #[arg(short = 'p', long)]
+    profile: Option<PathBuf>,
+
+    /// Step in bp between the profile windows
+    #[arg(long, default_value_t = 1, requires = "profile")]
+    profile_step: usize,
+
+    /// (Optional) Also write the DI stats to this parquet file, with the `sample_id`, `runid` and
+    /// machine columns of the other MIRA parquet files
+    #[arg(long)]
+    parquet: Option<PathBuf>,
+
+    /// (Optional) Sequencing instrument for the machine column of the parquet output
+    #[arg(short = 'm', long, requires = "parquet")]
+    machine: Option<String>,
+
+    /// (Optional) Comma separated columns to keep in the parquet output, in this order
+    #[arg(long, value_delimiter = ',', requires = "parquet")]
+    columns: Vec<String>,
+
+    /// (Optional) Rename a parquet column, as `column=new_name`. Can be given more than once
+    #[arg(long, value_parser = parse_column_rename, requires = "parquet")]
+    rename: Vec<(String, String)>,
+
+    /// (Optional) Number of bootstrap replicates for 95% confidence intervals of the 5' and 3'
+    /// ratios. Segments are then only flagged when the lower bound reaches the threshold
+    #[arg(short = 'b', long)]
+    bootstrap: Option<usize>,
+
+    /// Seed for the bootstrap resampling, so reruns give the same intervals
+    #[arg(long, default_value_t = DEFAULT_SEED, requires = "bootstrap")]
+    seed: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum DIFormat {
+    Tsv,
+    Csv,
+    Json,
+}
+
+/// One row of the DI stats table
+#[derive(Debug, Serialize)]
+pub struct DIRecord {
+    pub run_id: String,
+    pub sample_id: String,
+    pub segment: String,
+    pub prime5: f64,
+    pub prime3: f64,
+    pub di_ratios_5prime_3prime: String,
+    /// Either end ratio is at or above its threshold, or with --bootstrap either lower bound is
+    #[serde(rename = "DI_flag")]
+    pub di_flag: bool,
+    /// Bootstrap 95% confidence intervals, only written with --bootstrap
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prime5_ci_low: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prime5_ci_high: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prime3_ci_low: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prime3_ci_high: Option<f64>,
+    /// Seed the intervals were resampled with, so they can be reproduced
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bootstrap_seed: Option<u64>,
+}
+
+/// Flagged segments of one sample
+#[derive(Debug, Serialize)]
+pub struct DISampleSummary {
+    pub run_id: String,
+    pub sample_id: String,
+    pub segments: usize,
+    pub flagged_segments: usize,
+    /// `;` separated names of the flagged segments
+    pub flagged: String,
+}
+
+/// One window of a segment's sliding window DI profile
+#[derive(Debug, Serialize)]
+pub struct DIProfilePoint {
+    pub run_id: String,
+    pub sample_id: String,
+    pub segment: String,
+    /// 1-based position of the window center
+    pub position: usize,
+    pub window_mean: f64,
+    /// Window mean / middle window mean
+    pub ratio: f64,
+}
+
+fn summarize_samples(records: &[DIRecord]) -> Vec<DISampleSummary> {
+    let mut summaries: Vec<DISampleSummary> = Vec::new();
+    for record in records {
+        let index = if let Some(index) = summaries
+            .iter()
+            .position(|s| s.sample_id == record.sample_id)
+        {
+            index
+        } else {
+            summaries.push(DISampleSummary {
+                run_id: record.run_id.clone(),
+                sample_id: record.sample_id.clone(),
+                segments: 0,
+                flagged_segments: 0,
+                flagged: String::new(),
+            });
+            summaries.len() - 1
+        };
+        let summary = &mut summaries[index];
+        summary.segments += 1;
+        if record.di_flag {
+            summary.flagged_segments += 1;
+            if !summary.flagged.is_empty() {
+                summary.flagged.push(';');
+            }
+            summary.flagged.push_str(&record.segment);
+        }
+    }
+    summaries
 }
 
 #[derive(Debug, Deserialize)]
@@ -25,21 +190,103 @@ pub struct CoverageRecord {
     coverage_depth: f64,
 }
 
-/// Given a <seg>-coverage.txt file from IRMA's output,
-/// this function returns a tuple with two ratios, one for the 5'
-/// end and one for the 3' end.
-#[allow(clippy::unnecessary_debug_formatting)]
-#[allow(clippy::cast_precision_loss)]
-pub fn di_stat(cov_file: &Path, length: usize) -> Result<(f64, f64), Box<dyn Error>> {
+/// Reads the coverage depth column of a <seg>-coverage.txt (or <seg>-coverage.a2m.txt) file
+/// from IRMA's output
+pub fn read_coverage_depths(cov_file: &Path) -> Result<Vec<f64>, Box<dyn Error>> {
     let mut rdr = csv::ReaderBuilder::new()
         .delimiter(b'\t')
         .from_path(cov_file)?;
 
-    let data: Vec<f64> = rdr
+    Ok(rdr
         .deserialize::<CoverageRecord>()
         .filter_map(|result| result.ok().map(|record| record.coverage_depth))
-        .collect();
+        .collect())
+}
+
+// The middle window, which the end windows are compared against
+fn middle_window(data: &[f64], length: usize) -> &[f64] {
+    let mid = data.len() / 2;
+    &data[mid.saturating_sub(length / 2)..mid + (length / 2)]
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn middle_mean(data: &[f64], length: usize) -> f64 {
+    let mid_slice = middle_window(data, length);
+    mid_slice.iter().sum::<f64>() / mid_slice.len() as f64
+}
+
+// Mean of a resample, with replacement, of the depths in a window
+#[allow(clippy::cast_precision_loss)]
+fn resampled_mean(window: &[f64], rng: &mut SeededRng) -> f64 {
+    let n = window.len() as u64;
+    let total: f64 = (0..window.len())
+        .map(|_| window[usize::try_from(rng.below(n)).unwrap_or_default()])
+        .sum();
+    total / window.len() as f64
+}
+
+// Value at quantile `q` of sorted values, by linear interpolation
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let rank = q * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
 
+/// Percentile bootstrap 95% confidence intervals of the 5' and 3' ratios, as
+/// ((5' low, 5' high), (3' low, 3' high)). The depths of each of the three windows are resampled
+/// with replacement `replicates` times. A segment with little coverage in its middle window
+/// gets a wide interval rather than a confidently high ratio. Expects at least `2 * length`
+/// depths, as checked by `di_stat`.
+pub fn di_bootstrap(
+    data: &[f64],
+    length: usize,
+    replicates: usize,
+    rng: &mut SeededRng,
+) -> ((f64, f64), (f64, f64)) {
+    let prime5_window = &data[..length];
+    let mid_window = middle_window(data, length);
+    let prime3_window = &data[data.len() - length..];
+
+    let mut prime5_ratios = Vec::with_capacity(replicates);
+    let mut prime3_ratios = Vec::with_capacity(replicates);
+    for _ in 0..replicates {
+        let mid_mean = resampled_mean(mid_window, rng);
+        let prime5_mean = resampled_mean(prime5_window, rng);
+        let prime3_mean = resampled_mean(prime3_window, rng);
+        if mid_mean == 0.0 {
+            prime5_ratios.push(0.0);
+            prime3_ratios.push(0.0);
+        } else {
+            prime5_ratios.push(prime5_mean / mid_mean);
+            prime3_ratios.push(prime3_mean / mid_mean);
+        }
+    }
+
+    let interval = |ratios: &mut Vec<f64>| {
+        if ratios.is_empty() {
+            return (0.0, 0.0);
+        }
+        ratios.sort_by(f64::total_cmp);
+        (
+            (quantile(ratios, 0.025) * 1000.0).round() / 1000.0,
+            (quantile(ratios, 0.975) * 1000.0).round() / 1000.0,
+        )
+    };
+    (interval(&mut prime5_ratios), interval(&mut prime3_ratios))
+}
+
+/// Given the coverage depths of a <seg>-coverage.txt file,
+/// this function returns a tuple with two ratios, one for the 5'
+/// end and one for the 3' end.
+#[allow(clippy::unnecessary_debug_formatting)]
+#[allow(clippy::cast_precision_loss)]
+pub fn di_stat(data: &[f64], cov_file: &Path, length: usize) -> Result<(f64, f64), Box<dyn Error>> {
     if data.len() < length * 2 {
         return Err(format!(
             "Not enough data in {:?} for calculation ({} points < {} required)",
@@ -50,12 +297,7 @@ pub fn di_stat(cov_file: &Path, length: usize) -> Result<(f64, f64), Box<dyn Err
         .into());
     }
 
-    let mid = data.len() / 2;
-    let mid_start = mid.saturating_sub(length / 2);
-    let mid_end = mid + (length / 2);
-
-    let mid_slice = &data[mid_start..mid_end];
-    let mid_mean = mid_slice.iter().sum::<f64>() / mid_slice.len() as f64;
+    let mid_mean = middle_mean(data, length);
 
     if mid_mean == 0.0 {
         return Ok((0.0, 0.0));
@@ -73,15 +315,125 @@ pub fn di_stat(cov_file: &Path, length: usize) -> Result<(f64, f64), Box<dyn Err
     Ok((prime5_ratio, prime3_ratio))
 }
 
-/// Calculate 5p and 3p DI stats for an entire assembly directory.
-#[allow(clippy::unnecessary_debug_formatting)]
+/// The mean depth of every `length` window, `step` bp apart, and its ratio to the middle
+/// window mean as (window center, window mean, ratio). Ratios are 0 when the middle window
+/// has no coverage. Expects at least `2 * length` depths, as checked by `di_stat`.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn di_profile(data: &[f64], length: usize, step: usize) -> Vec<(usize, f64, f64)> {
+    let mid_mean = middle_mean(data, length);
+
+    // Running sums so that each window mean is O(1)
+    let mut prefix = Vec::with_capacity(data.len() + 1);
+    prefix.push(0.0);
+    for depth in data {
+        prefix.push(prefix[prefix.len() - 1] + depth);
+    }
+
+    (0..=data.len() - length)
+        .step_by(step)
+        .map(|start| {
+            let window_mean = (prefix[start + length] - prefix[start]) / length as f64;
+            let ratio = if mid_mean == 0.0 {
+                0.0
+            } else {
+                (window_mean / mid_mean * 1000.0).round() / 1000.0
+            };
+            (
+                start + length / 2 + 1,
+                (window_mean * 1000.0).round() / 1000.0,
+                ratio,
+            )
+        })
+        .collect()
+}
+
+/// The DI record and, when profiling, the sliding window profile of one coverage file
+type SegmentStats = (DIRecord, Vec<DIProfilePoint>);
+
+/// Bootstrap replicates and the seed of the first coverage file
+#[derive(Debug, Clone, Copy)]
+pub struct Bootstrap {
+    pub replicates: usize,
+    pub seed: u64,
+}
+
+#[allow(clippy::unnecessary_debug_formatting, clippy::too_many_arguments)]
+fn di_stat_segment(
+    cov_path: &Path,
+    sample_id: &str,
+    segment: &str,
+    run_id: &str,
+    length: usize,
+    thresholds: (f64, f64),
+    profile_step: Option<usize>,
+    bootstrap: Option<Bootstrap>,
+) -> Result<SegmentStats, String> {
+    let data = read_coverage_depths(cov_path).map_err(|e| e.to_string())?;
+    let (prime5, prime3) = di_stat(&data, cov_path, length).map_err(|e| e.to_string())?;
+    let intervals =
+        bootstrap.map(|b| di_bootstrap(&data, length, b.replicates, &mut SeededRng::new(b.seed)));
+    // Flag on the lower bounds when there are intervals, so that a high ratio over a
+    // barely covered middle window is not called
+    let di_flag = match intervals {
+        Some(((prime5_low, _), (prime3_low, _))) => {
+            prime5_low >= thresholds.0 || prime3_low >= thresholds.1
+        }
+        None => prime5 >= thresholds.0 || prime3 >= thresholds.1,
+    };
+    let record = DIRecord {
+        run_id: run_id.to_string(),
+        sample_id: sample_id.to_string(),
+        segment: segment.to_string(),
+        prime5,
+        prime3,
+        di_ratios_5prime_3prime: format!("{prime5};{prime3}"),
+        di_flag,
+        prime5_ci_low: intervals.map(|((low, _), _)| low),
+        prime5_ci_high: intervals.map(|((_, high), _)| high),
+        prime3_ci_low: intervals.map(|(_, (low, _))| low),
+        prime3_ci_high: intervals.map(|(_, (_, high))| high),
+        bootstrap_seed: bootstrap.map(|b| b.seed),
+    };
+    let profile = profile_step.map_or_else(Vec::new, |step| {
+        di_profile(&data, length, step)
+            .into_iter()
+            .map(|(position, window_mean, ratio)| DIProfilePoint {
+                run_id: run_id.to_string(),
+                sample_id: sample_id.to_string(),
+                segment: segment.to_string(),
+                position,
+                window_mean,
+                ratio,
+            })
+            .collect()
+    });
+    Ok((record, profile))
+}
+
+/// Calculate 5p and 3p DI stats for an entire assembly directory, from the coverage files
+/// matching `cov_glob` in each sample's tables directory.
+/// Coverage files are processed in parallel, but the results keep the order of the directory
+/// listing so the output is the same from run to run. Each file is bootstrapped with its own
+/// seed, offset from the given one by its place in that order.
+#[allow(clippy::unnecessary_debug_formatting, clippy::too_many_arguments)]
 pub fn di_stat_assembly(
     assembly_dir: &Path,
+    sample_glob: &str,
+    cov_glob: &str,
     run_id: &str,
-    writer: &mut impl Write,
-) -> Result<(), Box<dyn Error>> {
-    let path_pattern = format!("{}/*/IRMA/*", assembly_dir.to_str().unwrap_or_default());
+    length: usize,
+    thresholds: (f64, f64),
+    profile_step: Option<usize>,
+    bootstrap: Option<Bootstrap>,
+) -> Result<(Vec<DIRecord>, Vec<DIProfilePoint>), Box<dyn Error>> {
+    let path_pattern = format!(
+        "{}/{sample_glob}",
+        assembly_dir.to_str().unwrap_or_default()
+    );
 
+    // (sample, segment, coverage file) for every segment of every sample
+    let mut cov_files: Vec<(String, String, PathBuf)> = Vec::new();
     for entry in glob(&path_pattern)?.filter_map(Result::ok) {
         if entry.is_dir() {
             let sample_id = entry
@@ -90,10 +442,7 @@ pub fn di_stat_assembly(
                 .to_str()
                 .unwrap_or_default();
 
-            let cov_pattern = format!(
-                "{}/tables/*coverage.txt",
-                entry.to_str().unwrap_or_default()
-            );
+            let cov_pattern = format!("{}/tables/{cov_glob}", entry.to_str().unwrap_or_default());
 
             for cov_path in glob(&cov_pattern)?.filter_map(Result::ok) {
                 let cov_str = cov_path.to_str().unwrap_or_default();
@@ -103,35 +452,175 @@ pub fn di_stat_assembly(
                     .nth(1)
                     .and_then(|s| s.split('-').next())
                 {
-                    match di_stat(&cov_path, 300) {
-                        Ok((prime5, prime3)) => {
-                            writeln!(
-                                writer,
-                                "{run_id}\t{sample_id}\t{seg}\t{prime5}\t{prime3}\t{prime5};{prime3}"
-                            )?;
-                        }
-                        Err(e) => eprintln!("Could not process file {cov_path:?}: {e}"),
-                    }
+                    cov_files.push((sample_id.to_string(), seg.to_string(), cov_path.clone()));
                 }
             }
         }
     }
 
+    if cov_files.is_empty() {
+        eprintln!(
+            "Warning: no tables/{cov_glob} files found under {path_pattern}, check --sample-glob"
+        );
+    }
+
+    let results: Vec<Result<SegmentStats, String>> = cov_files
+        .par_iter()
+        .enumerate()
+        .map(|(index, (sample_id, seg, cov_path))| {
+            di_stat_segment(
+                cov_path,
+                sample_id,
+                seg,
+                run_id,
+                length,
+                thresholds,
+                profile_step,
+                bootstrap.map(|b| Bootstrap {
+                    seed: b.seed.wrapping_add(index as u64),
+                    ..b
+                }),
+            )
+        })
+        .collect();
+
+    let mut records = Vec::with_capacity(results.len());
+    let mut profile = Vec::new();
+    for ((_, _, cov_path), result) in cov_files.iter().zip(results) {
+        match result {
+            Ok((record, points)) => {
+                records.push(record);
+                profile.extend(points);
+            }
+            Err(e) => eprintln!("Could not process file {cov_path:?}: {e}"),
+        }
+    }
+
+    Ok((records, profile))
+}
+
+fn write_records<T: Serialize>(
+    records: &[T],
+    format: DIFormat,
+    mut writer: impl Write,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        DIFormat::Tsv | DIFormat::Csv => {
+            let delimiter = if format == DIFormat::Tsv { b'\t' } else { b',' };
+            let mut csv_writer = csv::WriterBuilder::new()
+                .delimiter(delimiter)
+                .from_writer(writer);
+            for record in records {
+                csv_writer.serialize(record)?;
+            }
+            csv_writer.flush()?;
+        }
+        DIFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, records)?;
+            writeln!(writer)?;
+            writer.flush()?;
+        }
+    }
     Ok(())
 }
 
 pub fn di_stats_process(args: &DIStatArgs) -> Result<(), std::io::Error> {
-    let file = File::create("di_stats.txt")?;
-    let mut writer = BufWriter::new(file);
+    if args.length == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "--length must be greater than 0",
+        ));
+    }
+
+    if args.profile_step == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "--profile-step must be greater than 0",
+        ));
+    }
+
+    let thresholds = (args.threshold_5prime, args.threshold_3prime);
+    if args.bootstrap == Some(0) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "--bootstrap must be greater than 0",
+        ));
+    }
+
+    let profile_step = args.profile.as_ref().map(|_| args.profile_step);
+    let bootstrap = args.bootstrap.map(|replicates| Bootstrap {
+        replicates,
+        seed: args.seed,
+    });
+    let run_id = args.run_id.clone().unwrap_or_else(|| {
+        let run_id = args
+            .assemblies_dir
+            .canonicalize()
+            .unwrap_or_else(|_| args.assemblies_dir.clone())
+            .file_name()
+            .map_or_else(
+                || "unknown".to_string(),
+                |name| name.to_string_lossy().into(),
+            );
+        status!("No --run-id given, using {run_id}");
+        run_id
+    });
+    let (records, profile) = match di_stat_assembly(
+        &args.assemblies_dir,
+        &args.sample_glob,
+        if args.a2m {
+            "*coverage.a2m.txt"
+        } else {
+            "*coverage.txt"
+        },
+        &run_id,
+        args.length,
+        thresholds,
+        profile_step,
+        bootstrap,
+    ) {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("Application error: {e}");
+            (Vec::new(), Vec::new())
+        }
+    };
 
-    // Write header
-    writeln!(
-        writer,
-        "run_id\tsample_id\tsegment\tprime5\tprime3\tdi_ratios_5prime_3prime"
-    )?;
+    let file = File::create(&args.output)?;
+    write_records(&records, args.format, BufWriter::new(file))
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
 
-    if let Err(e) = di_stat_assembly(&args.assemblies_dir, &args.run_id, &mut writer) {
-        eprintln!("Application error: {e}");
+    let summaries = summarize_samples(&records);
+    for summary in summaries.iter().filter(|s| s.flagged_segments > 0) {
+        status!(
+            "{}: {} of {} segments flagged as suspected DI ({})",
+            summary.sample_id,
+            summary.flagged_segments,
+            summary.segments,
+            summary.flagged
+        );
+    }
+    if let Some(path) = &args.sample_summary {
+        let file = File::create(path)?;
+        write_records(&summaries, args.format, BufWriter::new(file))
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+    }
+    if let Some(path) = &args.parquet {
+        write_di_stats_to_parquet(
+            &records,
+            args.machine.as_deref(),
+            &path.display().to_string(),
+            &ParquetColumns {
+                columns: args.columns.clone(),
+                rename: args.rename.clone(),
+            },
+        )
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    }
+    if let Some(path) = &args.profile {
+        let file = File::create(path)?;
+        write_records(&profile, args.format, BufWriter::new(file))
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
     }
 
     Ok(())
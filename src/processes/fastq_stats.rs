@@ -0,0 +1,185 @@
+#![allow(clippy::cast_precision_loss)]
+use crate::status;
+use crate::utils::{
+    fastq_read::open_fastq_file, output::output_writer, read_duplication::estimate_duplication,
+};
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Phred+33 quality encoding offset
+const PHRED_OFFSET: u8 = 33;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Parser)]
+#[command(about = "Read count, length, quality and GC statistics for FASTQ files (gzip aware)")]
+pub struct FastqStatsArgs {
+    #[arg(short = 'i', long, num_args = 1.., required = true)]
+    /// FASTQ files, gzipped or not
+    inputs: Vec<PathBuf>,
+
+    #[arg(short = 'o', long)]
+    /// Optional output file. Defaults to stdout
+    output: Option<PathBuf>,
+
+    #[arg(long, value_enum, default_value_t = OutputFormat::Csv)]
+    /// Output format
+    output_format: OutputFormat,
+
+    #[arg(long)]
+    /// Also estimate the fraction of unique reads (see find-chemistry --duplication). Paired
+    /// `_R1` files are keyed with their `_R2` mate
+    duplication: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct FastqStats {
+    file: String,
+    reads: usize,
+    total_bases: usize,
+    min_length: usize,
+    max_length: usize,
+    mean_length: f64,
+    median_length: usize,
+    n50: usize,
+    /// Fraction of bases with quality >= 20
+    q20_fraction: f64,
+    /// Fraction of bases with quality >= 30
+    q30_fraction: f64,
+    gc_percent: f64,
+    /// Estimated fraction of distinct reads, with --duplication
+    unique_fraction: Option<f64>,
+}
+
+// Read length -> number of reads. Keeps memory flat however many reads a file has
+type LengthHistogram = BTreeMap<usize, usize>;
+
+// The length of the middle read, the lower of the two for an even number of reads
+fn median_length(lengths: &LengthHistogram, reads: usize) -> usize {
+    let mut seen = 0;
+    for (&length, &count) in lengths {
+        seen += count;
+        if seen * 2 >= reads {
+            return length;
+        }
+    }
+    0
+}
+
+// Half of all bases are in reads at least this long
+fn n50(lengths: &LengthHistogram, total_bases: usize) -> usize {
+    let mut bases = 0;
+    for (&length, &count) in lengths.iter().rev() {
+        bases += length * count;
+        if bases * 2 >= total_bases {
+            return length;
+        }
+    }
+    0
+}
+
+fn fastq_stats(path: &Path, duplication: bool) -> Result<FastqStats, Box<dyn Error>> {
+    let reader = open_fastq_file(path)
+        .map_err(|e| format!("Could not open file '{}': {e}", path.display()))?;
+
+    let mut lengths = LengthHistogram::new();
+    let mut reads = 0;
+    let mut total_bases = 0;
+    let mut q20_bases = 0;
+    let mut q30_bases = 0;
+    let mut gc_bases = 0;
+    for result in reader {
+        let record = result?;
+        let sequence = record.sequence.as_bytes();
+        reads += 1;
+        total_bases += sequence.len();
+        *lengths.entry(sequence.len()).or_default() += 1;
+        gc_bases += sequence
+            .iter()
+            .filter(|base| matches!(base.to_ascii_uppercase(), b'G' | b'C' | b'S'))
+            .count();
+        for &quality in record.quality.as_bytes() {
+            let phred = quality.saturating_sub(PHRED_OFFSET);
+            if phred >= 20 {
+                q20_bases += 1;
+            }
+            if phred >= 30 {
+                q30_bases += 1;
+            }
+        }
+    }
+
+    let fraction = |count: usize, total: usize| {
+        if total == 0 {
+            0.0
+        } else {
+            count as f64 / total as f64
+        }
+    };
+
+    let unique_fraction = if duplication {
+        estimate_duplication(path)?.map(|estimate| estimate.unique_fraction())
+    } else {
+        None
+    };
+
+    Ok(FastqStats {
+        file: path.file_name().map_or_else(
+            || path.display().to_string(),
+            |f| f.to_string_lossy().into(),
+        ),
+        reads,
+        total_bases,
+        min_length: lengths.keys().next().copied().unwrap_or(0),
+        max_length: lengths.keys().next_back().copied().unwrap_or(0),
+        mean_length: fraction(total_bases, reads),
+        median_length: median_length(&lengths, reads),
+        n50: n50(&lengths, total_bases),
+        q20_fraction: fraction(q20_bases, total_bases),
+        q30_fraction: fraction(q30_bases, total_bases),
+        gc_percent: fraction(gc_bases, total_bases) * 100.0,
+        unique_fraction,
+    })
+}
+
+pub fn fastq_stats_process(args: &FastqStatsArgs) -> Result<(), Box<dyn Error>> {
+    let mut stats = Vec::with_capacity(args.inputs.len());
+    for path in &args.inputs {
+        status!("Reading {}", path.display());
+        stats.push(fastq_stats(path, args.duplication)?);
+    }
+
+    let mut writer = output_writer(args.output.as_deref())?;
+    match args.output_format {
+        OutputFormat::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(writer);
+            for row in &stats {
+                csv_writer.serialize(row)?;
+            }
+            csv_writer.flush()?;
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &stats)?;
+            writeln!(&mut writer)?;
+            writer.flush()?;
+        }
+    }
+    if let Some(path) = &args.output {
+        let format = match args.output_format {
+            OutputFormat::Csv => "CSV",
+            OutputFormat::Json => "JSON",
+        };
+        status!(" -> {format} written to {}", path.display());
+    }
+    Ok(())
+}
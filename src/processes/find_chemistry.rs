@@ -1,69 +1,303 @@
 use clap::{Parser, ValueEnum, builder::PossibleValue};
+use csv::Reader;
+use serde::{Deserialize, Serialize, Serializer};
 use std::{
+    collections::BTreeMap,
     fmt,
-    fs::OpenOptions,
+    fs::{OpenOptions, read_to_string},
     io::{BufWriter, Write},
     path::{Path, PathBuf},
+    sync::OnceLock,
 };
 use zoe::prelude::*;
 
-use crate::utils::fastq_read::open_fastq_file;
+use crate::{
+    processes::create_nextflow_samplesheet::find_fastq,
+    status,
+    utils::{
+        adapter_screen::untrimmed_fraction,
+        fastq_read::{
+            Platform, ReadLayout, check_mates, count_reads, detect_read_layout, mate_path,
+            open_fastq_file,
+        },
+        read_duplication::estimate_duplication,
+    },
+};
+
+/// Share of untrimmed reads above which the adapter screen warns
+const UNTRIMMED_WARN_FRACTION: f64 = 0.05;
 
+// The bools are separate opt-in checks and switches, each its own --flag
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Debug, Parser)]
 #[command(about = "Get relevant IRMA configuration and modules for the current experiment.")]
 pub struct FindChemArgs {
-    #[arg(short = 's', long)]
+    #[arg(short = 's', long, required_unless_present = "samplesheet")]
     /// Name of sample
-    pub sample: String,
+    pub sample: Option<String>,
 
-    #[arg(short = 'q', long)]
+    #[arg(short = 'q', long, required_unless_present = "samplesheet")]
     /// Path to fastq file
-    pub fastq: PathBuf,
+    pub fastq: Option<PathBuf>,
+
+    #[arg(short = 'b', long, conflicts_with_all = ["sample", "fastq"], requires = "fastq_dir")]
+    /// Samplesheet CSV with a `sample_id` (or `sample`) column. Runs every sample in one
+    /// invocation and writes a single combined chemistry CSV
+    pub samplesheet: Option<PathBuf>,
+
+    #[arg(short = 'f', long, requires = "samplesheet")]
+    /// Directory containing the fastq files for the samples in the samplesheet
+    pub fastq_dir: Option<PathBuf>,
+
+    #[arg(short = 'o', long)]
+    /// (Optional) Output file. Default is `<sample>_chemistry.csv`, or `chemistry.csv` when
+    /// using --samplesheet (`.json` with --output-format json)
+    pub output: Option<PathBuf>,
 
-    #[arg(short = 'e', long, ignore_case = true)]
-    /// Experiment type
+    #[arg(long, value_enum, default_value_t = OutputFormat::Csv)]
+    /// Output format. JSON also includes the detected read length and any warnings
+    pub output_format: OutputFormat,
+
+    #[arg(short = 'e', long, value_parser = parse_experiment)]
+    /// Experiment type: Flu-Illumina, SC2-Whole-Genome-Illumina, RSV-Illumina, Flu-ONT,
+    /// SC2-Spike-Only-ONT, SC2-Whole-Genome-ONT, RSV-ONT, Flu-AD, or an experiment defined in
+    /// --experiment-config. Use `auto` to infer the platform from the reads (requires --virus)
     pub experiment: Experiment,
 
+    #[arg(short = 'x', long)]
+    /// (Optional) YAML file defining additional experiment types, mapping each name to its IRMA
+    /// module and configs
+    pub experiment_config: Option<PathBuf>,
+
+    #[arg(short = 'v', long)]
+    /// Virus (flu, sc2, sc2-spike, rsv, or a virus from --experiment-config). Used with
+    /// `--experiment auto`
+    pub virus: Option<String>,
+
     #[arg(short = 'p', long)]
     /// Path to working directory
     pub wd_path: PathBuf,
 
-    #[arg(short = 'c', long)]
-    /// Read counts
-    pub read_count: usize,
+    #[arg(
+        short = 'c',
+        long,
+        required_unless_present_any = ["count_reads", "samplesheet"],
+        conflicts_with = "samplesheet"
+    )]
+    /// Read counts. Not used with --samplesheet, where each sample's fastq is counted
+    pub read_count: Option<usize>,
+
+    #[arg(short = 'n', long, default_value_t = 1000)]
+    /// Number of reads (from each of R1 and R2 for paired data) used to estimate the read length
+    /// when choosing between the 2x75 and standard IRMA configs
+    pub n_reads: usize,
+
+    #[arg(short = 'm', long, default_value_t = 1)]
+    /// Minimum number of reads a fastq needs to be assembled. Empty fastqs and samples below it,
+    /// in the fastq or in the read count used, get a row flagged `skip` (`skip_assembly` true)
+    /// with the reason instead of an IRMA config
+    pub min_reads: usize,
+
+    #[arg(short = 't', long)]
+    /// (Optional) Target mean genome coverage, e.g. 2000. Computes the subsample from the read
+    /// length and genome size instead of passing the read count through, capped at the reads
+    /// available (--read-count or --count-reads)
+    pub target_coverage: Option<f64>,
+
+    #[arg(long, requires = "target_coverage")]
+    /// (Optional) Genome size in bases for --target-coverage. Default is the size for the
+    /// experiment's virus
+    pub genome_size: Option<usize>,
+
+    #[arg(long)]
+    /// Screen the first --n-reads reads for leftover ONT adapter/barcode sequences and report
+    /// the fraction of untrimmed reads
+    pub adapter_screen: bool,
+
+    #[arg(long)]
+    /// Estimate the PCR duplication rate of Illumina libraries from read prefixes and report
+    /// the estimated fraction of unique reads
+    pub duplication: bool,
+
+    #[arg(long)]
+    /// For paired Illumina data, check that R1 and R2 have the same number of reads and that
+    /// the read names of the first --n-reads pairs match. Samples that fail are flagged `skip`
+    pub check_mates: bool,
+
+    #[arg(long, conflicts_with = "read_count")]
+    /// Count the reads in the fastq instead of using --read-count. For paired Illumina data the
+    /// R2 mate is counted too and the number of read pairs is used
+    pub count_reads: bool,
 
     #[arg(short = 'i', long, ignore_case = true, default_value = "None")]
     /// Alternative IRMA config. To use Sensitive, Secondary, or UTR, the
-    /// experiment type must be Flu-Illumina, Flu-ONT, or Flu-AD.
+    /// experiment type must be Flu-Illumina. To use Ad it must be Flu-AD.
     pub irma_config: IRMAConfig,
 
     #[arg(short = 'g', long)]
     /// Custom irma config path
     pub irma_config_path: Option<PathBuf>,
+
+    #[arg(long)]
+    /// Downgrade experiment/IRMA config mismatches from errors to warnings
+    pub allow_mismatch: bool,
+
+    #[arg(long = "irma-set", value_name = "KEY=VALUE", value_parser = parse_irma_override, requires = "emit_config")]
+    /// Override a parameter of the selected IRMA config, e.g. `--irma-set MIN_LEN=100`. Can be
+    /// given more than once
+    pub irma_overrides: Vec<(String, String)>,
+
+    #[arg(long)]
+    /// (Optional) Write the selected IRMA config, with --irma-set overrides applied, to this file
+    /// and report it as the config to run. With --samplesheet this is a directory holding one
+    /// `<sample>_irma_config.sh` per sample
+    pub emit_config: Option<PathBuf>,
+}
+
+/// Problems found when checking the experiment and IRMA config arguments against each other
+#[derive(Debug)]
+pub enum ChemistryValidationError {
+    /// The IRMA config is only meant for other experiment types
+    ConfigExperimentMismatch {
+        experiment: Experiment,
+        irma_config: IRMAConfig,
+        expected: &'static str,
+    },
+    /// A custom config was requested without a config path
+    MissingCustomConfigPath,
+    /// A config path was given but the IRMA config is not Custom, so it is ignored
+    UnusedCustomConfigPath(PathBuf),
+    /// The experiment is neither built in nor defined in the experiment config
+    UnknownExperiment(String),
 }
 
+impl ChemistryValidationError {
+    /// Whether the problem should stop the run. Mismatches can be allowed by the user, but a
+    /// custom config without a path cannot produce any config at all.
+    fn is_fatal(&self, allow_mismatch: bool) -> bool {
+        match self {
+            Self::ConfigExperimentMismatch { .. } => !allow_mismatch,
+            Self::MissingCustomConfigPath | Self::UnknownExperiment(_) => true,
+            Self::UnusedCustomConfigPath(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for ChemistryValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ConfigExperimentMismatch {
+                experiment,
+                irma_config,
+                expected,
+            } => write!(
+                f,
+                "Invalid combination: {irma_config:?} IRMA config can only be used with {expected}, not {experiment} (would run the {} module with a {irma_config:?} config). Use --allow-mismatch to run anyway",
+                experiment.get_module()
+            ),
+            Self::MissingCustomConfigPath => write!(
+                f,
+                "Custom IRMA config specified but no path to config file was provided (--irma-config-path)"
+            ),
+            Self::UnusedCustomConfigPath(path) => write!(
+                f,
+                "IRMA config path {} is ignored because the IRMA config is not Custom",
+                path.display()
+            ),
+            Self::UnknownExperiment(name) => write!(
+                f,
+                "Unknown experiment type {name}. It is not a built in experiment and is not defined in --experiment-config"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ChemistryValidationError {}
+
 impl FindChemArgs {
     /// Function for ensuring that specific IRMA configs are only used with the
     /// proper experiment. Secondary, Sensitive, and UTR must be matched with a
     /// Flu experiment. Flu-AD config must be matched with the Flu-AD experiment.
-    fn validate(&self) -> Result<(), String> {
+    /// Returns every problem found, see [`ChemistryValidationError::is_fatal`] for which stop
+    /// the run.
+    /// The experiment is passed in separately as `auto` is only resolved once the reads are seen.
+    fn validate(&self, experiment: &Experiment) -> Vec<ChemistryValidationError> {
+        let mut issues = Vec::new();
+
+        if let Experiment::External(name) = experiment
+            && external_experiment(name).is_none()
+        {
+            issues.push(ChemistryValidationError::UnknownExperiment(name.clone()));
+            return issues;
+        }
+
         match self.irma_config {
             IRMAConfig::Sensitive | IRMAConfig::Secondary | IRMAConfig::UTR
-                if self.experiment == Experiment::FluIllumina =>
+                if *experiment != Experiment::FluIllumina =>
             {
-                Ok(())
+                issues.push(ChemistryValidationError::ConfigExperimentMismatch {
+                    experiment: experiment.clone(),
+                    irma_config: self.irma_config,
+                    expected: "Flu-Illumina",
+                });
+            }
+            IRMAConfig::Ad if *experiment != Experiment::FluAD => {
+                issues.push(ChemistryValidationError::ConfigExperimentMismatch {
+                    experiment: experiment.clone(),
+                    irma_config: self.irma_config,
+                    expected: "Flu-AD",
+                });
+            }
+            IRMAConfig::Custom if self.irma_config_path.is_none() => {
+                issues.push(ChemistryValidationError::MissingCustomConfigPath);
             }
-            IRMAConfig::Ad if self.experiment == Experiment::FluAD => Ok(()),
-            IRMAConfig::Custom | IRMAConfig::NoConfig => Ok(()),
-            _ => Err(format!(
-                "Invalid combination: {:?} cannot be used with {:?}",
-                self.experiment, self.irma_config
-            )),
+            _ => {}
+        }
+
+        if self.irma_config != IRMAConfig::Custom
+            && let Some(path) = &self.irma_config_path
+        {
+            issues.push(ChemistryValidationError::UnusedCustomConfigPath(
+                path.clone(),
+            ));
         }
+
+        issues
+    }
+
+    /// Prints validation problems for the experiment, exiting if any of them are fatal.
+    /// Returns the warnings that were printed.
+    fn check_experiment(&self, experiment: &Experiment) -> Vec<String> {
+        let mut invalid = false;
+        let mut warnings = Vec::new();
+        for issue in self.validate(experiment) {
+            if issue.is_fatal(self.allow_mismatch) {
+                eprintln!("Error: {issue}");
+                invalid = true;
+            } else {
+                warn(&mut warnings, issue.to_string());
+            }
+        }
+        if invalid {
+            std::process::exit(1);
+        }
+        warnings
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// Prints a warning and keeps it for the JSON output
+fn warn(warnings: &mut Vec<String>, message: String) {
+    eprintln!("Warning: {message}");
+    warnings.push(message);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 /// Enum for the possible experiment types, both Illumina and ONT
 pub enum Experiment {
     FluIllumina,
@@ -74,6 +308,17 @@ pub enum Experiment {
     SC2WholeGenomeONT,
     RSVONT,
     FluAD,
+    Auto,
+    /// An experiment defined in the `--experiment-config` yaml, by name
+    External(String),
+}
+
+/// Built in experiments match by their literal strings (ignoring case), anything else is looked
+/// up in the experiment config once it has been loaded
+#[allow(clippy::unnecessary_wraps)]
+fn parse_experiment(value: &str) -> Result<Experiment, String> {
+    Ok(<Experiment as ValueEnum>::from_str(value, true)
+        .unwrap_or_else(|_| Experiment::External(value.to_string())))
 }
 
 impl ValueEnum for Experiment {
@@ -88,6 +333,7 @@ impl ValueEnum for Experiment {
             Self::SC2WholeGenomeONT,
             Self::RSVONT,
             Self::FluAD,
+            Self::Auto,
         ]
     }
 
@@ -114,13 +360,88 @@ impl ValueEnum for Experiment {
             }
             Experiment::RSVONT => Some(PossibleValue::new("RSV-ONT").alias("RSVONT")),
             Experiment::FluAD => Some(PossibleValue::new("Flu-AD").alias("FluAD")),
+            Experiment::Auto => Some(PossibleValue::new("auto")),
+            Experiment::External(_) => None,
         }
     }
 }
 
+impl fmt::Display for Experiment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::External(name) => write!(f, "{name}"),
+            _ => match self.to_possible_value() {
+                Some(value) => write!(f, "{}", value.get_name()),
+                None => write!(f, "{self:?}"),
+            },
+        }
+    }
+}
+
+/// An experiment type defined in the `--experiment-config` yaml, so a new assay only needs an
+/// IRMA module and config rather than a code change
+#[derive(Debug, Deserialize)]
+pub struct ExternalExperiment {
+    /// IRMA module to run, e.g. `MEASLES`
+    module: String,
+    /// Platform the reads are expected from (`illumina` or `ont`)
+    #[serde(default)]
+    platform: Option<Platform>,
+    /// Virus name used to resolve `--experiment auto`
+    #[serde(default)]
+    virus: Option<String>,
+    /// IRMA config, relative to the working directory like the built in configs
+    config: String,
+    /// (Optional) IRMA config used when the median read length is below `short_read_length`
+    #[serde(default)]
+    short_read_config: Option<String>,
+    #[serde(default)]
+    short_read_length: Option<usize>,
+    /// (Optional) Genome size in bases, used with `--target-coverage`
+    #[serde(default)]
+    genome_size: Option<usize>,
+}
+
+impl ExternalExperiment {
+    fn config_for(&self, seq_len: usize) -> &str {
+        match (&self.short_read_config, self.short_read_length) {
+            (Some(short_config), Some(min_len)) if seq_len < min_len => short_config,
+            _ => &self.config,
+        }
+    }
+}
+
+/// Experiments from `--experiment-config`, keyed by name. Loaded once before any sample is run.
+static EXTERNAL_EXPERIMENTS: OnceLock<BTreeMap<String, ExternalExperiment>> = OnceLock::new();
+
+fn load_experiment_config(path: &Path) -> Result<(), std::io::Error> {
+    let contents = read_to_string(path)?;
+    let experiments: BTreeMap<String, ExternalExperiment> = serde_yaml_ng::from_str(&contents)
+        .map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Could not parse experiment config '{}': {e}",
+                    path.display()
+                ),
+            )
+        })?;
+    EXTERNAL_EXPERIMENTS.get_or_init(|| experiments);
+    Ok(())
+}
+
+/// Looks up an experiment from the experiment config, ignoring case like the built in names
+fn external_experiment(name: &str) -> Option<&'static ExternalExperiment> {
+    EXTERNAL_EXPERIMENTS
+        .get()?
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, experiment)| experiment)
+}
+
 impl Experiment {
     /// Selects the appropriate IRMA Module from the user provided experiment type
-    fn get_module(self) -> IrmaModule {
+    fn get_module(&self) -> IrmaModule {
         match self {
             Self::FluIllumina => IrmaModule::FLU,
             Self::FluAD => IrmaModule::FluAd,
@@ -128,6 +449,65 @@ impl Experiment {
             Self::SC2WholeGenomeIllumina | Self::SC2WholeGenomeONT => IrmaModule::CoV,
             Self::FluONT => IrmaModule::FLUMinion,
             Self::SC2SpikeOnlyONT => IrmaModule::CoVsGene,
+            Self::Auto => unreachable!("auto experiment is resolved before selecting a module"),
+            Self::External(name) => IrmaModule::Custom(
+                external_experiment(name)
+                    .expect("external experiments are validated before selecting a module")
+                    .module
+                    .clone(),
+            ),
+        }
+    }
+
+    /// The platform the experiment expects reads from. Flu-AD is not tied to one platform.
+    fn platform(&self) -> Option<Platform> {
+        match self {
+            Self::FluIllumina | Self::SC2WholeGenomeIllumina | Self::RSVIllumina => {
+                Some(Platform::Illumina)
+            }
+            Self::FluONT | Self::SC2SpikeOnlyONT | Self::SC2WholeGenomeONT | Self::RSVONT => {
+                Some(Platform::ONT)
+            }
+            Self::FluAD | Self::Auto => None,
+            Self::External(name) => external_experiment(name).and_then(|e| e.platform),
+        }
+    }
+
+    /// Approximate genome size in bases of the experiment's virus, for `--target-coverage`. For
+    /// spike only experiments this is the spike gene.
+    fn genome_size(&self) -> Option<usize> {
+        match self {
+            Self::FluIllumina | Self::FluONT | Self::FluAD => Some(13_600),
+            Self::SC2WholeGenomeIllumina | Self::SC2WholeGenomeONT => Some(29_903),
+            Self::SC2SpikeOnlyONT => Some(3_822),
+            Self::RSVIllumina | Self::RSVONT => Some(15_200),
+            Self::Auto => None,
+            Self::External(name) => external_experiment(name).and_then(|e| e.genome_size),
+        }
+    }
+
+    /// Picks the experiment for `--experiment auto` from the virus and the detected platform.
+    /// Built in experiments take precedence over ones from the experiment config.
+    fn from_virus_and_platform(virus: &str, platform: Platform) -> Option<Self> {
+        match (virus.to_lowercase().as_str(), platform) {
+            ("flu", Platform::Illumina) => Some(Self::FluIllumina),
+            ("flu", Platform::ONT) => Some(Self::FluONT),
+            ("sc2" | "sc2-wgs", Platform::Illumina) => Some(Self::SC2WholeGenomeIllumina),
+            ("sc2" | "sc2-wgs", Platform::ONT) => Some(Self::SC2WholeGenomeONT),
+            ("sc2-spike", Platform::ONT) => Some(Self::SC2SpikeOnlyONT),
+            ("rsv", Platform::Illumina) => Some(Self::RSVIllumina),
+            ("rsv", Platform::ONT) => Some(Self::RSVONT),
+            _ => EXTERNAL_EXPERIMENTS
+                .get()?
+                .iter()
+                .find_map(|(name, experiment)| {
+                    let same_virus = experiment
+                        .virus
+                        .as_deref()
+                        .is_some_and(|v| v.eq_ignore_ascii_case(virus));
+                    (same_virus && experiment.platform == Some(platform))
+                        .then(|| Self::External(name.clone()))
+                }),
         }
     }
 }
@@ -170,7 +550,7 @@ impl ValueEnum for IRMAConfig {
 
 /// Selects the correct config file based on experiment, custom config path, and
 /// length of sequences
-fn get_config_path(args: &FindChemArgs, seq_len: Option<usize>) -> String {
+fn get_config_path(args: &FindChemArgs, experiment: &Experiment, seq_len: Option<usize>) -> String {
     if args.irma_config == IRMAConfig::Custom {
         return args
             .irma_config_path
@@ -181,7 +561,7 @@ fn get_config_path(args: &FindChemArgs, seq_len: Option<usize>) -> String {
             .to_string();
     }
 
-    let path_extension = match (args.experiment, seq_len, args.irma_config) {
+    let path_extension = match (experiment, seq_len, args.irma_config) {
         (_, None, _) => return String::new(),
         (_, _, IRMAConfig::Sensitive) => "/bin/irma_config/FLU-sensitive.sh",
         (_, _, IRMAConfig::Secondary) => "/bin/irma_config/FLU-secondary.sh",
@@ -189,7 +569,7 @@ fn get_config_path(args: &FindChemArgs, seq_len: Option<usize>) -> String {
         (_, _, IRMAConfig::Ad) | (Experiment::FluAD, Some(_), IRMAConfig::NoConfig) => {
             "/bin/irma_config/FLU-AD.sh"
         }
-        (_, _, IRMAConfig::Custom) => unreachable!(),
+        (_, _, IRMAConfig::Custom) | (Experiment::Auto, _, _) => unreachable!(),
         (Experiment::FluIllumina, Some(seq_len), IRMAConfig::NoConfig) => {
             if seq_len >= 145 {
                 "/bin/irma_config/FLU.sh"
@@ -219,6 +599,11 @@ fn get_config_path(args: &FindChemArgs, seq_len: Option<usize>) -> String {
             "/bin/irma_config/SC2-WGS-Nanopore.sh"
         }
         (Experiment::RSVONT, _, IRMAConfig::NoConfig) => "/bin/irma_config/RSV-Nanopore.sh",
+        (Experiment::External(name), Some(seq_len), IRMAConfig::NoConfig) => {
+            external_experiment(name)
+                .expect("external experiments are validated before selecting a config")
+                .config_for(seq_len)
+        }
     };
 
     let wd_path = args
@@ -228,6 +613,85 @@ fn get_config_path(args: &FindChemArgs, seq_len: Option<usize>) -> String {
     format!("{wd_path}{path_extension}")
 }
 
+fn parse_irma_override(value: &str) -> Result<(String, String), String> {
+    let Some((key, setting)) = value.split_once('=') else {
+        return Err(format!("expected KEY=VALUE, got '{value}'"));
+    };
+    if key.is_empty()
+        || !key
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Err(format!("'{key}' is not an IRMA config parameter name"));
+    }
+    Ok((key.to_string(), setting.to_string()))
+}
+
+/// Reads the IRMA config at `source` and substitutes the overridden parameters. Parameters the
+/// config doesn't set are appended at the end.
+fn resolve_irma_config(
+    source: &str,
+    overrides: &[(String, String)],
+) -> Result<String, std::io::Error> {
+    let contents = read_to_string(source).map_err(|e| {
+        std::io::Error::new(
+            e.kind(),
+            format!("Could not read IRMA config '{source}': {e}"),
+        )
+    })?;
+
+    let mut applied = vec![false; overrides.len()];
+    let mut lines: Vec<String> = Vec::new();
+    for line in contents.lines() {
+        let assigned = line.trim_start().split_once('=').map(|(key, _)| key.trim());
+        match overrides
+            .iter()
+            .position(|(key, _)| Some(key.as_str()) == assigned)
+        {
+            Some(i) => {
+                let indent = &line[..line.len() - line.trim_start().len()];
+                let (key, value) = &overrides[i];
+                lines.push(format!("{indent}{key}={value}"));
+                applied[i] = true;
+            }
+            None => lines.push(line.to_string()),
+        }
+    }
+
+    let missing: Vec<_> = overrides
+        .iter()
+        .zip(&applied)
+        .filter(|(_, applied)| !**applied)
+        .map(|((key, value), _)| format!("{key}={value}"))
+        .collect();
+    if !missing.is_empty() {
+        lines.push(String::new());
+        lines.push("# Set with find-chemistry --irma-set".to_string());
+        lines.extend(missing);
+    }
+    lines.push(String::new());
+    Ok(lines.join("\n"))
+}
+
+/// Writes the resolved config for a sample and returns the path it was written to
+fn emit_irma_config(
+    args: &FindChemArgs,
+    emit_path: &Path,
+    sample: &str,
+    source: &str,
+) -> Result<String, std::io::Error> {
+    let path = if args.samplesheet.is_some() {
+        std::fs::create_dir_all(emit_path)?;
+        emit_path.join(format!("{sample}_irma_config.sh"))
+    } else {
+        emit_path.to_path_buf()
+    };
+    let resolved = resolve_irma_config(source, &args.irma_overrides)?;
+    std::fs::write(&path, format!("# Resolved from {source}\n{resolved}"))?;
+    status!("{sample}: IRMA config written to {}", path.display());
+    Ok(path.display().to_string())
+}
+
 #[derive(Debug)]
 pub enum IrmaModule {
     FLU,
@@ -236,6 +700,8 @@ pub enum IrmaModule {
     RSV,
     FLUMinion,
     CoVsGene,
+    /// Module named in the experiment config
+    Custom(String),
 }
 
 impl fmt::Display for IrmaModule {
@@ -247,75 +713,509 @@ impl fmt::Display for IrmaModule {
             IrmaModule::RSV => write!(f, "RSV"),
             IrmaModule::FLUMinion => write!(f, "FLU-minion"),
             IrmaModule::CoVsGene => write!(f, "CoV-s-gene"),
+            IrmaModule::Custom(ref module) => write!(f, "{module}"),
         }
     }
 }
 
-#[derive(Debug)]
+impl Serialize for IrmaModule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Whether the pipeline should assemble the sample
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChemistryStatus {
+    Run,
+    Skip,
+}
+
+impl fmt::Display for ChemistryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ChemistryStatus::Run => write!(f, "run"),
+            ChemistryStatus::Skip => write!(f, "skip"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
 pub struct ChemistryOutput {
+    #[serde(rename = "sample_ID")]
     pub sample: String,
     pub irma_custom: String,
     pub subsample: usize,
-    pub irma_module: IrmaModule,
+    /// Not known for a skipped sample with `--experiment auto`
+    pub irma_module: Option<IrmaModule>,
+    pub status: ChemistryStatus,
+    /// Why the sample is skipped
+    pub reason: Option<String>,
+    /// Fraction of screened reads with adapter/barcode sequence left, with --adapter-screen
+    pub untrimmed_fraction: Option<f64>,
+    /// Estimated fraction of distinct reads (or read pairs), with --duplication
+    pub unique_fraction: Option<f64>,
+    /// `ok` or `mismatch` for paired data, with --check-mates
+    pub mate_check: Option<String>,
+    /// True when `status` is `skip`, so the pipeline can drop the sample before IRMA
+    pub skip_assembly: bool,
+    /// Median read length used to pick the IRMA config. Only in the JSON output
+    pub read_length: Option<usize>,
+    /// Warnings raised for this sample. Only in the JSON output
+    pub warnings: Vec<String>,
 }
 
 impl fmt::Display for ChemistryOutput {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "{},{},{},{}",
-            self.sample, self.irma_custom, self.subsample, self.irma_module
+            "{},{},{},{},{},{},{},{},{},{}",
+            self.sample,
+            self.irma_custom,
+            self.subsample,
+            self.irma_module
+                .as_ref()
+                .map_or(String::new(), ToString::to_string),
+            self.status,
+            self.reason.as_deref().unwrap_or(""),
+            self.untrimmed_fraction
+                .map_or(String::new(), |fraction| format!("{fraction:.4}")),
+            self.unique_fraction
+                .map_or(String::new(), |fraction| format!("{fraction:.4}")),
+            self.mate_check.as_deref().unwrap_or(""),
+            self.skip_assembly
         )
     }
 }
 
-/// Averages the first five sequence lengths if possible. If the file has no sequences, returns None
-fn get_average_line_length<P: AsRef<Path>>(fastq_path: P) -> Result<Option<usize>, std::io::Error> {
-    const SAMPLE_SIZE: usize = 5;
+/// Median length of the first `n_reads` reads, including the R2 mate's reads for paired data.
+/// The median is robust to the odd short or very long read that skews a mean, which matters for
+/// ONT data. If the files have no sequences, returns None
+fn get_median_read_length<P: AsRef<Path>>(
+    fastq_path: P,
+    n_reads: usize,
+) -> Result<Option<usize>, std::io::Error> {
+    let mut lengths = Vec::with_capacity(n_reads);
 
-    let fastq_reader = open_fastq_file(fastq_path)?;
+    let mut files = vec![fastq_path.as_ref().to_path_buf()];
+    files.extend(mate_path(&fastq_path));
+    for file in files {
+        for result in open_fastq_file(file)?.take(n_reads) {
+            let record = result?;
+            lengths.push(record.sequence.len());
+        }
+    }
 
-    let mut total_len = 0;
-    let mut count = 0;
+    if lengths.is_empty() {
+        return Ok(None);
+    }
+    lengths.sort_unstable();
+    let mid = lengths.len() / 2;
+    let median = if lengths.len().is_multiple_of(2) {
+        usize::midpoint(lengths[mid - 1], lengths[mid])
+    } else {
+        lengths[mid]
+    };
+    Ok(Some(median))
+}
 
-    for result in fastq_reader.take(SAMPLE_SIZE) {
-        let record = result?;
-        total_len += record.sequence.len();
-        count += 1;
+#[derive(Debug, Deserialize)]
+struct BatchSampleRow {
+    #[serde(alias = "sample")]
+    sample_id: String,
+}
+
+/// Finds the fastq for a sample in the batch fastq directory, using the same naming patterns as
+/// the Nextflow samplesheet. For paired Illumina data R1 is used.
+fn find_sample_fastq(fastq_dir: &Path, sample: &str) -> Option<PathBuf> {
+    let dir = fastq_dir.to_string_lossy();
+    let patterns = vec![
+        format!("{dir}/{sample}_R1*.fastq*"),
+        format!("{dir}/{sample}_R1*.fq*"),
+        format!("{dir}/{sample}_nf_combined.fastq*"),
+        format!("{dir}/fastq_pass/cat_fastqs/{sample}_nf_combined.fastq*"),
+        format!("{dir}/{sample}.fastq*"),
+        format!("{dir}/{sample}.fq*"),
+    ];
+    find_fastq(&patterns)
+}
+
+/// Resolves `--experiment auto` from the reads, and warns when the reads do not look like they
+/// came from the platform of the given experiment
+fn resolve_experiment(
+    args: &FindChemArgs,
+    sample: &str,
+    layout: &ReadLayout,
+    warnings: &mut Vec<String>,
+) -> Result<Experiment, std::io::Error> {
+    if args.experiment == Experiment::Auto {
+        let virus = args.virus.as_deref().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "--virus is required with --experiment auto",
+            )
+        })?;
+        let platform = layout.platform.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Could not detect the platform for {sample}, no reads found"),
+            )
+        })?;
+        let experiment = Experiment::from_virus_and_platform(virus, platform).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("No experiment for virus {virus} on {platform:?}"),
+            )
+        })?;
+        status!(
+            "{sample}: detected {platform:?} reads (median length {}), using {experiment}",
+            layout.median_len
+        );
+        warnings.extend(args.check_experiment(&experiment));
+        return Ok(experiment);
     }
 
-    match total_len.checked_div(count) {
-        Some(avg) => Ok(Some(avg)),
-        None => Ok(None),
+    if let (Some(expected), Some(detected)) = (args.experiment.platform(), layout.platform) {
+        if expected != detected {
+            warn(
+                warnings,
+                format!(
+                    "{sample} reads look like {detected:?} (median length {}) but the experiment is {}",
+                    layout.median_len, args.experiment
+                ),
+            );
+        } else if expected == Platform::Illumina && !layout.paired {
+            warn(
+                warnings,
+                format!("{sample} looks like single-end Illumina data, no R2 file found"),
+            );
+        }
     }
+    Ok(args.experiment.clone())
+}
+
+/// Counts the reads for the subsample value. Paired files should hold the same number of reads,
+/// so a difference is reported and the smaller count (complete pairs) is used.
+fn count_sample_reads(
+    sample: &str,
+    fastq: &Path,
+    warnings: &mut Vec<String>,
+) -> Result<usize, std::io::Error> {
+    let r1_count = count_reads(fastq)?;
+    let Some(mate) = mate_path(fastq) else {
+        return Ok(r1_count);
+    };
+    let r2_count = count_reads(&mate)?;
+    if r1_count != r2_count {
+        warn(
+            warnings,
+            format!(
+                "{sample} has {r1_count} R1 reads but {r2_count} R2 reads in {}",
+                mate.display()
+            ),
+        );
+    }
+    Ok(r1_count.min(r2_count))
+}
+
+/// Reason to skip the sample when the fastq has fewer than `min_reads` reads. Only reads up to
+/// the minimum are read, so this is cheap even for large fastqs.
+fn skip_reason(fastq: &Path, min_reads: usize) -> Result<Option<String>, std::io::Error> {
+    let mut found = 0;
+    for result in open_fastq_file(fastq)?.take(min_reads.max(1)) {
+        result?;
+        found += 1;
+    }
+    let reason = if found == 0 {
+        Some("Empty FASTQ".to_string())
+    } else if found < min_reads {
+        Some(format!("Fewer than {min_reads} reads (found {found})"))
+    } else {
+        None
+    };
+    Ok(reason)
+}
+
+/// Number of reads (read pairs for paired data) needed to reach `target` mean coverage over the
+/// genome, capped at the reads available. A shortfall is reported with the coverage the
+/// available reads reach.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+fn coverage_target_subsample(
+    sample: &str,
+    target: f64,
+    genome_size: usize,
+    read_length: usize,
+    paired: bool,
+    available: usize,
+    warnings: &mut Vec<String>,
+) -> usize {
+    let bases_per_read = (read_length * if paired { 2 } else { 1 }).max(1) as f64;
+    let needed = (target * genome_size as f64 / bases_per_read).ceil() as usize;
+    if needed > available {
+        warn(
+            warnings,
+            format!(
+                "{sample}: {needed} reads needed for {target}x coverage but only {available} available (~{:.0}x)",
+                available as f64 * bases_per_read / genome_size as f64
+            ),
+        );
+    }
+    needed.min(available)
+}
+
+/// Checks an R1 fastq against its R2 mate. Gives the number of read pairs when they agree, or
+/// the reason to skip the sample, including a mate that can't be read to the end (e.g. a
+/// truncated gzip from an interrupted transfer).
+fn mate_mismatch(fastq: &Path, mate: &Path, n_names: usize) -> Result<usize, String> {
+    let check = check_mates(fastq, mate, n_names)
+        .map_err(|e| format!("Could not read R1/R2 pair ({e})"))?;
+    if check.is_consistent() {
+        return Ok(check.r1_reads);
+    }
+
+    let mut problems = Vec::new();
+    if check.r1_reads != check.r2_reads {
+        problems.push(format!(
+            "R1 has {} reads but R2 has {}",
+            check.r1_reads, check.r2_reads
+        ));
+    }
+    if let Some((name1, name2)) = &check.first_mismatch {
+        problems.push(format!(
+            "{} of {} read names differ (first {name1} vs {name2})",
+            check.name_mismatches, check.names_checked
+        ));
+    }
+    Err(format!("Mate mismatch: {}", problems.join("; ")))
 }
 
 /// Takes user input arguments and prepares them for output
-fn parse_chemistry_args(args: &FindChemArgs) -> Result<ChemistryOutput, std::io::Error> {
-    let line_length = get_average_line_length(&args.fastq)?;
+fn parse_chemistry_args(
+    args: &FindChemArgs,
+    sample: &str,
+    fastq: &Path,
+    mut warnings: Vec<String>,
+) -> Result<ChemistryOutput, std::io::Error> {
+    let skipped = |reason: String, mate_check: Option<String>, mut warnings: Vec<String>| {
+        warn(&mut warnings, format!("{sample}: {reason}, skipping"));
+        ChemistryOutput {
+            sample: sample.to_string(),
+            irma_custom: String::new(),
+            subsample: 0,
+            irma_module: (args.experiment != Experiment::Auto)
+                .then(|| args.experiment.get_module()),
+            status: ChemistryStatus::Skip,
+            reason: Some(reason),
+            untrimmed_fraction: None,
+            unique_fraction: None,
+            mate_check,
+            skip_assembly: true,
+            read_length: None,
+            warnings,
+        }
+    };
+    if let Some(reason) = skip_reason(fastq, args.min_reads)? {
+        return Ok(skipped(reason, None, warnings));
+    }
+
+    // Full read counts of both mates, when --check-mates has already taken them
+    let mut mate_counts = None;
+    let mate_check = match mate_path(fastq) {
+        Some(mate) if args.check_mates => match mate_mismatch(fastq, &mate, args.n_reads.max(1)) {
+            Ok(pairs) => {
+                mate_counts = Some(pairs);
+                Some("ok".to_string())
+            }
+            Err(reason) => {
+                return Ok(skipped(reason, Some("mismatch".to_string()), warnings));
+            }
+        },
+        _ => None,
+    };
+
+    // Without --read-count (--count-reads, or a samplesheet) the fastq is counted
+    let read_count = match (args.read_count, mate_counts) {
+        (Some(read_count), _) => read_count,
+        (_, Some(pairs)) => pairs,
+        _ => count_sample_reads(sample, fastq, &mut warnings)?,
+    };
+    // The fastq check above only reads up to --min-reads, a given or paired count can be lower
+    if read_count < args.min_reads {
+        return Ok(skipped(
+            format!(
+                "Read count {read_count} below --min-reads {}",
+                args.min_reads
+            ),
+            mate_check,
+            warnings,
+        ));
+    }
+
+    let layout = detect_read_layout(fastq)?;
+    let experiment = resolve_experiment(args, sample, &layout, &mut warnings)?;
+    let line_length = get_median_read_length(fastq, args.n_reads.max(1))?;
+
+    let mut irma_custom = get_config_path(args, &experiment, line_length);
+    if let Some(emit_path) = &args.emit_config
+        && !irma_custom.is_empty()
+    {
+        irma_custom = emit_irma_config(args, emit_path, sample, &irma_custom)?;
+    }
+    let irma_module = experiment.get_module();
+    let subsample = match (args.target_coverage, line_length) {
+        (Some(target), Some(read_length)) => {
+            let genome_size = args
+                .genome_size
+                .or_else(|| experiment.genome_size())
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("No genome size known for {experiment}, use --genome-size"),
+                    )
+                })?;
+            coverage_target_subsample(
+                sample,
+                target,
+                genome_size,
+                read_length,
+                layout.paired,
+                read_count,
+                &mut warnings,
+            )
+        }
+        _ => read_count,
+    };
+    let (untrimmed, unique_fraction) = screen_reads(args, sample, fastq, &layout, &mut warnings)?;
 
-    let irma_custom = get_config_path(args, line_length);
-    let irma_module = args.experiment.get_module();
     let out = ChemistryOutput {
-        sample: args.sample.clone(),
+        sample: sample.to_string(),
         irma_custom,
-        subsample: args.read_count,
-        irma_module,
+        subsample,
+        irma_module: Some(irma_module),
+        status: ChemistryStatus::Run,
+        reason: None,
+        untrimmed_fraction: untrimmed,
+        unique_fraction,
+        mate_check,
+        skip_assembly: false,
+        read_length: line_length,
+        warnings,
     };
     Ok(out)
 }
 
+/// Runs the optional adapter and duplication screens, giving the fraction of untrimmed reads
+/// and the estimated fraction of unique reads
+fn screen_reads(
+    args: &FindChemArgs,
+    sample: &str,
+    fastq: &Path,
+    layout: &ReadLayout,
+    warnings: &mut Vec<String>,
+) -> Result<(Option<f64>, Option<f64>), std::io::Error> {
+    let untrimmed = if args.adapter_screen {
+        untrimmed_fraction(fastq, args.n_reads.max(1))?
+    } else {
+        None
+    };
+    if let Some(fraction) = untrimmed
+        && fraction > UNTRIMMED_WARN_FRACTION
+    {
+        warn(
+            warnings,
+            format!(
+                "{sample}: {:.1}% of screened reads still carry ONT adapter/barcode sequence, check that the reads were trimmed",
+                fraction * 100.0
+            ),
+        );
+    }
+    // Duplicates are called on read starts, which only works for fragmented short read libraries
+    let unique_fraction = if !args.duplication {
+        None
+    } else if layout.platform == Some(Platform::ONT) {
+        warn(
+            warnings,
+            format!(
+                "{sample}: duplication estimate is only meaningful for Illumina reads, skipping"
+            ),
+        );
+        None
+    } else {
+        estimate_duplication(fastq)?.map(|estimate| {
+            status!(
+                "{sample}: ~{} unique of {} reads ({:.1}% unique)",
+                estimate.unique_reads,
+                estimate.reads,
+                estimate.unique_fraction() * 100.0
+            );
+            estimate.unique_fraction()
+        })
+    };
+    Ok((untrimmed, unique_fraction))
+}
+
 pub fn find_chemistry_process(args: &FindChemArgs) -> Result<(), std::io::Error> {
     //let args = CheckChemArgs::parse();
-    // handle input validation to ensure valid combinations of
-    if let Err(e) = args.validate() {
-        eprintln!("Error: {e}");
-        std::process::exit(1);
+    // handle input validation to ensure valid combinations of experiment and IRMA config.
+    // An auto experiment is validated per sample once it is resolved.
+    if let Some(experiment_config) = &args.experiment_config {
+        load_experiment_config(experiment_config)?;
     }
+    let run_warnings = if args.experiment == Experiment::Auto {
+        Vec::new()
+    } else {
+        args.check_experiment(&args.experiment)
+    };
+    let extension = match args.output_format {
+        OutputFormat::Csv => "csv",
+        OutputFormat::Json => "json",
+    };
     // parse the arguments into output format
-    let output = parse_chemistry_args(args)?;
-    let filename = format!("{}_chemistry.csv", args.sample);
-    let headers = "sample_ID,irma_custom,subsample,irma_module";
+    let mut outputs = Vec::new();
+    let default_filename =
+        if let (Some(samplesheet), Some(fastq_dir)) = (&args.samplesheet, &args.fastq_dir) {
+            let mut rdr = Reader::from_path(samplesheet)?;
+            for result in rdr.deserialize() {
+                let record: BatchSampleRow = result?;
+                let Some(fastq) = find_sample_fastq(fastq_dir, &record.sample_id) else {
+                    eprintln!(
+                        "Warning: no fastq found for {} in {}, skipping",
+                        record.sample_id,
+                        fastq_dir.display()
+                    );
+                    continue;
+                };
+                outputs.push(parse_chemistry_args(
+                    args,
+                    &record.sample_id,
+                    &fastq,
+                    run_warnings.clone(),
+                )?);
+            }
+            format!("chemistry.{extension}")
+        } else {
+            let (Some(sample), Some(fastq)) = (&args.sample, &args.fastq) else {
+                eprintln!("Error: --sample and --fastq are required without --samplesheet");
+                std::process::exit(1);
+            };
+            outputs.push(parse_chemistry_args(
+                args,
+                sample,
+                fastq,
+                run_warnings.clone(),
+            )?);
+            format!("{sample}_chemistry.{extension}")
+        };
+    let filename = args
+        .output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(default_filename));
+    let headers = "sample_ID,irma_custom,subsample,irma_module,status,reason,untrimmed_fraction,unique_fraction,mate_check,skip_assembly";
 
     let mut writer = {
         let file = OpenOptions::new()
@@ -325,8 +1225,23 @@ pub fn find_chemistry_process(args: &FindChemArgs) -> Result<(), std::io::Error>
             .open(filename)?;
         BufWriter::new(file)
     };
-    writeln!(&mut writer, "{headers}")?;
-    writeln!(&mut writer, "{output}")?;
+    match args.output_format {
+        OutputFormat::Csv => {
+            writeln!(&mut writer, "{headers}")?;
+            for output in &outputs {
+                writeln!(&mut writer, "{output}")?;
+            }
+        }
+        // A single object for one sample, an array in batch mode
+        OutputFormat::Json => {
+            if args.samplesheet.is_some() {
+                serde_json::to_writer_pretty(&mut writer, &outputs)?;
+            } else {
+                serde_json::to_writer_pretty(&mut writer, &outputs[0])?;
+            }
+            writeln!(&mut writer)?;
+        }
+    }
     writer.flush()?;
     Ok(())
 }
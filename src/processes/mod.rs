@@ -1,12 +1,23 @@
 pub mod all_sample_hd;
 pub mod all_sample_nt_diffs;
-pub mod di_stats;
+pub mod append_csv;
 pub mod check_mira_version;
 pub mod create_nextflow_samplesheet;
+pub mod di_stats;
+pub mod fastq_stats;
 pub mod find_chemistry;
+pub mod ont_yield;
+pub mod parquet_compact;
 pub mod plotter;
 pub mod positions_of_interest;
 pub mod prepare_mira_reports;
+pub mod qc_config;
+pub mod qc_sweep;
+pub mod read_trace;
+pub mod reference_panel;
+pub mod report_index;
 pub mod samplesheet_check;
+pub mod snp_clusters;
 pub mod summary_report_update;
+pub mod swap_check;
 pub mod variants_of_interest;
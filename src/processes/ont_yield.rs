@@ -0,0 +1,397 @@
+#![allow(clippy::cast_precision_loss)]
+use crate::constants::status_palette::NEUTRAL_COLOR;
+use crate::status;
+use crate::utils::fastq_read::open_fastq_file;
+use clap::Parser;
+use csv::Writer;
+use plotly::{
+    Layout, Plot, Scatter,
+    common::{DashType, Mode, Title},
+    configuration::{ImageButtonFormats, ToImageButtonOptions},
+    layout::{Axis, GridPattern, LayoutGrid, Shape, ShapeLine, ShapeType},
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+use zoe::prelude::Len;
+
+#[derive(Debug, Parser)]
+#[command(about = "Per-barcode read count and yield over time of an ONT run")]
+pub struct OntYieldArgs {
+    #[arg(short = 'i', long)]
+    /// ONT run directory, or its `fastq_pass` directory, with a subdirectory of FASTQ files per
+    /// barcode
+    run_dir: PathBuf,
+
+    #[arg(short = 'o', long)]
+    /// Output yield over time plot HTML
+    output: PathBuf,
+
+    #[arg(long)]
+    /// Optional CSV of each barcode's cumulative reads and bases after each FASTQ file
+    csv: Option<PathBuf>,
+
+    #[arg(short = 'w', long)]
+    /// Keep watching the run: rescan it every this many seconds and update the plot, which
+    /// then reloads itself in the browser
+    watch: Option<u64>,
+
+    #[arg(long, default_value_t = 60, requires = "watch")]
+    /// Stop watching once no new FASTQ files were written for this many minutes
+    idle_minutes: u64,
+
+    #[arg(short = 't', long)]
+    /// Optional number of reads wanted per barcode, drawn on the plot and reported as each
+    /// barcode reaches it
+    target_reads: Option<usize>,
+}
+
+/// Reads and bases of one FASTQ file, which `MinKNOW` writes in batches as the run goes
+#[derive(Debug)]
+struct FastqBatch {
+    barcode: String,
+    modified: SystemTime,
+    size: u64,
+    reads: usize,
+    bases: usize,
+}
+
+/// A row of the `--csv` output
+#[derive(Debug, Serialize)]
+struct YieldRow<'a> {
+    barcode: &'a str,
+    file: String,
+    hours: f64,
+    reads: usize,
+    bases: usize,
+    cumulative_reads: usize,
+    cumulative_bases: usize,
+}
+
+fn is_fastq(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_ascii_lowercase();
+    [".fastq", ".fastq.gz", ".fq", ".fq.gz"]
+        .iter()
+        .any(|ext| name.ends_with(ext))
+}
+
+fn count_batch(path: &Path) -> Result<(usize, usize), Box<dyn Error>> {
+    let reader = open_fastq_file(path)
+        .map_err(|e| format!("Could not open file '{}': {e}", path.display()))?;
+    let mut reads = 0;
+    let mut bases = 0;
+    for result in reader {
+        let record = result?;
+        reads += 1;
+        bases += record.sequence.len();
+    }
+    Ok((reads, bases))
+}
+
+/// Counts the FASTQ files of every barcode directory that are new or changed since the last
+/// scan. Files that can't be read yet, most likely still being written, are retried on the
+/// next scan. Returns the number of files counted
+fn scan_run(
+    fastq_pass: &Path,
+    batches: &mut HashMap<PathBuf, FastqBatch>,
+) -> Result<usize, Box<dyn Error>> {
+    let mut counted = 0;
+    for barcode_dir in fs::read_dir(fastq_pass)
+        .map_err(|e| format!("Could not read {}: {e}", fastq_pass.display()))?
+    {
+        let barcode_dir = barcode_dir?.path();
+        if !barcode_dir.is_dir() {
+            continue;
+        }
+        let barcode = barcode_dir
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        for file in fs::read_dir(&barcode_dir)? {
+            let path = file?.path();
+            if !is_fastq(&path) {
+                continue;
+            }
+            let metadata = fs::metadata(&path)?;
+            if batches
+                .get(&path)
+                .is_some_and(|batch| batch.size == metadata.len())
+            {
+                continue;
+            }
+            match count_batch(&path) {
+                Ok((reads, bases)) => {
+                    batches.insert(
+                        path,
+                        FastqBatch {
+                            barcode: barcode.clone(),
+                            modified: metadata.modified()?,
+                            size: metadata.len(),
+                            reads,
+                            bases,
+                        },
+                    );
+                    counted += 1;
+                }
+                Err(e) => eprintln!("Warning: skipping {} for now: {e}", path.display()),
+            }
+        }
+    }
+    Ok(counted)
+}
+
+/// Each barcode's batches in the order they were written, with hours since the run's first
+/// batch
+fn batches_by_barcode(
+    batches: &HashMap<PathBuf, FastqBatch>,
+) -> BTreeMap<&str, Vec<(&Path, f64, &FastqBatch)>> {
+    let first = batches.values().map(|batch| batch.modified).min();
+    let mut by_barcode: BTreeMap<&str, Vec<(&Path, f64, &FastqBatch)>> = BTreeMap::new();
+    for (path, batch) in batches {
+        let hours = first
+            .and_then(|first| batch.modified.duration_since(first).ok())
+            .map_or(0.0, |elapsed| elapsed.as_secs_f64() / 3600.0);
+        by_barcode
+            .entry(&batch.barcode)
+            .or_default()
+            .push((path, hours, batch));
+    }
+    for barcode_batches in by_barcode.values_mut() {
+        barcode_batches.sort_by(|a, b| a.1.total_cmp(&b.1));
+    }
+    by_barcode
+}
+
+/// Cumulative reads (top) and megabases (bottom) of every barcode over the run
+fn yield_plot(
+    by_barcode: &BTreeMap<&str, Vec<(&Path, f64, &FastqBatch)>>,
+    run_name: &str,
+    target_reads: Option<usize>,
+) -> Plot {
+    let mut plot = Plot::new();
+    let mut total_reads = 0;
+    let mut max_hours: f64 = 0.0;
+    for (barcode, barcode_batches) in by_barcode {
+        let hours: Vec<f64> = barcode_batches.iter().map(|(_, h, _)| *h).collect();
+        let reads: Vec<usize> = barcode_batches
+            .iter()
+            .scan(0, |total, (_, _, batch)| {
+                *total += batch.reads;
+                Some(*total)
+            })
+            .collect();
+        let megabases: Vec<f64> = barcode_batches
+            .iter()
+            .scan(0, |total, (_, _, batch)| {
+                *total += batch.bases;
+                Some(*total as f64 / 1e6)
+            })
+            .collect();
+        total_reads += reads.last().copied().unwrap_or(0);
+        max_hours = max_hours.max(hours.last().copied().unwrap_or(0.0));
+
+        plot.add_trace(
+            Scatter::new(hours.clone(), reads)
+                .mode(Mode::Lines)
+                .name(*barcode)
+                .legend_group(*barcode)
+                .hover_template(format!(
+                    "<b>{barcode}</b><br>%{{x:.2f}} h<br>%{{y:,}} reads<extra></extra>"
+                )),
+        );
+        plot.add_trace(
+            Scatter::new(hours, megabases)
+                .mode(Mode::Lines)
+                .name(*barcode)
+                .legend_group(*barcode)
+                .show_legend(false)
+                .hover_template(format!(
+                    "<b>{barcode}</b><br>%{{x:.2f}} h<br>%{{y:.1f}} Mb<extra></extra>"
+                ))
+                .x_axis("x2")
+                .y_axis("y2"),
+        );
+    }
+
+    let mut layout = Layout::new()
+        .grid(
+            LayoutGrid::new()
+                .rows(2)
+                .columns(1)
+                .pattern(GridPattern::Independent),
+        )
+        .title(format!(
+            "ONT Yield | {run_name} | {total_reads} reads in {max_hours:.1} h"
+        ))
+        .x_axis(Axis::new().title(Title::with_text("Hours since the first reads")))
+        .y_axis(Axis::new().title(Title::with_text("Cumulative reads")))
+        .x_axis2(Axis::new().title(Title::with_text("Hours since the first reads")))
+        .y_axis2(Axis::new().title(Title::with_text("Cumulative yield (Mb)")));
+    if let Some(target) = target_reads {
+        layout = layout.shapes(vec![
+            Shape::new()
+                .shape_type(ShapeType::Line)
+                .x_ref("paper")
+                .y_ref("y")
+                .x0(0.0)
+                .x1(1.0)
+                .y0(target as f64)
+                .y1(target as f64)
+                .line(ShapeLine::new().color(NEUTRAL_COLOR).dash(DashType::Dash)),
+        ]);
+    }
+    plot.set_layout(layout);
+
+    plot.set_configuration(
+        plotly::Configuration::new()
+            .responsive(true)
+            .display_logo(false)
+            .fill_frame(true)
+            .to_image_button_options(
+                ToImageButtonOptions::new()
+                    .format(ImageButtonFormats::Svg)
+                    .filename("ont_yield_plot"),
+            ),
+    );
+    plot
+}
+
+/// Writes the plot atomically (temp file + rename) so a browser reloading it never reads a
+/// partially written file. While watching, the page reloads itself every `refresh` seconds
+fn write_plot(plot: &Plot, output: &Path, refresh: Option<u64>) -> Result<(), Box<dyn Error>> {
+    let mut html = plot.to_html();
+    if let Some(seconds) = refresh {
+        html = html.replacen(
+            "<head>",
+            &format!("<head>\n    <meta http-equiv=\"refresh\" content=\"{seconds}\" />"),
+            1,
+        );
+    }
+
+    let mut tmp_name = output.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = Path::new(&tmp_name);
+    let mut file = File::create(tmp_path)?;
+    file.write_all(html.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(tmp_path, output)?;
+    Ok(())
+}
+
+fn write_csv(
+    path: &Path,
+    by_barcode: &BTreeMap<&str, Vec<(&Path, f64, &FastqBatch)>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(path)?;
+    for (barcode, barcode_batches) in by_barcode {
+        let (mut cumulative_reads, mut cumulative_bases) = (0, 0);
+        for (file, hours, batch) in barcode_batches {
+            cumulative_reads += batch.reads;
+            cumulative_bases += batch.bases;
+            wtr.serialize(YieldRow {
+                barcode,
+                file: file.display().to_string(),
+                hours: (hours * 1000.0).round() / 1000.0,
+                reads: batch.reads,
+                bases: batch.bases,
+                cumulative_reads,
+                cumulative_bases,
+            })?;
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Barcodes at or above the target, for the status line
+fn barcodes_at_target(
+    by_barcode: &BTreeMap<&str, Vec<(&Path, f64, &FastqBatch)>>,
+    target: usize,
+) -> usize {
+    by_barcode
+        .values()
+        .filter(|batches| batches.iter().map(|(_, _, b)| b.reads).sum::<usize>() >= target)
+        .count()
+}
+
+pub fn ont_yield_process(args: &OntYieldArgs) -> Result<(), Box<dyn Error>> {
+    let fastq_pass = if args.run_dir.join("fastq_pass").is_dir() {
+        args.run_dir.join("fastq_pass")
+    } else {
+        args.run_dir.clone()
+    };
+    let run_name = fastq_pass
+        .parent()
+        .filter(|_| fastq_pass.ends_with("fastq_pass"))
+        .unwrap_or(&fastq_pass)
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+
+    let mut batches = HashMap::new();
+    let mut last_new_file = Instant::now();
+    let mut first_scan = true;
+    loop {
+        let counted = scan_run(&fastq_pass, &mut batches)?;
+        if counted > 0 {
+            last_new_file = Instant::now();
+        }
+        let idle = args.watch.is_some_and(|_| {
+            last_new_file.elapsed() >= Duration::from_secs(args.idle_minutes * 60)
+        });
+        let watching = args.watch.filter(|_| !idle);
+
+        if counted > 0 || first_scan || watching.is_none() {
+            let by_barcode = batches_by_barcode(&batches);
+            let plot = yield_plot(&by_barcode, &run_name, args.target_reads);
+            write_plot(&plot, &args.output, watching)?;
+            if let Some(csv) = &args.csv {
+                write_csv(csv, &by_barcode)?;
+            }
+
+            let total_reads: usize = batches.values().map(|batch| batch.reads).sum();
+            match args.target_reads {
+                Some(target) => status!(
+                    " -> {} FASTQ files, {total_reads} reads; {} of {} barcodes at {target} reads",
+                    batches.len(),
+                    barcodes_at_target(&by_barcode, target),
+                    by_barcode.len()
+                ),
+                None => status!(
+                    " -> {} FASTQ files, {total_reads} reads in {} barcodes",
+                    batches.len(),
+                    by_barcode.len()
+                ),
+            }
+        }
+        first_scan = false;
+
+        let Some(seconds) = watching else {
+            if idle {
+                status!(
+                    "No new FASTQ files for {} minutes, stopped watching",
+                    args.idle_minutes
+                );
+            }
+            break;
+        };
+        thread::sleep(Duration::from_secs(seconds));
+    }
+
+    status!(" -> Yield plot written to {}", args.output.display());
+    Ok(())
+}
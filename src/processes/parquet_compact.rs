@@ -0,0 +1,211 @@
+use crate::{
+    status,
+    utils::cancel::{PartialFile, check_cancelled, install_signal_handler},
+};
+use arrow::{
+    compute::{SortColumn, concat_batches, lexsort_to_indices, take_record_batch},
+    datatypes::SchemaRef,
+    record_batch::RecordBatch,
+};
+use clap::Parser;
+use parquet::{
+    arrow::{ArrowWriter, arrow_reader::ParquetRecordBatchReaderBuilder},
+    file::{metadata::KeyValue, properties::WriterProperties},
+};
+use rayon::prelude::*;
+use std::{
+    error::Error,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+/// Columns used to order the compacted rows, in priority order. Each entry lists the names the
+/// column goes by across the MIRA table types; columns a table doesn't have are skipped.
+const SORT_COLUMNS: [&[&str]; 4] = [
+    &["runid"],
+    &["sample_id", "sample"],
+    &["reference", "reference_name", "segment"],
+    &["position", "reference_position"],
+];
+
+/// Arrow stores its own schema in the parquet metadata, which the writer adds back itself
+const ARROW_SCHEMA_KEY: &str = "ARROW:schema";
+
+#[derive(Debug, Parser)]
+#[command(
+    about = "Compact many run level parquet files of one table type into larger, sorted files"
+)]
+pub struct CompactArgs {
+    #[arg(short = 'i', long, num_args = 1.., required = true)]
+    /// Parquet files to compact. All must be the same table type (same schema)
+    inputs: Vec<PathBuf>,
+
+    #[arg(short = 'o', long)]
+    /// Output parquet file. With --max-file-rows this is used as the prefix of the numbered files
+    output: PathBuf,
+
+    #[arg(short = 'g', long, default_value_t = 1_000_000)]
+    /// Maximum rows per row group in the output
+    row_group_size: usize,
+
+    #[arg(short = 'm', long)]
+    /// (Optional) Maximum rows per output file. Default is to write a single file
+    max_file_rows: Option<usize>,
+}
+
+type ParquetContents = (SchemaRef, Option<Vec<KeyValue>>, Vec<RecordBatch>);
+
+// Reads all record batches of a parquet file with its schema and key/value metadata
+fn read_parquet(path: &PathBuf) -> Result<ParquetContents, Box<dyn Error + Send + Sync>> {
+    let file =
+        File::open(path).map_err(|e| format!("Could not open file '{}': {e}", path.display()))?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+    let schema = builder.schema().clone();
+    let key_values = builder
+        .metadata()
+        .file_metadata()
+        .key_value_metadata()
+        .cloned();
+    let batches = builder.build()?.collect::<Result<Vec<_>, _>>()?;
+    Ok((schema, key_values, batches))
+}
+
+// Sorts by runid/sample/segment/position, using whichever of those columns the table has
+fn sort_batch(batch: &RecordBatch) -> Result<RecordBatch, Box<dyn Error>> {
+    let schema = batch.schema();
+    let sort_columns: Vec<SortColumn> = SORT_COLUMNS
+        .iter()
+        .filter_map(|names| names.iter().find_map(|name| schema.index_of(name).ok()))
+        .map(|idx| SortColumn {
+            values: batch.column(idx).clone(),
+            options: None,
+        })
+        .collect();
+
+    if sort_columns.is_empty() {
+        return Ok(batch.clone());
+    }
+    let indices = lexsort_to_indices(&sort_columns, None)?;
+    Ok(take_record_batch(batch, &indices)?)
+}
+
+// Written a row group at a time, so a termination signal stops it between them, and moved
+// into place only once complete
+fn write_parquet(
+    path: &Path,
+    batch: &RecordBatch,
+    properties: WriterProperties,
+    row_group_size: usize,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (partial, file) = PartialFile::create(path)?;
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(properties))?;
+    for offset in (0..batch.num_rows()).step_by(row_group_size) {
+        check_cancelled()?;
+        writer.write(&batch.slice(offset, row_group_size.min(batch.num_rows() - offset)))?;
+    }
+    writer.close()?;
+    partial.finish()?;
+    status!(" -> PARQUET written to {}", path.display());
+    Ok(())
+}
+
+pub fn parquet_compact_process(args: &CompactArgs) -> Result<(), Box<dyn Error>> {
+    install_signal_handler();
+    let mut schema: Option<SchemaRef> = None;
+    let mut key_values: Vec<KeyValue> = Vec::new();
+    let mut batches = Vec::new();
+
+    // Files are read in parallel, each stopping before it starts once cancelled
+    let contents = args
+        .inputs
+        .par_iter()
+        .map(|path| {
+            check_cancelled()?;
+            read_parquet(path)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e as Box<dyn Error>)?;
+
+    for (path, (file_schema, file_key_values, mut file_batches)) in args.inputs.iter().zip(contents)
+    {
+        match &schema {
+            None => schema = Some(file_schema),
+            Some(first) if first.fields() != file_schema.fields() => {
+                return Err(format!(
+                    "{} has a different schema than {}, only files of the same table type can be compacted",
+                    path.display(),
+                    args.inputs[0].display()
+                )
+                .into());
+            }
+            Some(_) => {}
+        }
+        // Keep the first value seen for each metadata key
+        for kv in file_key_values.unwrap_or_default() {
+            if kv.key != ARROW_SCHEMA_KEY && !key_values.iter().any(|k| k.key == kv.key) {
+                key_values.push(kv);
+            }
+        }
+        batches.append(&mut file_batches);
+    }
+
+    let Some(schema) = schema else {
+        return Err("No input parquet files given".into());
+    };
+    let combined = concat_batches(&schema, &batches)?;
+    drop(batches);
+    check_cancelled()?;
+    let sorted = sort_batch(&combined)?;
+    drop(combined);
+    check_cancelled()?;
+
+    let row_group_size = args.row_group_size.max(1);
+    let properties = || {
+        WriterProperties::builder()
+            .set_max_row_group_size(row_group_size)
+            .set_key_value_metadata((!key_values.is_empty()).then(|| key_values.clone()))
+            .build()
+    };
+
+    let total_rows = sorted.num_rows();
+    let mut jobs: Vec<(PathBuf, (usize, usize))> = Vec::new();
+    match args.max_file_rows {
+        Some(max_rows) if max_rows > 0 && total_rows > max_rows => {
+            let stem = args
+                .output
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("compacted");
+            let extension = args
+                .output
+                .extension()
+                .and_then(|s| s.to_str())
+                .unwrap_or("parq");
+            for (i, offset) in (0..total_rows).step_by(max_rows).enumerate() {
+                let len = max_rows.min(total_rows - offset);
+                let path = args
+                    .output
+                    .with_file_name(format!("{stem}_{:03}.{extension}", i + 1));
+                jobs.push((path, (offset, len)));
+            }
+        }
+        _ => jobs.push((args.output.clone(), (0, total_rows))),
+    }
+
+    // Output files are written in parallel, each stopping before it starts once cancelled
+    jobs.into_par_iter()
+        .map(|(path, (offset, len))| {
+            check_cancelled()?;
+            write_parquet(
+                &path,
+                &sorted.slice(offset, len),
+                properties(),
+                row_group_size,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e as Box<dyn Error>)?;
+
+    status!("Compacted {} files ({total_rows} rows)", args.inputs.len());
+    Ok(())
+}
@@ -1,7 +1,17 @@
 #![allow(clippy::cast_precision_loss, clippy::struct_excessive_bools)]
+use crate::constants::status_palette::{
+    CATEGORY_BLUE, CATEGORY_CYAN, FAIL_COLOR, NEUTRAL_COLOR, PASS_COLOR,
+};
+use crate::io::data_ingest::{
+    CoverageData, CoverageTables, MinorVariantsData, irma_dir_coverage_tables,
+    irma_dir_minor_variants, irma_dir_read_counts,
+};
+use crate::io::virus_plugins::strip_reference_suffix;
+use crate::status;
+use crate::utils::coordinates::{
+    CoordinateSystem, PaddingOffsets, convert_coverage, convert_minor_variants, padding_offsets,
+};
 use clap::Parser;
-use csv::ReaderBuilder;
-use glob::glob;
 use plotly::{
     Layout, Plot, Sankey, Scatter,
     common::{Mode, Title},
@@ -11,8 +21,6 @@ use plotly::{
 use std::{
     collections::HashMap,
     error::Error,
-    fs::File,
-    io::{BufRead, BufReader},
     path::{Path, PathBuf},
 };
 
@@ -22,7 +30,7 @@ pub fn get_segment_color(segment_name: &str) -> &'static str {
     // This ensures the same segment always gets the same color across all plots
     // Check if segment_name contains any of our known segment identifiers
     if segment_name.contains("PB2") {
-        "#3366CC" // blue
+        CATEGORY_BLUE
     } else if segment_name.contains("PB1") {
         "#DC3912" // red
     } else if segment_name.contains("PA") {
@@ -34,7 +42,7 @@ pub fn get_segment_color(segment_name: &str) -> &'static str {
     } else if segment_name.contains("NA") {
         "#3B3EAC" // indigo
     } else if segment_name.contains("MP") {
-        "#0099C6" // cyan
+        CATEGORY_CYAN
     } else if segment_name.contains("NS") {
         "#DD4477" // pink
     } else {
@@ -43,13 +51,13 @@ pub fn get_segment_color(segment_name: &str) -> &'static str {
             .bytes()
             .fold(0u32, |acc, b| acc.wrapping_add(u32::from(b)));
         match hash % 10 {
-            0 => "#3366CC", // blue
+            0 => CATEGORY_BLUE,
             1 => "#DC3912", // red
             2 => "#FF9900", // orange
             3 => "#109618", // green
             4 => "#990099", // purple
             5 => "#3B3EAC", // indigo
-            6 => "#0099C6", // cyan
+            6 => CATEGORY_CYAN,
             7 => "#DD4477", // pink
             8 => "#66AA00", // lime
             _ => "#B82E2E", // dark red
@@ -57,6 +65,80 @@ pub fn get_segment_color(segment_name: &str) -> &'static str {
     }
 }
 
+/// Coverage points per figure above which the traces are binned, since Plotly traces with
+/// hundreds of thousands of points (e.g. deep ONT whole genome runs) freeze the browser
+const DEFAULT_MAX_POINTS: usize = 50_000;
+
+// Number of positions averaged into each plotted point so the whole figure stays under
+// `max_points`. 1 means no binning
+fn coverage_bin_size(total_points: usize, max_points: usize) -> usize {
+    total_points.div_ceil(max_points.max(1)).max(1)
+}
+
+// Averages each run of `bin_size` positions into one point at the run's first position
+fn bin_coverage(x_values: &[u32], y_values: &[u32], bin_size: usize) -> (Vec<u32>, Vec<f64>) {
+    x_values
+        .chunks(bin_size)
+        .zip(y_values.chunks(bin_size))
+        .map(|(xs, ys)| {
+            let mean = ys.iter().map(|&y| f64::from(y)).sum::<f64>() / ys.len() as f64;
+            (xs[0], mean)
+        })
+        .unzip()
+}
+
+// Note shown on a figure whose coverage traces were binned
+fn binning_annotation(bin_size: usize, total_points: usize) -> plotly::layout::Annotation {
+    plotly::layout::Annotation::new()
+        .text(format!(
+            "Binned for display: {total_points} positions shown as the mean of every {bin_size}"
+        ))
+        .x_ref("paper")
+        .y_ref("paper")
+        .x(0.5)
+        .y(1.06)
+        .font(plotly::common::Font::new().size(12).color("#B82E2E"))
+        .show_arrow(false)
+}
+
+/// Positions and depths of a coverage table, each with its segment name
+type CoveragePoints = Vec<(String, (Vec<u32>, Vec<u32>))>;
+
+/// Depth and consensus base by position, for each segment
+type SegmentCoverage = HashMap<String, HashMap<u32, (u32, String)>>;
+
+// Position and depth of every row of an IRMA coverage table
+fn coverage_xy(rows: &[CoverageData]) -> (Vec<u32>, Vec<u32>) {
+    rows.iter()
+        .map(|row| {
+            (
+                row.position.unsigned_abs(),
+                row.coverage_depth.unsigned_abs(),
+            )
+        })
+        .unzip()
+}
+
+// Coverage tables of an IRMA directory in `coordinates`, along with the padding of every
+// reference for placing other positions in them
+fn coverage_tables(
+    input_directory: &Path,
+    coordinates: CoordinateSystem,
+) -> Result<(CoverageTables, PaddingOffsets), Box<dyn Error>> {
+    let mut tables = irma_dir_coverage_tables(input_directory)?;
+    let mut padding = PaddingOffsets::new();
+    let mut dropped = 0;
+    for (_, rows) in &mut tables {
+        let table_padding = padding_offsets(rows);
+        dropped += convert_coverage(rows, &table_padding, coordinates);
+        padding.extend(table_padding);
+    }
+    if dropped > 0 {
+        eprintln!("Warning: {dropped} coverage positions have no {coordinates} position, left out");
+    }
+    Ok((tables, padding))
+}
+
 #[derive(Debug, Parser)]
 #[command(version, about = "Generate plotly plots for IRMA output")]
 pub struct PlotterArgs {
@@ -103,71 +185,80 @@ pub struct PlotterArgs {
     )]
     inline_html: bool,
 
+    #[arg(
+        short = 'm',
+        long,
+        help = "Second IRMA directory of the same sample to compare against, e.g. run with another IRMA config; plots the per-position depth difference, log2 ratio and consensus disagreements for matching segments (Optional)"
+    )]
+    compare_dir: Option<PathBuf>,
+
     #[arg(
         short = 'o',
         long,
         help = "Output standalone HTML file path (Optional)"
     )]
     output: Option<PathBuf>,
+
+    #[arg(
+        short = 'p',
+        long,
+        default_value_t = DEFAULT_MAX_POINTS,
+        help = "Coverage points per figure above which positions are binned to their mean depth (Default: 50000)"
+    )]
+    max_points: usize,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = CoordinateSystem::Raw,
+        help = "Coordinates of the plotted positions: raw (IRMA consensus), padded (padded consensus) or hmm (reference) (Default: raw)"
+    )]
+    coordinates: CoordinateSystem,
 }
 
-pub fn generate_plot_coverage(input_directory: &Path) -> Result<Plot, Box<dyn Error>> {
+pub fn generate_plot_coverage(
+    input_directory: &Path,
+    max_points: usize,
+    coordinates: CoordinateSystem,
+) -> Result<Plot, Box<dyn Error>> {
     // Create a Plotly plot
     let mut plot = Plot::new();
 
-    // Iterate over all coverage files in the input directory
-    for entry in glob(&format!(
-        "{}/tables/*coverage.txt",
-        input_directory.display()
-    ))? {
-        match entry {
-            Ok(path) => {
-                // Open the CSV file
-                let file = File::open(&path)?;
-
-                // Create a CSV reader
-                let mut rdr = ReaderBuilder::new().delimiter(b'\t').from_reader(file);
-
-                // Vectors to store the data
-                let mut x_values = Vec::new();
-                let mut y_values = Vec::new();
-
-                // Read the CSV file
-                for result in rdr.records() {
-                    let record = result?;
-                    let x: u32 = record[1].parse()?;
-                    let y: u32 = record[2].parse()?;
-                    x_values.push(x);
-                    y_values.push(y);
-                }
+    // Read all coverage files in the input directory first, so the binning can be chosen
+    // from the total number of points
+    let tables: Vec<(String, Vec<u32>, Vec<u32>)> = coverage_tables(input_directory, coordinates)?
+        .0
+        .into_iter()
+        .map(|(segment_name, rows)| {
+            let (x_values, y_values) = coverage_xy(&rows);
+            (segment_name, x_values, y_values)
+        })
+        .collect();
 
-                // Extract segment name
-                let segment_name = path
-                    .file_name()
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .split('-')
-                    .next()
-                    .unwrap();
-
-                // Get color for this segment
-                let segment_color = get_segment_color(segment_name);
-
-                // Create a trace for the current CSV file with consistent color
-                let trace = Scatter::new(x_values, y_values)
-                    .mode(Mode::Lines)
-                    .name(segment_name)
-                    .line(plotly::common::Line::new().color(segment_color));
-
-                plot.add_trace(trace);
-            }
-            Err(e) => eprintln!("Error reading file: {e}"),
-        }
+    let total_points: usize = tables.iter().map(|(_, x, _)| x.len()).sum();
+    let bin_size = coverage_bin_size(total_points, max_points);
+    if bin_size > 1 {
+        eprintln!(
+            "Warning: {total_points} coverage points, binning every {bin_size} positions for display"
+        );
+    }
+
+    for (segment_name, x_values, y_values) in &tables {
+        // Get color for this segment
+        let segment_color = get_segment_color(segment_name);
+        let (x_values, y_values) = bin_coverage(x_values, y_values, bin_size);
+
+        // Create a trace for the current CSV file with consistent color
+        let trace = Scatter::new(x_values, y_values)
+            .mode(Mode::Lines)
+            .name(segment_name)
+            .line(plotly::common::Line::new().color(segment_color));
+
+        plot.add_trace(trace);
     }
 
     // Set the figure title
-    let layout = Layout::new()
+    let mut layout = Layout::new()
         .title(format!(
             "Coverage | {}",
             input_directory
@@ -181,6 +272,9 @@ pub fn generate_plot_coverage(input_directory: &Path) -> Result<Plot, Box<dyn Er
         ))
         .x_axis(Axis::new().title(Title::with_text("Position")))
         .y_axis(Axis::new().title(Title::with_text("Coverage")));
+    if bin_size > 1 {
+        layout = layout.annotations(vec![binning_annotation(bin_size, total_points)]);
+    }
     plot.set_layout(layout);
 
     // Apply configuration to plot
@@ -199,114 +293,55 @@ pub fn generate_plot_coverage(input_directory: &Path) -> Result<Plot, Box<dyn Er
     Ok(plot)
 }
 
-#[allow(clippy::type_complexity)]
-pub fn generate_plot_coverage_seg(input_directory: &Path) -> Result<Plot, Box<dyn Error>> {
+#[allow(clippy::too_many_lines)]
+pub fn generate_plot_coverage_seg(
+    input_directory: &Path,
+    max_points: usize,
+    coordinates: CoordinateSystem,
+) -> Result<Plot, Box<dyn Error>> {
     // Init a Plotly plot
     let mut plot = Plot::new();
 
-    // Track number of files for subplot layout
-    let mut file_paths = Vec::new();
-
-    // First, count files and collect paths
-    for path in (glob(&format!(
-        "{}/tables/*coverage.txt",
-        input_directory.display()
-    ))?)
-    .flatten()
-    {
-        //file_count += 1;
-        file_paths.push(path);
-    }
+    // Read every coverage table up front to choose the binning from the total number of points
+    let (tables, padding) = coverage_tables(input_directory, coordinates)?;
+    let tables: CoveragePoints = tables
+        .into_iter()
+        .map(|(segment_name, rows)| (segment_name, coverage_xy(&rows)))
+        .collect();
 
     // Calculate grid dimensions for subplots
     let rows = 4; //((file_count + 2) as f64).sqrt().ceil() as usize;
     let cols = 2; //(file_count + rows - 1) / rows; // Ceiling division
 
-    // Load variant data into a HashMap keyed by segment name
-    // TODO: consider a struct with named fields
-    let mut variants_data: HashMap<String, Vec<(u32, String, String, u32, u32, f32)>> =
-        HashMap::new();
-
-    // Look for variant files with matching prefixes in the directory
-    for variant_path in (glob(&format!(
-        "{}/tables/*variants.txt",
-        input_directory.display()
-    ))?)
-    .flatten()
-    {
-        let file = File::open(&variant_path)?;
-
-        // Create a TSV reader
-        let mut rdr = ReaderBuilder::new()
-            .delimiter(b'\t')
-            .has_headers(true)
-            .from_reader(file);
-
-        for result in rdr.records() {
-            let record = result?;
-            if record.len() >= 8 {
-                let segment_name = record[0].to_string();
-                let position: u32 = record[1].parse()?;
-                let consensus_allele: String = record[3].to_string();
-                let minority_allele: String = record[4].to_string();
-                let consensus_count: u32 = record[5].parse()?;
-                let minority_count: u32 = record[6].parse()?;
-                let minority_frequency: f32 = record[8].parse()?;
-
-                variants_data.entry(segment_name).or_default().push((
-                    position,
-                    consensus_allele,
-                    minority_allele,
-                    consensus_count,
-                    minority_count,
-                    minority_frequency,
-                ));
-            }
-        }
+    // Minor variants keyed by segment name
+    let mut variants_data: HashMap<String, Vec<MinorVariantsData>> = HashMap::new();
+    let mut variants = irma_dir_minor_variants(input_directory)?;
+    convert_minor_variants(&mut variants, &padding, coordinates);
+    for variant in variants {
+        variants_data
+            .entry(variant.reference.clone())
+            .or_default()
+            .push(variant);
     }
 
-    // Process each file and create a subplot
-    for (idx, path) in file_paths.iter().enumerate() {
-        // Extract segment name from file path
-        let segment_name = path
-            .file_name()
-            .unwrap_or_default()
-            .to_str()
-            .unwrap_or("Unknown")
-            .split('-')
-            .next()
-            .unwrap_or("Unknown")
-            .to_string();
+    let total_points: usize = tables.iter().map(|(_, (x, _))| x.len()).sum();
+    let bin_size = coverage_bin_size(total_points, max_points);
+    if bin_size > 1 {
+        eprintln!(
+            "Warning: {total_points} coverage points, binning every {bin_size} positions for display"
+        );
+    }
 
+    // Process each table and create a subplot
+    for (idx, (segment_name, (x_values, y_values))) in tables.iter().enumerate() {
         // Get color for this segment
-        let segment_color = get_segment_color(&segment_name);
-
-        // Open the CSV file
-        let file = File::open(path)?;
-
-        // Create a CSV reader
-        let mut rdr = ReaderBuilder::new()
-            .delimiter(b'\t')
-            .has_headers(true)
-            .from_reader(file);
-
-        // Vectors to store the data
-        let mut x_values = Vec::new();
-        let mut y_values = Vec::new();
-
-        // Read the CSV file
-        for result in rdr.records() {
-            let record = result?;
-            let x: u32 = record[1].parse()?;
-            let y: u32 = record[2].parse()?;
-            x_values.push(x);
-            y_values.push(y);
-        }
+        let segment_color = get_segment_color(segment_name);
+        let (x_values, y_values) = bin_coverage(x_values, y_values, bin_size);
 
         // Create a trace for the current CSV file with consistent color
-        let trace = Scatter::new(x_values, y_values.clone())
+        let trace = Scatter::new(x_values, y_values)
             .mode(Mode::Lines)
-            .name(&segment_name)
+            .name(segment_name)
             .line(plotly::common::Line::new().color(segment_color))
             .hover_template("<b>Position:</b> %{x}<br><b>Coverage:</b> %{y}<br>")
             .show_legend(false);
@@ -333,35 +368,28 @@ pub fn generate_plot_coverage_seg(input_directory: &Path) -> Result<Plot, Box<dy
         plot.add_trace(trace);
 
         // Add variant data as scatter traces if we have data for this segment
-        if let Some(variants) = variants_data.get(&segment_name) {
+        if let Some(variants) = variants_data.get(segment_name) {
             // Collect positions and values for consensus and minority traces
-            let mut variant_positions: Vec<u32> = Vec::new();
-            let mut consensus_values: Vec<u32> = Vec::new();
-            let mut minority_values: Vec<u32> = Vec::new();
+            let mut variant_positions: Vec<i32> = Vec::new();
+            let mut consensus_values: Vec<i32> = Vec::new();
+            let mut minority_values: Vec<i32> = Vec::new();
             let mut hover_texts: Vec<String> = Vec::new();
 
-            for &(
-                position,
-                ref consensus_allele,
-                ref minority_allele,
-                consensus_count,
-                minority_count,
-                minority_frequency,
-            ) in variants
-            {
-                variant_positions.push(position);
-                consensus_values.push(consensus_count + minority_count); // Total height
-                minority_values.push(minority_count);
+            for variant in variants {
+                let total = variant.consensus_count + variant.minority_count;
+                variant_positions.push(variant.sample_position);
+                consensus_values.push(total); // Total height
+                minority_values.push(variant.minority_count);
                 hover_texts.push(format!(
                     "<b>Position:</b> {}<br><br><b>Consensus Allele:</b> {}<br><b>Consensus Count:</b> {}<br><br><b>Minority Allele:</b> {}<br><b>Minority Count:</b> {}<br><b>Minority Frequency:</b> {:.2}%<br><br><b>Total:</b> {}",
-                    position, consensus_allele, consensus_count, minority_allele, minority_count, minority_frequency * 100.0, consensus_count + minority_count
+                    variant.sample_position, variant.consensus_allele, variant.consensus_count, variant.minority_allele, variant.minority_count, variant.minority_frequency.as_percent(), total
                 ));
             }
 
             // Create trace for minority values with consistent color (but with transparency)
             let minority_trace = Scatter::new(variant_positions, minority_values)
                 .mode(Mode::Markers)
-                .name(&segment_name)
+                .name(segment_name)
                 .marker(
                     plotly::common::Marker::new()
                         .color(segment_color)
@@ -399,31 +427,10 @@ pub fn generate_plot_coverage_seg(input_directory: &Path) -> Result<Plot, Box<dy
 
     // Add annotations for each segment title
     let mut annotations = Vec::new();
-    for (idx, path) in file_paths.iter().enumerate() {
-        let ctype: Vec<&str> = path
-            .file_name()
-            .unwrap_or_default()
-            .to_str()
-            .unwrap_or("Unknown")
-            .split('-')
-            .next()
-            .unwrap_or("Unknown")
-            .split('_')
-            .collect();
-        let segment_name = ctype[1];
-        /* .file_name()
-        .unwrap_or_default()
-        .to_str()
-        .unwrap_or("Unknown")
-        .split('-')
-        .next()
-        .unwrap_or("Unknown")
-        .split('_')
-        .next()
-        .unwrap_or("Unknown")
-        .next()
-        .unwrap_or("Unknown");
-        */
+    for (idx, (table_segment, _)) in tables.iter().enumerate() {
+        // A_HA_H3 -> HA
+        let table_segment = strip_reference_suffix(table_segment);
+        let segment_name = table_segment.split('_').nth(1).unwrap_or(table_segment);
         let row = idx / cols;
         let col = idx % cols;
 
@@ -455,6 +462,10 @@ pub fn generate_plot_coverage_seg(input_directory: &Path) -> Result<Plot, Box<dy
         );
     }
 
+    if bin_size > 1 {
+        annotations.push(binning_annotation(bin_size, total_points));
+    }
+
     // Add annotations to layout
     layout = layout.annotations(annotations);
 
@@ -476,24 +487,182 @@ pub fn generate_plot_coverage_seg(input_directory: &Path) -> Result<Plot, Box<dy
     Ok(plot)
 }
 
-// TO DO: fix colors for Sankey diagram, abstract parts of this
+// Depth and consensus base of every position of every segment coverage table in an IRMA
+// directory, keyed by segment name
+fn read_segment_coverage(
+    input_directory: &Path,
+    coordinates: CoordinateSystem,
+) -> Result<SegmentCoverage, Box<dyn Error>> {
+    let mut coverage = HashMap::new();
+    for (segment_name, rows) in coverage_tables(input_directory, coordinates)?.0 {
+        let depths = rows
+            .into_iter()
+            .map(|row| {
+                (
+                    row.position.unsigned_abs(),
+                    (
+                        row.coverage_depth.unsigned_abs(),
+                        row.consensus.to_ascii_uppercase(),
+                    ),
+                )
+            })
+            .collect();
+        coverage.insert(segment_name, depths);
+    }
+
+    Ok(coverage)
+}
+
+/// Plot the per-position depth difference (compare - input), log2 depth ratio and consensus
+/// disagreements for the segments found in both IRMA directories, e.g. original vs
+/// re-extracted specimens or the same sample run with different IRMA configs.
 #[allow(clippy::too_many_lines)]
-pub fn generate_sankey_plot(input_directory: &Path) -> Result<Plot, Box<dyn Error>> {
-    // Path to READ_COUNTS.txt
-    let read_counts_path = input_directory.join("tables").join("READ_COUNTS.txt");
+pub fn generate_plot_coverage_diff(
+    input_directory: &Path,
+    compare_directory: &Path,
+    coordinates: CoordinateSystem,
+) -> Result<Plot, Box<dyn Error>> {
+    let mut plot = Plot::new();
+
+    let base = read_segment_coverage(input_directory, coordinates)?;
+    let compare = read_segment_coverage(compare_directory, coordinates)?;
 
-    // Check if file exists
-    if !read_counts_path.exists() {
+    let mut segments: Vec<&String> = base.keys().filter(|s| compare.contains_key(*s)).collect();
+    segments.sort();
+
+    if segments.is_empty() {
         return Err(format!(
-            "READ_COUNTS.txt not found at {}",
-            read_counts_path.display()
+            "No matching segments found between {} and {}",
+            input_directory.display(),
+            compare_directory.display()
         )
         .into());
     }
 
-    // Open and read the file
-    let file = File::open(read_counts_path)?;
-    let reader = BufReader::new(file);
+    let dir_name = |dir: &Path| {
+        dir.file_name()
+            .unwrap_or_default()
+            .to_str()
+            .unwrap_or("Unknown")
+            .to_string()
+    };
+
+    for segment_name in segments {
+        let base_depths = &base[segment_name];
+        let compare_depths = &compare[segment_name];
+
+        let mut positions: Vec<u32> = base_depths
+            .keys()
+            .chain(compare_depths.keys())
+            .copied()
+            .collect();
+        positions.sort_unstable();
+        positions.dedup();
+
+        let mut diff_values = Vec::with_capacity(positions.len());
+        let mut ratio_values = Vec::with_capacity(positions.len());
+        let mut disagreements = Vec::new();
+        let mut disagreement_texts = Vec::new();
+        for pos in &positions {
+            let base_position = base_depths.get(pos);
+            let compare_position = compare_depths.get(pos);
+            let b = f64::from(base_position.map_or(0, |(depth, _)| *depth));
+            let c = f64::from(compare_position.map_or(0, |(depth, _)| *depth));
+            diff_values.push(c - b);
+            // Pseudocount keeps zero coverage positions finite
+            ratio_values.push(((c + 1.0) / (b + 1.0)).log2());
+
+            if let (Some((_, base_call)), Some((_, compare_call))) =
+                (base_position, compare_position)
+                && base_call != compare_call
+            {
+                disagreements.push(*pos);
+                disagreement_texts.push(format!(
+                    "<b>Position:</b> {pos}<br><b>{}:</b> {base_call}<br><b>{}:</b> {compare_call}",
+                    dir_name(input_directory),
+                    dir_name(compare_directory)
+                ));
+            }
+        }
+        status!(
+            "{segment_name}: {} consensus disagreements",
+            disagreements.len()
+        );
+
+        let segment_color = get_segment_color(segment_name);
+
+        let diff_trace = Scatter::new(positions.clone(), diff_values)
+            .mode(Mode::Lines)
+            .name(segment_name)
+            .legend_group(segment_name)
+            .line(plotly::common::Line::new().color(segment_color))
+            .hover_template("<b>Position:</b> %{x}<br><b>Depth difference:</b> %{y}<br>");
+        plot.add_trace(diff_trace);
+
+        let ratio_trace = Scatter::new(positions, ratio_values)
+            .mode(Mode::Lines)
+            .name(segment_name)
+            .legend_group(segment_name)
+            .show_legend(false)
+            .line(plotly::common::Line::new().color(segment_color))
+            .hover_template("<b>Position:</b> %{x}<br><b>log2 depth ratio:</b> %{y:.2f}<br>")
+            .x_axis("x2")
+            .y_axis("y2");
+        plot.add_trace(ratio_trace);
+
+        let segment_labels = vec![segment_name.clone(); disagreements.len()];
+        let disagreement_trace = Scatter::new(disagreements, segment_labels)
+            .mode(Mode::Markers)
+            .name(segment_name)
+            .legend_group(segment_name)
+            .show_legend(false)
+            .marker(plotly::common::Marker::new().color(segment_color).size(8))
+            .text_array(disagreement_texts)
+            .hover_template("%{text}<extra></extra>")
+            .x_axis("x3")
+            .y_axis("y3");
+        plot.add_trace(disagreement_trace);
+    }
+
+    let layout = Layout::new()
+        .grid(
+            LayoutGrid::new()
+                .rows(3)
+                .columns(1)
+                .pattern(GridPattern::Independent),
+        )
+        .title(format!(
+            "Coverage Difference | {} vs {}",
+            dir_name(compare_directory),
+            dir_name(input_directory)
+        ))
+        .x_axis(Axis::new().title(Title::with_text("Position")))
+        .y_axis(Axis::new().title(Title::with_text("Depth difference")))
+        .x_axis2(Axis::new().title(Title::with_text("Position")))
+        .y_axis2(Axis::new().title(Title::with_text("log2 depth ratio")))
+        .x_axis3(Axis::new().title(Title::with_text("Position")))
+        .y_axis3(Axis::new().title(Title::with_text("Consensus disagreements")));
+    plot.set_layout(layout);
+
+    plot.set_configuration(
+        plotly::Configuration::new()
+            .responsive(true)
+            .display_logo(false)
+            .fill_frame(true)
+            .to_image_button_options(
+                ToImageButtonOptions::new()
+                    .format(ImageButtonFormats::Svg)
+                    .filename("coverage_diff_plot"),
+            ),
+    );
+
+    Ok(plot)
+}
+
+// TO DO: fix colors for Sankey diagram, abstract parts of this
+#[allow(clippy::too_many_lines)]
+pub fn generate_sankey_plot(input_directory: &Path) -> Result<Plot, Box<dyn Error>> {
+    let read_counts = irma_dir_read_counts(input_directory)?;
 
     // Data structures for Sankey diagram
     let mut node_labels = Vec::new();
@@ -503,26 +672,12 @@ pub fn generate_sankey_plot(input_directory: &Path) -> Result<Plot, Box<dyn Erro
     let mut values = Vec::new();
     let mut node_map = HashMap::new();
 
-    // Skip the header line
-    let mut lines = reader.lines();
-    if let Some(Ok(_)) = lines.next() {
-        // Header skipped
-    }
-
-    // Process data and build node map first
-    let mut records = Vec::new();
-    for line in lines.map_while(Result::ok) {
-        let parts: Vec<&str> = line.split('\t').collect();
-        if parts.len() >= 3 {
-            let record = parts[0];
-            let reads: u32 = parts[1].parse().unwrap_or(0);
-
-            // Skip "NA" values and 0 reads
-            if parts[1] != "NA" && reads > 0 {
-                records.push((record.to_string(), reads));
-            }
-        }
-    }
+    // Skip 0 reads
+    let records: Vec<(String, u32)> = read_counts
+        .into_iter()
+        .filter(|row| row.reads > 0)
+        .map(|row| (row.record, row.reads.unsigned_abs()))
+        .collect();
 
     // Add initial nodes
     add_node(
@@ -530,42 +685,50 @@ pub fn generate_sankey_plot(input_directory: &Path) -> Result<Plot, Box<dyn Erro
         &mut node_labels,
         &mut node_map,
         &mut node_colors,
-        "#3366CC",
+        CATEGORY_BLUE,
     );
     add_node(
         "Pass QC",
         &mut node_labels,
         &mut node_map,
         &mut node_colors,
-        "#109618",
-    ); // green
+        PASS_COLOR,
+    );
     add_node(
         "Fail QC",
         &mut node_labels,
         &mut node_map,
         &mut node_colors,
-        "#990099",
-    ); // purple
+        FAIL_COLOR,
+    );
     add_node(
         "No Match",
         &mut node_labels,
         &mut node_map,
         &mut node_colors,
-        "#3B3EAC",
-    ); // indigo
+        NEUTRAL_COLOR,
+    );
     add_node(
         "Alt Match",
         &mut node_labels,
         &mut node_map,
         &mut node_colors,
-        "#0099C6",
-    ); // cyan
+        CATEGORY_CYAN,
+    );
+    add_node(
+        "Chimeric",
+        &mut node_labels,
+        &mut node_map,
+        &mut node_colors,
+        "#DD4477",
+    ); // pink
     // Process records to create nodes and links
     let mut _initial_reads = 0;
     let mut pass_qc = 0;
     let mut fail_qc = 0;
     let mut no_match = 0;
-    let mut chi_alt_reads = 0;
+    let mut alt_reads = 0;
+    let mut chimeric_reads = 0;
     let mut primary_match_sum = 0;
     let mut four_segments: Vec<(String, u32)> = Vec::new();
 
@@ -575,7 +738,8 @@ pub fn generate_sankey_plot(input_directory: &Path) -> Result<Plot, Box<dyn Erro
             "2-failQC" => fail_qc = *reads,
             "2-passQC" => pass_qc = *reads,
             "3-nomatch" => no_match = *reads,
-            "3-chimeric" | "3-altmatch" => chi_alt_reads += *reads,
+            "3-altmatch" => alt_reads = *reads,
+            "3-chimeric" => chimeric_reads = *reads,
             _ => {
                 if let Some(stripped) = record.strip_prefix("4-") {
                     primary_match_sum += *reads;
@@ -617,22 +781,50 @@ pub fn generate_sankey_plot(input_directory: &Path) -> Result<Plot, Box<dyn Erro
         values.push(reads);
     }
 
-    // Now process 5- records as before
+    // Break the alternative matches out by virus/type (the part of the segment name before
+    // the first '_', e.g. B_HA in a flu A sample) and then into their 5- segment assignments
+    let mut alt_groups: Vec<(String, Vec<(String, u32)>)> = Vec::new();
     for (record, reads) in &records {
         if let Some(stripped) = record.strip_prefix("5-") {
-            let segment = stripped.to_string();
-            let segment_color = get_segment_color(&segment);
+            let group = alt_match_group(stripped);
+            if let Some((_, segments)) = alt_groups.iter_mut().find(|(g, _)| *g == group) {
+                segments.push((stripped.to_string(), *reads));
+            } else {
+                alt_groups.push((group, vec![(stripped.to_string(), *reads)]));
+            }
+        }
+    }
+
+    let mut alt_group_labels = Vec::new();
+    let mut alt_segment_labels = Vec::new();
+    for (group, segments) in &alt_groups {
+        let group_label = format!("Alt Match: {group}");
+        add_node(
+            &group_label,
+            &mut node_labels,
+            &mut node_map,
+            &mut node_colors,
+            CATEGORY_CYAN,
+        );
+        source_indices.push(node_map["Alt Match"]);
+        target_indices.push(node_map[&group_label]);
+        values.push(segments.iter().map(|(_, reads)| reads).sum());
+        alt_group_labels.push(group_label.clone());
+
+        for (segment, reads) in segments {
+            // Suffix the label so the node is not merged with the primary 4- node of the same name
+            let segment_label = format!("{segment} (alt)");
             add_node(
-                &segment,
+                &segment_label,
                 &mut node_labels,
                 &mut node_map,
                 &mut node_colors,
-                segment_color,
+                get_segment_color(segment),
             );
-            // Link from Alt Match to this segment
-            source_indices.push(node_map["Alt Match"]);
-            target_indices.push(node_map[&segment]);
+            source_indices.push(node_map[&group_label]);
+            target_indices.push(node_map[&segment_label]);
             values.push(*reads);
+            alt_segment_labels.push(segment_label);
         }
     }
 
@@ -650,10 +842,16 @@ pub fn generate_sankey_plot(input_directory: &Path) -> Result<Plot, Box<dyn Erro
     }
 
     // Link: Pass QC -> alt match
-    if chi_alt_reads > 0 {
+    if alt_reads > 0 {
         source_indices.push(node_map["Pass QC"]);
         target_indices.push(node_map["Alt Match"]);
-        values.push(chi_alt_reads);
+        values.push(alt_reads);
+    }
+    // Link: Pass QC -> chimeric
+    if chimeric_reads > 0 {
+        source_indices.push(node_map["Pass QC"]);
+        target_indices.push(node_map["Chimeric"]);
+        values.push(chimeric_reads);
     }
     // Link: Pass QC -> No Match
     if no_match > 0 {
@@ -675,10 +873,28 @@ pub fn generate_sankey_plot(input_directory: &Path) -> Result<Plot, Box<dyn Erro
     let n = node_labels.len();
     let mut x = vec![0.0; n];
     let mut y = vec![0.0; n];
-    // Assign positions for the first five nodes (Initial Reads, Pass QC, Fail QC, No Match, Alt Match)
-    // Remaining nodes (segments) are stacked vertically in the last column
+    // Assign positions for the fixed nodes (Initial Reads, Pass QC, Fail QC, No Match, Alt Match,
+    // Chimeric, Primary Match). Alt match groups get their own column and the remaining nodes
+    // (segments) are stacked vertically in the last columns
+    let fixed_nodes = if primary_match_sum > 0 { 7 } else { 6 };
+    let primary_segments =
+        n.saturating_sub(fixed_nodes + alt_group_labels.len() + alt_segment_labels.len());
     let mut seg_idx = 0;
+    let mut alt_seg_idx = 0;
+    let mut alt_group_idx = 0;
     for (i, label) in node_labels.iter().enumerate() {
+        if alt_group_labels.contains(label) {
+            x[i] = 0.55;
+            y[i] = 0.7 + 0.25 * f64::from(alt_group_idx) / (alt_group_labels.len() as f64);
+            alt_group_idx += 1;
+            continue;
+        }
+        if alt_segment_labels.contains(label) {
+            x[i] = 0.85;
+            y[i] = 0.7 + 0.25 * f64::from(alt_seg_idx) / (alt_segment_labels.len() as f64);
+            alt_seg_idx += 1;
+            continue;
+        }
         match label.as_str() {
             "Initial Reads" => {
                 x[i] = 0.0;
@@ -700,6 +916,10 @@ pub fn generate_sankey_plot(input_directory: &Path) -> Result<Plot, Box<dyn Erro
                 x[i] = 0.4;
                 y[i] = 0.8;
             }
+            "Chimeric" => {
+                x[i] = 0.4;
+                y[i] = 0.95;
+            }
             "Primary Match" => {
                 x[i] = 0.4;
                 y[i] = 0.5;
@@ -707,7 +927,7 @@ pub fn generate_sankey_plot(input_directory: &Path) -> Result<Plot, Box<dyn Erro
             _ => {
                 // Segment nodes: stack vertically in last column
                 x[i] = 0.7;
-                y[i] = 0.1 + 0.8 * f64::from(seg_idx) / ((n - 5).max(1) as f64);
+                y[i] = 0.1 + 0.6 * f64::from(seg_idx) / (primary_segments.max(1) as f64);
                 seg_idx += 1;
             }
         }
@@ -775,6 +995,14 @@ pub fn generate_sankey_plot(input_directory: &Path) -> Result<Plot, Box<dyn Erro
     Ok(plot)
 }
 
+// Virus/type grouping for an alternative match, e.g. "B_HA" -> "B", "RSV_AD" -> "RSV"
+fn alt_match_group(segment: &str) -> String {
+    segment
+        .split_once('_')
+        .map_or("Other", |(group, _)| group)
+        .to_string()
+}
+
 // Helper function to add node and maintain the node map
 #[allow(clippy::implicit_hasher)]
 pub fn add_node(
@@ -802,7 +1030,7 @@ pub fn plotter_process(args: PlotterArgs) -> Result<(), Box<dyn Error>> {
 
     // Generate coverage plot if specified
     if args.coverage {
-        let plot = generate_plot_coverage(&input_directory)?;
+        let plot = generate_plot_coverage(&input_directory, args.max_points, args.coordinates)?;
 
         // Save the plot as an HTML file if output path is provided
         if let Some(optional_file) = &output_html_file {
@@ -822,7 +1050,7 @@ pub fn plotter_process(args: PlotterArgs) -> Result<(), Box<dyn Error>> {
 
     // Generate segmented coverage subplots if specified
     if args.coverage_seg {
-        let plot = generate_plot_coverage_seg(&input_directory)?;
+        let plot = generate_plot_coverage_seg(&input_directory, args.max_points, args.coordinates)?;
 
         // Save the plot as an HTML file if output path is provided
         if let Some(optional_file) = &output_html_file {
@@ -850,6 +1078,36 @@ pub fn plotter_process(args: PlotterArgs) -> Result<(), Box<dyn Error>> {
         }
     }
 
+    // Generate coverage difference plot if a second directory is given
+    if let Some(compare_directory) = &args.compare_dir {
+        let plot =
+            generate_plot_coverage_diff(&input_directory, compare_directory, args.coordinates)?;
+
+        // Save the plot as an HTML file if output path is provided
+        if let Some(optional_file) = &output_html_file {
+            let diff_file = optional_file.with_file_name(format!(
+                "{}_diff{}",
+                optional_file
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy(),
+                optional_file
+                    .extension()
+                    .map_or_else(String::new, |ext| format!(".{}", ext.to_string_lossy()))
+            ));
+            plot.write_html(diff_file);
+        }
+
+        // Show the plot if specified
+        if args.display {
+            plot.show();
+        }
+        // If inline HTML is requested, print the HTML to stdout
+        if args.inline_html {
+            println!("{}", plot.to_inline_html(None));
+        }
+    }
+
     // Generate read flow sankey diagram if specified
     if args.read_flow {
         let plot = generate_sankey_plot(&input_directory)?;
@@ -1,13 +1,12 @@
 #![allow(unreachable_patterns)]
 #![allow(dead_code, unused_imports)]
 use clap::Parser;
-use csv::ReaderBuilder;
-use either::Either;
-use serde::{self, Deserialize, de::DeserializeOwned};
+use rayon::prelude::*;
+use serde::{self, Deserialize};
 use std::{
+    collections::HashMap,
     error::Error,
-    fs::{File, OpenOptions},
-    io::{BufRead, BufReader, BufWriter, Stdin, Write, stdin, stdout},
+    io::{BufRead, Write},
     path::{Path, PathBuf},
 };
 use zoe::{
@@ -20,7 +19,14 @@ use zoe::{
     prelude::{Len, Nucleotides},
 };
 
-use crate::utils::alignment::align_sequences;
+use crate::io::data_ingest::{AllAllelesData, all_alleles_data_collection};
+use crate::processes::variants_of_interest::RefInput;
+use crate::utils::{
+    alignment::align_sequences,
+    coordinates::{dais_cds_position, dais_query_position, stripped_insertions},
+    dais_tables::{create_reader, index_references, read_tsv, sample_from_dais_id},
+    output::output_writer,
+};
 
 #[derive(Debug, Parser)]
 #[command(about = "Tool for observing codon and amino acid differences at a given poistion")]
@@ -44,25 +50,21 @@ pub struct PositionsArgs {
     #[arg(short = 'd', long, default_value = ",")]
     /// Use the provider delimiter for separating fields. Default is ','
     output_delimiter: String,
-}
 
-// input files *must* be tab-separated
-fn read_tsv<T: DeserializeOwned, R: std::io::Read>(
-    reader: R,
-    has_headers: bool,
-) -> Result<Vec<T>, Box<dyn std::error::Error>> {
-    let mut rdr = ReaderBuilder::new()
-        .has_headers(has_headers)
-        .delimiter(b'\t')
-        .from_reader(reader);
-
-    let mut records = Vec::new();
-    for result in rdr.deserialize() {
-        let record: T = result?;
-        records.push(record);
-    }
+    #[arg(long, requires = "minor_output")]
+    /// Optional MIRA-NF output directory with the IRMA results. When given, mutations of
+    /// interest only found in the minor variants of the IRMA allele tables are also reported,
+    /// in the `--minor-output` file
+    irma_path: Option<PathBuf>,
+
+    #[arg(long, requires = "irma_path")]
+    /// Delimited file the minor variants of interest are written to
+    minor_output: Option<PathBuf>,
 
-    Ok(records)
+    #[arg(long, default_value_t = 0.01, requires = "irma_path")]
+    /// Minor variants below this frequency are left out of the minor variant mode. Default is
+    /// 0.01
+    min_frequency: f64,
 }
 
 #[derive(Deserialize, Debug)]
@@ -83,20 +85,6 @@ pub struct DaisInput {
     cds_nt_coordinates: String,
 }
 
-#[derive(Deserialize, Debug)]
-pub struct RefInput {
-    isolate_id: String,
-    isolate_name: String,
-    subtype: String,
-    passage_history: String,
-    nt_id: String,
-    ctype: String,
-    reference_id: String,
-    protein: String,
-    aa_aln: String,
-    cds_aln: String,
-}
-
 #[derive(Deserialize, Debug)]
 pub struct MutsOfInterestInput {
     subtype: String,
@@ -122,6 +110,19 @@ pub struct Entry<'a> {
 }
 
 impl Entry<'_> {
+    /// How the sample's amino acid compares to the reference strain's: `wildtype`, `mutant`,
+    /// `deletion` when the sample's codon is deleted, or `missing` and `partial` when it can't
+    /// be read
+    fn status(&self) -> &'static str {
+        match self.aa_mut {
+            '~' => "partial",
+            '-' => "deletion",
+            '.' | 'X' => "missing",
+            aa if aa == self.aa_ref => "wildtype",
+            _ => "mutant",
+        }
+    }
+
     fn update_entry_from_alignment(
         &mut self,
         subtype: &str,
@@ -156,17 +157,6 @@ impl Entry<'_> {
     }
 }
 
-fn create_reader(path: Option<&PathBuf>) -> std::io::Result<BufReader<Either<File, Stdin>>> {
-    let reader = if let Some(ref file_path) = path {
-        let file = OpenOptions::new().read(true).open(file_path)?;
-        BufReader::new(Either::Left(file))
-    } else {
-        BufReader::new(Either::Right(stdin()))
-    };
-
-    Ok(reader)
-}
-
 pub fn lines_to_vec<R: BufRead>(reader: R) -> std::io::Result<Vec<Vec<String>>> {
     let mut columns: Vec<Vec<String>> = Vec::new();
 
@@ -185,8 +175,369 @@ pub fn lines_to_vec<R: BufRead>(reader: R) -> std::io::Result<Vec<Vec<String>>>
 
     Ok(columns)
 }
-//todo: abstract/split this up
+/// Compares a DAIS entry's CDS to one reference strain's, writing a row for each position in
+/// the mutations of interest
 #[allow(clippy::too_many_lines)]
+fn compare_to_reference<W: Write>(
+    dais_entry: &DaisInput,
+    ref_entry: &RefInput,
+    muts_interest: &[MutsOfInterestInput],
+    delim: &str,
+    mut writer: W,
+) -> std::io::Result<()> {
+    let nt_seq1: Nucleotides = ref_entry.cds_aln.clone().into();
+    let nt_seq2: Nucleotides = dais_entry.cds_aln.clone().into();
+
+    if nt_seq1.len() == nt_seq2.len() {
+        let mut entry = Entry {
+            sample_id: &dais_entry.sample_id,
+            ref_strain: &ref_entry.isolate_name,
+            gisaid_accession: &ref_entry.isolate_id,
+            subtype: &dais_entry.subtype,
+            dais_ref: &dais_entry.ref_strain,
+            protein: &dais_entry.protein,
+            ref_codon: "NNN".to_string(),
+            mut_codon: "NNN".to_string(),
+            aa_position: 0,
+            aa_ref: 'X',
+            aa_mut: 'X',
+            phenotypic_consequences: String::new(),
+        };
+
+        let mut tail_index = 0;
+        let (codons1, tail1) = nt_seq1.as_codons();
+        let (codons2, tail2) = nt_seq2.as_codons();
+
+        for (index, (ref_codon, query_codon)) in codons1.iter().zip(codons2.iter()).enumerate() {
+            let aa_index = index + 1;
+            tail_index = aa_index;
+            let ref_aa = StdGeneticCode::translate_codon(ref_codon);
+            let query_aa = StdGeneticCode::translate_codon(query_codon);
+
+            entry.ref_codon = std::str::from_utf8(ref_codon)
+                .expect("Invalid UTF-8 sequence")
+                .to_string();
+            entry.mut_codon = std::str::from_utf8(query_codon)
+                .expect("Invalid UTF-8 sequence")
+                .to_string();
+            entry.aa_position = aa_index;
+            entry.aa_ref = ref_aa as char;
+            entry.aa_mut = query_aa as char;
+
+            if entry.update_entry_from_alignment(
+                &ref_entry.subtype,
+                ref_aa,
+                query_aa,
+                muts_interest,
+            ) {
+                let Entry {
+                    sample_id,
+                    ref_strain,
+                    gisaid_accession,
+                    subtype,
+                    dais_ref,
+                    protein,
+                    ref_codon,
+                    mut_codon,
+                    aa_ref,
+                    aa_position,
+                    aa_mut,
+                    phenotypic_consequences,
+                } = &entry;
+                let d = delim;
+
+                writeln!(
+                    &mut writer,
+                    "{sample_id}{d}{ref_strain}{d}{gisaid_accession}{d}\
+                            {subtype}{d}{dais_ref}{d}{protein}{d}\
+                            {ref_codon}{d}{mut_codon}{d}\
+                            {aa_ref}:{aa_position}:{aa_mut}{d}\
+                            {phenotypic_consequences}{d}{}",
+                    entry.status(),
+                )?;
+            }
+        }
+
+        let partial_codon = b'~';
+        entry.ref_codon = std::str::from_utf8(tail1)
+            .expect("Invalid UTF-8 sequence")
+            .to_string();
+        entry.mut_codon = std::str::from_utf8(tail2)
+            .expect("Invalid UTF-8 sequence")
+            .to_string();
+        entry.aa_position = tail_index + 1;
+        entry.aa_ref = '~';
+        entry.aa_mut = '~';
+
+        if entry.update_entry_from_alignment(
+            &ref_entry.subtype,
+            partial_codon,
+            partial_codon,
+            muts_interest,
+        ) {
+            let Entry {
+                sample_id,
+                ref_strain,
+                gisaid_accession,
+                subtype,
+                dais_ref,
+                protein,
+                ref_codon,
+                mut_codon,
+                aa_ref,
+                aa_position,
+                aa_mut,
+                phenotypic_consequences,
+            } = &entry;
+            let d = delim;
+
+            writeln!(
+                &mut writer,
+                "{sample_id}{d}{ref_strain}{d}{gisaid_accession}{d}\
+                        {subtype}{d}{dais_ref}{d}{protein}{d}\
+                        {ref_codon}{d}{mut_codon}{d}\
+                        {aa_ref}:{aa_position}:{aa_mut}{d}\
+                        {phenotypic_consequences}{d}{}",
+                entry.status(),
+            )?;
+        }
+    } else {
+        let query = dais_entry.cds_aln.as_bytes();
+        let reference = ref_entry.cds_aln.as_bytes();
+        let (aligned_1, aligned_2) = {
+            let (a1, a2) = align_sequences(query, reference);
+            (Nucleotides::from(a1), Nucleotides::from(a2))
+        };
+
+        let mut entry = Entry {
+            sample_id: &dais_entry.sample_id,
+            ref_strain: &ref_entry.isolate_name,
+            gisaid_accession: &ref_entry.isolate_id,
+            subtype: &dais_entry.subtype,
+            dais_ref: &dais_entry.ref_strain,
+            protein: &dais_entry.protein,
+            ref_codon: "NNN".to_string(),
+            mut_codon: "NNN".to_string(),
+            aa_position: 0,
+            aa_ref: 'X',
+            aa_mut: 'X',
+            phenotypic_consequences: String::new(),
+        };
+
+        let mut tail_index = 0;
+        let (codons1, tail1) = aligned_1.as_codons();
+        let (codons2, tail2) = aligned_2.as_codons();
+
+        for (index, (ref_codon, query_codon)) in codons1.iter().zip(codons2.iter()).enumerate() {
+            let aa_index = index + 1;
+            tail_index = aa_index;
+            let ref_aa = StdGeneticCode::translate_codon(ref_codon);
+            let query_aa = StdGeneticCode::translate_codon(query_codon);
+
+            entry.ref_codon = std::str::from_utf8(ref_codon)
+                .expect("Invalid UTF-8 sequence")
+                .to_string();
+            entry.mut_codon = std::str::from_utf8(query_codon)
+                .expect("Invalid UTF-8 sequence")
+                .to_string();
+            entry.aa_position = aa_index;
+            entry.aa_ref = ref_aa as char;
+            entry.aa_mut = query_aa as char;
+
+            if entry.update_entry_from_alignment(
+                &ref_entry.subtype,
+                ref_aa,
+                query_aa,
+                muts_interest,
+            ) {
+                let Entry {
+                    sample_id,
+                    ref_strain,
+                    gisaid_accession,
+                    subtype,
+                    dais_ref,
+                    protein,
+                    ref_codon,
+                    mut_codon,
+                    aa_ref,
+                    aa_position,
+                    aa_mut,
+                    phenotypic_consequences,
+                } = &entry;
+                let d = delim;
+
+                writeln!(
+                    &mut writer,
+                    "{sample_id}{d}{ref_strain}{d}{gisaid_accession}{d}\
+                            {subtype}{d}{dais_ref}{d}{protein}{d}\
+                            {ref_codon}{d}{mut_codon}{d}\
+                            {aa_ref}:{aa_position}:{aa_mut}{d}\
+                            {phenotypic_consequences}{d}{}",
+                    entry.status(),
+                )?;
+            }
+        }
+
+        if !tail1.is_empty() {
+            let partial_codon = b'~';
+            entry.ref_codon = std::str::from_utf8(tail1)
+                .expect("Invalid UTF-8 sequence")
+                .to_string();
+            entry.mut_codon = std::str::from_utf8(tail2)
+                .expect("Invalid UTF-8 sequence")
+                .to_string();
+            entry.aa_position = tail_index + 1;
+            entry.aa_ref = '~';
+            entry.aa_mut = '~';
+            if entry.update_entry_from_alignment(
+                &ref_entry.subtype,
+                partial_codon,
+                partial_codon,
+                muts_interest,
+            ) {
+                let Entry {
+                    sample_id,
+                    ref_strain,
+                    gisaid_accession,
+                    subtype,
+                    dais_ref,
+                    protein,
+                    ref_codon,
+                    mut_codon,
+                    aa_ref,
+                    aa_position,
+                    aa_mut,
+                    phenotypic_consequences,
+                } = &entry;
+                let d = delim;
+
+                writeln!(
+                    &mut writer,
+                    "{sample_id}{d}{ref_strain}{d}{gisaid_accession}{d}\
+                            {subtype}{d}{dais_ref}{d}{protein}{d}\
+                            {ref_codon}{d}{mut_codon}{d}\
+                            {aa_ref}:{aa_position}:{aa_mut}{d}\
+                            {phenotypic_consequences}{d}{}",
+                    entry.status(),
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Minor alleles of the IRMA allele tables by sample, IRMA reference and consensus position
+type MinorAlleles<'a> = HashMap<(&'a str, &'a str, i32), Vec<&'a AllAllelesData>>;
+
+// Position on the consensus of each column of a sample's `cds_aln`, `None` for gaps
+fn consensus_positions(dais_entry: &DaisInput) -> Vec<Option<i32>> {
+    let insertions = stripped_insertions(&dais_entry.cds_seq, &dais_entry.cds_aln);
+    let mut ordinal = 0;
+    dais_entry
+        .cds_aln
+        .bytes()
+        .map(|base| {
+            if !base.is_ascii_alphabetic() {
+                return None;
+            }
+            let cds_position = dais_cds_position(&insertions, ordinal);
+            ordinal += 1;
+            dais_query_position(
+                &dais_entry.query_nt_coordinates,
+                &dais_entry.cds_nt_coordinates,
+                cds_position,
+            )
+        })
+        .collect()
+}
+
+/// Writes a row for each minor allele that makes a mutation of interest the sample's consensus
+/// doesn't have. Each base of a position's codon is changed on its own, as the allele tables
+/// don't say which minor alleles are on the same reads
+fn minor_variants_of_interest<W: Write>(
+    dais_entry: &DaisInput,
+    ref_entry: &RefInput,
+    muts_interest: &[MutsOfInterestInput],
+    minor_alleles: &MinorAlleles,
+    consensus_positions: &[Option<i32>],
+    delim: &str,
+    mut writer: W,
+) -> std::io::Result<()> {
+    let irma_sample = sample_from_dais_id(&dais_entry.sample_id);
+    let sample_aln = dais_entry.cds_aln.as_bytes();
+    let ref_aln = ref_entry.cds_aln.as_bytes();
+    let d = delim;
+
+    for muts_entry in muts_interest {
+        if muts_entry.subtype != ref_entry.subtype || muts_entry.protein != dais_entry.protein {
+            continue;
+        }
+        let Ok(aa_position) = muts_entry.aa_position.trim().parse::<usize>() else {
+            continue;
+        };
+        let Some(start) = aa_position.checked_sub(1).map(|index| index * 3) else {
+            continue;
+        };
+        let Some(&[b1, b2, b3]) = sample_aln.get(start..start + 3) else {
+            continue;
+        };
+        let consensus_codon = [b1, b2, b3];
+        if !consensus_codon.iter().all(u8::is_ascii_alphabetic) {
+            continue;
+        }
+        let consensus_aa = char::from(StdGeneticCode::translate_codon(&consensus_codon));
+        if consensus_aa
+            .to_string()
+            .eq_ignore_ascii_case(&muts_entry.aa)
+        {
+            continue;
+        }
+        let ref_aa = ref_aln.get(start..start + 3).map_or('X', |codon| {
+            char::from(StdGeneticCode::translate_codon(&[
+                codon[0], codon[1], codon[2],
+            ]))
+        });
+
+        for offset in 0..3 {
+            let Some(position) = consensus_positions.get(start + offset).copied().flatten() else {
+                continue;
+            };
+            let key = (irma_sample, dais_entry.subtype.as_str(), position);
+            for allele in minor_alleles.get(&key).into_iter().flatten() {
+                let Some(&base) = allele.allele.as_bytes().first() else {
+                    continue;
+                };
+                let mut minor_codon = consensus_codon;
+                minor_codon[offset] = base.to_ascii_uppercase();
+                let minor_aa = char::from(StdGeneticCode::translate_codon(&minor_codon));
+                if !minor_aa.to_string().eq_ignore_ascii_case(&muts_entry.aa) {
+                    continue;
+                }
+                writeln!(
+                    &mut writer,
+                    "{}{d}{}{d}{}{d}{}{d}{}{d}{}{d}{}{d}{}{d}{ref_aa}:{aa_position}:{minor_aa}{d}\
+                    {consensus_aa}{d}{}{d}{position}{d}{}{d}{}{d}{}",
+                    dais_entry.sample_id,
+                    ref_entry.isolate_name,
+                    ref_entry.isolate_id,
+                    dais_entry.subtype,
+                    dais_entry.ref_strain,
+                    dais_entry.protein,
+                    String::from_utf8_lossy(&minor_codon),
+                    String::from_utf8_lossy(&consensus_codon),
+                    muts_entry.description,
+                    allele.total_count,
+                    allele.allele_count,
+                    allele.allele_frequency,
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
 pub fn positions_of_interest_process(args: PositionsArgs) -> Result<(), Box<dyn Error>> {
     let delim = args.output_delimiter;
 
@@ -199,267 +550,117 @@ pub fn positions_of_interest_process(args: PositionsArgs) -> Result<(), Box<dyn
     let ref_reader = create_reader(Some(&args.ref_file))?;
     let refs: Vec<RefInput> = read_tsv(ref_reader, true)?;
 
-    let mut writer = if let Some(ref file_path) = args.output_xsv {
-        let file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(file_path)?;
-        BufWriter::new(Either::Left(file))
-    } else {
-        BufWriter::new(Either::Right(stdout()))
+    // The run and instrument of the alleles aren't reported, so they are left empty
+    let alleles = match &args.irma_path {
+        Some(irma_path) => all_alleles_data_collection(irma_path, "", "")?,
+        None => Vec::new(),
     };
+    let mut minor_alleles: MinorAlleles = HashMap::new();
+    for allele in &alleles {
+        if allele.allele_type == "Minority" && allele.allele_frequency >= args.min_frequency {
+            minor_alleles
+                .entry((
+                    allele.sample_id.as_deref().unwrap_or_default(),
+                    allele.reference.as_str(),
+                    allele.position,
+                ))
+                .or_default()
+                .push(allele);
+        }
+    }
+
+    let mut writer = output_writer(args.output_xsv.as_deref())?;
     writeln!(
         &mut writer,
-        "sample, reference_strain,gisaid_accession,ctype,dais_reference,protein,sample_codon,reference_codon,aa_mutation,phenotypic_consequence",
+        "sample, reference_strain,gisaid_accession,ctype,dais_reference,protein,sample_codon,reference_codon,aa_mutation,phenotypic_consequence,status",
     )?;
+    let mut minor_writer = match &args.minor_output {
+        Some(path) => {
+            let mut minor_writer = output_writer(Some(path))?;
+            let d = &delim;
+            writeln!(
+                &mut minor_writer,
+                "sample{d}reference_strain{d}gisaid_accession{d}ctype{d}dais_reference{d}protein{d}\
+                minor_codon{d}consensus_codon{d}aa_mutation{d}consensus_aa{d}phenotypic_consequence{d}\
+                consensus_position{d}depth{d}allele_count{d}allele_frequency",
+            )?;
+            Some(minor_writer)
+        }
+        None => None,
+    };
 
-    for dais_entry in &dais {
-        for ref_entry in &refs {
-            if dais_entry.subtype == ref_entry.ctype
-                && dais_entry.ref_strain == ref_entry.reference_id
-                && dais_entry.protein == ref_entry.protein
-            {
-                let nt_seq1: Nucleotides = ref_entry.cds_aln.clone().into();
-                let nt_seq2: Nucleotides = dais_entry.cds_aln.clone().into();
-
-                if nt_seq1.len() == nt_seq2.len() {
-                    let mut entry = Entry {
-                        sample_id: &dais_entry.sample_id,
-                        ref_strain: &ref_entry.isolate_name,
-                        gisaid_accession: &ref_entry.isolate_id,
-                        subtype: &dais_entry.subtype,
-                        dais_ref: &dais_entry.ref_strain,
-                        protein: &dais_entry.protein,
-                        ref_codon: "NNN".to_string(),
-                        mut_codon: "NNN".to_string(),
-                        aa_position: 0,
-                        aa_ref: 'X',
-                        aa_mut: 'X',
-                        phenotypic_consequences: String::new(),
-                    };
-
-                    let mut tail_index = 0;
-                    let (codons1, tail1) = nt_seq1.as_codons();
-                    let (codons2, tail2) = nt_seq2.as_codons();
-
-                    for (index, (ref_codon, query_codon)) in
-                        codons1.iter().zip(codons2.iter()).enumerate()
-                    {
-                        let aa_index = index + 1;
-                        tail_index = aa_index;
-                        let ref_aa = StdGeneticCode::translate_codon(ref_codon);
-                        let query_aa = StdGeneticCode::translate_codon(query_codon);
-
-                        entry.ref_codon = std::str::from_utf8(ref_codon)
-                            .expect("Invalid UTF-8 sequence")
-                            .to_string();
-                        entry.mut_codon = std::str::from_utf8(query_codon)
-                            .expect("Invalid UTF-8 sequence")
-                            .to_string();
-                        entry.aa_position = aa_index;
-                        entry.aa_ref = ref_aa as char;
-                        entry.aa_mut = query_aa as char;
-
-                        if entry.update_entry_from_alignment(
-                            &ref_entry.subtype,
-                            ref_aa,
-                            query_aa,
-                            &muts_interest,
-                        ) {
-                            let Entry {
-                                sample_id,
-                                ref_strain,
-                                gisaid_accession,
-                                subtype,
-                                dais_ref,
-                                protein,
-                                ref_codon,
-                                mut_codon,
-                                aa_ref,
-                                aa_position,
-                                aa_mut,
-                                phenotypic_consequences,
-                            } = &entry;
-                            let d = &delim;
-
-                            writeln!(
-                                &mut writer,
-                                "{sample_id}{d}{ref_strain}{d}{gisaid_accession}{d}\
-                                        {subtype}{d}{dais_ref}{d}{protein}{d}\
-                                        {ref_codon}{d}{mut_codon}{d}\
-                                        {aa_ref}:{aa_position}:{aa_mut}{d}\
-                                        {phenotypic_consequences}",
-                            )?;
-                        }
-                    }
-
-                    let partial_codon = b'~';
-                    entry.ref_codon = std::str::from_utf8(tail1)
-                        .expect("Invalid UTF-8 sequence")
-                        .to_string();
-                    entry.mut_codon = std::str::from_utf8(tail2)
-                        .expect("Invalid UTF-8 sequence")
-                        .to_string();
-                    entry.aa_position = tail_index + 1;
-                    entry.aa_ref = '~';
-                    entry.aa_mut = '~';
-
-                    if entry.update_entry_from_alignment(
-                        &ref_entry.subtype,
-                        partial_codon,
-                        partial_codon,
+    let refs_by_key = index_references(&refs);
+
+    // Entries are compared in parallel, each into its own buffer, and written in the order of
+    // the DAIS file
+    let rows = dais
+        .par_iter()
+        .map(|dais_entry| {
+            let key = (
+                dais_entry.subtype.as_str(),
+                dais_entry.ref_strain.as_str(),
+                dais_entry.protein.as_str(),
+            );
+            let mut rows = Vec::new();
+            let mut minor_rows = Vec::new();
+            let positions = if args.minor_output.is_some() {
+                consensus_positions(dais_entry)
+            } else {
+                Vec::new()
+            };
+            for ref_entry in refs_by_key.get(&key).into_iter().flatten() {
+                compare_to_reference(dais_entry, ref_entry, &muts_interest, &delim, &mut rows)?;
+                if args.minor_output.is_some() {
+                    minor_variants_of_interest(
+                        dais_entry,
+                        ref_entry,
                         &muts_interest,
-                    ) {
-                        let Entry {
-                            sample_id,
-                            ref_strain,
-                            gisaid_accession,
-                            subtype,
-                            dais_ref,
-                            protein,
-                            ref_codon,
-                            mut_codon,
-                            aa_ref,
-                            aa_position,
-                            aa_mut,
-                            phenotypic_consequences,
-                        } = &entry;
-                        let d = &delim;
-
-                        writeln!(
-                            &mut writer,
-                            "{sample_id}{d}{ref_strain}{d}{gisaid_accession}{d}\
-                                    {subtype}{d}{dais_ref}{d}{protein}{d}\
-                                    {ref_codon}{d}{mut_codon}{d}\
-                                    {aa_ref}:{aa_position}:{aa_mut}{d}\
-                                    {phenotypic_consequences}",
-                        )?;
-                    }
-                } else {
-                    let query = dais_entry.cds_aln.as_bytes();
-                    let reference = ref_entry.cds_aln.as_bytes();
-                    let (aligned_1, aligned_2) = {
-                        let (a1, a2) = align_sequences(query, reference);
-                        (Nucleotides::from(a1), Nucleotides::from(a2))
-                    };
-
-                    let mut entry = Entry {
-                        sample_id: &dais_entry.sample_id,
-                        ref_strain: &ref_entry.isolate_name,
-                        gisaid_accession: &ref_entry.isolate_id,
-                        subtype: &dais_entry.subtype,
-                        dais_ref: &dais_entry.ref_strain,
-                        protein: &dais_entry.protein,
-                        ref_codon: "NNN".to_string(),
-                        mut_codon: "NNN".to_string(),
-                        aa_position: 0,
-                        aa_ref: 'X',
-                        aa_mut: 'X',
-                        phenotypic_consequences: String::new(),
-                    };
-
-                    let mut tail_index = 0;
-                    let (codons1, tail1) = aligned_1.as_codons();
-                    let (codons2, tail2) = aligned_2.as_codons();
-
-                    for (index, (ref_codon, query_codon)) in
-                        codons1.iter().zip(codons2.iter()).enumerate()
-                    {
-                        let aa_index = index + 1;
-                        tail_index = aa_index;
-                        let ref_aa = StdGeneticCode::translate_codon(ref_codon);
-                        let query_aa = StdGeneticCode::translate_codon(query_codon);
-
-                        entry.ref_codon = std::str::from_utf8(ref_codon)
-                            .expect("Invalid UTF-8 sequence")
-                            .to_string();
-                        entry.mut_codon = std::str::from_utf8(query_codon)
-                            .expect("Invalid UTF-8 sequence")
-                            .to_string();
-                        entry.aa_position = aa_index;
-                        entry.aa_ref = ref_aa as char;
-                        entry.aa_mut = query_aa as char;
-
-                        if entry.update_entry_from_alignment(
-                            &ref_entry.subtype,
-                            ref_aa,
-                            query_aa,
-                            &muts_interest,
-                        ) {
-                            let Entry {
-                                sample_id,
-                                ref_strain,
-                                gisaid_accession,
-                                subtype,
-                                dais_ref,
-                                protein,
-                                ref_codon,
-                                mut_codon,
-                                aa_ref,
-                                aa_position,
-                                aa_mut,
-                                phenotypic_consequences,
-                            } = &entry;
-                            let d = &delim;
-
-                            writeln!(
-                                &mut writer,
-                                "{sample_id}{d}{ref_strain}{d}{gisaid_accession}{d}\
-                                        {subtype}{d}{dais_ref}{d}{protein}{d}\
-                                        {ref_codon}{d}{mut_codon}{d}\
-                                        {aa_ref}:{aa_position}:{aa_mut}{d}\
-                                        {phenotypic_consequences}",
-                            )?;
-                        }
-                    }
-
-                    if !tail1.is_empty() {
-                        let partial_codon = b'~';
-                        entry.ref_codon = std::str::from_utf8(tail1)
-                            .expect("Invalid UTF-8 sequence")
-                            .to_string();
-                        entry.mut_codon = std::str::from_utf8(tail2)
-                            .expect("Invalid UTF-8 sequence")
-                            .to_string();
-                        entry.aa_position = tail_index + 1;
-                        entry.aa_ref = '~';
-                        entry.aa_mut = '~';
-                        if entry.update_entry_from_alignment(
-                            &ref_entry.subtype,
-                            partial_codon,
-                            partial_codon,
-                            &muts_interest,
-                        ) {
-                            let Entry {
-                                sample_id,
-                                ref_strain,
-                                gisaid_accession,
-                                subtype,
-                                dais_ref,
-                                protein,
-                                ref_codon,
-                                mut_codon,
-                                aa_ref,
-                                aa_position,
-                                aa_mut,
-                                phenotypic_consequences,
-                            } = &entry;
-                            let d = &delim;
-
-                            writeln!(
-                                &mut writer,
-                                "{sample_id}{d}{ref_strain}{d}{gisaid_accession}{d}\
-                                        {subtype}{d}{dais_ref}{d}{protein}{d}\
-                                        {ref_codon}{d}{mut_codon}{d}\
-                                        {aa_ref}:{aa_position}:{aa_mut}{d}\
-                                        {phenotypic_consequences}",
-                            )?;
-                        }
-                    }
+                        &minor_alleles,
+                        &positions,
+                        &delim,
+                        &mut minor_rows,
+                    )?;
                 }
             }
+            Ok((rows, minor_rows))
+        })
+        .collect::<std::io::Result<Vec<(Vec<u8>, Vec<u8>)>>>()?;
+    for (row, minor_row) in rows {
+        writer.write_all(&row)?;
+        if let Some(minor_writer) = &mut minor_writer {
+            minor_writer.write_all(&minor_row)?;
         }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(aa_ref: char, aa_mut: char) -> Entry<'static> {
+        Entry {
+            sample_id: "s1_4",
+            ref_strain: "A/Georgia/12/2022",
+            gisaid_accession: "EPI_ISL_15724408",
+            subtype: "A_NA_N1",
+            dais_ref: "CALI07",
+            protein: "NA",
+            ref_codon: "CAC".to_string(),
+            mut_codon: "---".to_string(),
+            aa_ref,
+            aa_position: 275,
+            aa_mut,
+            phenotypic_consequences: String::new(),
+        }
+    }
+
+    #[test]
+    fn status_of_each_amino_acid() {
+        assert_eq!(entry('H', 'H').status(), "wildtype");
+        assert_eq!(entry('H', 'Y').status(), "mutant");
+        assert_eq!(entry('H', '-').status(), "deletion");
+        assert_eq!(entry('H', 'X').status(), "missing");
+        assert_eq!(entry('~', '~').status(), "partial");
+    }
+}
@@ -1,38 +1,64 @@
 #![allow(dead_code, unused_imports)]
 use crate::io::coverage_json_per_sample::create_coverage_plot;
 use crate::io::coverage_to_heatmap::coverage_to_heatmap_json;
+use crate::io::create_aa_alignment_html::write_aa_alignment_html;
+use crate::io::create_badges::write_sample_badges;
 use crate::io::create_passfail_heatmap::create_passfail_heatmap;
+use crate::io::create_qc_gauge_panel::create_qc_gauge_panel;
 use crate::io::create_statichtml::generate_html_report;
 use crate::io::data_ingest::{all_alleles_data_collection, split_by_comma};
+use crate::io::flu_subtype_references::{FluSubtypeMap, read_flu_subtype_map};
 use crate::io::reads_to_piechart::create_barcode_distribution_figure;
 use crate::io::reads_to_sankey_json::reads_to_sankey_json;
-use crate::io::write_fasta_files::write_out_nextclade_fasta_files;
-use crate::io::write_parquet_files::write_samplesheet_to_parquet;
+use crate::io::sample_metadata::{add_sample_metadata, read_sample_metadata};
+use crate::io::segment_completeness::write_segment_completeness;
+use crate::io::timeline_json_per_sample::create_sample_timelines;
+use crate::io::virus_plugins::{load_virus_plugins, validate_virus, virus_plugin};
+use crate::io::watchlist_alerts::{print_alerts, read_alerts_json};
+use crate::io::write_fasta_files::{write_out_nextclade_fasta_files, write_to_fasta};
+use crate::io::write_parquet_files::{
+    ParquetColumns, parse_column_rename, write_samplesheet_to_parquet,
+};
+use crate::io::write_quarantine::{QuarantineMode, write_quarantine};
+use crate::io::write_xlsx_files::write_summary_xlsx;
+use crate::status;
+use crate::utils::cancel::install_signal_handler;
+use crate::utils::coordinates::{CoordinateSystem, convert_coverage, padding_offsets};
+use crate::utils::coverage_depths::{CoverageDepths, coverage_rows_depths};
 use crate::utils::data_processing::{
-    DaisVarsData, NextcladeSequences, ProcessedCoverage, Subtype, collect_analysis_metadata,
-    collect_negatives, collect_sample_id, compute_cvv_dais_variants, compute_dais_variants,
-    create_aa_seq_vec, create_irma_summary_vec, create_nt_seq_vec, create_vtype_data,
+    DaisVarsData, NextcladeSequences, ProcessedCoverage, Subtype, add_secondary_subtypes,
+    collect_analysis_metadata, collect_negatives, collect_sample_id, compute_consensus_changes,
+    compute_cvv_dais_variants, compute_dais_variants, compute_premature_stops,
+    compute_translation_qc, create_aa_seq_vec, create_irma_summary_vec, create_nt_seq_vec,
+    create_qc_criteria_vec, create_read_fate_vec, create_secondary_assembly_vec, create_vtype_data,
     divide_aa_into_pass_fail_vec, divide_nt_into_nextclade_vec, divide_nt_into_pass_fail_vec,
-    extract_field, extract_subtype_flu, extract_subtype_sc2, melt_reads_data,
-    process_position_coverage_data, process_wgs_coverage_data, return_seg_data,
-    transform_coverage_to_heatmap,
+    extract_field, extract_subtype_flu, extract_subtype_plugin, extract_subtype_sc2,
+    melt_reads_data, process_position_coverage_data, process_wgs_coverage_data, return_seg_data,
+    transform_coverage_to_heatmap, trim_nt_to_cds,
 };
+use crate::utils::units::{Depth, Percent};
 use crate::{
     io::{
         data_ingest::{
-            DaisSeqData, QCConfig, QCSettings, amended_consensus_data_collection,
+            DaisSeqData, PlotFormat, QCConfig, QCSettings, amended_consensus_data_collection,
             coverage_data_collection, create_reader, dais_ref_seq_data_collection,
             dais_sequence_data_collection, di_stat_data_collection, get_reference_lens,
-            indels_data_collection, minor_variant_data_collection, read_csv, read_yaml,
-            reads_data_collection, run_info_collection,
+            indels_data_collection, iteration_references_collection, minor_variant_data_collection,
+            nf_trace_data_collection, read_csv, read_plot_format_yaml, read_yaml,
+            reads_data_collection, run_info_collection, take_decode_warnings,
         },
-        write_csv_files::write_out_all_csv_mira_reports,
+        write_csv_files::{write_out_all_csv_mira_reports, write_structs_to_csv_file},
         write_fasta_files::write_out_all_consensus_fasta_files,
-        write_json_files::{negative_qc_statement, write_out_all_json_files},
+        write_json_files::{
+            build_run_status, negative_qc_statement, write_out_all_json_files,
+            write_run_status_json,
+        },
         write_parquet_files::{
             write_aa_seq_to_parquet, write_alleles_to_parquet, write_coverage_to_parquet,
             write_indels_to_parquet, write_irma_summary_to_parquet, write_minor_vars_to_parquet,
-            write_nt_seq_to_parquet, write_reads_to_parquet, write_run_info_to_parquet,
+            write_nt_seq_to_parquet, write_qc_criteria_to_parquet, write_read_fate_to_parquet,
+            write_reads_to_parquet, write_run_info_to_parquet,
+            write_secondary_assemblies_to_parquet,
         },
     },
     utils::data_processing::extract_subtype_rsv,
@@ -42,15 +68,18 @@ use csv::ReaderBuilder;
 use either::Either;
 use serde::{self, Deserialize, Serialize, de::DeserializeOwned};
 use serde_json::json;
+use std::collections::BTreeMap;
 use std::fs;
 use std::sync::Arc;
 use std::{
     error::Error,
-    fs::*,
+    fs::File,
     io::{BufRead, BufReader, BufWriter, Read, Stdin, Write, stdin, stdout},
     path::{Path, PathBuf},
 };
 
+// Every bool turns on one optional output or ingest step, and any mix of them can be asked for
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Debug, Parser)]
 #[command(about = "Package for aggregating MIRA outputs into json files")]
 pub struct ReportsArgs {
@@ -77,7 +106,7 @@ pub struct ReportsArgs {
 
     #[arg(short = 'v', long)]
     /// The virus the the data was generated from.
-    /// Options: flu, sc2-wgs, sc2-spike or rsv
+    /// Options: flu, sc2-wgs, sc2-spike, rsv or a virus from `--virus-config-dir`
     virus: String,
 
     #[arg(short = 'r', long)]
@@ -99,6 +128,108 @@ pub struct ReportsArgs {
     #[arg(short = 't', long, default_value = "")]
     /// (Optional) if a custom qc template is used for QC.
     qc_template: String,
+
+    #[arg(long)]
+    /// (Optional) Yaml file overriding plot hover templates and number formatting
+    /// (thousands/decimal separators and decimal places).
+    plot_format: Option<PathBuf>,
+
+    #[arg(short = 'a', long)]
+    /// (Optional) Protein(s) to export as a paged, color-coded amino acid alignment HTML
+    /// (e.g. HA1). Can be given more than once.
+    aa_viewer_protein: Vec<String>,
+
+    #[arg(long, default_value_t = 60)]
+    /// (Optional) Number of alignment positions shown per page of the amino acid alignment HTML.
+    aa_viewer_page_width: usize,
+
+    #[arg(long)]
+    /// (Optional) Write a machine-readable run status JSON (e.g. `run_status.json`) with the
+    /// overall run verdict, counts per QC status and paths to key outputs.
+    status_file: Option<PathBuf>,
+
+    #[arg(long)]
+    /// (Optional) Watchlist alerts JSON written by variants-of-interest --alerts-json. The alerts
+    /// are listed at the top of the HTML summary, in the run status file and on stderr.
+    alerts: Option<PathBuf>,
+
+    #[arg(long)]
+    /// (Optional) A flag to also write the summary as an Excel workbook with per-segment
+    /// coverage sparklines.
+    xlsx: bool,
+
+    #[arg(long)]
+    /// (Optional) A flag to also write passing consensus sequences trimmed to the CDS boundaries
+    /// found by DAIS-ribosome and to coverage, for database submission.
+    trim_to_cds: bool,
+
+    #[arg(long, default_value_t = 10, requires = "trim_to_cds")]
+    /// (Optional) With --trim-to-cds, the ends of the CDS with fewer reads than this are trimmed
+    /// too. Default is 10
+    trim_min_depth: u32,
+
+    #[arg(long)]
+    /// (Optional) A flag to also write the samples x segments completeness matrix as a heatmap
+    /// HTML. The CSV is always written.
+    completeness_heatmap: bool,
+
+    #[arg(long)]
+    /// (Optional) A flag to ingest IRMA's secondary assemblies (alt-matched references, e.g. B
+    /// reads in an A sample), write them to their own outputs and list the secondary subtypes
+    /// of every sample in the summary.
+    secondary_assemblies: bool,
+
+    #[arg(long)]
+    /// (Optional) A flag to ingest the references of IRMA's iterations and log the positions
+    /// where each segment's consensus changed between the last two. Segments are failed on it
+    /// when the QC yaml sets `max_consensus_iteration_changes`.
+    iteration_changes: bool,
+
+    #[arg(long, value_delimiter = ',', requires = "parq")]
+    /// (Optional) Comma separated columns to keep in the parquet files, in this order. Listed
+    /// columns a file doesn't have are skipped.
+    columns: Vec<String>,
+
+    #[arg(long, value_parser = parse_column_rename, requires = "parq")]
+    /// (Optional) Rename a parquet column, as `column=new_name`. Can be given more than once.
+    rename: Vec<(String, String)>,
+
+    #[arg(long, value_enum)]
+    /// (Optional) Move the failed consensus of samples with failed segments into a
+    /// `quarantine/` directory, with their IRMA amended consensus (copied or symlinked) and a
+    /// README.json of failure reasons, so submission automation can't pick them up.
+    quarantine: Option<QuarantineMode>,
+
+    #[arg(long)]
+    /// (Optional) A flag to show each segment's observed QC values next to their thresholds,
+    /// with the difference, in the HTML report.
+    qc_values: bool,
+
+    #[arg(long)]
+    /// (Optional) The Nextflow trace file of the run (`-with-trace`), to show each sample's
+    /// pipeline timeline with task durations on its coverage and sankey page.
+    nf_trace: Option<PathBuf>,
+
+    #[arg(long)]
+    /// (Optional) Directory of virus configs for pathogens MIRA doesn't support out of the
+    /// box. Each subdirectory with a segments.tsv, qc.yaml and optional subtypes.tsv becomes a
+    /// `--virus` option named after it.
+    virus_config_dir: Option<PathBuf>,
+
+    #[arg(long)]
+    /// (Optional) CSV (or TSV) of sample metadata with a `sample_id` column. Its other columns,
+    /// like county or age group, are added to the summary in every output.
+    metadata: Option<PathBuf>,
+
+    #[arg(long)]
+    /// (Optional) CSV (or TSV) mapping the DAIS reference IDs of flu HA and NA proteins to the
+    /// subtype they call, headed by a `# version:` line. Replaces the built-in mapping.
+    flu_subtype_map: Option<PathBuf>,
+
+    #[arg(long, value_enum)]
+    /// (Optional) Coordinates to report coverage in: raw (IRMA's consensus), padded (the padded
+    /// consensus of SARS-CoV-2 and RSV) or hmm (the reference). Default is IRMA's own coordinates.
+    coordinates: Option<CoordinateSystem>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -141,8 +272,10 @@ fn ensure_output_directory(output_path: &Path) -> std::io::Result<()> {
 #[allow(clippy::too_many_lines)]
 pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Error>> {
     ensure_output_directory(&args.output_path)?;
+    // Preempted runs stop before the next parquet file instead of leaving one half-written
+    install_signal_handler();
 
-    println!("Starting data ingestion...");
+    status!("Starting data ingestion...");
     /////////////// Read in and process data from IRMA and Dais ///////////////
     // Read in samplesheet
     let samplesheet_path = create_reader(&args.samplesheet)?;
@@ -166,13 +299,61 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
         Samplesheet::ONT(ref sheet) => collect_negatives(sheet),
     };
 
+    // Register the plugin viruses before anything reads the virus
+    if let Some(config_dir) = &args.virus_config_dir {
+        load_virus_plugins(config_dir)?;
+    }
+    validate_virus(&args.virus)?;
+    let plugin = virus_plugin(&args.virus);
+
+    // Read the metadata up front so a bad file fails before any work is done
+    let sample_metadata = args
+        .metadata
+        .as_deref()
+        .map(read_sample_metadata)
+        .transpose()?;
+
+    // Read the flu subtype references up front too, so a bad file fails early
+    let flu_subtype_map = match &args.flu_subtype_map {
+        Some(path) => read_flu_subtype_map(path)?,
+        None => FluSubtypeMap::default(),
+    };
+    if args.virus.to_lowercase() == "flu" {
+        status!(
+            " -> Flu subtype references version {}",
+            flu_subtype_map.version
+        );
+    }
+
     // Read in qc yaml
     let qc_yaml_path = create_reader(&args.qc_yaml)?;
     let qc_config: QCConfig = read_yaml(qc_yaml_path)?;
 
+    // Read in plot formatting overrides
+    let plot_format = match &args.plot_format {
+        Some(path) => read_plot_format_yaml(create_reader(path)?)?,
+        None => PlotFormat::default(),
+    };
+
     // Read in IRMA data
-    let coverage_data =
+    let mut coverage_data =
         coverage_data_collection(&args.irma_path, &args.platform, &args.runid, &args.virus)?;
+    // Depths on IRMA's consensus for --trim-to-cds, whatever coordinates the coverage is put in
+    let consensus_depths = if args.trim_to_cds {
+        coverage_rows_depths(&coverage_data)
+    } else {
+        CoverageDepths::new()
+    };
+    // Only put in other coordinates when asked, e.g. padded to line up with the padded consensus
+    if let Some(coordinates) = args.coordinates {
+        let padding = padding_offsets(&coverage_data);
+        let dropped = convert_coverage(&mut coverage_data, &padding, coordinates);
+        if dropped > 0 {
+            eprintln!(
+                "Warning: {dropped} coverage positions have no {coordinates} position, left out"
+            );
+        }
+    }
     let read_data = reads_data_collection(&args.irma_path, &args.platform, &args.runid)?;
     let vtype_data = create_vtype_data(&read_data);
     let minor_variant_data =
@@ -206,7 +387,38 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
     // In MIRA-NF the di_stats right before prepare-mira-report
     // and outputs are fed right to the working directory to be used in this step
     let di_stats_data = di_stat_data_collection("./")?;
-    println!("Finished ingesting data.");
+
+    // Read in the reference of every IRMA iteration if asked for
+    let iteration_refs = if args.iteration_changes {
+        iteration_references_collection(&args.irma_path)?
+    } else {
+        Vec::new()
+    };
+
+    // Read in the Nextflow trace if given
+    let nf_trace = match &args.nf_trace {
+        Some(trace_path) => nf_trace_data_collection(trace_path)?,
+        None => Vec::new(),
+    };
+
+    // Report any inputs that needed invalid bytes replaced rather than failing the run
+    let decode_warnings = take_decode_warnings();
+    let input_warnings_path = args
+        .output_path
+        .join(format!("mira_{}_input_warnings.csv", args.runid));
+    if !decode_warnings.is_empty() {
+        let mut writer = BufWriter::new(File::create(&input_warnings_path)?);
+        writeln!(writer, "file,invalid_utf8_sequences")?;
+        for (file, count) in &decode_warnings {
+            writeln!(writer, "{},{count}", file.display())?;
+        }
+        writer.flush()?;
+        status!(
+            "  -> input warnings saved to {:?}",
+            input_warnings_path.display()
+        );
+    }
+    status!("Finished ingesting data.");
 
     //////////////////////////////// Processing ingested IRMA and Dais data ////////////////////////////////
     // Calculate AA variants for aavars.csv and dais_vars.json
@@ -232,7 +444,8 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
     let mut calculated_cov_vec: Vec<ProcessedCoverage> = Vec::new();
     let mut calculated_position_cov_vec: Vec<ProcessedCoverage> = Vec::new();
 
-    if args.virus.to_lowercase() == "flu" || args.virus.to_lowercase() == "rsv" {
+    if args.virus.to_lowercase() == "flu" || args.virus.to_lowercase() == "rsv" || plugin.is_some()
+    {
         calculated_cov_vec = process_wgs_coverage_data(&coverage_data, &ref_lengths)?;
     } else if args.virus.to_lowercase() == "sc2-spike" {
         calculated_cov_vec = process_position_coverage_data(&coverage_data, 21563, 25384)?;
@@ -244,11 +457,13 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
     //Gather subtype information
     let mut subtype_data: Vec<Subtype> = Vec::new();
     if args.virus.to_lowercase() == "flu" {
-        subtype_data = extract_subtype_flu(&dais_vars_data, &calculated_cov_vec)?;
+        subtype_data = extract_subtype_flu(&dais_vars_data, &calculated_cov_vec, &flu_subtype_map)?;
     } else if args.virus.to_lowercase() == "sc2-wgs" || args.virus.to_lowercase() == "sc2-spike" {
         subtype_data = extract_subtype_sc2(&dais_vars_data)?;
     } else if args.virus.to_lowercase() == "rsv" {
         subtype_data = extract_subtype_rsv(&dais_vars_data)?;
+    } else if let Some(plugin) = plugin {
+        subtype_data = extract_subtype_plugin(plugin, &calculated_cov_vec)?;
     }
 
     //Gather Anlysis Metadata for irma_summary
@@ -273,17 +488,33 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
         &di_stats_data,
     )?;
 
+    // Secondary assemblies are only ingested when asked for, leaving the summary as it was
+    let secondary_assemblies = if args.secondary_assemblies {
+        let secondary = create_secondary_assembly_vec(&read_data);
+        add_secondary_subtypes(&mut irma_summary, &secondary);
+        secondary
+    } else {
+        Vec::new()
+    };
+
+    if let Some(table) = &sample_metadata {
+        add_sample_metadata(&mut irma_summary, table);
+    }
+
     let mut qc_values = QCSettings {
-        med_cov: 0,
+        med_cov: Depth(0),
         minor_vars: 0,
         stop_codon_restricted_proteins: String::new(),
-        perc_ref_covered: 0,
-        negative_control_perc: 0,
-        negative_control_perc_exception: 0,
+        perc_ref_covered: Percent(0.0),
+        negative_control_perc: Percent(0.0),
+        negative_control_perc_exception: Percent(0.0),
         positive_control_minimum: 0,
         padded_consensus: false,
         med_spike_cov: None,
         perc_ref_spike_covered: None,
+        translation_qc_fail: String::new(),
+        min_cds_perc_of_reference: None,
+        max_consensus_iteration_changes: None,
     };
     // Set qc values based on given virus and platform
     if args.virus.to_lowercase() == "flu" {
@@ -306,19 +537,60 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
         } else {
             qc_values = qc_config.ont_rsv;
         }
+    } else if let Some(plugin) = plugin {
+        qc_values = plugin.qc_settings(&args.platform).clone();
     }
 
     // Get proteins that can not have premature stop codons
     let no_premature_stop_codon_proteins =
         split_by_comma(&qc_values.stop_codon_restricted_proteins);
 
+    // Check the translation of every CDS, failing segments only for the flags the QC yaml lists
+    let translation_qc = compute_translation_qc(
+        &dais_seq_data,
+        &dais_ref_data,
+        &args.virus,
+        qc_values.min_cds_perc_of_reference.unwrap_or(Percent(90.0)),
+        &args.runid,
+        &args.platform,
+    );
+
+    // Where each premature stop truncates its protein, behind the stop codon QC
+    let premature_stops = compute_premature_stops(
+        &dais_vars_data,
+        &dais_ref_data,
+        &args.virus,
+        &no_premature_stop_codon_proteins,
+        &args.runid,
+        &args.platform,
+    );
+
+    // Positions where the consensus changed between the last two IRMA iterations
+    let consensus_changes = compute_consensus_changes(&iteration_refs, &args.runid, &args.platform);
+
     // Add pass fail information to irma summary
     for sample in &mut irma_summary {
         if sample.pass_fail_reason.is_none() {
-            sample.add_pass_fail_qc(&dais_vars_data, &args.virus, &qc_values)?;
+            sample.add_pass_fail_qc(
+                &dais_vars_data,
+                &translation_qc,
+                &consensus_changes,
+                &args.virus,
+                &qc_values,
+            )?;
         }
     }
 
+    // The same QC in long form, one row per segment and criterion
+    let qc_criteria = create_qc_criteria_vec(
+        &irma_summary,
+        &dais_vars_data,
+        &translation_qc,
+        &consensus_changes,
+        &args.virus,
+        &qc_values,
+    );
+
     // Construct seq info and add pass fail information
     let nt_seq_vec = create_nt_seq_vec(
         &seq_data,
@@ -363,9 +635,9 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
     let transformed_cov_data = transform_coverage_to_heatmap(&coverage_data, &args.virus);
 
     //////////////////////////////// Write all files ////////////////////////////////
-    println!("Writing Output Files...");
+    status!("Writing Output Files...");
 
-    println!("Writing FASTA files");
+    status!("Writing FASTA files");
     write_out_all_consensus_fasta_files(
         &args.output_path,
         &processed_nt_seq.passed_seqs,
@@ -375,10 +647,45 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
         &args.runid,
     )?;
 
+    if let Some(mode) = args.quarantine {
+        write_quarantine(
+            &args.output_path,
+            &args.irma_path,
+            &args.runid,
+            &processed_nt_seq.failed_seqs,
+            &processed_aa_seq.failed_seqs,
+            mode,
+        )?;
+    }
+
     // Write fasta inputs files
     write_out_nextclade_fasta_files(&args.output_path, &nextclade_nt_seq, &args.runid)?;
 
-    println!("Writing CSV files");
+    // Submission ready consensus without the low confidence UTR/primer ends
+    if args.trim_to_cds {
+        let trimmed_nt_seq = divide_nt_into_pass_fail_vec(
+            &trim_nt_to_cds(
+                &nt_seq_vec,
+                &dais_seq_data,
+                &consensus_depths,
+                args.trim_min_depth,
+                &args.virus,
+            ),
+            &args.platform,
+            &args.virus,
+            &no_premature_stop_codon_proteins,
+        )?;
+        write_to_fasta(
+            &format!(
+                "{}/mira_{}_amended_consensus_cds_trimmed.fasta",
+                args.output_path.display(),
+                args.runid
+            ),
+            &trimmed_nt_seq.passed_seqs,
+        )?;
+    }
+
+    status!("Writing CSV files");
     write_out_all_csv_mira_reports(
         &args.output_path,
         &coverage_data,
@@ -393,8 +700,141 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
         &args.runid,
         &args.virus,
     )?;
+    write_structs_to_csv_file(
+        &format!(
+            "{}/mira_{}_translation_qc.csv",
+            args.output_path.display(),
+            args.runid
+        ),
+        &translation_qc,
+        &[
+            "sample_id",
+            "reference",
+            "protein",
+            "cds_length",
+            "reference_cds_length",
+            "missing_start",
+            "internal_stop_count",
+            "length_not_multiple_of_3",
+            "short_cds",
+            "runid",
+            "instrument",
+        ],
+        &[
+            "sample_id",
+            "reference",
+            "protein",
+            "cds_length",
+            "reference_cds_length",
+            "missing_start",
+            "internal_stop_count",
+            "length_not_multiple_of_3",
+            "short_cds",
+            "runid",
+            "instrument",
+        ],
+    )?;
+    let premature_stop_columns = [
+        "sample_id",
+        "reference",
+        "protein",
+        "first_stop_position",
+        "stop_count",
+        "stops",
+        "reference_protein_length",
+        "percent_of_protein_retained",
+        "restricted",
+        "runid",
+        "instrument",
+    ];
+    write_structs_to_csv_file(
+        &format!(
+            "{}/mira_{}_premature_stops.csv",
+            args.output_path.display(),
+            args.runid
+        ),
+        &premature_stops,
+        &premature_stop_columns,
+        &premature_stop_columns,
+    )?;
+
+    let qc_criteria_columns = [
+        "sample_id",
+        "reference",
+        "criterion",
+        "observed_value",
+        "threshold",
+        "status",
+        "runid",
+        "instrument",
+    ];
+    write_structs_to_csv_file(
+        &format!(
+            "{}/mira_{}_qc_criteria.csv",
+            args.output_path.display(),
+            args.runid
+        ),
+        &qc_criteria,
+        &qc_criteria_columns,
+        &qc_criteria_columns,
+    )?;
 
-    println!("Writing JSON files");
+    if args.iteration_changes {
+        let consensus_change_columns = [
+            "sample_id",
+            "reference",
+            "from_round",
+            "to_round",
+            "position",
+            "from_base",
+            "to_base",
+            "runid",
+            "instrument",
+        ];
+        write_structs_to_csv_file(
+            &format!(
+                "{}/mira_{}_consensus_iteration_changes.csv",
+                args.output_path.display(),
+                args.runid
+            ),
+            &consensus_changes,
+            &consensus_change_columns,
+            &consensus_change_columns,
+        )?;
+    }
+
+    if args.secondary_assemblies {
+        let secondary_columns = [
+            "sample_id",
+            "reference",
+            "secondary_subtype",
+            "reads",
+            "percent_of_pass_qc",
+            "runid",
+            "instrument",
+        ];
+        write_structs_to_csv_file(
+            &format!(
+                "{}/mira_{}_secondary_assemblies.csv",
+                args.output_path.display(),
+                args.runid
+            ),
+            &secondary_assemblies,
+            &secondary_columns,
+            &secondary_columns,
+        )?;
+    }
+
+    write_segment_completeness(
+        &irma_summary,
+        &sample_list,
+        &args.virus,
+        &args.output_path,
+        &args.runid,
+        args.completeness_heatmap,
+    )?;
+
+    status!("Writing JSON files");
     write_out_all_json_files(
         &args.output_path,
         &coverage_data,
@@ -409,10 +849,24 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
         &args.virus,
     )?;
 
+    if args.xlsx {
+        status!("Writing XLSX files");
+        write_summary_xlsx(
+            &args.output_path,
+            &args.runid,
+            &irma_summary,
+            &coverage_data,
+        )?;
+    }
+
     // Write fields to parq if flag given
     // Why separate you ask? parquet set up is niche
     if args.parq {
-        println!("Writing PARQUET files");
+        status!("Writing PARQUET files");
+        let parquet_columns = ParquetColumns {
+            columns: args.columns.clone(),
+            rename: args.rename.clone(),
+        };
         write_coverage_to_parquet(
             &coverage_data,
             &format!(
@@ -420,6 +874,7 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
                 args.output_path.display(),
                 args.runid
             ),
+            &parquet_columns,
         )?;
         write_reads_to_parquet(
             &read_data,
@@ -428,6 +883,16 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
                 args.output_path.display(),
                 args.runid
             ),
+            &parquet_columns,
+        )?;
+        write_read_fate_to_parquet(
+            &create_read_fate_vec(&read_data),
+            &format!(
+                "{}/mira_{}_read_fate.parq",
+                args.output_path.display(),
+                args.runid
+            ),
+            &parquet_columns,
         )?;
         write_indels_to_parquet(
             &indel_data,
@@ -436,6 +901,7 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
                 args.output_path.display(),
                 args.runid
             ),
+            &parquet_columns,
         )?;
         write_minor_vars_to_parquet(
             &minor_variant_data.all_minor_variants,
@@ -444,6 +910,7 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
                 args.output_path.display(),
                 args.runid
             ),
+            &parquet_columns,
         )?;
         write_irma_summary_to_parquet(
             &irma_summary,
@@ -453,6 +920,7 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
                 args.output_path.display(),
                 args.runid
             ),
+            &parquet_columns,
         )?;
         write_nt_seq_to_parquet(
             &nt_seq_vec,
@@ -461,6 +929,7 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
                 args.output_path.display(),
                 args.runid
             ),
+            &parquet_columns,
         )?;
         write_aa_seq_to_parquet(
             &aa_seq_vec,
@@ -469,7 +938,28 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
                 args.output_path.display(),
                 args.runid
             ),
+            &parquet_columns,
+        )?;
+        write_qc_criteria_to_parquet(
+            &qc_criteria,
+            &format!(
+                "{}/mira_{}_qc_criteria.parq",
+                args.output_path.display(),
+                args.runid
+            ),
+            &parquet_columns,
         )?;
+        if args.secondary_assemblies {
+            write_secondary_assemblies_to_parquet(
+                &secondary_assemblies,
+                &format!(
+                    "{}/mira_{}_secondary_assemblies.parq",
+                    args.output_path.display(),
+                    args.runid
+                ),
+                &parquet_columns,
+            )?;
+        }
         write_run_info_to_parquet(
             &run_info,
             &format!(
@@ -477,6 +967,7 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
                 args.output_path.display(),
                 args.runid
             ),
+            &parquet_columns,
         )?;
         write_samplesheet_to_parquet(
             samplesheet,
@@ -485,6 +976,7 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
                 args.output_path.display(),
                 args.runid
             ),
+            &parquet_columns,
             &args.runid,
             &args.platform,
         )?;
@@ -500,6 +992,7 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
                 args.output_path.display(),
                 args.runid
             ),
+            &parquet_columns,
         )?;
     }
 
@@ -516,6 +1009,7 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
         &read_data,
         &args.virus,
         &format!("{}/", args.output_path.display()),
+        &plot_format,
     );
 
     let cov_heatmap_json = coverage_to_heatmap_json(
@@ -523,6 +1017,7 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
         &sample_list,
         &args.virus,
         &format!("{}/", args.output_path.display()),
+        &plot_format,
     );
 
     let pass_fail_heatmap_json = create_passfail_heatmap(
@@ -530,14 +1025,39 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
         &sample_list,
         &args.virus,
         &format!("{}/", args.output_path.display()),
+        &plot_format,
     );
 
-    let barcode_distribution_json =
-        create_barcode_distribution_figure(&read_data, &format!("{}/", args.output_path.display()));
+    let qc_gauge_panel_json = create_qc_gauge_panel(
+        &irma_summary,
+        &qc_values,
+        &format!("{}/", args.output_path.display()),
+        &plot_format,
+    );
+
+    let barcode_distribution_json = create_barcode_distribution_figure(
+        &read_data,
+        &format!("{}/", args.output_path.display()),
+        &plot_format,
+    );
+
+    let timeline_json_per_sample = if args.nf_trace.is_some() {
+        create_sample_timelines(&nf_trace, &sample_list)
+    } else {
+        Vec::new()
+    };
+
+    write_sample_badges(&irma_summary, &args.output_path)?;
+
+    let alerts = match &args.alerts {
+        Some(path) => read_alerts_json(path)?,
+        None => Vec::new(),
+    };
 
     //////////////////////////////// Create staticHTML ////////////////////////////////
     let _ = generate_html_report(
         &args.output_path,
+        &alerts,
         &irma_summary,
         &dais_vars_data,
         &minor_variant_data.all_minor_variants,
@@ -545,12 +1065,89 @@ pub fn prepare_mira_reports_process(args: &ReportsArgs) -> Result<(), Box<dyn Er
         &barcode_distribution_json,
         &pass_fail_heatmap_json,
         &cov_heatmap_json,
+        &qc_gauge_panel_json,
         &coverage_json_per_sample,
         &sankey_json_per_sample,
+        &timeline_json_per_sample,
+        args.qc_values.then_some(qc_criteria.as_slice()),
         &args.runid,
         Some(&args.workdir_path),
         &args.virus,
     );
 
+    for protein in &args.aa_viewer_protein {
+        write_aa_alignment_html(
+            &args.output_path,
+            &args.runid,
+            protein,
+            &dais_seq_data,
+            &dais_ref_data,
+            args.aa_viewer_page_width,
+        )?;
+    }
+
+    //////////////////////////////// Run status for workflow hooks ////////////////////////////////
+    if let Some(status_file) = &args.status_file {
+        let output_dir = args.output_path.display();
+        let runid = &args.runid;
+        let mut artifacts = BTreeMap::new();
+        artifacts.insert(
+            "summary_html".to_string(),
+            format!("{output_dir}/mira_{runid}_summary.html"),
+        );
+        artifacts.insert(
+            "summary_csv".to_string(),
+            format!("{output_dir}/mira_{runid}_summary.csv"),
+        );
+        artifacts.insert(
+            "irma_summary_json".to_string(),
+            format!("{output_dir}/irma_summary.json"),
+        );
+        artifacts.insert(
+            "pass_fail_qc_json".to_string(),
+            format!("{output_dir}/pass_fail_qc.json"),
+        );
+        artifacts.insert(
+            "qc_statement_json".to_string(),
+            format!("{output_dir}/qc_statement.json"),
+        );
+        if !decode_warnings.is_empty() {
+            artifacts.insert(
+                "input_warnings_csv".to_string(),
+                input_warnings_path.display().to_string(),
+            );
+        }
+        if args.quarantine.is_some() {
+            artifacts.insert(
+                "quarantine_dir".to_string(),
+                format!("{output_dir}/quarantine"),
+            );
+        }
+        if args.xlsx {
+            artifacts.insert(
+                "summary_xlsx".to_string(),
+                format!("{output_dir}/mira_{runid}_summary.xlsx"),
+            );
+        }
+        if args.parq {
+            artifacts.insert(
+                "summary_parquet".to_string(),
+                format!("{output_dir}/mira_{runid}_summary.parq"),
+            );
+        }
+
+        let run_status = build_run_status(
+            runid,
+            &irma_summary,
+            &read_data,
+            &neg_control_list,
+            artifacts,
+            alerts.clone(),
+        );
+        write_run_status_json(status_file, &run_status)?;
+    }
+
+    print_alerts(&alerts);
+
     Ok(())
 }
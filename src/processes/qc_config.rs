@@ -0,0 +1,150 @@
+use crate::status;
+use clap::{Args, Parser, Subcommand};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fs::{read_to_string, write},
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Parser)]
+#[command(about = "Tools for working with MIRA QC yaml configs")]
+pub struct QcConfigArgs {
+    #[command(subcommand)]
+    command: QcConfigCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum QcConfigCommand {
+    /// Compare the QC thresholds of two QC yaml configs, per platform/virus
+    Diff(QcConfigDiffArgs),
+}
+
+#[derive(Debug, Args)]
+struct QcConfigDiffArgs {
+    /// The original QC yaml
+    old: PathBuf,
+
+    /// The updated QC yaml
+    new: PathBuf,
+
+    #[arg(short = 'j', long)]
+    /// (Optional) Also write the diff as JSON to this file
+    json: Option<PathBuf>,
+}
+
+/// Each platform/virus section (e.g. `illumina-flu`) maps setting names to values
+type QcYaml = BTreeMap<String, BTreeMap<String, Value>>;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Change {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, Serialize)]
+struct SettingDiff {
+    section: String,
+    setting: String,
+    change: Change,
+    old: Option<Value>,
+    new: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct QcConfigDiff {
+    old_config: String,
+    new_config: String,
+    differences: Vec<SettingDiff>,
+}
+
+fn read_qc_yaml(path: &Path) -> Result<QcYaml, Box<dyn Error>> {
+    let contents = read_to_string(path)
+        .map_err(|e| format!("Could not open file '{}': {e}", path.display()))?;
+    let config = serde_yaml_ng::from_str(&contents)
+        .map_err(|e| format!("Could not parse QC yaml '{}': {e}", path.display()))?;
+    Ok(config)
+}
+
+// Settings of every section in either config, in sorted order. A section only present in one
+// config shows up as all of its settings added or removed.
+fn diff_qc_configs(old: &QcYaml, new: &QcYaml) -> Vec<SettingDiff> {
+    let empty = BTreeMap::new();
+    let sections: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+
+    let mut differences = Vec::new();
+    for section in sections {
+        let old_settings = old.get(section).unwrap_or(&empty);
+        let new_settings = new.get(section).unwrap_or(&empty);
+        let settings: BTreeSet<&String> = old_settings.keys().chain(new_settings.keys()).collect();
+
+        for setting in settings {
+            let old_value = old_settings.get(setting);
+            let new_value = new_settings.get(setting);
+            let change = match (old_value, new_value) {
+                (None, Some(_)) => Change::Added,
+                (Some(_), None) => Change::Removed,
+                (Some(o), Some(n)) if o != n => Change::Changed,
+                _ => continue,
+            };
+            differences.push(SettingDiff {
+                section: section.clone(),
+                setting: setting.clone(),
+                change,
+                old: old_value.cloned(),
+                new: new_value.cloned(),
+            });
+        }
+    }
+    differences
+}
+
+fn print_diff(diff: &QcConfigDiff) {
+    println!("QC config diff: {} -> {}", diff.old_config, diff.new_config);
+    if diff.differences.is_empty() {
+        println!("No differences");
+        return;
+    }
+
+    let mut current_section = "";
+    for d in &diff.differences {
+        if d.section != current_section {
+            println!("[{}]", d.section);
+            current_section = &d.section;
+        }
+        let show = |v: &Option<Value>| v.as_ref().map_or(String::new(), ToString::to_string);
+        match d.change {
+            Change::Added => println!("  + {}: {}", d.setting, show(&d.new)),
+            Change::Removed => println!("  - {}: {}", d.setting, show(&d.old)),
+            Change::Changed => {
+                println!("  ~ {}: {} -> {}", d.setting, show(&d.old), show(&d.new));
+            }
+        }
+    }
+}
+
+pub fn qc_config_process(args: &QcConfigArgs) -> Result<(), Box<dyn Error>> {
+    match &args.command {
+        QcConfigCommand::Diff(diff_args) => {
+            let old = read_qc_yaml(&diff_args.old)?;
+            let new = read_qc_yaml(&diff_args.new)?;
+
+            let diff = QcConfigDiff {
+                old_config: diff_args.old.display().to_string(),
+                new_config: diff_args.new.display().to_string(),
+                differences: diff_qc_configs(&old, &new),
+            };
+            print_diff(&diff);
+
+            if let Some(json_path) = &diff_args.json {
+                write(json_path, serde_json::to_string_pretty(&diff)?)?;
+                status!(" -> JSON written to {}", json_path.display());
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use crate::{
+    io::data_ingest::{create_reader, read_csv},
+    processes::summary_report_update::UpdatedIRMASummary,
+    utils::{
+        output::output_writer,
+        units::{Depth, Percent},
+    },
+};
+use clap::Parser;
+use std::{collections::HashSet, error::Error, io::Write, path::PathBuf};
+
+#[derive(Debug, Parser)]
+#[command(
+    about = "Re-evaluate QC pass/fail from an existing MIRA summary across a grid of thresholds, reporting how many samples change status"
+)]
+pub struct QcSweepArgs {
+    #[arg(short = 's', long)]
+    /// The `mira_<runid>_summary.csv` to re-evaluate
+    summary_csv: PathBuf,
+
+    #[arg(short = 'm', long, value_delimiter = ',', num_args = 1.., required = true)]
+    /// Median coverage thresholds to test, e.g. 20,50,100
+    med_cov: Vec<Depth>,
+
+    #[arg(short = 'p', long, value_delimiter = ',', num_args = 1.., required = true)]
+    /// Percent reference covered thresholds to test, e.g. 80,90,95
+    perc_ref_covered: Vec<Percent>,
+
+    #[arg(short = 'n', long, value_delimiter = ',', num_args = 1..)]
+    /// (Optional) Minor variant count thresholds to test. If not given, minor variant failures
+    /// from the original summary are kept as is
+    minor_vars: Vec<u32>,
+
+    #[arg(short = 'o', long)]
+    /// Optional output delimited file
+    output_xsv: Option<PathBuf>,
+
+    #[arg(short = 'd', long)]
+    /// Use the provider delimiter for separating fields. Default is ','
+    output_delimiter: Option<char>,
+}
+
+// Whether a summary row passes under the given thresholds. Failures that are not part of the
+// sweep (no assembly, premature stop codons and minor variants when not swept) are carried over.
+fn passes(
+    row: &UpdatedIRMASummary,
+    med_cov: Depth,
+    perc_ref: Percent,
+    minor_vars: Option<u32>,
+) -> bool {
+    let reason = row.pass_fail_reason.as_deref().unwrap_or("");
+    if reason == "No assembly" || reason.contains("Premature stop codon") {
+        return false;
+    }
+    let (Some(coverage), Some(median)) = (row.percent_reference_coverage, row.median_coverage)
+    else {
+        return false;
+    };
+    if coverage < perc_ref || median < med_cov {
+        return false;
+    }
+    match minor_vars {
+        Some(max_minor) => row
+            .count_minor_snv_at_or_over_5_pct
+            .is_none_or(|count| i64::from(count) <= i64::from(max_minor)),
+        None => !reason.contains("Count of minor variants"),
+    }
+}
+
+pub fn qc_sweep_process(args: &QcSweepArgs) -> Result<(), Box<dyn Error>> {
+    let delim = args.output_delimiter.unwrap_or(',');
+
+    let summary_path = create_reader(&args.summary_csv)?;
+    let summary_data: Vec<UpdatedIRMASummary> = read_csv(summary_path, true)?;
+
+    //output
+    let mut writer = output_writer(args.output_xsv.as_deref())?;
+
+    writeln!(
+        &mut writer,
+        "med_cov{delim}perc_ref_covered{delim}minor_vars{delim}records_pass{delim}records_fail{delim}records_flipped_to_pass{delim}records_flipped_to_fail{delim}samples_flipped"
+    )?;
+
+    let minor_var_grid: Vec<Option<u32>> = if args.minor_vars.is_empty() {
+        vec![None]
+    } else {
+        args.minor_vars.iter().copied().map(Some).collect()
+    };
+
+    for &med_cov in &args.med_cov {
+        for &perc_ref in &args.perc_ref_covered {
+            for &minor_vars in &minor_var_grid {
+                let mut records_pass = 0;
+                let mut records_fail = 0;
+                let mut to_pass = 0;
+                let mut to_fail = 0;
+                let mut samples_flipped = HashSet::new();
+
+                for row in &summary_data {
+                    let original = row.pass_fail_reason.as_deref() == Some("Pass");
+                    let now = passes(row, med_cov, perc_ref, minor_vars);
+                    if now {
+                        records_pass += 1;
+                    } else {
+                        records_fail += 1;
+                    }
+                    if now != original {
+                        if now {
+                            to_pass += 1;
+                        } else {
+                            to_fail += 1;
+                        }
+                        samples_flipped.insert(row.sample_id.clone().unwrap_or_default());
+                    }
+                }
+
+                let minor_vars = minor_vars.map_or("original".to_string(), |m| m.to_string());
+                writeln!(
+                    &mut writer,
+                    "{med_cov}{delim}{perc_ref}{delim}{minor_vars}{delim}{records_pass}{delim}{records_fail}{delim}{to_pass}{delim}{to_fail}{delim}{}",
+                    samples_flipped.len()
+                )?;
+            }
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
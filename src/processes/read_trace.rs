@@ -0,0 +1,104 @@
+use crate::utils::{bam_read::BamReader, output::output_writer};
+use clap::Parser;
+use std::{
+    io::{self, ErrorKind, Write},
+    path::PathBuf,
+};
+
+#[derive(Debug, Parser)]
+#[command(
+    about = "Debug tool for listing the reads and base calls supporting a single position in an IRMA assembly"
+)]
+pub struct ReadTraceArgs {
+    #[arg(short = 'i', long)]
+    /// The sample's IRMA output directory (containing the segment BAM files)
+    irma_dir: PathBuf,
+
+    #[arg(short = 's', long)]
+    /// Segment/reference name as used by IRMA, e.g. `A_HA_H3`
+    segment: String,
+
+    #[arg(short = 'p', long)]
+    /// 1-based position within the segment's reference
+    position: usize,
+
+    #[arg(short = 'o', long)]
+    /// Optional output delimited file
+    output_xsv: Option<PathBuf>,
+
+    #[arg(short = 'd', long)]
+    /// Use the provider delimiter for separating fields. Default is ','
+    output_delimiter: Option<char>,
+}
+
+pub fn read_trace_process(args: &ReadTraceArgs) -> Result<(), io::Error> {
+    let delim = args.output_delimiter.unwrap_or(',');
+
+    if args.position == 0 {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "Position is 1-based and must be greater than 0",
+        ));
+    }
+    let ref_pos = i32::try_from(args.position - 1)
+        .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "Position is too large"))?;
+
+    let bam_path = args.irma_dir.join(format!("{}.bam", args.segment));
+    if !bam_path.exists() {
+        return Err(io::Error::new(
+            ErrorKind::NotFound,
+            format!(
+                "No BAM found for {} at {}",
+                args.segment,
+                bam_path.display()
+            ),
+        ));
+    }
+
+    let mut reader = BamReader::from_path(&bam_path)?;
+    let ref_id = reader
+        .references
+        .iter()
+        .position(|r| *r == args.segment)
+        .and_then(|i| i32::try_from(i).ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::NotFound,
+                format!(
+                    "{} is not a reference in {}",
+                    args.segment,
+                    bam_path.display()
+                ),
+            )
+        })?;
+
+    //output
+    let mut writer = output_writer(args.output_xsv.as_deref())?;
+
+    writeln!(
+        &mut writer,
+        "read_name{delim}segment{delim}position{delim}base{delim}quality{delim}strand{delim}mapq"
+    )?;
+
+    while let Some(record) = reader.next_record()? {
+        if record.is_unmapped() || record.ref_id != ref_id {
+            continue;
+        }
+        // IRMA BAMs are coordinate sorted, so nothing past here can overlap the position
+        if record.pos > ref_pos {
+            break;
+        }
+        if let Some((base, qual)) = record.base_at(ref_pos) {
+            let strand = if record.is_reverse() { '-' } else { '+' };
+            let qual = qual.map_or(String::new(), |q| q.to_string());
+            writeln!(
+                &mut writer,
+                "{}{delim}{}{delim}{}{delim}{}{delim}{qual}{delim}{strand}{delim}{}",
+                record.read_name, args.segment, args.position, base as char, record.mapq
+            )?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use crate::processes::variants_of_interest::{DaisInput, MutsOfInterestInput, RefInput};
+use crate::status;
+use crate::utils::dais_tables::read_tsv;
+use clap::Parser;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    error::Error,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Parser)]
+#[command(
+    about = "Build or refresh the variants-of-interest reference panel from DAIS-ribosome aligned references"
+)]
+pub struct ReferencePanelArgs {
+    #[arg(short = 'a', long, num_args = 1.., required = true)]
+    /// DAIS-ribosome output (.seq) for the curated reference sequences
+    alignments: Vec<PathBuf>,
+
+    #[arg(short = 't', long)]
+    /// Reference metadata TSV with a header: `sequence_id` (the FASTA header given to DAIS),
+    /// `isolate_id`, `isolate_name`, subtype, `passage_history`
+    metadata: PathBuf,
+
+    #[arg(short = 'e', long)]
+    /// (Optional) Existing reference panel to refresh. Its rows are kept unless rebuilt here
+    existing: Option<PathBuf>,
+
+    #[arg(short = 'm', long)]
+    /// (Optional) Variants of interest file. Every subtype/protein pair in it must be covered by
+    /// the panel
+    muts_file: Option<PathBuf>,
+
+    #[arg(short = 'o', long)]
+    /// Output reference panel TSV
+    output: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReferenceMetadata {
+    sequence_id: String,
+    isolate_id: String,
+    isolate_name: String,
+    subtype: String,
+    #[serde(default)]
+    passage_history: String,
+}
+
+fn open_file(path: &Path) -> Result<File, Box<dyn Error>> {
+    File::open(path).map_err(|e| format!("Could not open file '{}': {e}", path.display()).into())
+}
+
+// Joins the DAIS rows of the references with their metadata
+fn panel_rows(
+    dais: &[DaisInput],
+    metadata: &[ReferenceMetadata],
+) -> Result<Vec<RefInput>, Box<dyn Error>> {
+    let by_sequence: HashMap<&str, &ReferenceMetadata> = metadata
+        .iter()
+        .map(|m| (m.sequence_id.as_str(), m))
+        .collect();
+
+    let mut missing = BTreeSet::new();
+    let mut rows = Vec::new();
+    for entry in dais {
+        let Some(meta) = by_sequence.get(entry.sample_id.as_str()) else {
+            missing.insert(entry.sample_id.as_str());
+            continue;
+        };
+        rows.push(RefInput {
+            isolate_id: meta.isolate_id.clone(),
+            isolate_name: meta.isolate_name.clone(),
+            subtype: meta.subtype.clone(),
+            passage_history: meta.passage_history.clone(),
+            nt_id: entry.cds_id.clone(),
+            ctype: entry.ctype.clone(),
+            reference_id: entry.ref_strain.clone(),
+            protein: entry.protein.clone(),
+            aa_aln: entry.query_aa_aln_seq.clone(),
+            cds_aln: entry.cds_aln.clone(),
+        });
+    }
+
+    if !missing.is_empty() {
+        return Err(format!(
+            "No metadata for aligned sequences: {}",
+            missing.into_iter().collect::<Vec<_>>().join(", ")
+        )
+        .into());
+    }
+    Ok(rows)
+}
+
+pub fn reference_panel_process(args: &ReferencePanelArgs) -> Result<(), Box<dyn Error>> {
+    let metadata: Vec<ReferenceMetadata> = read_tsv(open_file(&args.metadata)?, true)?;
+    let mut dais: Vec<DaisInput> = Vec::new();
+    for path in &args.alignments {
+        dais.extend(read_tsv::<DaisInput, _>(open_file(path)?, false)?);
+    }
+    let mut panel = panel_rows(&dais, &metadata)?;
+    let sequences: BTreeSet<&str> = dais.iter().map(|d| d.sample_id.as_str()).collect();
+    status!(
+        "Built {} reference panel rows from {} aligned sequences",
+        panel.len(),
+        sequences.len()
+    );
+
+    // Rebuilt isolate/ctype/protein rows replace those of the existing panel
+    if let Some(existing) = &args.existing {
+        let rebuilt: BTreeSet<(String, String, String)> = panel
+            .iter()
+            .map(|r| (r.isolate_id.clone(), r.ctype.clone(), r.protein.clone()))
+            .collect();
+        let old: Vec<RefInput> = read_tsv(open_file(existing)?, true)?;
+        let kept: Vec<RefInput> = old
+            .into_iter()
+            .filter(|r| {
+                !rebuilt.contains(&(r.isolate_id.clone(), r.ctype.clone(), r.protein.clone()))
+            })
+            .collect();
+        status!("Kept {} rows of {}", kept.len(), existing.display());
+        panel.extend(kept);
+    }
+    panel.sort_by(|a, b| {
+        (&a.subtype, &a.ctype, &a.protein, &a.isolate_id).cmp(&(
+            &b.subtype,
+            &b.ctype,
+            &b.protein,
+            &b.isolate_id,
+        ))
+    });
+
+    // variants-of-interest matches mutations to reference rows on subtype and protein
+    if let Some(muts_file) = &args.muts_file {
+        let muts: Vec<MutsOfInterestInput> = read_tsv(open_file(muts_file)?, false)?;
+        let covered: BTreeSet<(&str, &str)> = panel
+            .iter()
+            .map(|r| (r.subtype.as_str(), r.protein.as_str()))
+            .collect();
+        let missing: BTreeSet<String> = muts
+            .iter()
+            .filter(|m| !covered.contains(&(m.subtype.as_str(), m.protein.as_str())))
+            .map(|m| format!("{} {}", m.subtype, m.protein))
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!(
+                "The reference panel has no rows for these subtype/protein pairs of {}: {}",
+                muts_file.display(),
+                missing.into_iter().collect::<Vec<_>>().join(", ")
+            )
+            .into());
+        }
+    }
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b'\t')
+        .from_path(&args.output)?;
+    for row in &panel {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    status!(" -> TSV written to {}", args.output.display());
+    Ok(())
+}
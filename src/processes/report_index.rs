@@ -0,0 +1,181 @@
+#![allow(clippy::format_push_string)]
+use crate::status;
+use crate::utils::markup::escape_markup;
+use clap::Parser;
+use glob::glob;
+use std::{
+    collections::BTreeMap,
+    fs::write,
+    io::{self, ErrorKind},
+    path::PathBuf,
+};
+
+#[derive(Debug, Parser)]
+#[command(
+    about = "Write an index.html linking all of the plot HTML files in a MIRA output directory"
+)]
+pub struct ReportIndexArgs {
+    #[arg(short = 'i', long)]
+    /// Output directory containing previously generated plot HTML files
+    input_dir: PathBuf,
+
+    #[arg(short = 'o', long)]
+    /// (Optional) Path of the index to write. Default is `<input_dir>/index.html`
+    output: Option<PathBuf>,
+
+    #[arg(short = 'r', long)]
+    /// (Optional) The run id, used to tell run level plots apart from per-sample plots
+    runid: Option<String>,
+}
+
+// Per-sample coverage/sankey pages are named mira_{sample}_coverage.html
+fn sample_from_file_name(file_name: &str, runid: Option<&str>) -> Option<String> {
+    if let Some(runid) = runid
+        && file_name.starts_with(&format!("mira_{runid}_"))
+    {
+        return None;
+    }
+    file_name
+        .strip_prefix("mira_")?
+        .strip_suffix("_coverage.html")
+        .map(ToString::to_string)
+}
+
+// A scaled down live preview of the page, used as its thumbnail
+fn thumbnail_card(file_name: &str, label: &str) -> String {
+    let (file_name, label) = (escape_markup(file_name), escape_markup(label));
+    format!(
+        r#"<div class="card">
+    <a href="./{file_name}" target="_blank">
+        <div class="thumb"><iframe src="./{file_name}" loading="lazy" scrolling="no" tabindex="-1"></iframe></div>
+        {label}
+    </a>
+</div>
+"#
+    )
+}
+
+pub fn report_index_process(args: &ReportIndexArgs) -> io::Result<()> {
+    let pattern = format!("{}/*.html", args.input_dir.display());
+    let index_path = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input_dir.join("index.html"));
+
+    let mut run_plots: Vec<String> = Vec::new();
+    let mut sample_plots: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    let entries =
+        glob(&pattern).map_err(|e| io::Error::new(ErrorKind::InvalidInput, e.to_string()))?;
+    for path in entries.flatten() {
+        if path == index_path {
+            continue;
+        }
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if let Some(sample) = sample_from_file_name(file_name, args.runid.as_deref()) {
+            sample_plots
+                .entry(sample)
+                .or_default()
+                .push(file_name.to_string());
+        } else {
+            run_plots.push(file_name.to_string());
+        }
+    }
+    run_plots.sort();
+
+    if run_plots.is_empty() && sample_plots.is_empty() {
+        return Err(io::Error::new(
+            ErrorKind::NotFound,
+            format!("No HTML files found in {}", args.input_dir.display()),
+        ));
+    }
+
+    let mut run_html = String::new();
+    for file_name in &run_plots {
+        let label = file_name.trim_end_matches(".html");
+        run_html.push_str(&thumbnail_card(file_name, label));
+    }
+
+    let mut samples_html = String::new();
+    for (sample, files) in &sample_plots {
+        samples_html.push_str(&format!(
+            "<h3>{}</h3>\n<div class=\"grid\">\n",
+            escape_markup(sample)
+        ));
+        for file_name in files {
+            samples_html.push_str(&thumbnail_card(file_name, sample));
+        }
+        samples_html.push_str("</div>\n");
+    }
+
+    let title = args.runid.as_deref().map_or("MIRA Plots".to_string(), |r| {
+        format!("MIRA Plots | {}", escape_markup(r))
+    });
+
+    let html = format!(
+        r#"
+<html>
+<head>
+    <style>
+        body {{
+            font-family: Helvetica;
+            margin-bottom: 20px;
+            margin-left: 100px;
+            margin-right: 100px;
+        }}
+        h1, h2 {{
+            text-align: center;
+        }}
+        .grid {{
+            display: flex;
+            flex-wrap: wrap;
+            gap: 20px;
+            justify-content: center;
+        }}
+        .card {{
+            width: 240px;
+            text-align: center;
+            font-size: 14px;
+            word-break: break-word;
+        }}
+        .thumb {{
+            width: 240px;
+            height: 150px;
+            overflow: hidden;
+            border: 1px solid #b3d1ff;
+            border-radius: 8px;
+            margin-bottom: 6px;
+        }}
+        .thumb iframe {{
+            width: 1200px;
+            height: 750px;
+            border: 0;
+            transform: scale(0.2);
+            transform-origin: 0 0;
+            pointer-events: none;
+        }}
+    </style>
+    <title>{title}</title>
+</head>
+<body>
+    <h1>{title}</h1>
+    <hr>
+    <h2>Run Plots</h2>
+    <div class="grid">
+    {run_html}
+    </div>
+    <hr>
+    <h2>Sample Plots</h2>
+    {samples_html}
+</body>
+</html>
+"#
+    );
+
+    write(&index_path, html)?;
+    status!("  -> HTML index saved to {:?}", index_path.display());
+
+    Ok(())
+}
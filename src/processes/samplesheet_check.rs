@@ -1,10 +1,15 @@
 use clap::Parser;
 use csv::{Reader, Writer};
+use md5::Md5;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::{
     collections::{HashMap, HashSet},
-    io::{self},
-    path::PathBuf,
+    fmt::Write,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
     process,
 };
 
@@ -20,6 +25,15 @@ pub struct SamplesheetCheckArgs {
     /// Output CSV samplesheet
     #[arg(short = 'o', long)]
     pub file_out: PathBuf,
+
+    /// Optional md5sum or sha256sum manifest (`<checksum>  <file>` lines) to verify every
+    /// FASTQ in the samplesheet against before the output is written
+    #[arg(short = 'c', long)]
+    pub checksums: Option<PathBuf>,
+
+    /// Optional CSV report of the checksum verification, one row per FASTQ
+    #[arg(long, requires = "checksums")]
+    pub checksum_report: Option<PathBuf>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -116,6 +130,173 @@ impl RowChecker {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChecksumAlgorithm {
+    Md5,
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    fn name(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Md5 => "md5",
+            ChecksumAlgorithm::Sha256 => "sha256",
+        }
+    }
+}
+
+/// A line of the checksum manifest
+#[derive(Debug)]
+struct ManifestEntry {
+    file: String,
+    algorithm: ChecksumAlgorithm,
+    checksum: String,
+}
+
+/// A row of the checksum report
+#[derive(Debug, Serialize)]
+struct ChecksumRow {
+    file: String,
+    algorithm: &'static str,
+    expected: String,
+    observed: String,
+    status: &'static str,
+}
+
+/// Reads md5sum/sha256sum output. The algorithm of each line is told by its checksum length
+fn read_manifest(path: &Path) -> io::Result<Vec<ManifestEntry>> {
+    let mut entries = Vec::new();
+    for (idx, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Line {} of {} is not a `<checksum>  <file>` line.",
+                    idx + 1,
+                    path.display()
+                ),
+            )
+        };
+        let (checksum, file) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+        let algorithm = match checksum.len() {
+            32 => ChecksumAlgorithm::Md5,
+            64 => ChecksumAlgorithm::Sha256,
+            _ => return Err(invalid()),
+        };
+        if !checksum.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        entries.push(ManifestEntry {
+            // `*` marks binary mode in md5sum output
+            file: file.trim_start().trim_start_matches('*').to_string(),
+            algorithm,
+            checksum: checksum.to_ascii_lowercase(),
+        });
+    }
+    Ok(entries)
+}
+
+fn hex_digest<D: Digest>(path: &Path) -> io::Result<String> {
+    let mut reader = BufReader::with_capacity(1 << 20, File::open(path)?);
+    let mut hasher = D::new();
+    loop {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            break;
+        }
+        hasher.update(buffer);
+        let read = buffer.len();
+        reader.consume(read);
+    }
+    let digest = hasher.finalize();
+    let mut hex = String::with_capacity(digest.len() * 2);
+    for byte in &digest {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    Ok(hex)
+}
+
+/// The manifest entry of a FASTQ, by its path as written in the samplesheet or else by its file
+/// name, since manifests are usually written where the sequencer put the files
+fn find_entry<'a>(entries: &'a [ManifestEntry], file: &str) -> Option<&'a ManifestEntry> {
+    let file_name = |path: &str| Path::new(path).file_name().map(ToOwned::to_owned);
+    entries.iter().find(|entry| entry.file == file).or_else(|| {
+        entries
+            .iter()
+            .find(|entry| file_name(&entry.file) == file_name(file))
+    })
+}
+
+fn verify_file(entries: &[ManifestEntry], file: &str) -> ChecksumRow {
+    let Some(entry) = find_entry(entries, file) else {
+        return ChecksumRow {
+            file: file.to_string(),
+            algorithm: "",
+            expected: String::new(),
+            observed: String::new(),
+            status: "not_in_manifest",
+        };
+    };
+    let observed = match entry.algorithm {
+        ChecksumAlgorithm::Md5 => hex_digest::<Md5>(Path::new(file)),
+        ChecksumAlgorithm::Sha256 => hex_digest::<Sha256>(Path::new(file)),
+    };
+    let (observed, status) = match observed {
+        Ok(observed) if observed == entry.checksum => (observed, "ok"),
+        Ok(observed) => (observed, "mismatch"),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (String::new(), "missing"),
+        Err(e) => (e.to_string(), "unreadable"),
+    };
+    ChecksumRow {
+        file: file.to_string(),
+        algorithm: entry.algorithm.name(),
+        expected: entry.checksum.clone(),
+        observed,
+        status,
+    }
+}
+
+/// Checksums every FASTQ in parallel and compares them to the manifest. Every file is checked
+/// so the report is complete, then the run fails if any of them didn't match
+fn verify_checksums(files: &[String], manifest: &Path, report: Option<&Path>) -> io::Result<()> {
+    let entries = read_manifest(manifest)?;
+    let rows: Vec<ChecksumRow> = files
+        .par_iter()
+        .map(|file| verify_file(&entries, file))
+        .collect();
+
+    if let Some(report) = report {
+        let mut wtr = Writer::from_path(report)?;
+        for row in &rows {
+            wtr.serialize(row)?;
+        }
+        wtr.flush()?;
+    }
+
+    let failed: Vec<&ChecksumRow> = rows.iter().filter(|row| row.status != "ok").collect();
+    for row in &failed {
+        eprintln!("{}: {}", row.status, row.file);
+    }
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} of {} FASTQ files failed checksum verification against {}.",
+                failed.len(),
+                rows.len(),
+                manifest.display()
+            ),
+        ))
+    }
+}
+
 fn suffixes(path: &str) -> Vec<&str> {
     let parts: Vec<&str> = path.split('.').collect();
     if parts.len() >= 3 {
@@ -140,9 +321,9 @@ pub fn samplesheet_check(args: &SamplesheetCheckArgs) -> io::Result<()> {
     }
 
     let mut rdr = Reader::from_path(&args.file_in)?;
-    let mut wtr = Writer::from_path(&args.file_out)?;
 
     let mut checker = RowChecker::new();
+    let mut outputs = Vec::new();
 
     for (idx, result) in rdr.deserialize().enumerate() {
         let row: InputRow = result.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
@@ -151,9 +332,26 @@ pub fn samplesheet_check(args: &SamplesheetCheckArgs) -> io::Result<()> {
             .validate_and_transform(row, idx + 2)
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
-        wtr.serialize(output)?;
+        outputs.push(output);
     }
 
+    // Corrupted transfers are caught before anything downstream reads the samplesheet
+    if let Some(manifest) = &args.checksums {
+        let mut files: Vec<String> = outputs
+            .iter()
+            .flat_map(|output| [&output.fastq_1, &output.fastq_2])
+            .filter(|file| !file.is_empty())
+            .cloned()
+            .collect();
+        files.sort_unstable();
+        files.dedup();
+        verify_checksums(&files, manifest, args.checksum_report.as_deref())?;
+    }
+
+    let mut wtr = Writer::from_path(&args.file_out)?;
+    for output in outputs {
+        wtr.serialize(output)?;
+    }
     wtr.flush()?;
     Ok(())
 }
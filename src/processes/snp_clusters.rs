@@ -0,0 +1,244 @@
+use crate::status;
+use crate::utils::output::output_writer;
+use clap::Parser;
+use either::Either;
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, Write, stdin},
+    path::PathBuf,
+};
+
+#[derive(Debug, Parser)]
+#[command(
+    about = "Cluster samples whose pairwise SNP distance is within a threshold, from a hamming distance matrix"
+)]
+pub struct SnpClustersArgs {
+    #[arg(short = 'i', long)]
+    /// Distance matrix written by `hamming`, in any of its layouts. Defaults to stdin
+    input_matrix: Option<PathBuf>,
+
+    #[arg(short = 't', long)]
+    /// Samples at this distance or closer are linked, and linked samples share a cluster
+    threshold: f64,
+
+    #[arg(short = 's', long)]
+    /// Delimiter of the input matrix. Default is ','. Ignored for PHYLIP matrices
+    input_delimiter: Option<char>,
+
+    #[arg(short = 'o', long)]
+    /// Optional output delimited file. Defaults to stdout
+    output_xsv: Option<PathBuf>,
+
+    #[arg(short = 'd', long)]
+    /// Use the provider delimiter for separating fields. Default is ','
+    output_delimiter: Option<char>,
+}
+
+/// Sample names and the distances between them. Pairs missing from the input are `None`
+#[derive(Debug)]
+struct Distances {
+    names: Vec<String>,
+    matrix: Vec<Vec<Option<f64>>>,
+}
+
+impl Distances {
+    fn new(names: Vec<String>) -> Self {
+        let n = names.len();
+        Distances {
+            names,
+            matrix: vec![vec![None; n]; n],
+        }
+    }
+
+    fn set(&mut self, r: usize, c: usize, distance: f64) {
+        self.matrix[r][c] = Some(distance);
+        self.matrix[c][r] = Some(distance);
+    }
+}
+
+/// The cluster a sample was placed in
+#[derive(Debug)]
+struct ClusterAssignment {
+    sample: String,
+    cluster_id: usize,
+    cluster_size: usize,
+    /// Largest distance between two samples of the cluster, which can be above the threshold
+    /// when the cluster is a chain of links
+    max_intra_cluster_distance: f64,
+}
+
+fn invalid_input(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
+
+fn parse_distance(value: &str) -> Result<f64, std::io::Error> {
+    value
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| invalid_input(format!("Could not read the distance '{value}'")))
+}
+
+/// Reads the square, lower, PHYLIP and long layouts of the hamming tool
+fn read_distances(lines: &[String], delim: char) -> Result<Distances, std::io::Error> {
+    let Some(header) = lines.first() else {
+        return Err(invalid_input("The distance matrix is empty".to_string()));
+    };
+
+    // PHYLIP: a sample count, then whitespace separated rows
+    if header.trim().parse::<usize>().is_ok() {
+        let rows: Vec<Vec<&str>> = lines[1..]
+            .iter()
+            .map(|line| line.split_whitespace().collect())
+            .collect();
+        let mut distances = Distances::new(rows.iter().map(|row| row[0].to_string()).collect());
+        for (r, row) in rows.iter().enumerate() {
+            for (c, value) in row[1..].iter().enumerate().take(r) {
+                distances.set(r, c, parse_distance(value)?);
+            }
+        }
+        return Ok(distances);
+    }
+
+    // Long: one pair per row
+    let header_fields: Vec<&str> = header.split(delim).collect();
+    if header_fields.first() == Some(&"sequence_1") {
+        let mut names = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut pairs = Vec::new();
+        for line in &lines[1..] {
+            let fields: Vec<&str> = line.split(delim).collect();
+            if fields.len() < 3 {
+                return Err(invalid_input(format!(
+                    "Expected 3 or more fields in '{line}'"
+                )));
+            }
+            let mut sample_index = |name: &str| {
+                *index.entry(name.to_string()).or_insert_with(|| {
+                    names.push(name.to_string());
+                    names.len() - 1
+                })
+            };
+            let r = sample_index(fields[0]);
+            let c = sample_index(fields[1]);
+            // With p-distances the last column is the p-distance, otherwise the count
+            pairs.push((r, c, parse_distance(fields[fields.len() - 1])?));
+        }
+        let mut distances = Distances::new(names);
+        for (r, c, distance) in pairs {
+            distances.set(r, c, distance);
+        }
+        return Ok(distances);
+    }
+
+    // Square or lower: a header row of names, then a row per sample. Only the cells below the
+    // diagonal are read, which both layouts have
+    let mut distances =
+        Distances::new(header_fields[1..].iter().map(ToString::to_string).collect());
+    for (r, line) in lines[1..].iter().enumerate() {
+        for (c, value) in line.split(delim).skip(1).enumerate().take(r) {
+            distances.set(r, c, parse_distance(value)?);
+        }
+    }
+    Ok(distances)
+}
+
+fn find_root(parents: &mut [usize], mut sample: usize) -> usize {
+    while parents[sample] != sample {
+        parents[sample] = parents[parents[sample]];
+        sample = parents[sample];
+    }
+    sample
+}
+
+/// Single linkage clusters: the connected components of the graph linking every pair of
+/// samples within the threshold. Clusters are numbered from 1 by size, largest first, and
+/// then by the first sample in the input.
+fn snp_clusters(distances: &Distances, threshold: f64) -> Vec<ClusterAssignment> {
+    let n = distances.names.len();
+    let mut parents: Vec<usize> = (0..n).collect();
+    for r in 0..n {
+        for c in r + 1..n {
+            if distances.matrix[r][c].is_some_and(|d| d <= threshold) {
+                let root_r = find_root(&mut parents, r);
+                let root_c = find_root(&mut parents, c);
+                parents[root_c] = root_r;
+            }
+        }
+    }
+
+    let mut members: Vec<Vec<usize>> = Vec::new();
+    let mut cluster_of_root: HashMap<usize, usize> = HashMap::new();
+    for sample in 0..n {
+        let root = find_root(&mut parents, sample);
+        let cluster = *cluster_of_root.entry(root).or_insert_with(|| {
+            members.push(Vec::new());
+            members.len() - 1
+        });
+        members[cluster].push(sample);
+    }
+    // Stable, so equal sizes keep the input order
+    members.sort_by_key(|samples| Reverse(samples.len()));
+
+    let mut assignments = Vec::with_capacity(n);
+    for (cluster, samples) in members.iter().enumerate() {
+        let max_intra_cluster_distance = samples
+            .iter()
+            .flat_map(|&r| samples.iter().filter_map(move |&c| distances.matrix[r][c]))
+            .fold(0.0, f64::max);
+        for &sample in samples {
+            assignments.push(ClusterAssignment {
+                sample: distances.names[sample].clone(),
+                cluster_id: cluster + 1,
+                cluster_size: samples.len(),
+                max_intra_cluster_distance,
+            });
+        }
+    }
+    assignments
+}
+
+pub fn snp_clusters_process(args: &SnpClustersArgs) -> Result<(), std::io::Error> {
+    let in_delim = args.input_delimiter.unwrap_or(',');
+    let delim = args.output_delimiter.unwrap_or(',');
+
+    let reader = if let Some(ref file_path) = args.input_matrix {
+        BufReader::new(Either::Left(File::open(file_path)?))
+    } else {
+        BufReader::new(Either::Right(stdin()))
+    };
+    let lines = reader
+        .lines()
+        .filter(|line| !line.as_ref().is_ok_and(|l| l.trim().is_empty()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let distances = read_distances(&lines, in_delim)?;
+    let assignments = snp_clusters(&distances, args.threshold);
+
+    let mut writer = output_writer(args.output_xsv.as_deref())?;
+    writeln!(
+        &mut writer,
+        "sample{delim}cluster_id{delim}cluster_size{delim}max_intra_cluster_distance"
+    )?;
+    for assignment in &assignments {
+        writeln!(
+            &mut writer,
+            "{}{delim}{}{delim}{}{delim}{}",
+            assignment.sample,
+            assignment.cluster_id,
+            assignment.cluster_size,
+            assignment.max_intra_cluster_distance
+        )?;
+    }
+    writer.flush()?;
+
+    let clustered = assignments.iter().filter(|a| a.cluster_size > 1).count();
+    status!(
+        "{clustered} of {} samples are within {} of another sample",
+        assignments.len(),
+        args.threshold
+    );
+
+    Ok(())
+}
@@ -1,4 +1,5 @@
 #![allow(dead_code, unused_imports)]
+use crate::status;
 use std::{
     collections::HashMap,
     error::Error,
@@ -22,9 +23,14 @@ use crate::{
         reads_to_sankey_json::SampleSankeyJson,
         write_csv_files::write_out_updated_summary_csv,
         write_json_files::{write_out_updated_json_files, write_structs_to_split_json_file},
-        write_parquet_files::write_updated_irma_summary_to_parquet,
+        write_parquet_files::{
+            ParquetColumns, parse_column_rename, write_updated_irma_summary_to_parquet,
+        },
+    },
+    utils::{
+        data_processing::{DaisVarsData, IRMASummary},
+        units::{Depth, Percent},
     },
-    utils::data_processing::{DaisVarsData, IRMASummary},
 };
 
 #[derive(Debug, Parser)]
@@ -59,6 +65,14 @@ pub struct SummaryUpdateArgs {
 
     #[arg(short = 'f', long)]
     parq: bool,
+
+    /// Comma separated columns to keep in the parquet summary, in this order
+    #[arg(long, value_delimiter = ',', requires = "parq")]
+    columns: Vec<String>,
+
+    /// Rename a parquet column, as `column=new_name`. Can be given more than once
+    #[arg(long, value_parser = parse_column_rename, requires = "parq")]
+    rename: Vec<(String, String)>,
 }
 
 #[derive(Debug, Clone)]
@@ -96,11 +110,11 @@ pub struct UpdatedIRMASummary {
     pub pass_qc: Option<i32>,
     pub reads_mapped: Option<i32>,
     pub reference: Option<String>,
-    pub percent_reference_coverage: Option<f64>,
-    pub median_coverage: Option<i32>,
+    pub percent_reference_coverage: Option<Percent>,
+    pub median_coverage: Option<Depth>,
     pub count_minor_snv_at_or_over_5_pct: Option<i32>,
-    pub spike_percent_coverage: Option<f64>,
-    pub spike_median_coverage: Option<i32>,
+    pub spike_percent_coverage: Option<Percent>,
+    pub spike_median_coverage: Option<Depth>,
     #[serde(rename = "di_5prime;di_3prime")]
     pub di_ratios_5prime_3prime: Option<String>,
     pub pass_fail_reason: Option<String>,
@@ -125,14 +139,14 @@ fn normalize_nextclade_field(field: &mut Option<String>) {
 
 #[allow(clippy::too_many_lines)]
 pub fn summary_report_update_process(args: &SummaryUpdateArgs) -> Result<(), Box<dyn Error>> {
-    println!("Starting data ingestion...");
+    status!("Starting data ingestion...");
     let summary_path = create_reader(&args.summary_csv)?;
     let mut summary_data: Vec<UpdatedIRMASummary> = read_csv(summary_path, true)?;
 
     let nextclade_data: Vec<NextcladeData> =
         nextclade_data_collection(&args.nextclade_path, &args.virus)?;
 
-    println!("Finished ingesting data.");
+    status!("Finished ingesting data.");
 
     let nextclade_map: HashMap<String, NextcladeData> = nextclade_data
         .into_iter()
@@ -282,7 +296,7 @@ pub fn summary_report_update_process(args: &SummaryUpdateArgs) -> Result<(), Box
     // Write CSV and PARQUET outputs
     write_out_updated_summary_csv(&summary_data, &args.virus, &args.runid, &args.output_path)?;
     if args.parq {
-        println!("Writing PARQUET files");
+        status!("Writing PARQUET files");
         write_updated_irma_summary_to_parquet(
             &summary_data,
             &args.virus,
@@ -291,6 +305,10 @@ pub fn summary_report_update_process(args: &SummaryUpdateArgs) -> Result<(), Box
                 args.output_path.display(),
                 args.runid
             ),
+            &ParquetColumns {
+                columns: args.columns.clone(),
+                rename: args.rename.clone(),
+            },
         )?;
     }
 
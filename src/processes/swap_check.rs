@@ -0,0 +1,195 @@
+use crate::utils::output::output_writer;
+use clap::Parser;
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufReader, Write},
+    path::{Path, PathBuf},
+};
+use zoe::{data::fasta::FastaNT, distance::dna::NucleotidesDistance, prelude::*};
+
+#[derive(Debug, Parser)]
+#[command(
+    about = "Flag possible sample swaps where a sample's nearest HA neighbor and nearest NA neighbor disagree"
+)]
+pub struct SwapCheckArgs {
+    #[arg(short = 'a', long)]
+    /// Aligned HA FASTA of the run. Headers are `<sample>` or `<sample> | <reference>`
+    ha_fasta: PathBuf,
+
+    #[arg(short = 'n', long)]
+    /// Aligned NA FASTA of the run, with the same header format
+    na_fasta: PathBuf,
+
+    #[arg(short = 'm', long, default_value_t = 10)]
+    /// How many more differences than the nearest neighbor, in both segments, the other
+    /// segment's nearest neighbor must have for the sample to be flagged
+    min_difference: usize,
+
+    #[arg(short = 'o', long)]
+    /// Optional output delimited file. Defaults to stdout
+    output_xsv: Option<PathBuf>,
+
+    #[arg(short = 'd', long)]
+    /// Use the provider delimiter for separating fields. Default is ','
+    output_delimiter: Option<char>,
+}
+
+/// Nearest neighbors of one sample in each segment and the cross distances supporting the call
+#[derive(Debug)]
+struct SwapCheck {
+    sample: String,
+    ha_neighbor: String,
+    ha_distance: usize,
+    /// HA distance to the sample's nearest NA neighbor
+    ha_distance_to_na_neighbor: usize,
+    na_neighbor: String,
+    na_distance: usize,
+    /// NA distance to the sample's nearest HA neighbor
+    na_distance_to_ha_neighbor: usize,
+    swap_suspect: bool,
+}
+
+// The sample ID of a MIRA consensus header, `<sample> | <reference>`
+fn sample_id(header: &str) -> &str {
+    header.split(" | ").next().unwrap_or(header).trim()
+}
+
+fn read_segment(path: &Path) -> Result<Vec<(String, Nucleotides)>, std::io::Error> {
+    let file = File::open(path).map_err(|e| {
+        std::io::Error::new(
+            e.kind(),
+            format!("Could not open file '{}': {e}", path.display()),
+        )
+    })?;
+    let mut seen = HashSet::new();
+    let mut sequences = Vec::new();
+    for record in FastaReader::new(BufReader::new(file)) {
+        let FastaNT { name, sequence } = record?.recode_to_dna();
+        let sample = sample_id(&name).to_string();
+        if seen.insert(sample.clone()) {
+            sequences.push((sample, sequence));
+        } else {
+            eprintln!(
+                "Warning: {sample} appears more than once in {}, keeping the first sequence",
+                path.display()
+            );
+        }
+    }
+    Ok(sequences)
+}
+
+// All pairwise Hamming distances
+fn distance_matrix(sequences: &[&Nucleotides]) -> Vec<Vec<usize>> {
+    let n = sequences.len();
+    let mut matrix = vec![vec![0; n]; n];
+    for (r, seq_r) in sequences.iter().enumerate() {
+        for (c, seq_c) in sequences.iter().enumerate().skip(r + 1) {
+            let distance = seq_r.distance_hamming(*seq_c);
+            matrix[r][c] = distance;
+            matrix[c][r] = distance;
+        }
+    }
+    matrix
+}
+
+// Index of the closest other sample. Ties go to the first sample in the file
+fn nearest_neighbor(distances: &[usize], sample: usize) -> Option<usize> {
+    distances
+        .iter()
+        .enumerate()
+        .filter(|&(other, _)| other != sample)
+        .min_by_key(|&(_, distance)| *distance)
+        .map(|(other, _)| other)
+}
+
+fn check_swaps(
+    ha: &[(String, Nucleotides)],
+    na: &[(String, Nucleotides)],
+    min_difference: usize,
+) -> Vec<SwapCheck> {
+    // Only samples with both segments can be compared, in the order of the HA file
+    let samples: Vec<(&str, &Nucleotides, &Nucleotides)> = ha
+        .iter()
+        .filter_map(|(sample, ha_seq)| {
+            na.iter()
+                .find(|(other, _)| other == sample)
+                .map(|(_, na_seq)| (sample.as_str(), ha_seq, na_seq))
+        })
+        .collect();
+    let ha_matrix = distance_matrix(&samples.iter().map(|s| s.1).collect::<Vec<_>>());
+    let na_matrix = distance_matrix(&samples.iter().map(|s| s.2).collect::<Vec<_>>());
+
+    let mut checks = Vec::with_capacity(samples.len());
+    for (i, (sample, _, _)) in samples.iter().enumerate() {
+        let (Some(ha_nn), Some(na_nn)) = (
+            nearest_neighbor(&ha_matrix[i], i),
+            nearest_neighbor(&na_matrix[i], i),
+        ) else {
+            continue;
+        };
+        let ha_distance = ha_matrix[i][ha_nn];
+        let na_distance = na_matrix[i][na_nn];
+        let ha_distance_to_na_neighbor = ha_matrix[i][na_nn];
+        let na_distance_to_ha_neighbor = na_matrix[i][ha_nn];
+        checks.push(SwapCheck {
+            sample: (*sample).to_string(),
+            ha_neighbor: samples[ha_nn].0.to_string(),
+            ha_distance,
+            ha_distance_to_na_neighbor,
+            na_neighbor: samples[na_nn].0.to_string(),
+            na_distance,
+            na_distance_to_ha_neighbor,
+            swap_suspect: ha_nn != na_nn
+                && ha_distance_to_na_neighbor >= ha_distance + min_difference
+                && na_distance_to_ha_neighbor >= na_distance + min_difference,
+        });
+    }
+    checks
+}
+
+pub fn swap_check_process(args: &SwapCheckArgs) -> Result<(), std::io::Error> {
+    let delim = args.output_delimiter.unwrap_or(',');
+
+    let ha = read_segment(&args.ha_fasta)?;
+    let na = read_segment(&args.na_fasta)?;
+    let checks = check_swaps(&ha, &na, args.min_difference);
+    if checks.is_empty() {
+        eprintln!("Warning: fewer than two samples have both an HA and an NA sequence");
+    }
+
+    let mut writer = output_writer(args.output_xsv.as_deref())?;
+
+    writeln!(
+        &mut writer,
+        "sample{delim}ha_neighbor{delim}ha_distance{delim}ha_distance_to_na_neighbor{delim}na_neighbor{delim}na_distance{delim}na_distance_to_ha_neighbor{delim}swap_suspect"
+    )?;
+    for check in &checks {
+        writeln!(
+            &mut writer,
+            "{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}",
+            check.sample,
+            check.ha_neighbor,
+            check.ha_distance,
+            check.ha_distance_to_na_neighbor,
+            check.na_neighbor,
+            check.na_distance,
+            check.na_distance_to_ha_neighbor,
+            check.swap_suspect
+        )?;
+    }
+    writer.flush()?;
+
+    for check in checks.iter().filter(|c| c.swap_suspect) {
+        eprintln!(
+            "Possible swap: {} is closest to {} by HA ({} nt) but to {} by NA ({} nt)",
+            check.sample,
+            check.ha_neighbor,
+            check.ha_distance,
+            check.na_neighbor,
+            check.na_distance
+        );
+    }
+
+    Ok(())
+}
@@ -1,18 +1,35 @@
-use crate::utils::alignment::align_sequences;
-use clap::Parser;
-use csv::ReaderBuilder;
-use either::Either;
-use serde::{self, Deserialize, de::DeserializeOwned};
+use crate::io::data_ingest::{CoverageData, coverage_data_collection};
+use crate::io::watchlist_alerts::{WatchlistAlert, print_alerts, write_alerts_json};
+use crate::io::write_parquet_files::{ParquetColumns, write_mutation_matrix_to_parquet};
+use crate::status;
+use crate::utils::{
+    alignment::align_sequences,
+    catalogs::Catalog,
+    coordinates::{
+        CoordinateSystem, convert_coverage, dais_cds_position, dais_query_position,
+        padding_offsets, stripped_insertions,
+    },
+    dais_tables::{RefsByKey, create_reader, index_references, read_tsv, sample_from_dais_id},
+    hgvs::{
+        coding_change, coding_deletion, coding_insertion, protein_change, protein_deletion,
+        protein_frameshift, protein_insertion,
+    },
+    iupac::iupac_bases,
+    output::output_writer,
+};
+use clap::{Parser, ValueEnum};
+use rayon::prelude::*;
+use serde::{self, Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet},
     error::Error,
-    fs::{File, OpenOptions},
-    io::{BufRead, BufReader, BufWriter, Stdin, Write, stdin, stdout},
-    path::PathBuf,
+    fmt::{self, Write as _},
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
 };
 use zoe::{
     data::{mappings::StdGeneticCode, nucleotides::GetCodons},
-    prelude::{Len, Nucleotides},
+    prelude::Nucleotides,
 };
 
 #[derive(Debug, Parser)]
@@ -26,9 +43,14 @@ pub struct VariantsArgs {
     /// Reference strains file
     ref_file: PathBuf,
 
-    #[arg(short = 'm', long)]
+    #[arg(short = 'm', long, required_unless_present = "catalog")]
     /// Variants of interest file
-    muts_file: PathBuf,
+    muts_file: Option<PathBuf>,
+
+    #[arg(long, value_enum, conflicts_with = "muts_file")]
+    /// Built-in mutation catalog to use instead of a variants of interest file. Its version
+    /// and source are printed on stderr, and recorded in the header of VCF output
+    catalog: Option<Catalog>,
 
     #[arg(short = 'v', long)]
     /// virus that is being analyzed
@@ -41,59 +63,99 @@ pub struct VariantsArgs {
     #[arg(short = 'd', long, default_value = ",")]
     /// Use the provider delimiter for separating fields. Default is ','
     output_delimiter: String,
-}
 
-// input files *must* be tab-separated
-fn read_tsv<T: DeserializeOwned, R: std::io::Read>(
-    reader: R,
-    has_headers: bool,
-) -> Result<Vec<T>, Box<dyn std::error::Error>> {
-    let mut rdr = ReaderBuilder::new()
-        .has_headers(has_headers)
-        .delimiter(b'\t')
-        .from_reader(reader);
-
-    let mut records = Vec::new();
-    for result in rdr.deserialize() {
-        let record: T = result?;
-        records.push(record);
-    }
+    #[arg(long, value_enum, default_value_t = OutputFormat::Xsv)]
+    /// Format of the output. `vcf` ignores the delimiter
+    output_format: OutputFormat,
+
+    #[arg(long)]
+    /// (Optional) Treat the mutations marked in the `alert` column of the variants of interest
+    /// file as a watchlist: adds an `alert` column to the table and lists the detected alerts
+    /// on stderr
+    watchlist: bool,
+
+    #[arg(short = 'a', long, requires = "watchlist")]
+    /// (Optional) With --watchlist, write the detected alerts to this JSON file as an `alerts`
+    /// array
+    alerts_json: Option<PathBuf>,
+
+    #[arg(long)]
+    /// (Optional) MIRA-NF output directory with the IRMA results. Adds the coverage depth,
+    /// consensus count and average quality at each variant from the IRMA coverage tables
+    irma_path: Option<PathBuf>,
+
+    #[arg(long)]
+    /// (Optional) Write a matrix of the mutations of interest each sample carries to this CSV,
+    /// with a row per sample and a column per mutation, and as parquet next to it (`.parq`)
+    matrix: Option<PathBuf>,
+
+    #[arg(short = 'g', long)]
+    /// (Optional) Write the N-linked glycosylation sites the HA and NA proteins gained or lost
+    /// against each reference strain to this delimited file
+    glycosylation: Option<PathBuf>,
+
+    #[arg(long, value_enum, default_value_t = CoordinateSystem::Raw, requires = "irma_path")]
+    /// Coordinates of the consensus DAIS was run on: `raw` for flu, `padded` for the padded
+    /// consensus of SARS-CoV-2 and RSV
+    coordinates: CoordinateSystem,
+}
 
-    Ok(records)
+/// Formats the variants can be written in
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Delimited table with a row per sample and variant
+    Xsv,
+    /// VCF with a record per sample and variant, on protein coordinates in nucleotides
+    Vcf,
 }
 
 #[allow(dead_code)]
 #[derive(Deserialize, Debug, Clone)]
 pub struct DaisInput {
-    sample_id: String,
-    ctype: String,
-    ref_strain: String,
-    protein: String,
+    pub(crate) sample_id: String,
+    pub(crate) ctype: String,
+    pub(crate) ref_strain: String,
+    pub(crate) protein: String,
     nt_hash: String,
     query_nt_seq: String,
-    query_aa_aln_seq: String,
-    cds_id: String,
+    pub(crate) query_aa_aln_seq: String,
+    pub(crate) cds_id: String,
     insertion: String,
     inert_shift: String,
     cds_seq: String,
-    cds_aln: String,
+    pub(crate) cds_aln: String,
     query_nt_coordinates: String,
     cds_nt_coordinates: String,
 }
 
+impl DaisInput {
+    /// Whether DAIS found insertions against its reference, which it leaves out of `cds_aln`
+    fn has_insertion(&self) -> bool {
+        !matches!(
+            self.insertion.trim().to_ascii_lowercase().as_str(),
+            "" | "false" | "0" | "no"
+        )
+    }
+
+    /// Whether DAIS found those insertions shift the reading frame
+    fn shifts_frame(&self) -> bool {
+        self.inert_shift.trim().eq_ignore_ascii_case("true")
+    }
+}
+
 #[allow(dead_code)]
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct RefInput {
-    isolate_id: String,
-    isolate_name: String,
-    subtype: String,
-    passage_history: String,
-    nt_id: String,
-    ctype: String,
-    reference_id: String,
-    protein: String,
-    aa_aln: String,
-    cds_aln: String,
+    pub(crate) isolate_id: String,
+    pub(crate) isolate_name: String,
+    pub(crate) subtype: String,
+    pub(crate) passage_history: String,
+    pub(crate) nt_id: String,
+    pub(crate) ctype: String,
+    pub(crate) reference_id: String,
+    pub(crate) protein: String,
+    pub(crate) aa_aln: String,
+    pub(crate) cds_aln: String,
 }
 
 #[derive(Deserialize, Debug)]
@@ -106,13 +168,590 @@ pub struct SampleSubtpyes {
 
 #[derive(Deserialize, Debug)]
 pub struct MutsOfInterestInput {
-    subtype: String,
-    protein: String,
+    pub(crate) subtype: String,
+    pub(crate) protein: String,
     aa_position: String,
     aa: String,
     description: String,
+    /// Optional watchlist column. Mutations marked `alert` are escalated when detected
+    alert: Option<String>,
+}
+
+impl MutsOfInterestInput {
+    fn is_alert(&self) -> bool {
+        self.alert.as_deref().is_some_and(|a| {
+            ["alert", "true", "yes"]
+                .iter()
+                .any(|v| a.trim().eq_ignore_ascii_case(v))
+        })
+    }
+
+    /// The mutation's column in the mutation matrix, `NA:275Y (A / H1N1)`
+    fn label(&self) -> String {
+        format!(
+            "{}:{}{} ({})",
+            self.protein,
+            self.aa_position.trim(),
+            self.aa,
+            self.subtype
+        )
+    }
+
+    /// Whether a variant found in a sample is this mutation
+    fn matches(&self, entry: &Entry) -> bool {
+        if self.subtype != entry.subtype || self.protein != entry.protein {
+            return false;
+        }
+        match &entry.indel {
+            Some(indel) => {
+                self.aa.eq_ignore_ascii_case(indel.label())
+                    && self.positions().is_some_and(|(start, end)| {
+                        start <= indel.last_aa.1 && indel.first_aa.1 <= end
+                    })
+            }
+            None => {
+                self.aa_position.trim() == entry.aa_position.to_string() && entry.carries(&self.aa)
+            }
+        }
+    }
+
+    /// First and last amino acid the entry covers: `119`, or `119-120` for a run of them
+    fn positions(&self) -> Option<(usize, usize)> {
+        let position = self.aa_position.trim();
+        let (start, end) = position
+            .split_once(['-', '_'])
+            .unwrap_or((position, position));
+        Some((start.trim().parse().ok()?, end.trim().parse().ok()?))
+    }
+}
+
+/// Whether an indel took bases out of the sample or put them in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum IndelKind {
+    Deletion,
+    Insertion,
+}
+
+/// An insertion or deletion in a sample's CDS against its reference strain, in the columns of
+/// their alignment
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Indel {
+    kind: IndelKind,
+    /// 1-based position of the first deleted base, or of the base an insertion follows
+    nt_position: usize,
+    /// Reference bases deleted, or sample bases inserted
+    bases: String,
+    /// Reference base before the indel, which VCF writes it from
+    anchor: char,
+    /// Reference base after the indel, which VCF writes a deletion of the first base from
+    following: char,
+    /// First amino acid a deletion takes out, or the one an insertion follows, with its
+    /// reference amino acid
+    first_aa: (char, usize),
+    /// Last amino acid a deletion takes out, or the one an insertion comes before
+    last_aa: (char, usize),
+    frameshift: bool,
+}
+
+impl Indel {
+    /// The indel over alignment columns `start..end`, the reference bases a deletion takes
+    /// out or the insertion columns. An insertion DAIS took out of the alignment has
+    /// `start == end`, the column it goes before. `None` for an insertion before the first
+    /// base, which follows no amino acid
+    fn new(
+        kind: IndelKind,
+        (start, end): (usize, usize),
+        bases: &[u8],
+        reference: &[u8],
+        shifted: bool,
+    ) -> Option<Indel> {
+        if bases.is_empty() || start == 0 {
+            return None;
+        }
+        let (first, last, nt_position) = match kind {
+            IndelKind::Deletion => (start / 3 + 1, (end - 1) / 3 + 1, start + 1),
+            IndelKind::Insertion => ((start - 1) / 3 + 1, start / 3 + 1, start),
+        };
+        let base =
+            |base: Option<u8>| base.map_or('N', |base| char::from(base.to_ascii_uppercase()));
+        let anchor = base(
+            reference[..start]
+                .iter()
+                .rev()
+                .copied()
+                .find(u8::is_ascii_alphabetic),
+        );
+        let following = base(
+            reference
+                .get(end..)
+                .unwrap_or_default()
+                .iter()
+                .copied()
+                .find(u8::is_ascii_alphabetic),
+        );
+
+        Some(Indel {
+            kind,
+            nt_position,
+            bases: String::from_utf8_lossy(bases).to_ascii_uppercase(),
+            anchor,
+            following,
+            first_aa: (reference_aa(reference, first), first),
+            last_aa: (reference_aa(reference, last), last),
+            frameshift: shifted || !bases.len().is_multiple_of(3),
+        })
+    }
+
+    /// `del`, `ins` or `fs`, as the indel is written in the muts-of-interest `aa` column
+    fn label(&self) -> &'static str {
+        match (self.frameshift, self.kind) {
+            (true, _) => "fs",
+            (false, IndelKind::Deletion) => "del",
+            (false, IndelKind::Insertion) => "ins",
+        }
+    }
+
+    /// Amino acid positions it touches, `119` or `119-120`
+    fn span(&self) -> String {
+        if self.first_aa.1 == self.last_aa.1 {
+            self.first_aa.1.to_string()
+        } else {
+            format!("{}-{}", self.first_aa.1, self.last_aa.1)
+        }
+    }
+
+    /// HGVS protein notation. Deletions and insertions that don't line up with the codons
+    /// change an amino acid as well, and have none
+    fn hgvs_protein(&self) -> Option<String> {
+        match (self.frameshift, self.kind) {
+            (true, _) => protein_frameshift(self.first_aa),
+            (false, IndelKind::Deletion) if (self.nt_position - 1).is_multiple_of(3) => {
+                protein_deletion(self.first_aa, self.last_aa)
+            }
+            (false, IndelKind::Insertion) if self.nt_position.is_multiple_of(3) => {
+                let inserted: String = self
+                    .bases
+                    .as_bytes()
+                    .chunks_exact(3)
+                    .map(|codon| {
+                        char::from(StdGeneticCode::translate_codon(&[
+                            codon[0], codon[1], codon[2],
+                        ]))
+                    })
+                    .collect();
+                protein_insertion(self.first_aa, self.last_aa, &inserted)
+            }
+            _ => None,
+        }
+    }
+
+    fn hgvs_coding(&self) -> String {
+        match self.kind {
+            IndelKind::Deletion => {
+                coding_deletion(self.nt_position, self.nt_position + self.bases.len() - 1)
+            }
+            IndelKind::Insertion => coding_insertion(self.nt_position, &self.bases),
+        }
+    }
+}
+
+// Reference amino acid at a 1-based position of the alignment, `X` past its end
+fn reference_aa(reference: &[u8], position: usize) -> char {
+    let start = (position - 1) * 3;
+    reference.get(start..start + 3).map_or('X', |codon| {
+        char::from(StdGeneticCode::translate_codon(&[
+            codon[0], codon[1], codon[2],
+        ]))
+    })
+}
+
+// How many of the sample's bases the local alignment left off its start
+fn clipped_bases(original: &[u8], aligned: &[u8]) -> usize {
+    let bases = |seq: &[u8]| -> Vec<u8> {
+        seq.iter()
+            .copied()
+            .filter(u8::is_ascii_alphabetic)
+            .collect()
+    };
+    let (original, aligned) = (bases(original), bases(aligned));
+    if aligned.is_empty() || aligned.len() > original.len() {
+        return 0;
+    }
+    original
+        .windows(aligned.len())
+        .position(|window| window == aligned.as_slice())
+        .unwrap_or(0)
+}
+
+/// Indels of a sample against its reference strain, aligned in the same columns. Runs of
+/// sample gaps between its first and last base are deletions and runs of reference gaps
+/// insertions. DAIS takes insertions against its own reference out of `cds_aln`, so when its
+/// Insertion column has any they are put back from `cds_seq`, frameshifting if `Shift_Insert`
+/// says so
+fn sample_indels(dais_entry: &DaisInput, reference: &[u8], sample: &[u8]) -> Vec<Indel> {
+    let is_base = |base: u8| base.is_ascii_alphabetic();
+    let length = reference.len().min(sample.len());
+    let mut indels = Vec::new();
+    // Gaps before the sample's first base or after its last are missing coverage
+    let (Some(first), Some(last)) = (
+        sample[..length].iter().position(|&base| is_base(base)),
+        sample[..length].iter().rposition(|&base| is_base(base)),
+    ) else {
+        return indels;
+    };
+
+    let mut column = first;
+    while column <= last {
+        let start = column;
+        let kind = if sample[column] == b'-' && is_base(reference[column]) {
+            while column <= last && sample[column] == b'-' {
+                column += 1;
+            }
+            IndelKind::Deletion
+        } else if reference[column] == b'-' && is_base(sample[column]) {
+            while column <= last && reference[column] == b'-' {
+                column += 1;
+            }
+            IndelKind::Insertion
+        } else {
+            column += 1;
+            continue;
+        };
+        let side = match kind {
+            IndelKind::Deletion => reference,
+            IndelKind::Insertion => sample,
+        };
+        let bases: Vec<u8> = side[start..column]
+            .iter()
+            .copied()
+            .filter(|&base| is_base(base))
+            .collect();
+        indels.extend(Indel::new(kind, (start, column), &bases, reference, false));
+    }
+
+    if dais_entry.has_insertion() {
+        let columns: Vec<usize> = (0..length).filter(|&i| is_base(sample[i])).collect();
+        let clipped = clipped_bases(dais_entry.cds_aln.as_bytes(), sample);
+        for (before, bases) in stripped_insertions(&dais_entry.cds_seq, &dais_entry.cds_aln) {
+            if let Some(&column) = before.checked_sub(clipped).and_then(|i| columns.get(i)) {
+                indels.extend(Indel::new(
+                    IndelKind::Insertion,
+                    (column, column),
+                    &bases,
+                    reference,
+                    dais_entry.shifts_frame(),
+                ));
+            }
+        }
+    }
+
+    indels.sort_by_key(|indel| indel.nt_position);
+    indels
+}
+
+/// Whether a sample gained or lost an N-linked glycosylation site against its reference strain
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SequonChange {
+    Gain,
+    Loss,
+}
+
+impl fmt::Display for SequonChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SequonChange::Gain => "gain",
+            SequonChange::Loss => "loss",
+        })
+    }
+}
+
+/// An N-linked glycosylation site a sample's HA or NA gained or lost
+#[derive(Debug)]
+struct GlycosylationEntry<'a> {
+    sample_id: &'a str,
+    ref_strain: &'a str,
+    gisaid_accession: &'a str,
+    ctype: &'a str,
+    dais_ref: &'a str,
+    protein: &'a str,
+    /// 1-based position of the site's N in the reference alignment
+    aa_position: usize,
+    ref_motif: String,
+    sample_motif: String,
+    change: SequonChange,
+}
+
+// The three amino acids from each one of an aligned protein, by its column, and whether they
+// make an N-X-S/T sequon (X not P). Gaps are skipped over, so a sequon can span a deletion.
+// `None` when a missing or partial amino acid leaves it open
+fn sequon_windows(aa_aln: &str) -> HashMap<usize, (String, Option<bool>)> {
+    let unknown = |aa: u8| matches!(aa, b'X' | b'.' | b'~' | b'?');
+    let residues: Vec<(usize, u8)> = aa_aln
+        .bytes()
+        .enumerate()
+        .filter(|&(_, aa)| aa != b'-')
+        .map(|(column, aa)| (column, aa.to_ascii_uppercase()))
+        .collect();
+
+    residues
+        .windows(3)
+        .map(|window| {
+            let (column, [first, second, third]) =
+                (window[0].0, [window[0].1, window[1].1, window[2].1]);
+            let ruled_out = (!unknown(first) && first != b'N')
+                || second == b'P'
+                || (!unknown(third) && !matches!(third, b'S' | b'T'));
+            let is_sequon = if ruled_out {
+                Some(false)
+            } else if [first, second, third].into_iter().any(unknown) {
+                None
+            } else {
+                Some(true)
+            };
+            let motif = String::from_utf8_lossy(&[first, second, third]).into_owned();
+            (column, (motif, is_sequon))
+        })
+        .collect()
+}
+
+/// N-linked glycosylation sites of a sample's HA or NA gained or lost against a reference
+/// strain, both aligned to the DAIS reference. A site is lost when the sample's amino acids
+/// there don't make a sequon or its N is deleted, and open sites aren't reported
+fn glycosylation_changes<'a>(
+    dais_entry: &'a DaisInput,
+    ref_entry: &'a RefInput,
+) -> Vec<GlycosylationEntry<'a>> {
+    if !(dais_entry.protein.starts_with("HA") || dais_entry.protein.starts_with("NA")) {
+        return Vec::new();
+    }
+    let sample = sequon_windows(&dais_entry.query_aa_aln_seq);
+    let reference = sequon_windows(&ref_entry.aa_aln);
+    let deleted = |column: usize| dais_entry.query_aa_aln_seq.as_bytes().get(column) == Some(&b'-');
+
+    let mut columns: Vec<usize> = sample.keys().chain(reference.keys()).copied().collect();
+    columns.sort_unstable();
+    columns.dedup();
+
+    let mut changes = Vec::new();
+    for column in columns {
+        let (ref_motif, ref_sequon) = reference
+            .get(&column)
+            .map_or(("-", Some(false)), |(motif, sequon)| {
+                (motif.as_str(), *sequon)
+            });
+        let (sample_motif, sample_sequon) = match sample.get(&column) {
+            Some((motif, sequon)) => (motif.as_str(), *sequon),
+            None if deleted(column) => ("-", Some(false)),
+            None => continue,
+        };
+        let change = match (ref_sequon, sample_sequon) {
+            (Some(true), Some(false)) => SequonChange::Loss,
+            (Some(false), Some(true)) => SequonChange::Gain,
+            _ => continue,
+        };
+        changes.push(GlycosylationEntry {
+            sample_id: &dais_entry.sample_id,
+            ref_strain: &ref_entry.isolate_name,
+            gisaid_accession: &ref_entry.isolate_id,
+            ctype: &dais_entry.ctype,
+            dais_ref: &dais_entry.ref_strain,
+            protein: &dais_entry.protein,
+            aa_position: column + 1,
+            ref_motif: ref_motif.to_string(),
+            sample_motif: sample_motif.to_string(),
+            change,
+        });
+    }
+    changes
+}
+
+fn write_glycosylation<W: Write>(
+    writer: &mut W,
+    entries: &[GlycosylationEntry],
+    delim: &str,
+) -> std::io::Result<()> {
+    let d = delim;
+    writeln!(
+        writer,
+        "sample{d}reference_strain{d}gisaid_accession{d}ctype{d}dais_reference{d}protein{d}\
+        aa_position{d}reference_motif{d}sample_motif{d}glycosylation_change",
+    )?;
+    for entry in entries {
+        let GlycosylationEntry {
+            sample_id,
+            ref_strain,
+            gisaid_accession,
+            ctype,
+            dais_ref,
+            protein,
+            aa_position,
+            ref_motif,
+            sample_motif,
+            change,
+        } = entry;
+        writeln!(
+            writer,
+            "{sample_id}{d}{ref_strain}{d}{gisaid_accession}{d}{ctype}{d}{dais_ref}{d}\
+            {protein}{d}{aa_position}{d}{ref_motif}{d}{sample_motif}{d}{change}",
+        )?;
+    }
+    Ok(())
 }
 
+/// Writes the glycosylation sites each DAIS entry gained or lost against its reference
+/// strains to `path`
+fn write_glycosylation_report(
+    path: &Path,
+    dais: &[DaisInput],
+    refs_by_key: &RefsByKey,
+    delim: &str,
+) -> Result<(), Box<dyn Error>> {
+    let changes: Vec<GlycosylationEntry> = dais
+        .par_iter()
+        .flat_map_iter(|dais_entry| {
+            references_of(refs_by_key, dais_entry)
+                .flat_map(|ref_entry| glycosylation_changes(dais_entry, ref_entry))
+        })
+        .collect();
+    let mut writer = output_writer(Some(path))?;
+    write_glycosylation(&mut writer, &changes, delim)?;
+    writer.flush()?;
+    status!(
+        " -> {} glycosylation site changes written to {}",
+        changes.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Which mutations of interest each sample carries, a row per sample and a column per
+/// mutation
+pub struct MutationMatrix {
+    /// Labels of the mutations, in the order of the mutations of interest
+    pub columns: Vec<String>,
+    /// Each sample and its cells: `present`, or with the IRMA coverage the fraction of reads
+    /// with the consensus base at the mutation, `absent`, or `None` when the sample has no
+    /// protein compared to a reference strain of the mutation's subtype
+    pub rows: Vec<(String, Vec<Option<String>>)>,
+}
+
+/// The mutation matrix of the samples in the DAIS file, with the reference strains each of
+/// their proteins was compared to
+fn mutation_matrix(
+    dais: &[DaisInput],
+    refs_by_key: &RefsByKey,
+    muts_interest: &[MutsOfInterestInput],
+    entries: &[Entry],
+    coverage: Option<&CoverageIndex>,
+) -> MutationMatrix {
+    let mut samples: Vec<&str> = Vec::new();
+    let mut compared: HashSet<(&str, &str, &str)> = HashSet::new();
+    for dais_entry in dais {
+        let sample = sample_from_dais_id(&dais_entry.sample_id);
+        if !samples.contains(&sample) {
+            samples.push(sample);
+        }
+        for ref_entry in references_of(refs_by_key, dais_entry) {
+            compared.insert((
+                sample,
+                ref_entry.subtype.as_str(),
+                dais_entry.protein.as_str(),
+            ));
+        }
+    }
+
+    let mut mutations: Vec<&MutsOfInterestInput> = Vec::new();
+    for muts_entry in muts_interest {
+        if !mutations.iter().any(|m| m.label() == muts_entry.label()) {
+            mutations.push(muts_entry);
+        }
+    }
+
+    let rows = samples
+        .into_iter()
+        .map(|sample| {
+            let cells = mutations
+                .iter()
+                .map(|muts_entry| {
+                    let found = entries.iter().find(|entry| {
+                        sample_from_dais_id(entry.sample_id) == sample && muts_entry.matches(entry)
+                    });
+                    let key = (
+                        sample,
+                        muts_entry.subtype.as_str(),
+                        muts_entry.protein.as_str(),
+                    );
+                    match found {
+                        Some(entry) => Some(present_cell(entry, coverage)),
+                        None if compared.contains(&key) => Some("absent".to_string()),
+                        None => None,
+                    }
+                })
+                .collect();
+            (sample.to_string(), cells)
+        })
+        .collect();
+
+    MutationMatrix {
+        columns: mutations.iter().map(|m| m.label()).collect(),
+        rows,
+    }
+}
+
+// The cell of a mutation found in a sample: with the IRMA coverage, the fraction of reads with
+// the consensus base at it, otherwise `present`
+fn present_cell(entry: &Entry, coverage: Option<&CoverageIndex>) -> String {
+    match coverage.and_then(|coverage| entry.coverage(coverage)) {
+        Some(row) if row.coverage_depth > 0 => format!(
+            "{:.3}",
+            f64::from(row.consensus_count) / f64::from(row.coverage_depth)
+        ),
+        _ => "present".to_string(),
+    }
+}
+
+fn write_mutation_matrix_csv(matrix: &MutationMatrix, path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer
+        .write_record(std::iter::once("sample").chain(matrix.columns.iter().map(String::as_str)))?;
+    for (sample, cells) in &matrix.rows {
+        writer.write_record(
+            std::iter::once(sample.as_str())
+                .chain(cells.iter().map(|cell| cell.as_deref().unwrap_or_default())),
+        )?;
+    }
+    writer.flush()?;
+    status!(" -> CSV written to {}", path.display());
+    Ok(())
+}
+
+/// Writes the mutation matrix as a CSV at `path` and as parquet next to it
+fn write_mutation_matrix(matrix: &MutationMatrix, path: &Path) -> Result<(), Box<dyn Error>> {
+    write_mutation_matrix_csv(matrix, path)?;
+    write_mutation_matrix_to_parquet(
+        matrix,
+        &path.with_extension("parq").to_string_lossy(),
+        &ParquetColumns::default(),
+    )?;
+    Ok(())
+}
+
+/// The reference strains a DAIS entry is compared to
+fn references_of<'a>(
+    refs_by_key: &'a RefsByKey<'a>,
+    dais_entry: &'a DaisInput,
+) -> impl Iterator<Item = &'a RefInput> {
+    let key = (
+        dais_entry.ctype.as_str(),
+        dais_entry.ref_strain.as_str(),
+        dais_entry.protein.as_str(),
+    );
+    refs_by_key.get(&key).into_iter().flatten().copied()
+}
+
+/// IRMA coverage rows by sample, reference and position on the consensus
+type CoverageIndex<'a> = HashMap<(&'a str, &'a str, i32), &'a CoverageData>;
+
 #[derive(Clone, Debug)]
 pub struct Entry<'a> {
     sample_id: &'a str,
@@ -127,7 +766,16 @@ pub struct Entry<'a> {
     aa_ref: char,
     aa_position: usize,
     aa_mut: char,
+    /// Amino acids of a sample codon whose IUPAC ambiguity codes translate to more than one,
+    /// reported in place of `aa_mut`
+    mixed_residues: Vec<char>,
     phenotypic_consequences: String,
+    alert: bool,
+    /// Set for an insertion or deletion, which the codons and amino acids then describe
+    indel: Option<Indel>,
+    /// Position on the consensus of the first base that changed, or of the base before a
+    /// deletion
+    consensus_position: Option<i32>,
 }
 
 impl Entry<'_> {
@@ -148,8 +796,17 @@ impl Entry<'_> {
                 && self.protein == muts_entry.protein
                 && self.aa_position.to_string() == muts_entry.aa_position
             {
+                let carried = self.carries(&muts_entry.aa);
+                self.alert = carried && muts_entry.is_alert();
                 // Use match for cleaner handling of `hold_aa_mut` cases
                 self.phenotypic_consequences = match hold_aa_mut.as_str() {
+                    _ if self.is_mixed() => {
+                        if carried {
+                            muts_entry.description.clone()
+                        } else {
+                            String::new()
+                        }
+                    }
                     "~" => "partial amino acid".to_string(),
                     "-" => "amino acid covered".to_string(),
                     "X" | "." => "amino acid information missing".to_string(),
@@ -163,9 +820,149 @@ impl Entry<'_> {
 
         false
     }
+
+    /// Makes the entry an indel, with the bases deleted or inserted as its codons, against
+    /// gaps on the other side
+    fn set_indel(&mut self, indel: Indel) {
+        let gaps = "-".repeat(indel.bases.len());
+        (self.ref_codon, self.mut_codon) = match indel.kind {
+            IndelKind::Deletion => (indel.bases.clone(), gaps),
+            IndelKind::Insertion => (gaps, indel.bases.clone()),
+        };
+        (self.aa_ref, self.aa_position) = indel.first_aa;
+        self.aa_mut = '-';
+        self.mixed_residues.clear();
+        self.phenotypic_consequences = String::new();
+        self.alert = false;
+        self.indel = Some(indel);
+    }
+
+    /// Looks the entry's indel up in the mutations of interest. Like a substitution, it's
+    /// reported when it touches a listed position, and described by a listing of its kind
+    /// (`del`, `ins` or `fs`) there
+    fn update_entry_from_indel(
+        &mut self,
+        subtype: &str,
+        muts_columns: &[MutsOfInterestInput],
+    ) -> bool {
+        let Some(indel) = &self.indel else {
+            return false;
+        };
+        let (first, last) = (indel.first_aa.1, indel.last_aa.1);
+        let label = indel.label();
+        let listed: Vec<&MutsOfInterestInput> = muts_columns
+            .iter()
+            .filter(|muts_entry| {
+                subtype == muts_entry.subtype
+                    && self.protein == muts_entry.protein
+                    && muts_entry
+                        .positions()
+                        .is_some_and(|(start, end)| start <= last && first <= end)
+            })
+            .collect();
+        let Some(muts_entry) = listed
+            .iter()
+            .find(|muts_entry| muts_entry.aa.eq_ignore_ascii_case(label))
+            .or(listed.first())
+        else {
+            return false;
+        };
+
+        let matches = muts_entry.aa.eq_ignore_ascii_case(label);
+        self.alert = matches && muts_entry.is_alert();
+        self.phenotypic_consequences = if matches {
+            muts_entry.description.clone()
+        } else {
+            String::new()
+        };
+        true
+    }
 }
 
 impl Entry<'_> {
+    /// Whether the sample's codon is a mixture of amino acids
+    fn is_mixed(&self) -> bool {
+        !self.mixed_residues.is_empty()
+    }
+
+    /// Whether the sample has the amino acid, alone or as one of a mixed position's
+    fn carries(&self, aa: &str) -> bool {
+        if self.is_mixed() {
+            self.mixed_residues
+                .iter()
+                .any(|residue| aa == residue.to_string())
+        } else {
+            aa == self.aa_mut.to_string()
+        }
+    }
+
+    /// The sample's amino acid, or those of a mixed position separated by `/`, `E/K`
+    fn sample_aa(&self) -> String {
+        if self.is_mixed() {
+            let residues: Vec<String> = self.mixed_residues.iter().map(char::to_string).collect();
+            residues.join("/")
+        } else {
+            self.aa_mut.to_string()
+        }
+    }
+
+    /// The `aa_mutation` column, `E:119:V` or `E:119:E/K` for a mixed position, or for an
+    /// indel `E:119:del` and `K:120-121:ins`
+    fn aa_mutation(&self) -> String {
+        match &self.indel {
+            Some(indel) => format!("{}:{}:{}", self.aa_ref, indel.span(), indel.label()),
+            None => format!("{}:{}:{}", self.aa_ref, self.aa_position, self.sample_aa()),
+        }
+    }
+
+    /// The amino acid change written together, `E119V` or `E119del`
+    fn aa_change(&self) -> String {
+        match &self.indel {
+            Some(indel) => format!("{}{}{}", self.aa_ref, indel.span(), indel.label()),
+            None => format!("{}{}{}", self.aa_ref, self.aa_position, self.sample_aa()),
+        }
+    }
+
+    /// The variant in HGVS protein notation, `p.Glu119Val`. Mixed positions have none
+    fn hgvs_protein(&self) -> String {
+        if let Some(indel) = &self.indel {
+            return indel.hgvs_protein().unwrap_or_default();
+        }
+        if self.is_mixed() {
+            return String::new();
+        }
+        protein_change(self.aa_ref, self.aa_position, self.aa_mut).unwrap_or_default()
+    }
+
+    /// The variant in HGVS coding notation, `c.355G>A`. `ref_codon` is the reference
+    /// strain's codon
+    fn hgvs_coding(&self) -> String {
+        if let Some(indel) = &self.indel {
+            return indel.hgvs_coding();
+        }
+        if self.aa_position == 0 || self.ref_codon.len() != 3 || self.mut_codon.len() != 3 {
+            return String::new();
+        }
+        coding_change(
+            (self.aa_position - 1) * 3 + 1,
+            self.ref_codon.as_bytes(),
+            self.mut_codon.as_bytes(),
+        )
+        .unwrap_or_default()
+    }
+
+    /// The IRMA coverage row at the entry's consensus position, matched to the IRMA sample and
+    /// the entry's ctype as the IRMA reference
+    fn coverage<'c>(&self, coverage: &'c CoverageIndex) -> Option<&'c CoverageData> {
+        coverage
+            .get(&(
+                sample_from_dais_id(self.sample_id),
+                self.ctype,
+                self.consensus_position?,
+            ))
+            .copied()
+    }
+
     // Helper function to compare two entries ignoring `ref_strain`
     fn is_same_except_ref_strain(&self, other: &Entry) -> bool {
         self.sample_id == other.sample_id
@@ -177,21 +974,12 @@ impl Entry<'_> {
             && self.aa_ref == other.aa_ref
             && self.aa_position == other.aa_position
             && self.aa_mut == other.aa_mut
+            && self.mixed_residues == other.mixed_residues
             && self.phenotypic_consequences == other.phenotypic_consequences
+            && self.indel == other.indel
     }
 }
 
-fn create_reader(path: Option<&PathBuf>) -> std::io::Result<BufReader<Either<File, Stdin>>> {
-    let reader = if let Some(ref file_path) = path {
-        let file = OpenOptions::new().read(true).open(file_path)?;
-        BufReader::new(Either::Left(file))
-    } else {
-        BufReader::new(Either::Right(stdin()))
-    };
-
-    Ok(reader)
-}
-
 pub fn lines_to_vec<R: BufRead>(reader: R) -> std::io::Result<Vec<Vec<String>>> {
     let mut columns: Vec<Vec<String>> = Vec::new();
 
@@ -335,12 +1123,515 @@ fn find_duplicate_aa_entries_with_diff_strain<'a>(
     result
 }
 
-#[allow(clippy::too_many_lines)]
+// Lists the watchlist hits prominently on stderr (stdout may be the table itself) and
+// optionally writes them as JSON for automated escalation
+fn report_alerts(entries: &[Entry], alerts_json: Option<&PathBuf>) -> Result<(), Box<dyn Error>> {
+    let alerts: Vec<WatchlistAlert> = entries
+        .iter()
+        .filter(|e| e.alert)
+        .map(|e| WatchlistAlert {
+            sample: e.sample_id.to_string(),
+            subtype: e.subtype.to_string(),
+            protein: e.protein.to_string(),
+            aa_mutation: e.aa_change(),
+            description: e.phenotypic_consequences.clone(),
+        })
+        .collect();
+
+    print_alerts(&alerts);
+    if let Some(path) = alerts_json {
+        write_alerts_json(path, &alerts)?;
+    }
+    Ok(())
+}
+
+/// Writes the variants as a delimited table. With the IRMA coverage, each row also gets the
+/// coverage depth, consensus count and average quality at its consensus position, left empty
+/// where it has none
+fn write_xsv<W: Write>(
+    writer: &mut W,
+    entries: &[Entry],
+    delim: &str,
+    coverage: Option<&CoverageIndex>,
+    watchlist: bool,
+) -> std::io::Result<()> {
+    // Write the header
+    let d = delim;
+    write!(
+        writer,
+        "sample{d}reference_strain{d}gisaid_accession{d}ctype{d}dais_reference{d}protein{d}\
+        sample_codon{d}reference_codon{d}aa_mutation{d}phenotypic_consequence{d}",
+    )?;
+    if watchlist {
+        write!(writer, "alert{d}")?;
+    }
+    write!(writer, "hgvs_protein{d}hgvs_coding{d}mixed_position")?;
+    if coverage.is_some() {
+        write!(
+            writer,
+            "{delim}coverage_depth{delim}consensus_count{delim}consensus_average_quality"
+        )?;
+    }
+    writeln!(writer)?;
+
+    for entry in entries {
+        let Entry {
+            sample_id,
+            ref_strain,
+            gisaid_accession,
+            subtype: _,
+            ctype,
+            dais_ref,
+            protein,
+            ref_codon,
+            mut_codon,
+            aa_ref: _,
+            aa_position: _,
+            aa_mut: _,
+            mixed_residues: _,
+            phenotypic_consequences,
+            alert,
+            indel: _,
+            consensus_position: _,
+        } = entry;
+        let aa_mutation = entry.aa_mutation();
+        let (hgvs_protein, hgvs_coding) = (entry.hgvs_protein(), entry.hgvs_coding());
+        let mixed_position = entry.is_mixed();
+
+        write!(
+            writer,
+            "{sample_id}{d}{ref_strain}{d}{gisaid_accession}{d}\
+            {ctype}{d}{dais_ref}{d}{protein}{d}\
+            {ref_codon}{d}{mut_codon}{d}\
+            {aa_mutation}{d}\
+            {phenotypic_consequences}{d}",
+        )?;
+        if watchlist {
+            write!(writer, "{alert}{d}")?;
+        }
+        write!(writer, "{hgvs_protein}{d}{hgvs_coding}{d}{mixed_position}")?;
+        if let Some(coverage) = coverage {
+            match entry.coverage(coverage) {
+                Some(row) => write!(
+                    writer,
+                    "{d}{}{d}{}{d}{}",
+                    row.coverage_depth, row.consensus_count, row.consensus_avg_quality
+                )?,
+                None => write!(writer, "{d}{d}{d}")?,
+            }
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+// VCF 4.3 percent-encodes the characters with a meaning in the INFO column
+fn vcf_info_value(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '%' | ':' | ';' | '=' | ',' | '\r' | '\n' | '\t' => {
+                let _ = write!(encoded, "%{:02X}", u32::from(c));
+            }
+            _ => encoded.push(c),
+        }
+    }
+    encoded
+}
+
+// A contig per protein of each reference strain, named by its GISAID accession, as strains
+// can have other bases at the same position: `NA_EPI_ISL_227813`
+fn vcf_contig(entry: &Entry) -> String {
+    let strain = if entry.gisaid_accession.is_empty() {
+        entry.ref_strain
+    } else {
+        entry.gisaid_accession
+    };
+    format!("{}_{strain}", entry.protein)
+        .chars()
+        .map(|c| {
+            if c.is_whitespace() || matches!(c, ',' | '<' | '>' | '=' | '*') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+// VCF alleles are A, C, G, T or N, so other IUPAC codes become N
+fn vcf_allele(codon: &str) -> String {
+    codon
+        .chars()
+        .map(|c| match c.to_ascii_uppercase() {
+            base @ ('A' | 'C' | 'G' | 'T') => base,
+            _ => 'N',
+        })
+        .collect()
+}
+
+/// POS, REF and ALT of a variant. Codons that are partial, gapped, untranslatable or mixed,
+/// and those that didn't change, have none
+fn vcf_alleles(entry: &Entry) -> Option<(usize, String, String)> {
+    if let Some(indel) = &entry.indel {
+        let anchor = vcf_allele(&indel.anchor.to_string());
+        let with_bases = format!("{anchor}{}", vcf_allele(&indel.bases));
+        return Some(match indel.kind {
+            // With no base before it, a deletion is written from the base after it
+            IndelKind::Deletion if indel.nt_position == 1 => {
+                let following = vcf_allele(&indel.following.to_string());
+                let deleted = format!("{}{following}", vcf_allele(&indel.bases));
+                (1, deleted, following)
+            }
+            IndelKind::Deletion => (indel.nt_position - 1, with_bases, anchor),
+            IndelKind::Insertion => (indel.nt_position, anchor, with_bases),
+        });
+    }
+    if matches!(entry.aa_mut, '~' | '-' | 'X' | '.') || entry.is_mixed() || entry.aa_position == 0 {
+        return None;
+    }
+    // `ref_codon` is the reference strain's codon and `mut_codon` the sample's
+    let (mut reference, mut alternate) = (
+        vcf_allele(&entry.ref_codon).into_bytes(),
+        vcf_allele(&entry.mut_codon).into_bytes(),
+    );
+    if reference.len() != 3 || alternate.len() != 3 || reference == alternate {
+        return None;
+    }
+    let mut position = (entry.aa_position - 1) * 3 + 1;
+    while reference.len() > 1 && reference.last() == alternate.last() {
+        reference.pop();
+        alternate.pop();
+    }
+    while reference.len() > 1 && reference[0] == alternate[0] {
+        reference.remove(0);
+        alternate.remove(0);
+        position += 1;
+    }
+    Some((
+        position,
+        String::from_utf8_lossy(&reference).into_owned(),
+        String::from_utf8_lossy(&alternate).into_owned(),
+    ))
+}
+
+/// Writes the variants as a sites-only VCF. CHROM is the protein of the reference strain the
+/// sample was compared to, and POS the codon's first nucleotide in the reference CDS
+/// alignment, trimmed to the bases that changed. Indels are written from the reference base
+/// before them, or after a deletion of the first base. Codons that are partial, gapped or
+/// untranslatable aren't variants and are left out, nor are mixed positions, whose ambiguous
+/// bases VCF alleles can't hold. With the IRMA coverage, the depth, consensus count and
+/// average quality at the variant are added to INFO. A built-in catalog is recorded in a
+/// `##catalog` header line
+fn write_vcf<W: Write>(
+    writer: &mut W,
+    entries: &[Entry],
+    coverage: Option<&CoverageIndex>,
+    provenance: Option<&str>,
+) -> std::io::Result<()> {
+    let mut records: Vec<(String, usize, String, String, &Entry)> = entries
+        .iter()
+        .filter_map(|entry| {
+            let (position, reference, alternate) = vcf_alleles(entry)?;
+            Some((vcf_contig(entry), position, reference, alternate, entry))
+        })
+        .collect();
+    records.sort_by(|a, b| (&a.0, a.1, a.4.sample_id).cmp(&(&b.0, b.1, b.4.sample_id)));
+
+    writeln!(writer, "##fileformat=VCFv4.3")?;
+    writeln!(writer, "##source=mira-oxide variants-of-interest")?;
+    if let Some(provenance) = provenance {
+        writeln!(writer, "##catalog=\"{}\"", provenance.replace('"', "'"))?;
+    }
+    let mut contigs: Vec<&str> = records.iter().map(|record| record.0.as_str()).collect();
+    contigs.dedup();
+    for contig in contigs {
+        writeln!(writer, "##contig=<ID={contig}>")?;
+    }
+    for info in [
+        "<ID=SAMPLE,Number=1,Type=String,Description=\"Sample the variant was found in\">",
+        "<ID=REFSTRAIN,Number=1,Type=String,Description=\"Reference strain the sample was compared to\">",
+        "<ID=AACHANGE,Number=1,Type=String,Description=\"Amino acid change, reference amino acid, position and sample amino acid, or del, ins or fs for an indel\">",
+        "<ID=HGVSP,Number=1,Type=String,Description=\"Amino acid change in HGVS protein notation\">",
+        "<ID=HGVSC,Number=1,Type=String,Description=\"Codon change in HGVS coding notation\">",
+        "<ID=PHENO,Number=1,Type=String,Description=\"Phenotypic consequence from the variants of interest table\">",
+        "<ID=ALERT,Number=0,Type=Flag,Description=\"The variant is on the watchlist\">",
+        "<ID=DP,Number=1,Type=Integer,Description=\"IRMA coverage depth at the variant\">",
+        "<ID=CONSCOUNT,Number=1,Type=Integer,Description=\"Reads with the consensus base at the variant\">",
+        "<ID=AVGQ,Number=1,Type=Float,Description=\"Average quality of the consensus base at the variant\">",
+    ] {
+        writeln!(writer, "##INFO={info}")?;
+    }
+    writeln!(writer, "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO")?;
+
+    for (contig, position, reference, alternate, entry) in records {
+        write!(
+            writer,
+            "{contig}\t{position}\t.\t{reference}\t{alternate}\t.\tPASS\t\
+            SAMPLE={};REFSTRAIN={};AACHANGE={};HGVSP={};HGVSC={}",
+            vcf_info_value(entry.sample_id),
+            vcf_info_value(entry.ref_strain),
+            vcf_info_value(&entry.aa_change()),
+            vcf_info_value(&entry.hgvs_protein()),
+            vcf_info_value(&entry.hgvs_coding())
+        )?;
+        if !entry.phenotypic_consequences.is_empty() {
+            write!(
+                writer,
+                ";PHENO={}",
+                vcf_info_value(&entry.phenotypic_consequences)
+            )?;
+        }
+        if entry.alert {
+            write!(writer, ";ALERT")?;
+        }
+        if let Some(row) = coverage.and_then(|coverage| entry.coverage(coverage)) {
+            write!(
+                writer,
+                ";DP={};CONSCOUNT={};AVGQ={}",
+                row.coverage_depth, row.consensus_count, row.consensus_avg_quality
+            )?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Puts each entry on the consensus DAIS was run on, at the first base of its codon that
+/// changed, or for an indel the base before it. `reference` and `sample` are the alignment the
+/// entries were found in, and `clipped` the bases of the sample it left off its start
+fn set_consensus_positions(
+    entries: &mut [Entry],
+    dais_entry: &DaisInput,
+    reference: &[u8],
+    sample: &[u8],
+    clipped: usize,
+) {
+    let insertions = stripped_insertions(&dais_entry.cds_seq, &dais_entry.cds_aln);
+    for entry in entries {
+        let column = match &entry.indel {
+            Some(indel) => indel.nt_position.checked_sub(1),
+            None => entry.aa_position.checked_sub(1).map(|index| {
+                let start = index * 3;
+                (start..start + 3)
+                    .find(|&i| match (reference.get(i), sample.get(i)) {
+                        (Some(r), Some(s)) => !r.eq_ignore_ascii_case(s),
+                        _ => false,
+                    })
+                    .unwrap_or(start)
+            }),
+        };
+        // The base at the column, or the last one before it when the sample has a gap there
+        entry.consensus_position = column.and_then(|column| {
+            let before = sample
+                .get(..column)
+                .unwrap_or(sample)
+                .iter()
+                .filter(|base| base.is_ascii_alphabetic())
+                .count();
+            let ordinal = if sample.get(column).is_some_and(u8::is_ascii_alphabetic) {
+                before
+            } else {
+                before.checked_sub(1)?
+            };
+            dais_query_position(
+                &dais_entry.query_nt_coordinates,
+                &dais_entry.cds_nt_coordinates,
+                dais_cds_position(&insertions, clipped + ordinal),
+            )
+        });
+    }
+}
+
+/// Amino acids a codon with IUPAC ambiguity codes can translate to, sorted and without
+/// repeats. Codons without an ambiguity code, or with an `N` or gap, have none and are
+/// translated as usual, as an `N` could be any base
+fn ambiguous_translations(codon: &[u8]) -> Vec<u8> {
+    let [first, second, third] = codon else {
+        return Vec::new();
+    };
+    if [first, second, third]
+        .iter()
+        .any(|base| base.eq_ignore_ascii_case(&b'N'))
+    {
+        return Vec::new();
+    }
+    let (first, second, third) = (
+        iupac_bases(*first),
+        iupac_bases(*second),
+        iupac_bases(*third),
+    );
+    if first.len() * second.len() * third.len() <= 1 {
+        return Vec::new();
+    }
+    let mut residues: Vec<u8> = first
+        .iter()
+        .flat_map(|&a| {
+            second.iter().flat_map(move |&b| {
+                third
+                    .iter()
+                    .map(move |&c| StdGeneticCode::translate_codon(&[a, b, c]))
+            })
+        })
+        .collect();
+    residues.sort_unstable();
+    residues.dedup();
+    residues
+}
+
+/// Compares a DAIS entry's CDS to one reference strain's, returning its codon changes and
+/// indels at the positions in the mutations of interest
+fn compare_to_reference<'a>(
+    dais_entry: &'a DaisInput,
+    ref_entry: &'a RefInput,
+    muts_interest: &[MutsOfInterestInput],
+) -> Vec<Entry<'a>> {
+    let query = dais_entry.cds_aln.as_bytes();
+    let reference = ref_entry.cds_aln.as_bytes();
+
+    if query.len() == reference.len() {
+        compare_alignment(dais_entry, ref_entry, muts_interest, reference, query, 0)
+    } else {
+        let (aligned_ref, aligned_query) = align_sequences(query, reference);
+        let clipped = clipped_bases(query, &aligned_query);
+        compare_alignment(
+            dais_entry,
+            ref_entry,
+            muts_interest,
+            &aligned_ref,
+            &aligned_query,
+            clipped,
+        )
+    }
+}
+
+/// Walks the codons of an alignment of the reference strain's CDS and the sample's, returning
+/// the codon changes and indels at the positions in the mutations of interest. `clipped` is the
+/// number of bases of the sample the alignment left off its start
+fn compare_alignment<'a>(
+    dais_entry: &'a DaisInput,
+    ref_entry: &'a RefInput,
+    muts_interest: &[MutsOfInterestInput],
+    reference: &[u8],
+    sample: &[u8],
+    clipped: usize,
+) -> Vec<Entry<'a>> {
+    let mut mutations_vec: Vec<Entry> = Vec::new();
+    let nt_seq1 = Nucleotides::from(reference.to_vec());
+    let nt_seq2 = Nucleotides::from(sample.to_vec());
+
+    let mut entry = Entry {
+        sample_id: &dais_entry.sample_id,
+        ref_strain: &ref_entry.isolate_name,
+        gisaid_accession: &ref_entry.isolate_id,
+        subtype: &ref_entry.subtype,
+        ctype: &dais_entry.ctype,
+        dais_ref: &dais_entry.ref_strain,
+        protein: &dais_entry.protein,
+        ref_codon: "NNN".to_string(),
+        mut_codon: "NNN".to_string(),
+        aa_position: 0,
+        aa_ref: 'X',
+        aa_mut: 'X',
+        mixed_residues: Vec::new(),
+        phenotypic_consequences: String::new(),
+        alert: false,
+        indel: None,
+        consensus_position: None,
+    };
+
+    let mut tail_index = 0;
+    let (codons1, tail1) = nt_seq1.as_codons();
+    let (codons2, tail2) = nt_seq2.as_codons();
+
+    for (index, (ref_codon, query_codon)) in codons1
+        .iter()
+        .zip(codons2.iter())
+        .enumerate()
+        .filter(|(_, (ref_chunk, query_chunk))| ref_chunk != query_chunk)
+    {
+        let aa_index = index + 1;
+        tail_index = aa_index;
+        let ref_aa = StdGeneticCode::translate_codon(ref_codon);
+        let residues = ambiguous_translations(query_codon);
+        let query_aa = match residues.as_slice() {
+            [aa] => *aa,
+            _ => StdGeneticCode::translate_codon(query_codon),
+        };
+        entry.mixed_residues = if residues.len() > 1 {
+            residues.into_iter().map(char::from).collect()
+        } else {
+            Vec::new()
+        };
+
+        entry.ref_codon = std::str::from_utf8(ref_codon)
+            .expect("Invalid UTF-8 sequence")
+            .to_string();
+        entry.mut_codon = std::str::from_utf8(query_codon)
+            .expect("Invalid UTF-8 sequence")
+            .to_string();
+        entry.aa_position = aa_index;
+        entry.aa_ref = ref_aa as char;
+        entry.aa_mut = query_aa as char;
+
+        if entry.update_entry_from_alignment(&ref_entry.subtype, ref_aa, query_aa, muts_interest) {
+            mutations_vec.push(entry.clone()); // Save the entry to mutations_vec
+        }
+    }
+
+    if !tail1.is_empty() && tail1 != tail2 {
+        let partial_codon = b'~';
+        entry.ref_codon = std::str::from_utf8(tail1)
+            .expect("Invalid UTF-8 sequence")
+            .to_string();
+        entry.mut_codon = std::str::from_utf8(tail2)
+            .expect("Invalid UTF-8 sequence")
+            .to_string();
+        entry.aa_position = tail_index + 1;
+        entry.aa_ref = '~';
+        entry.aa_mut = '~';
+        entry.mixed_residues.clear();
+
+        if entry.update_entry_from_alignment(
+            &ref_entry.subtype,
+            partial_codon,
+            partial_codon,
+            muts_interest,
+        ) {
+            mutations_vec.push(entry.clone()); // Save the entry to mutations_vec
+        }
+    }
+
+    for indel in sample_indels(dais_entry, reference, sample) {
+        entry.set_indel(indel);
+        if entry.update_entry_from_indel(&ref_entry.subtype, muts_interest) {
+            mutations_vec.push(entry.clone());
+        }
+    }
+
+    set_consensus_positions(&mut mutations_vec, dais_entry, reference, sample, clipped);
+
+    mutations_vec
+}
+
 pub fn variants_of_interest_process(args: VariantsArgs) -> Result<(), Box<dyn Error>> {
     let delim = args.output_delimiter;
 
-    let muts_reader = create_reader(Some(&args.muts_file))?;
-    let muts_interest: Vec<MutsOfInterestInput> = read_tsv(muts_reader, false)?;
+    let mut muts_interest: Vec<MutsOfInterestInput> = match (&args.muts_file, args.catalog) {
+        (Some(muts_file), _) => read_tsv(create_reader(Some(muts_file))?, false)?,
+        (None, Some(catalog)) => read_tsv(catalog.rows().as_bytes(), false)?,
+        (None, None) => return Err("Either --muts-file or --catalog is needed".into()),
+    };
+    // The alert column is only a watchlist when asked for
+    if !args.watchlist {
+        for muts_entry in &mut muts_interest {
+            muts_entry.alert = None;
+        }
+    }
+    let provenance = args.catalog.map(Catalog::provenance);
+    if let Some(provenance) = &provenance {
+        status!("Mutation catalog: {provenance}");
+    }
 
     let dais_reader = create_reader(Some(&args.input_file))?;
     let dais: Vec<DaisInput> = read_tsv(dais_reader, false)?;
@@ -348,259 +1639,132 @@ pub fn variants_of_interest_process(args: VariantsArgs) -> Result<(), Box<dyn Er
     let ref_reader = create_reader(Some(&args.ref_file))?;
     let refs: Vec<RefInput> = read_tsv(ref_reader, true)?;
 
-    let mut writer = if let Some(ref file_path) = args.output_xsv {
-        let file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(file_path)?;
-        BufWriter::new(Either::Left(file))
+    let mut writer = output_writer(args.output_xsv.as_deref())?;
+
+    let refs_by_key = index_references(&refs);
+
+    // Entries are compared in parallel, keeping the order of the DAIS file
+    let mutations_vec: Vec<Entry> = dais
+        .par_iter()
+        .flat_map_iter(|dais_entry| {
+            references_of(&refs_by_key, dais_entry)
+                .flat_map(|ref_entry| compare_to_reference(dais_entry, ref_entry, &muts_interest))
+        })
+        .collect();
+
+    let sample_subtypes;
+    let mutations_vec = if &args.virus == "INFLUENZA" {
+        sample_subtypes = extract_unique_samples(&dais);
+        find_duplicate_aa_entries_with_diff_strain(&mutations_vec, &sample_subtypes)
     } else {
-        BufWriter::new(Either::Right(stdout()))
+        mutations_vec
     };
 
-    // Write the header
-    writeln!(
-        &mut writer,
-        "sample,reference_strain,gisaid_accession,ctype,dais_reference,protein,sample_codon,reference_codon,aa_mutation,phenotypic_consequence",
-    )?;
+    // The coverage at each variant, on the consensus DAIS was run on. The run and instrument
+    // of the rows aren't reported, so they are left empty
+    let coverage_rows = match &args.irma_path {
+        Some(irma_path) => {
+            let mut rows = coverage_data_collection(irma_path, "", "", "")?;
+            let padding = padding_offsets(&rows);
+            convert_coverage(&mut rows, &padding, args.coordinates);
+            rows
+        }
+        None => Vec::new(),
+    };
+    let coverage: Option<CoverageIndex> = args.irma_path.as_ref().map(|_| {
+        coverage_rows
+            .iter()
+            .map(|row| {
+                (
+                    (
+                        row.sample_id.as_deref().unwrap_or_default(),
+                        row.reference_name.as_str(),
+                        row.position,
+                    ),
+                    row,
+                )
+            })
+            .collect()
+    });
+
+    // Write all entries from mutations_vec at the end
+    match args.output_format {
+        OutputFormat::Xsv => write_xsv(
+            &mut writer,
+            &mutations_vec,
+            &delim,
+            coverage.as_ref(),
+            args.watchlist,
+        )?,
+        OutputFormat::Vcf => write_vcf(
+            &mut writer,
+            &mutations_vec,
+            coverage.as_ref(),
+            provenance.as_deref(),
+        )?,
+    }
+    if args.watchlist {
+        report_alerts(&mutations_vec, args.alerts_json.as_ref())?;
+    }
 
-    let mut mutations_vec: Vec<Entry> = Vec::new();
+    if let Some(path) = &args.matrix {
+        let matrix = mutation_matrix(
+            &dais,
+            &refs_by_key,
+            &muts_interest,
+            &mutations_vec,
+            coverage.as_ref(),
+        );
+        write_mutation_matrix(&matrix, path)?;
+    }
 
-    for dais_entry in &dais {
-        for ref_entry in &refs {
-            if dais_entry.ctype == ref_entry.ctype
-                && dais_entry.ref_strain == ref_entry.reference_id
-                && dais_entry.protein == ref_entry.protein
-            {
-                let nt_seq1: Nucleotides = ref_entry.cds_aln.clone().into();
-                let nt_seq2: Nucleotides = dais_entry.cds_aln.clone().into();
-
-                if nt_seq1.len() == nt_seq2.len() {
-                    let mut entry = Entry {
-                        sample_id: &dais_entry.sample_id,
-                        ref_strain: &ref_entry.isolate_name,
-                        gisaid_accession: &ref_entry.isolate_id,
-                        subtype: &ref_entry.subtype,
-                        ctype: &dais_entry.ctype,
-                        dais_ref: &dais_entry.ref_strain,
-                        protein: &dais_entry.protein,
-                        ref_codon: "NNN".to_string(),
-                        mut_codon: "NNN".to_string(),
-                        aa_position: 0,
-                        aa_ref: 'X',
-                        aa_mut: 'X',
-                        phenotypic_consequences: String::new(),
-                    };
-
-                    let mut tail_index = 0;
-                    let (codons1, tail1) = nt_seq1.as_codons();
-                    let (codons2, tail2) = nt_seq2.as_codons();
-
-                    for (index, (ref_codon, query_codon)) in codons1
-                        .iter()
-                        .zip(codons2.iter())
-                        .enumerate()
-                        .filter(|(_, (ref_chunk, query_chunk))| ref_chunk != query_chunk)
-                    {
-                        let aa_index = index + 1;
-                        tail_index = aa_index;
-                        let ref_aa = StdGeneticCode::translate_codon(ref_codon);
-                        let query_aa = StdGeneticCode::translate_codon(query_codon);
-
-                        if ref_codon != query_codon {
-                            entry.ref_codon = std::str::from_utf8(ref_codon)
-                                .expect("Invalid UTF-8 sequence")
-                                .to_string();
-                            entry.mut_codon = std::str::from_utf8(query_codon)
-                                .expect("Invalid UTF-8 sequence")
-                                .to_string();
-                            entry.aa_position = aa_index;
-                            entry.aa_ref = ref_aa as char;
-                            entry.aa_mut = query_aa as char;
-
-                            if entry.update_entry_from_alignment(
-                                &ref_entry.subtype,
-                                ref_aa,
-                                query_aa,
-                                &muts_interest,
-                            ) {
-                                mutations_vec.push(entry.clone()); // Save the entry to mutations_vec
-                            }
-                        }
-                    }
+    if let Some(path) = &args.glycosylation {
+        write_glycosylation_report(path, &dais, &refs_by_key, &delim)?;
+    }
 
-                    if tail1 != tail2 {
-                        let partial_codon = b'~';
-                        entry.ref_codon = std::str::from_utf8(tail1)
-                            .expect("Invalid UTF-8 sequence")
-                            .to_string();
-                        entry.mut_codon = std::str::from_utf8(tail2)
-                            .expect("Invalid UTF-8 sequence")
-                            .to_string();
-                        entry.aa_position = tail_index + 1;
-                        entry.aa_ref = '~';
-                        entry.aa_mut = '~';
-
-                        if entry.update_entry_from_alignment(
-                            &ref_entry.subtype,
-                            partial_codon,
-                            partial_codon,
-                            &muts_interest,
-                        ) {
-                            mutations_vec.push(entry.clone()); // Save the entry to mutations_vec
-                        }
-                    }
-                } else {
-                    let query = dais_entry.cds_aln.as_bytes();
-                    let reference = ref_entry.cds_aln.as_bytes();
-                    let (aligned_1, aligned_2) = {
-                        let (a1, a2) = align_sequences(query, reference);
-                        (Nucleotides::from(a1), Nucleotides::from(a2))
-                    };
-
-                    let mut entry = Entry {
-                        sample_id: &dais_entry.sample_id,
-                        ref_strain: &ref_entry.isolate_name,
-                        gisaid_accession: &ref_entry.isolate_id,
-                        subtype: &ref_entry.subtype,
-                        ctype: &dais_entry.ctype,
-                        dais_ref: &dais_entry.ref_strain,
-                        protein: &dais_entry.protein,
-                        ref_codon: "NNN".to_string(),
-                        mut_codon: "NNN".to_string(),
-                        aa_position: 0,
-                        aa_ref: 'X',
-                        aa_mut: 'X',
-                        phenotypic_consequences: String::new(),
-                    };
-
-                    let mut tail_index = 0;
-                    let (codons1, tail1) = aligned_1.as_codons();
-                    let (codons2, tail2) = aligned_2.as_codons();
-
-                    for (index, (ref_codon, query_codon)) in codons1
-                        .iter()
-                        .zip(codons2.iter())
-                        .enumerate()
-                        .filter(|(_, (ref_chunk, query_chunk))| ref_chunk != query_chunk)
-                    {
-                        let aa_index = index + 1;
-                        tail_index = aa_index;
-                        let ref_aa = StdGeneticCode::translate_codon(ref_codon);
-                        let query_aa = StdGeneticCode::translate_codon(query_codon);
-
-                        if ref_codon != query_codon {
-                            entry.ref_codon = std::str::from_utf8(ref_codon)
-                                .expect("Invalid UTF-8 sequence")
-                                .to_string();
-                            entry.mut_codon = std::str::from_utf8(query_codon)
-                                .expect("Invalid UTF-8 sequence")
-                                .to_string();
-                            entry.aa_position = aa_index;
-                            entry.aa_ref = ref_aa as char;
-                            entry.aa_mut = query_aa as char;
-
-                            if entry.update_entry_from_alignment(
-                                &ref_entry.subtype,
-                                ref_aa,
-                                query_aa,
-                                &muts_interest,
-                            ) {
-                                mutations_vec.push(entry.clone()); // Save the entry to mutations_vec
-                            }
-                        }
-                    }
+    Ok(())
+}
 
-                    if !tail1.is_empty() && tail1 != tail2 {
-                        let partial_codon = b'~';
-                        entry.ref_codon = std::str::from_utf8(tail1)
-                            .expect("Invalid UTF-8 sequence")
-                            .to_string();
-                        entry.mut_codon = std::str::from_utf8(tail2)
-                            .expect("Invalid UTF-8 sequence")
-                            .to_string();
-                        entry.aa_position = tail_index + 1;
-                        entry.aa_ref = '~';
-                        entry.aa_mut = '~';
-                        if entry.update_entry_from_alignment(
-                            &ref_entry.subtype,
-                            partial_codon,
-                            partial_codon,
-                            &muts_interest,
-                        ) {
-                            mutations_vec.push(entry.clone()); // Save the entry to mutations_vec
-                        }
-                    }
-                }
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> Entry<'static> {
+        Entry {
+            sample_id: "s1",
+            ref_strain: "A/Victoria/4897/2022",
+            gisaid_accession: "EPI_ISL_1",
+            subtype: "H1N1",
+            ctype: "A_HA_H1",
+            dais_ref: "CALI07",
+            protein: "HA",
+            ref_codon: "GAT".to_string(),
+            mut_codon: "GGT".to_string(),
+            aa_ref: 'D',
+            aa_position: 222,
+            aa_mut: 'G',
+            mixed_residues: Vec::new(),
+            phenotypic_consequences: String::new(),
+            alert: false,
+            indel: None,
+            consensus_position: Some(700),
         }
     }
 
-    if &args.virus == "INFLUENZA" {
-        let sample_subtypes = extract_unique_samples(&dais);
-
-        let mutations_vec =
-            find_duplicate_aa_entries_with_diff_strain(&mutations_vec, &sample_subtypes);
-
-        // Write all entries from mutations_vec at the end
-        for entry in &mutations_vec {
-            let Entry {
-                sample_id,
-                ref_strain,
-                gisaid_accession,
-                subtype: _,
-                ctype,
-                dais_ref,
-                protein,
-                ref_codon,
-                mut_codon,
-                aa_ref,
-                aa_position,
-                aa_mut,
-                phenotypic_consequences,
-            } = entry;
-            let d = &delim;
-
-            writeln!(
-                &mut writer,
-                "{sample_id}{d}{ref_strain}{d}{gisaid_accession}{d}\
-                {ctype}{d}{dais_ref}{d}{protein}{d}\
-                {ref_codon}{d}{mut_codon}{d}\
-                {aa_ref}:{aa_position}:{aa_mut}{d}\
-                {phenotypic_consequences}",
-            )?;
-        }
-    } else {
-        // Write all entries from mutations_vec at the end
-        for entry in &mutations_vec {
-            let Entry {
-                sample_id,
-                ref_strain,
-                gisaid_accession,
-                subtype: _,
-                ctype,
-                dais_ref,
-                protein,
-                ref_codon,
-                mut_codon,
-                aa_ref,
-                aa_position,
-                aa_mut,
-                phenotypic_consequences,
-            } = entry;
-            let d = &delim;
-
-            writeln!(
-                &mut writer,
-                "{sample_id}{d}{ref_strain}{d}{gisaid_accession}{d}\
-                {ctype}{d}{dais_ref}{d}{protein}{d}\
-                {ref_codon}{d}{mut_codon}{d}\
-                {aa_ref}:{aa_position}:{aa_mut}{d}\
-                {phenotypic_consequences}",
-            )?;
-        }
+    #[test]
+    fn unmatched_coverage_rows_keep_every_field() {
+        let coverage = CoverageIndex::new();
+        let mut out = Vec::new();
+        write_xsv(&mut out, &[entry()], "\t", Some(&coverage), true).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0].split('\t').count(),
+            lines[1].split('\t').count(),
+            "{out}"
+        );
+        assert!(lines[1].ends_with("\t\t\t"));
     }
-
-    Ok(())
 }
@@ -0,0 +1,93 @@
+// Quick screen for ONT adapter and barcode sequences left on reads. Untrimmed reads still
+// assemble, but the adapter sequence drags down IRMA's consensus at the segment ends.
+use crate::utils::fastq_read::open_fastq_file;
+use std::{collections::HashSet, path::Path};
+
+/// Known ONT adapter and native barcode sequences (as listed by Porechop)
+const ONT_ADAPTERS: [(&str, &str); 14] = [
+    ("Ligation adapter", "AATGTACTTCGTTCAGTTACGTATTGCT"),
+    (
+        "Rapid adapter",
+        "GTTTTCGCATTTATCGTGAAACGCTTTCGCGTTTTTCGTGCGCCGCTTCA",
+    ),
+    ("NB01", "CACAAAGACACCGACAACTTTCTT"),
+    ("NB02", "ACAGACGACTACAAACGGAATCGA"),
+    ("NB03", "CCTGGTAACTGGGACACAAGACTC"),
+    ("NB04", "TAGGGAAACACGATAGAATCCGAA"),
+    ("NB05", "AAGGTTACACAAACCCTGGACAAG"),
+    ("NB06", "GACTACTTTCTGCCTTTGCGAGAA"),
+    ("NB07", "AAGGATTCATTCCCACGGTAACAC"),
+    ("NB08", "ACGTAACTTGGTTTGTTCCCTGAA"),
+    ("NB09", "AACCAAGACTCGCTGTGCCTAGTT"),
+    ("NB10", "GAGAGGACAAAGGTTTCAACGCTT"),
+    ("NB11", "TCCATTCCCTCCGATAGATGAAAC"),
+    ("NB12", "TCCGATTCTGCTTCTTTCTACCTG"),
+];
+
+/// Length of the exact matches looked for. Long enough that a chance hit in viral sequence is
+/// unlikely, short enough to tolerate a few basecalling errors in the adapter.
+const KMER_LEN: usize = 16;
+
+/// Adapters are only expected this close to either end of a read
+const END_WINDOW: usize = 150;
+
+fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .rev()
+        .map(|base| match base.to_ascii_uppercase() {
+            b'A' => b'T',
+            b'T' => b'A',
+            b'C' => b'G',
+            b'G' => b'C',
+            other => other,
+        })
+        .collect()
+}
+
+/// Every k-mer of the adapters on both strands
+fn adapter_kmers() -> HashSet<Vec<u8>> {
+    let mut kmers = HashSet::new();
+    for (_, adapter) in ONT_ADAPTERS {
+        for strand in [
+            adapter.as_bytes().to_vec(),
+            reverse_complement(adapter.as_bytes()),
+        ] {
+            for kmer in strand.windows(KMER_LEN) {
+                kmers.insert(kmer.to_vec());
+            }
+        }
+    }
+    kmers
+}
+
+fn has_adapter(sequence: &[u8], kmers: &HashSet<Vec<u8>>) -> bool {
+    let sequence = sequence.to_ascii_uppercase();
+    let start = &sequence[..sequence.len().min(END_WINDOW)];
+    let end = &sequence[sequence.len().saturating_sub(END_WINDOW)..];
+    [start, end]
+        .iter()
+        .any(|window| window.windows(KMER_LEN).any(|kmer| kmers.contains(kmer)))
+}
+
+/// Fraction of the first `n_reads` reads that still carry an ONT adapter or barcode near either
+/// end. Returns None if the file has no reads.
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn untrimmed_fraction<P: AsRef<Path>>(
+    path: P,
+    n_reads: usize,
+) -> std::io::Result<Option<f64>> {
+    let kmers = adapter_kmers();
+    let mut screened = 0usize;
+    let mut untrimmed = 0usize;
+    for result in open_fastq_file(path)?.take(n_reads) {
+        let record = result?;
+        screened += 1;
+        if has_adapter(record.sequence.as_bytes(), &kmers) {
+            untrimmed += 1;
+        }
+    }
+    if screened == 0 {
+        return Ok(None);
+    }
+    Ok(Some(untrimmed as f64 / screened as f64))
+}
@@ -29,3 +29,49 @@ pub fn align_sequences<'a>(query: &'a [u8], reference: &'a [u8]) -> (Vec<u8>, Ve
 
     alignment.get_aligned_seqs(reference, query)
 }
+
+/// How the distance tools line sequences up before comparing them position by position
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum AlignMode {
+    /// Compare the sequences as given, which must already be aligned
+    None,
+    /// Align every pair of sequences before comparing them
+    Pairwise,
+    /// Align every sequence to one reference sequence and compare them in its coordinates
+    Reference,
+}
+
+/// Aligns `query` to `reference` and returns the 0-based start of the aligned region in the
+/// reference with the aligned reference and query, gaps as `-`. `None` when they don't align.
+#[must_use]
+pub fn align_with_offset(query: &[u8], reference: &[u8]) -> Option<(usize, Vec<u8>, Vec<u8>)> {
+    let (aligned_ref, aligned_query) = align_sequences(query, reference);
+    let ungapped_ref: Vec<u8> = aligned_ref.iter().copied().filter(|&b| b != b'-').collect();
+    if ungapped_ref.is_empty() {
+        return None;
+    }
+    // The local alignment only returns the aligned region, so find where it starts
+    let start = reference
+        .windows(ungapped_ref.len())
+        .position(|window| window.eq_ignore_ascii_case(&ungapped_ref))?;
+    Some((start, aligned_ref, aligned_query))
+}
+
+/// Aligns `query` to `reference` and returns it in reference coordinates: one base per
+/// reference position, `-` where the query has a deletion or doesn't reach, with insertions
+/// relative to the reference dropped. Sequences projected onto the same reference can then be
+/// compared position by position.
+#[must_use]
+pub fn project_onto_reference(query: &[u8], reference: &[u8]) -> Vec<u8> {
+    let mut projected = vec![b'-'; reference.len()];
+    if let Some((start, aligned_ref, aligned_query)) = align_with_offset(query, reference) {
+        let mut position = start;
+        for (ref_base, query_base) in aligned_ref.into_iter().zip(aligned_query) {
+            if ref_base != b'-' {
+                projected[position] = query_base;
+                position += 1;
+            }
+        }
+    }
+    projected
+}
@@ -0,0 +1,204 @@
+use flate2::read::MultiGzDecoder;
+use std::{
+    fs::File,
+    io::{self, BufReader, ErrorKind, Read},
+    path::Path,
+};
+
+/// The subset of a BAM alignment record needed for position level tracing.
+#[derive(Debug, Clone)]
+pub struct BamRecord {
+    pub read_name: String,
+    pub ref_id: i32,
+    /// 0-based leftmost mapping position
+    pub pos: i32,
+    pub mapq: u8,
+    pub flag: u16,
+    /// CIGAR operations as (length, op) where op is one of `MIDNSHP=X`
+    pub cigar: Vec<(u32, char)>,
+    pub sequence: Vec<u8>,
+    pub quality: Vec<u8>,
+}
+
+impl BamRecord {
+    #[must_use]
+    pub fn is_unmapped(&self) -> bool {
+        self.flag & 0x4 != 0
+    }
+
+    #[must_use]
+    pub fn is_reverse(&self) -> bool {
+        self.flag & 0x10 != 0
+    }
+
+    /// Walks the CIGAR to find what this read reports at a 0-based reference position.
+    /// Returns `None` if the read does not span the position, `Some((b'-', None))` for a
+    /// deletion and otherwise the base and its phred quality.
+    #[must_use]
+    pub fn base_at(&self, ref_pos: i32) -> Option<(u8, Option<u8>)> {
+        let mut r = i64::from(self.pos);
+        let mut q = 0usize;
+        let target = i64::from(ref_pos);
+        for &(len, op) in &self.cigar {
+            let len_i = i64::from(len);
+            match op {
+                'M' | '=' | 'X' => {
+                    if target >= r && target < r + len_i {
+                        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                        let idx = q + (target - r) as usize;
+                        // 0xFF means qualities were not stored
+                        let qual = self.quality.get(idx).copied().filter(|&q| q != 0xff);
+                        return Some((*self.sequence.get(idx)?, qual));
+                    }
+                    r += len_i;
+                    q += len as usize;
+                }
+                'D' | 'N' => {
+                    if target >= r && target < r + len_i {
+                        return Some((b'-', None));
+                    }
+                    r += len_i;
+                }
+                'I' | 'S' => q += len as usize,
+                _ => {}
+            }
+            if r > target {
+                break;
+            }
+        }
+        None
+    }
+}
+
+/// Minimal reader for BAM files as written by IRMA. BGZF is a series of gzip members, so the
+/// whole file can be streamed through a [`MultiGzDecoder`].
+pub struct BamReader<R: Read> {
+    inner: R,
+    pub references: Vec<String>,
+}
+
+const SEQ_CODES: &[u8; 16] = b"=ACMGRSVTWYHKDBN";
+
+fn read_i32<R: Read>(r: &mut R) -> io::Result<i32> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(i32::from_le_bytes(buf))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+#[allow(clippy::cast_sign_loss)]
+fn checked_len(n: i32) -> io::Result<usize> {
+    if n < 0 {
+        Err(invalid("Negative length in BAM file"))
+    } else {
+        Ok(n as usize)
+    }
+}
+
+impl BamReader<BufReader<MultiGzDecoder<File>>> {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        BamReader::new(BufReader::new(MultiGzDecoder::new(file)))
+    }
+}
+
+impl<R: Read> BamReader<R> {
+    /// Reads the BAM header and reference dictionary.
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut magic = [0u8; 4];
+        inner.read_exact(&mut magic)?;
+        if &magic != b"BAM\x01" {
+            return Err(invalid("Not a BAM file"));
+        }
+
+        let l_text = checked_len(read_i32(&mut inner)?)?;
+        io::copy(&mut (&mut inner).take(l_text as u64), &mut io::sink())?;
+
+        let n_ref = checked_len(read_i32(&mut inner)?)?;
+        let mut references = Vec::with_capacity(n_ref);
+        for _ in 0..n_ref {
+            let l_name = checked_len(read_i32(&mut inner)?)?;
+            let mut name = vec![0u8; l_name];
+            inner.read_exact(&mut name)?;
+            name.pop(); // NUL terminator
+            references.push(String::from_utf8_lossy(&name).to_string());
+            read_i32(&mut inner)?; // reference length
+        }
+
+        Ok(BamReader { inner, references })
+    }
+
+    /// Returns the next alignment record or `None` at the end of the file.
+    pub fn next_record(&mut self) -> io::Result<Option<BamRecord>> {
+        let mut size_buf = [0u8; 4];
+        match self.inner.read_exact(&mut size_buf) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let block_size = checked_len(i32::from_le_bytes(size_buf))?;
+        let mut block = vec![0u8; block_size];
+        self.inner.read_exact(&mut block)?;
+        if block.len() < 32 {
+            return Err(invalid("Truncated BAM record"));
+        }
+
+        let i32_at = |b: &[u8]| i32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+        let u16_at = |b: &[u8]| u16::from_le_bytes([b[0], b[1]]);
+
+        let ref_id = i32_at(&block[0..4]);
+        let pos = i32_at(&block[4..8]);
+        let l_read_name = block[8] as usize;
+        let mapq = block[9];
+        let n_cigar_op = u16_at(&block[12..14]) as usize;
+        let flag = u16_at(&block[14..16]);
+        let l_seq = checked_len(i32_at(&block[16..20]))?;
+
+        let mut offset = 32;
+        let name_end = offset + l_read_name;
+        let cigar_end = name_end + 4 * n_cigar_op;
+        let seq_end = cigar_end + l_seq.div_ceil(2);
+        let qual_end = seq_end + l_seq;
+        if l_read_name == 0 || block.len() < qual_end {
+            return Err(invalid("Truncated BAM record"));
+        }
+
+        let read_name = String::from_utf8_lossy(&block[offset..name_end - 1]).to_string();
+        offset = name_end;
+
+        let mut cigar = Vec::with_capacity(n_cigar_op);
+        while offset < cigar_end {
+            #[allow(clippy::cast_sign_loss)]
+            let op = i32_at(&block[offset..offset + 4]) as u32;
+            let code = b"MIDNSHP=X"
+                .get((op & 0xf) as usize)
+                .copied()
+                .unwrap_or(b'?');
+            cigar.push((op >> 4, code as char));
+            offset += 4;
+        }
+
+        let sequence = (0..l_seq)
+            .map(|i| {
+                let byte = block[cigar_end + i / 2];
+                let code = if i % 2 == 0 { byte >> 4 } else { byte & 0xf };
+                SEQ_CODES[code as usize]
+            })
+            .collect();
+        let quality = block[seq_end..qual_end].to_vec();
+
+        Ok(Some(BamRecord {
+            read_name,
+            ref_id,
+            pos,
+            mapq,
+            flag,
+            cigar,
+            sequence,
+            quality,
+        }))
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    error::Error,
+    fmt,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Exit code of a run stopped by a termination signal, which the pipeline takes as
+/// "preempted, resume later" (`EX_TEMPFAIL`)
+pub const PREEMPTED_EXIT_CODE: i32 = 75;
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Cancels the running work on SIGTERM, SIGINT or SIGHUP instead of dying mid-write. Only
+/// processes that check for it should install this, the rest keep the default of exiting.
+/// A second signal exits right away
+pub fn install_signal_handler() {
+    let installed = ctrlc::set_handler(|| {
+        if CANCELLED.swap(true, Ordering::SeqCst) {
+            std::process::exit(PREEMPTED_EXIT_CODE);
+        }
+        eprintln!("Warning: termination signal received, stopping after the current step");
+    });
+    if let Err(e) = installed {
+        eprintln!("Warning: could not install the signal handler: {e}");
+    }
+}
+
+/// Error of work stopped by a termination signal
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("cancelled by a termination signal")
+    }
+}
+
+impl Error for Cancelled {}
+
+#[must_use]
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// `Err(Cancelled)` once a termination signal was received, to check between steps
+pub fn check_cancelled() -> Result<(), Cancelled> {
+    if is_cancelled() {
+        Err(Cancelled)
+    } else {
+        Ok(())
+    }
+}
+
+/// Whether a process failed because it was cancelled, so it should exit with
+/// [`PREEMPTED_EXIT_CODE`]
+#[must_use]
+pub fn is_cancellation(error: &(dyn Error + 'static)) -> bool {
+    error.is::<Cancelled>()
+}
+
+/// An output written as `<path>.tmp` and moved into place once finished, so a cancelled or
+/// failed run never leaves a half-written file behind. Dropped unfinished, the `.tmp` file is
+/// removed
+pub struct PartialFile {
+    path: PathBuf,
+    tmp_path: PathBuf,
+    finished: bool,
+}
+
+impl PartialFile {
+    pub fn create(path: &Path) -> std::io::Result<(PartialFile, File)> {
+        let mut tmp_name = path.as_os_str().to_owned();
+        tmp_name.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_name);
+        let file = File::create(&tmp_path)?;
+        Ok((
+            PartialFile {
+                path: path.to_path_buf(),
+                tmp_path,
+                finished: false,
+            },
+            file,
+        ))
+    }
+
+    /// Moves the finished file into place
+    pub fn finish(mut self) -> std::io::Result<()> {
+        fs::rename(&self.tmp_path, &self.path)?;
+        self.finished = true;
+        Ok(())
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = fs::remove_file(&self.tmp_path);
+        }
+    }
+}
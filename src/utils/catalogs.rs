@@ -0,0 +1,75 @@
+use clap::ValueEnum;
+use std::fmt::{self, Write};
+
+/// Mutation catalogs shipped with MIRA, to use in place of a mutations of interest file. Each
+/// is a mutations of interest table headed by `# catalog:`, `# version:` and `# source:` lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Catalog {
+    /// Influenza NA substitutions reducing inhibition by neuraminidase inhibitors
+    FluNai,
+    /// Influenza PA substitutions reducing susceptibility to baloxavir
+    FluPai,
+    /// SARS-CoV-2 spike, Mpro and RNA polymerase substitutions reducing susceptibility to
+    /// monoclonal antibodies and antivirals
+    Sc2Therapeutics,
+}
+
+impl fmt::Display for Catalog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Catalog::FluNai => "flu-nai",
+            Catalog::FluPai => "flu-pai",
+            Catalog::Sc2Therapeutics => "sc2-therapeutics",
+        })
+    }
+}
+
+impl Catalog {
+    fn text(self) -> &'static str {
+        match self {
+            Catalog::FluNai => include_str!("../../assets/catalogs/flu_nai.tsv"),
+            Catalog::FluPai => include_str!("../../assets/catalogs/flu_pai.tsv"),
+            Catalog::Sc2Therapeutics => include_str!("../../assets/catalogs/sc2_therapeutics.tsv"),
+        }
+    }
+
+    // Value of a `# key: value` line of the catalog's header
+    fn header(self, key: &str) -> &'static str {
+        self.text()
+            .lines()
+            .filter_map(|line| line.strip_prefix('#'))
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim() == key)
+            .map_or("", |(_, value)| value.trim())
+    }
+
+    #[must_use]
+    pub fn version(self) -> &'static str {
+        self.header("version")
+    }
+
+    /// Where the catalog's mutations were curated from
+    #[must_use]
+    pub fn source(self) -> &'static str {
+        self.header("source")
+    }
+
+    /// The catalog's mutations as a tab separated mutations of interest table, without its
+    /// header
+    #[must_use]
+    pub fn rows(self) -> String {
+        self.text()
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.trim().is_empty())
+            .fold(String::new(), |mut rows, line| {
+                let _ = writeln!(rows, "{line}");
+                rows
+            })
+    }
+
+    /// The catalog, version and source, as recorded in the outputs made with it
+    #[must_use]
+    pub fn provenance(self) -> String {
+        format!("{self} version {} ({})", self.version(), self.source())
+    }
+}
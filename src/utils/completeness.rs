@@ -0,0 +1,65 @@
+use crate::utils::output::output_writer;
+use std::{io::Write, path::Path};
+
+/// Ns and alignment gaps, positions without a base call
+#[must_use]
+pub fn is_missing(base: u8) -> bool {
+    matches!(base, b'N' | b'n' | b'-' | b'.')
+}
+
+/// Start and end (exclusive) of a sequence without its leading and trailing Ns and gaps.
+/// Sequences without a base call are empty spans at 0
+#[must_use]
+pub fn terminal_trimmed_span(seq: &[u8]) -> (usize, usize) {
+    match seq.iter().position(|&b| !is_missing(b)) {
+        Some(start) => {
+            let end = seq
+                .iter()
+                .rposition(|&b| !is_missing(b))
+                .map_or(start, |e| e + 1);
+            (start, end)
+        }
+        None => (0, 0),
+    }
+}
+
+/// Fraction of `length` positions that are base calls, not Ns or gaps. `length` is the
+/// longest input sequence, so sequences that are short as well as those full of Ns count as
+/// incomplete
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn completeness(seq: &[u8], length: usize) -> f64 {
+    if length == 0 {
+        return 0.0;
+    }
+    seq.iter().filter(|&&b| !is_missing(b)).count() as f64 / length as f64
+}
+
+/// Writes the sequences left out for being below `min_completeness`, one per row with its
+/// completeness, and warns how many there were
+pub fn write_excluded_report(
+    path: Option<&Path>,
+    excluded: &[(String, f64)],
+    min_completeness: f64,
+    delim: char,
+) -> std::io::Result<()> {
+    if !excluded.is_empty() {
+        eprintln!(
+            "Warning: {} sequences below a completeness of {min_completeness} were excluded",
+            excluded.len()
+        );
+    }
+    let Some(path) = path else {
+        return Ok(());
+    };
+
+    let mut writer = output_writer(Some(path))?;
+    writeln!(writer, "sequence{delim}completeness{delim}min_completeness")?;
+    for (name, completeness) in excluded {
+        writeln!(
+            writer,
+            "{name}{delim}{completeness:.4}{delim}{min_completeness}"
+        )?;
+    }
+    writer.flush()
+}
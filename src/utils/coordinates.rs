@@ -0,0 +1,220 @@
+use crate::io::data_ingest::{CoverageData, MinorVariantsData};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt};
+
+/// Coordinate space of a position: the consensus as IRMA assembled it (`raw`), the padded
+/// consensus (`pad.fa`) with the reference ends IRMA had no reads for filled with `N`
+/// (`padded`), or the reference HMM IRMA aligned the reads to (`hmm`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum CoordinateSystem {
+    #[default]
+    Raw,
+    Padded,
+    Hmm,
+}
+
+impl fmt::Display for CoordinateSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CoordinateSystem::Raw => "raw",
+            CoordinateSystem::Padded => "padded",
+            CoordinateSystem::Hmm => "hmm",
+        })
+    }
+}
+
+impl CoordinateSystem {
+    /// Coordinates of the consensus sequences MIRA reports for a virus: the IRMA consensus for
+    /// flu, the spike alignment for sc2-spike and the padded consensus for the rest
+    #[must_use]
+    pub fn of_consensus(virus: &str) -> Self {
+        match virus.to_lowercase().as_str() {
+            "flu" => CoordinateSystem::Raw,
+            "sc2-spike" => CoordinateSystem::Hmm,
+            _ => CoordinateSystem::Padded,
+        }
+    }
+}
+
+/// Leading `N`s of the padded consensus of each sample and reference, the difference between
+/// padded and raw positions
+pub type PaddingOffsets = HashMap<(String, String), i32>;
+
+/// Padding of every sample and reference in raw coverage rows: the HMM position of the first
+/// consensus base less its raw position. The padded consensus starts at HMM position 1, so
+/// that's how many `N`s IRMA put in front of it
+#[must_use]
+pub fn padding_offsets(rows: &[CoverageData]) -> PaddingOffsets {
+    let mut first_positions: HashMap<(String, String), (i32, i32)> = HashMap::new();
+    for row in rows {
+        let (CoordinateSystem::Raw, Some(hmm_position)) = (row.coordinate_system, row.hmm_position)
+        else {
+            continue;
+        };
+        let key = (
+            row.sample_id.clone().unwrap_or_default(),
+            row.reference_name.clone(),
+        );
+        let first = first_positions
+            .entry(key)
+            .or_insert((row.position, hmm_position));
+        if row.position < first.0 {
+            *first = (row.position, hmm_position);
+        }
+    }
+
+    first_positions
+        .into_iter()
+        .map(|(key, (raw, hmm))| (key, (hmm - raw).max(0)))
+        .collect()
+}
+
+/// A position in `to` coordinates from its raw and HMM positions, or `None` when it has none
+/// there: insertions against the reference have no HMM position, and positions only known in
+/// HMM coordinates can't be placed on the consensus
+#[must_use]
+pub fn convert_position(
+    raw: Option<i32>,
+    hmm: Option<i32>,
+    padding: i32,
+    to: CoordinateSystem,
+) -> Option<i32> {
+    match to {
+        CoordinateSystem::Raw => raw,
+        CoordinateSystem::Padded => raw.map(|position| position + padding),
+        CoordinateSystem::Hmm => hmm,
+    }
+}
+
+fn padding_of(offsets: &PaddingOffsets, sample: Option<&String>, reference: &str) -> i32 {
+    offsets
+        .get(&(sample.cloned().unwrap_or_default(), reference.to_string()))
+        .copied()
+        .unwrap_or(0)
+}
+
+/// Converts coverage rows to `to` coordinates, recording them on every row. Rows without a
+/// position there are dropped, and how many is returned
+pub fn convert_coverage(
+    rows: &mut Vec<CoverageData>,
+    offsets: &PaddingOffsets,
+    to: CoordinateSystem,
+) -> usize {
+    let before = rows.len();
+    rows.retain_mut(|row| {
+        if row.coordinate_system == to {
+            return true;
+        }
+        let padding = if row.coordinate_system == CoordinateSystem::Padded
+            || to == CoordinateSystem::Padded
+        {
+            padding_of(offsets, row.sample_id.as_ref(), &row.reference_name)
+        } else {
+            0
+        };
+        let raw = match row.coordinate_system {
+            CoordinateSystem::Raw => Some(row.position),
+            CoordinateSystem::Padded => Some(row.position - padding),
+            CoordinateSystem::Hmm => None,
+        };
+        match convert_position(raw, row.hmm_coordinate(), padding, to) {
+            Some(position) => {
+                row.position = position;
+                row.coordinate_system = to;
+                true
+            }
+            None => false,
+        }
+    });
+    before - rows.len()
+}
+
+/// Moves the raw positions of minor variants to `to` coordinates, with the padding of their
+/// coverage. Variants without a position there are dropped, and how many is returned
+pub fn convert_minor_variants(
+    variants: &mut Vec<MinorVariantsData>,
+    offsets: &PaddingOffsets,
+    to: CoordinateSystem,
+) -> usize {
+    let before = variants.len();
+    variants.retain_mut(|variant| {
+        let padding = padding_of(offsets, variant.sample_id.as_ref(), &variant.reference);
+        match convert_position(
+            Some(variant.sample_position),
+            variant.reference_position,
+            padding,
+            to,
+        ) {
+            Some(position) => {
+                variant.sample_position = position;
+                true
+            }
+            None => false,
+        }
+    });
+    before - variants.len()
+}
+
+/// Insertions DAIS took out of a sample's `cds_aln`, found by walking it along `cds_seq`, as
+/// how many of the aligned bases come before each and its bases. Where an insertion could go
+/// in more than one place it's put as late as it can be
+#[must_use]
+pub fn stripped_insertions(cds_seq: &str, cds_aln: &str) -> Vec<(usize, Vec<u8>)> {
+    let sequence = cds_seq.as_bytes();
+    let mut insertions = Vec::new();
+    let mut next = 0;
+    for (before, base) in cds_aln.bytes().filter(u8::is_ascii_alphabetic).enumerate() {
+        let start = next;
+        while next < sequence.len() && !sequence[next].eq_ignore_ascii_case(&base) {
+            next += 1;
+        }
+        if next == sequence.len() {
+            break;
+        }
+        if next > start {
+            insertions.push((before, sequence[start..next].to_vec()));
+        }
+        next += 1;
+    }
+    insertions
+}
+
+/// 1-based position in a DAIS `cds_seq` of the base `ordinal` bases into its `cds_aln`,
+/// counting back in the insertions DAIS took out of the alignment
+#[must_use]
+pub fn dais_cds_position(insertions: &[(usize, Vec<u8>)], ordinal: usize) -> usize {
+    let inserted: usize = insertions
+        .iter()
+        .filter(|(before, _)| *before <= ordinal)
+        .map(|(_, bases)| bases.len())
+        .sum();
+    ordinal + 1 + inserted
+}
+
+/// Position in the DAIS query, the consensus it was run on, of a 1-based CDS position. DAIS
+/// pairs them up as `1..26;715..982` ranges in its query and CDS coordinate columns
+#[must_use]
+pub fn dais_query_position(
+    query_coordinates: &str,
+    cds_coordinates: &str,
+    cds_position: usize,
+) -> Option<i32> {
+    let ranges = |coordinates: &str| -> Vec<(usize, usize)> {
+        coordinates
+            .split(';')
+            .filter_map(|range| {
+                let (start, end) = range.trim().split_once("..")?;
+                Some((start.parse().ok()?, end.parse().ok()?))
+            })
+            .collect()
+    };
+    ranges(cds_coordinates)
+        .into_iter()
+        .zip(ranges(query_coordinates))
+        .find(|((start, end), _)| (*start..=*end).contains(&cds_position))
+        .and_then(|((cds_start, _), (query_start, _))| {
+            i32::try_from(query_start + cds_position - cds_start).ok()
+        })
+}
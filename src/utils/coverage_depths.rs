@@ -0,0 +1,217 @@
+use crate::io::data_ingest::{CoverageData, coverage_data_collection};
+use crate::utils::coordinates::{CoordinateSystem, convert_coverage, padding_offsets};
+use arrow::{
+    array::{Array, AsArray},
+    datatypes::Int32Type,
+};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use std::{collections::HashMap, fs::File, path::Path};
+
+/// Depth by 0-based position, keyed by sample and reference
+pub type CoverageDepths = HashMap<(String, String), Vec<Option<u32>>>;
+
+fn invalid_input(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
+
+// Coverage positions are 1-based
+fn set_depth(
+    depths: &mut CoverageDepths,
+    sample: &str,
+    reference: &str,
+    position: i32,
+    depth: i32,
+) {
+    let Some(index) = usize::try_from(position)
+        .ok()
+        .and_then(|p| p.checked_sub(1))
+    else {
+        return;
+    };
+    let sample_depths = depths
+        .entry((sample.to_string(), reference.to_string()))
+        .or_default();
+    if sample_depths.len() <= index {
+        sample_depths.resize(index + 1, None);
+    }
+    sample_depths[index] = Some(u32::try_from(depth).unwrap_or(0));
+}
+
+/// Depth by position for every sample and reference of a MIRA coverage parquet file. Files with
+/// a `coordinate_system` column must be in `coordinates`, as they can't be converted
+fn read_coverage_parquet(
+    path: &Path,
+    coordinates: CoordinateSystem,
+) -> Result<CoverageDepths, std::io::Error> {
+    let file = File::open(path).map_err(|e| {
+        std::io::Error::new(
+            e.kind(),
+            format!("Could not open file '{}': {e}", path.display()),
+        )
+    })?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .and_then(ParquetRecordBatchReaderBuilder::build)
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+
+    let expected = coordinates.to_string();
+    let mut depths = CoverageDepths::new();
+    for batch in reader {
+        let batch = batch.map_err(|e| std::io::Error::other(e.to_string()))?;
+        let missing =
+            |column: &str| invalid_input(format!("{} has no {column} column", path.display()));
+        let samples = batch
+            .column_by_name("sample_id")
+            .and_then(|c| c.as_string_opt::<i32>())
+            .ok_or_else(|| missing("sample_id"))?;
+        let references = batch
+            .column_by_name("reference_name")
+            .and_then(|c| c.as_string_opt::<i32>())
+            .ok_or_else(|| missing("reference_name"))?;
+        let positions = batch
+            .column_by_name("position")
+            .and_then(|c| c.as_primitive_opt::<Int32Type>())
+            .ok_or_else(|| missing("position"))?;
+        let coverage_depths = batch
+            .column_by_name("coverage_depth")
+            .and_then(|c| c.as_primitive_opt::<Int32Type>())
+            .ok_or_else(|| missing("coverage_depth"))?;
+        let coordinate_systems = batch
+            .column_by_name("coordinate_system")
+            .and_then(|c| c.as_string_opt::<i32>());
+
+        for row in 0..batch.num_rows() {
+            if samples.is_null(row) || references.is_null(row) || positions.is_null(row) {
+                continue;
+            }
+            if let Some(systems) = coordinate_systems
+                && !systems.is_null(row)
+                && systems.value(row) != expected
+            {
+                return Err(invalid_input(format!(
+                    "{} is in {} coordinates, not {coordinates}. Use the IRMA run directory to convert them",
+                    path.display(),
+                    systems.value(row)
+                )));
+            }
+            set_depth(
+                &mut depths,
+                samples.value(row),
+                references.value(row),
+                positions.value(row),
+                coverage_depths.value(row),
+            );
+        }
+    }
+    Ok(depths)
+}
+
+/// Depth by position in `coordinates` for every sample and reference, from a MIRA coverage
+/// parquet file or the IRMA coverage tables of a run directory
+/// (`<dir>/<sample>/IRMA/<sample>/tables/*coverage.txt`)
+pub fn read_coverage_depths(
+    path: &Path,
+    coordinates: CoordinateSystem,
+) -> Result<CoverageDepths, std::io::Error> {
+    if !path.is_dir() {
+        return read_coverage_parquet(path, coordinates);
+    }
+
+    let mut rows = coverage_data_collection(path, "", "", "")
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    if rows.is_empty() {
+        return Err(invalid_input(format!(
+            "No IRMA coverage tables found in {}",
+            path.display()
+        )));
+    }
+    let padding = padding_offsets(&rows);
+    convert_coverage(&mut rows, &padding, coordinates);
+    Ok(coverage_rows_depths(&rows))
+}
+
+/// Depth by position for every sample and reference of IRMA coverage rows, in the coordinates
+/// the rows are in
+#[must_use]
+pub fn coverage_rows_depths(rows: &[CoverageData]) -> CoverageDepths {
+    let mut depths = CoverageDepths::new();
+    for row in rows {
+        let Some(sample) = &row.sample_id else {
+            continue;
+        };
+        set_depth(
+            &mut depths,
+            sample,
+            &row.reference_name,
+            row.position,
+            row.coverage_depth,
+        );
+    }
+    depths
+}
+
+/// Depths along a FASTA sequence. The sequence is matched to the coverage sample with the
+/// longest ID that is its name or a `_` or `|` separated prefix of it, and bases (not gaps)
+/// take the coverage positions in order.
+pub fn sequence_depths(
+    name: &str,
+    raw: &[u8],
+    coverage: &CoverageDepths,
+    segment: Option<&str>,
+) -> Result<Option<Vec<Option<u32>>>, std::io::Error> {
+    let matches_sample = |sample: &str| {
+        name == sample
+            || name
+                .strip_prefix(sample)
+                .is_some_and(|rest| rest.starts_with(['_', '|']))
+    };
+    let Some(sample_len) = coverage
+        .keys()
+        .filter(|(sample, _)| matches_sample(sample))
+        .map(|(sample, _)| sample.len())
+        .max()
+    else {
+        return Ok(None);
+    };
+    let candidates: Vec<_> = coverage
+        .iter()
+        .filter(|((sample, reference), _)| {
+            sample.len() == sample_len
+                && matches_sample(sample)
+                && segment.is_none_or(|segment| reference.contains(segment))
+        })
+        .collect();
+    let depths = match candidates.as_slice() {
+        [] => return Ok(None),
+        [(_, depths)] => depths,
+        _ => {
+            return Err(invalid_input(format!(
+                "{name} matches several references in the coverage file, pick one with --coverage-segment"
+            )));
+        }
+    };
+
+    let mut coverage_positions = depths.iter();
+    Ok(Some(
+        raw.iter()
+            .map(|&base| {
+                if matches!(base, b'-' | b'.') {
+                    None
+                } else {
+                    coverage_positions.next().copied().flatten()
+                }
+            })
+            .collect(),
+    ))
+}
+
+/// Whether a position is masked for being under `mask_depth` in either sequence. Positions
+/// without a depth are never masked
+#[must_use]
+pub fn is_masked(depth1: Option<u32>, depth2: Option<u32>, mask_depth: Option<u32>) -> bool {
+    mask_depth.is_some_and(|mask_depth| {
+        depth1
+            .into_iter()
+            .chain(depth2)
+            .any(|depth| depth < mask_depth)
+    })
+}
@@ -0,0 +1,73 @@
+// The DAIS output, reference strain and mutations of interest tables read by the
+// positions-of-interest and variants-of-interest processes
+use crate::processes::variants_of_interest::RefInput;
+use csv::ReaderBuilder;
+use either::Either;
+use serde::de::DeserializeOwned;
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufReader, Read, Stdin, stdin},
+    path::PathBuf,
+};
+
+/// Reference strains by the ctype, DAIS reference and protein a DAIS entry is matched on
+pub type RefsByKey<'a> = HashMap<(&'a str, &'a str, &'a str), Vec<&'a RefInput>>;
+
+/// Reads a table into records. Input files *must* be tab-separated.
+pub fn read_tsv<T: DeserializeOwned, R: Read>(
+    reader: R,
+    has_headers: bool,
+) -> Result<Vec<T>, Box<dyn std::error::Error>> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(has_headers)
+        .delimiter(b'\t')
+        .flexible(true)
+        .from_reader(reader);
+
+    let mut records = Vec::new();
+    for result in rdr.deserialize() {
+        let record: T = result?;
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+/// Opens the file at `path`, or stdin when there is none
+pub fn create_reader(path: Option<&PathBuf>) -> std::io::Result<BufReader<Either<File, Stdin>>> {
+    let reader = if let Some(file_path) = path {
+        let file = OpenOptions::new().read(true).open(file_path)?;
+        BufReader::new(Either::Left(file))
+    } else {
+        BufReader::new(Either::Right(stdin()))
+    };
+
+    Ok(reader)
+}
+
+/// References indexed by what a DAIS entry is matched on, so each entry only visits its own
+#[must_use]
+pub fn index_references(refs: &[RefInput]) -> RefsByKey<'_> {
+    let mut refs_by_key: RefsByKey = HashMap::new();
+    for ref_entry in refs {
+        refs_by_key
+            .entry((
+                ref_entry.ctype.as_str(),
+                ref_entry.reference_id.as_str(),
+                ref_entry.protein.as_str(),
+            ))
+            .or_default()
+            .push(ref_entry);
+    }
+    refs_by_key
+}
+
+/// The IRMA sample of a DAIS sample ID. DAIS IDs are the consensus names, which for flu carry
+/// the segment number after the last `_` (`s3_6` is `s3`)
+#[must_use]
+pub fn sample_from_dais_id(sample_id: &str) -> &str {
+    sample_id
+        .rsplit_once('_')
+        .map_or(sample_id, |(sample, _)| sample)
+}
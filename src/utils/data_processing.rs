@@ -13,8 +13,18 @@ use crate::processes::prepare_mira_reports::SamplesheetO;
 use crate::{io::data_ingest::DIStatData, processes::prepare_mira_reports::SamplesheetI};
 
 use crate::io::data_ingest::{
-    CoverageData, DaisSeqData, MinorVariantsData, QCSettings, ReadsData, SeqData,
+    CoverageData, DaisSeqData, IterationReference, MinorVariantsData, QCSettings, ReadsData,
+    SeqData, split_by_comma,
 };
+use crate::io::flu_subtype_references::FluSubtypeMap;
+use crate::io::sample_metadata::SampleMetadata;
+use crate::io::virus_plugins::{
+    VirusPlugin, reference_segment, strip_reference_suffix, virus_plugin,
+};
+use crate::utils::coverage_depths::CoverageDepths;
+use crate::utils::dais_tables::sample_from_dais_id;
+use crate::utils::hgvs::variant_notations;
+use crate::utils::units::{Depth, Percent};
 
 /// vtype struct
 #[derive(Serialize, Debug, Clone)]
@@ -36,10 +46,83 @@ pub struct DaisVarsData {
     pub protein: String,
     pub aa_variant_count: i32,
     pub aa_variants: String,
+    /// The variants in HGVS protein notation, `p.Glu119Val`
+    pub hgvs_protein: String,
+    /// The variants in HGVS coding notation, `c.355G>A`
+    pub hgvs_coding: String,
     pub runid: String,
     pub instrument: String,
 }
 
+/// Translation QC Struct, one row per sample/protein
+#[derive(Serialize, Debug)]
+pub struct TranslationQC {
+    pub sample_id: String,
+    pub reference: String,
+    pub protein: String,
+    pub cds_length: usize,
+    pub reference_cds_length: Option<usize>,
+    pub missing_start: bool,
+    pub internal_stop_count: usize,
+    pub length_not_multiple_of_3: bool,
+    pub short_cds: bool,
+    pub runid: String,
+    pub instrument: String,
+}
+
+impl TranslationQC {
+    /// Names of the flags raised, as used by `translation_qc_fail` in the QC yaml
+    fn flags(&self) -> Vec<&'static str> {
+        let mut flags = Vec::new();
+        if self.missing_start {
+            flags.push("missing_start");
+        }
+        if self.internal_stop_count > 0 {
+            flags.push("internal_stop");
+        }
+        if self.length_not_multiple_of_3 {
+            flags.push("length_not_multiple_of_3");
+        }
+        if self.short_cds {
+            flags.push("short_cds");
+        }
+        flags
+    }
+}
+
+/// Premature stop codons of a protein, one row per sample/protein with any
+#[derive(Serialize, Debug)]
+pub struct PrematureStop {
+    pub sample_id: String,
+    pub reference: String,
+    pub protein: String,
+    /// Position of the first stop along the reference protein
+    pub first_stop_position: usize,
+    pub stop_count: usize,
+    /// Every stop as in the amino acid variants (`Q225*`), comma separated
+    pub stops: String,
+    /// Length of the reference protein without its stop
+    pub reference_protein_length: Option<usize>,
+    /// How much of the reference protein is translated before the first stop
+    pub percent_of_protein_retained: Option<Percent>,
+    /// Whether the QC yaml restricts stops in the protein, so it fails the segment
+    pub restricted: bool,
+    pub runid: String,
+    pub instrument: String,
+}
+
+/// Stop codons among amino acid variants (`E119V,Q225*`), as the variants
+fn stop_variants(aa_variants: &str) -> Vec<&str> {
+    aa_variants
+        .split(',')
+        .filter(|variant| {
+            variant
+                .strip_suffix('*')
+                .is_some_and(|rest| rest.ends_with(|c: char| c.is_ascii_digit()))
+        })
+        .collect()
+}
+
 /// Subtype Struct
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Subtype {
@@ -65,34 +148,196 @@ pub struct MeltedRecord {
     pass_qc: i32,
 }
 
-/// Processed Cov Calcs
-#[derive(Debug, Default)]
-pub struct ProcessedCoverage {
-    pub sample: String,
+/// Read fate struct - percent of passed QC reads per assignment
+#[derive(Serialize, Debug, Clone)]
+pub struct ReadFateData {
+    pub sample_id: String,
+    pub assignment: String,
+    pub reads: i32,
+    pub percent_of_pass_qc: Option<f64>,
+    pub runid: Option<String>,
+    pub instrument: Option<String>,
+}
+
+/// A reference IRMA matched reads to besides the sample's primary assembly (the `5-` records of
+/// `READ_COUNTS.txt`), e.g. influenza B reads in an influenza A sample
+#[derive(Serialize, Debug, Clone)]
+pub struct SecondaryAssembly {
+    pub sample_id: String,
     pub reference: String,
-    pub median_coverage: i32,
-    pub percent_reference_covered: Option<f64>,
+    /// Type, and for HA/NA the subtype, of the reference, e.g. `B` or `A_H1`
+    pub secondary_subtype: String,
+    pub reads: i32,
+    pub percent_of_pass_qc: Option<f64>,
+    pub runid: Option<String>,
+    pub instrument: Option<String>,
 }
 
-/// IRMA struct
+/// One QC criterion of one segment, the tidy long form of `pass_fail_reason`
 #[derive(Serialize, Debug, Clone)]
-pub struct IRMASummary {
+pub struct QCCriterion {
     pub sample_id: String,
-    pub total_reads: Option<i32>,
-    pub pass_qc: Option<i32>,
-    pub reads_mapped: Option<i32>,
-    pub reference: Option<String>,
-    pub percent_reference_coverage: Option<f64>,
-    pub median_coverage: Option<i32>,
-    pub count_minor_snv_at_or_over_5_pct: Option<i32>,
-    pub spike_percent_coverage: Option<f64>,
-    pub spike_median_coverage: Option<i32>,
-    pub pass_fail_reason: Option<String>,
-    pub subtype: Option<String>,
-    pub mira_module: Option<String>,
+    pub reference: String,
+    pub criterion: String,
+    pub observed_value: Option<String>,
+    pub threshold: String,
+    /// `pass`, `fail`, or `not_evaluated` when the value is missing
+    pub status: String,
     pub runid: Option<String>,
     pub instrument: Option<String>,
-    pub di_ratios_5prime_3prime: Option<String>,
+}
+
+/// A position where a segment's reference changed between IRMA's last two iterations
+#[derive(Serialize, Debug, Clone)]
+pub struct ConsensusChange {
+    pub sample_id: String,
+    pub reference: String,
+    pub from_round: u32,
+    pub to_round: u32,
+    /// 1-based position in the references
+    pub position: usize,
+    /// `-` past the end of the shorter reference
+    pub from_base: char,
+    pub to_base: char,
+    pub runid: String,
+    pub instrument: String,
+}
+
+/// Processed Cov Calcs
+#[derive(Debug, Default)]
+pub struct ProcessedCoverage {
+    pub sample: String,
+    pub reference: String,
+    pub median_coverage: Depth,
+    pub percent_reference_covered: Option<Percent>,
+}
+
+/// Declares a struct along with a `field_docs` function returning each field's doc comment, so
+/// the column descriptions of an output table live next to the fields behind them. A doc line
+/// starting with `Unit:` gives the column's unit. A field can have a `#[serde(...)]` attribute
+/// after its doc comment.
+macro_rules! documented_struct {
+    (
+        $(#[$struct_attr:meta])*
+        pub struct $name:ident {
+            $(
+                $(#[doc = $doc:literal])*
+                $(#[serde($($serde:tt)*)])?
+                pub $field:ident: $ty:ty,
+            )*
+        }
+    ) => {
+        $(#[$struct_attr])*
+        pub struct $name {
+            $(
+                $(#[doc = $doc])*
+                $(#[serde($($serde)*)])?
+                pub $field: $ty,
+            )*
+        }
+
+        impl $name {
+            /// Name and doc comment lines of every field, in declaration order
+            #[must_use]
+            pub fn field_docs() -> &'static [(&'static str, &'static [&'static str])] {
+                &[$((stringify!($field), &[$($doc),*])),*]
+            }
+        }
+    };
+}
+
+/// Description and unit of an output column, for `columns.json` and Parquet field metadata
+#[derive(Serialize, Debug)]
+pub struct ColumnDoc {
+    pub name: String,
+    pub description: String,
+    pub unit: Option<String>,
+}
+
+documented_struct! {
+    /// IRMA struct
+    #[derive(Serialize, Debug, Clone)]
+    pub struct IRMASummary {
+        /// Sample ID from the samplesheet
+        pub sample_id: String,
+        /// Reads in the sample before IRMA's quality filtering
+        /// Unit: reads
+        pub total_reads: Option<i32>,
+        /// Reads passing IRMA's quality filtering
+        /// Unit: reads
+        pub pass_qc: Option<i32>,
+        /// Reads assigned to this reference
+        /// Unit: reads
+        pub reads_mapped: Option<i32>,
+        /// Reference (segment or genome) the consensus was assembled against
+        pub reference: Option<String>,
+        /// Percent of the reference length with a called consensus base
+        /// Unit: percent
+        pub percent_reference_coverage: Option<Percent>,
+        /// Median read depth across the reference
+        /// Unit: reads
+        pub median_coverage: Option<Depth>,
+        /// Number of minor single nucleotide variants at or over 5% frequency
+        /// Unit: count
+        pub count_minor_snv_at_or_over_5_pct: Option<i32>,
+        /// Percent of the SARS-CoV-2 S gene with a called consensus base
+        /// Unit: percent
+        pub spike_percent_coverage: Option<Percent>,
+        /// Median read depth across the SARS-CoV-2 S gene
+        /// Unit: reads
+        pub spike_median_coverage: Option<Depth>,
+        /// `Pass`, or the QC criteria the reference failed separated by `;`
+        pub pass_fail_reason: Option<String>,
+        /// Subtype or lineage called from the assembled references
+        pub subtype: Option<String>,
+        /// Types and subtypes of the sample's secondary assemblies, separated by `;`. Only
+        /// filled in when secondary assemblies are ingested
+        pub secondary_subtypes: Option<String>,
+        /// MIRA version, IRMA module and IRMA config used, separated by `;`
+        pub mira_module: Option<String>,
+        /// Sequencing run ID
+        pub runid: Option<String>,
+        /// Sequencing instrument
+        pub instrument: Option<String>,
+        /// Ratio of defective interfering reads at the 5' and 3' ends, separated by `;`
+        pub di_ratios_5prime_3prime: Option<String>,
+        /// Columns joined from the `--metadata` CSV
+        #[serde(flatten)]
+        pub metadata: SampleMetadata,
+    }
+}
+
+/// Summary output column names that differ from the `IRMASummary` field names
+const SUMMARY_COLUMN_RENAMES: [(&str, &str); 2] = [
+    ("mira_module", "mira_version;module;irma_config"),
+    ("di_ratios_5prime_3prime", "di_5prime;di_3prime"),
+];
+
+impl IRMASummary {
+    /// Documentation for a column of the summary outputs, from the doc comment of its field
+    #[must_use]
+    pub fn column_doc(column: &str) -> Option<ColumnDoc> {
+        let field = SUMMARY_COLUMN_RENAMES
+            .iter()
+            .find(|(_, renamed)| *renamed == column)
+            .map_or(column, |(field, _)| field);
+        let (_, lines) = Self::field_docs().iter().find(|(name, _)| *name == field)?;
+
+        let mut description = Vec::new();
+        let mut unit = None;
+        for line in lines.iter().map(|line| line.trim()) {
+            if let Some(u) = line.strip_prefix("Unit:") {
+                unit = Some(u.trim().to_string());
+            } else if !line.is_empty() {
+                description.push(line);
+            }
+        }
+        Some(ColumnDoc {
+            name: column.to_string(),
+            description: description.join(" "),
+            unit,
+        })
+    }
 }
 
 /// Variant Count struct
@@ -278,6 +523,7 @@ where
 /////////////// Functions for manipulating IRMA data ///////////////
 /// Breaking up the records column into three string for the `create_vtype_data` function
 fn read_record2type(record: &str) -> (String, String, String) {
+    let record = strip_reference_suffix(record);
     let parts: Vec<&str> = record.split('_').collect();
     if parts.len() >= 2 {
         let vtype = parts[0][2..].to_string();
@@ -334,6 +580,7 @@ pub fn return_seg_data(
 
     let mut segset: Vec<String> = Vec::new();
     for segment in &segments {
+        let segment = strip_reference_suffix(segment);
         segset.push(segment.split('_').nth(1).unwrap_or(segment).to_string());
     }
 
@@ -386,6 +633,12 @@ pub fn compute_dais_variants(
                     }
                 }
 
+                let (hgvs_protein, hgvs_coding) = variant_notations(
+                    &ref_aa_seq,
+                    &sample_aa_seq,
+                    &ref_entry.aligned_cds_sequence,
+                    &sample_entry.aligned_cds_sequence,
+                );
                 let dais_vars_entry = DaisVarsData {
                     sample_id: sample_entry.sample_id.clone(),
                     ctype: sample_entry.ctype.clone(),
@@ -394,6 +647,8 @@ pub fn compute_dais_variants(
                     protein: sample_entry.protein.clone(),
                     aa_variant_count: var_aa_count,
                     aa_variants: aa_vars,
+                    hgvs_protein,
+                    hgvs_coding,
                     runid: runid.to_owned(),
                     instrument: instrument.to_owned(),
                 };
@@ -437,28 +692,34 @@ pub fn compute_cvv_dais_variants(
 
     // Filter dais var data based on virus type
     if virus == "sc2-spike" {
-        merged_data.retain(|entry| entry.protein == "S");
+        merged_data.retain(|(entry, _)| entry.protein == "S");
     }
 
     // Compute AA Variants
-    for entry in &mut merged_data {
+    for (entry, _) in &mut merged_data {
         entry.insertion = compute_aa_variants(&entry.aa_aln, &entry.aa_seq);
     }
 
     let mut merged_data_with_num_variants = merged_data
         .into_iter()
-        .map(|entry| {
+        .map(|(entry, ref_cds)| {
             let num_variants = if entry.insertion.is_empty() {
                 0
             } else {
                 entry.insertion.split(',').count()
             };
-            (entry, num_variants)
+            let notations = variant_notations(
+                &entry.aa_seq,
+                &entry.aa_aln,
+                &ref_cds,
+                &entry.aligned_cds_sequence,
+            );
+            (entry, (num_variants, notations))
         })
         .collect::<Vec<_>>();
 
     // Filter and sort the data - keep first
-    merged_data_with_num_variants.sort_by(|(a, a_num_variants), (b, b_num_variants)| {
+    merged_data_with_num_variants.sort_by(|(a, (a_num_variants, _)), (b, (b_num_variants, _))| {
         a.protein
             .cmp(&b.protein)
             .then(a.sample_id.cmp(&b.sample_id))
@@ -466,36 +727,41 @@ pub fn compute_cvv_dais_variants(
     });
 
     let mut unique_data = HashMap::new();
-    for (entry, num_variants) in merged_data_with_num_variants {
+    for (entry, variants) in merged_data_with_num_variants {
         let key = (entry.sample_id.clone(), entry.protein.clone());
-        unique_data.entry(key).or_insert((entry, num_variants));
+        unique_data.entry(key).or_insert((entry, variants));
     }
 
     // Convert DaisSeqData to DaisVarsData and collect into a Vec
     let result: Vec<DaisVarsData> = unique_data
         .into_values()
-        .map(|(entry, num_variants)| DaisVarsData {
-            sample_id: entry.sample_id,
-            ctype: entry.ctype,
-            aa_reference_id: entry.aa_reference_id,
-            positional_reference_id: entry.reference.clone(),
-            protein: entry.protein.clone(),
-            // TODO: Eventually, we can change this field to be a usize perhaps
-            aa_variant_count: num_variants as i32,
-            aa_variants: entry.insertion.clone(),
-            runid: runid.to_owned(),
-            instrument: instrument.to_owned(),
-        })
+        .map(
+            |(entry, (num_variants, (hgvs_protein, hgvs_coding)))| DaisVarsData {
+                sample_id: entry.sample_id,
+                ctype: entry.ctype,
+                aa_reference_id: entry.aa_reference_id,
+                positional_reference_id: entry.reference.clone(),
+                protein: entry.protein.clone(),
+                // TODO: Eventually, we can change this field to be a usize perhaps
+                aa_variant_count: num_variants as i32,
+                aa_variants: entry.insertion.clone(),
+                hgvs_protein,
+                hgvs_coding,
+                runid: runid.to_owned(),
+                instrument: instrument.to_owned(),
+            },
+        )
         .collect();
 
     Ok(result)
 }
 
-/// Merge sequences based on Coordspace and Protein - used by `compute_cvv_dais_variants` fn
+/// Merge sequences based on Coordspace and Protein - used by `compute_cvv_dais_variants` fn.
+/// Each merged entry comes with the reference's aligned CDS
 fn merge_sequences(
     ref_seqs_data: &[DaisSeqData],
     sample_seqs_data: &[DaisSeqData],
-) -> Vec<DaisSeqData> {
+) -> Vec<(DaisSeqData, String)> {
     let mut merged_data = Vec::new();
 
     for sample_entry in sample_seqs_data {
@@ -521,7 +787,7 @@ fn merge_sequences(
                     aa_reference_id: Some(ref_entry.sample_id.clone()),
                 };
 
-                merged_data.push(merged_entry);
+                merged_data.push((merged_entry, ref_entry.aligned_cds_sequence.clone()));
             }
         }
     }
@@ -548,22 +814,23 @@ fn compute_aa_variants(aligned_aa_sequence: &str, ref_aa_sequence: &str) -> Stri
     aa_vars
 }
 
-/// Get subtypes for flu
+/// Get subtypes for flu, from the references the HA and NA of each sample matched
 pub fn extract_subtype_flu(
     dais_vars: &[DaisVarsData],
     coverage_data: &[ProcessedCoverage],
+    subtype_map: &FluSubtypeMap,
 ) -> Result<Vec<Subtype>, Box<dyn Error>> {
     let mut subtype_data: Vec<Subtype> = Vec::new();
     let mut sample_hemagglutinin_map: HashMap<String, String> = HashMap::new();
     let mut sample_neuraminidase_map: HashMap<String, String> = HashMap::new();
 
     // Map sample_id -> HA percent_reference_covered
-    let mut ha_coverage_map: HashMap<String, f64> = HashMap::new();
+    let mut ha_coverage_map: HashMap<String, Percent> = HashMap::new();
     for cov in coverage_data {
         if cov.reference.contains("HA") {
             ha_coverage_map.insert(
                 cov.sample.clone(),
-                cov.percent_reference_covered.unwrap_or(0.0),
+                cov.percent_reference_covered.unwrap_or_default(),
             );
         }
     }
@@ -571,59 +838,27 @@ pub fn extract_subtype_flu(
     // Collect all sample IDs
     let mut all_sample_ids: HashSet<String> = HashSet::new();
     for entry in dais_vars {
-        let hold_sample = entry.sample_id.clone();
-        let sample_id = hold_sample[..hold_sample.len() - 2].to_string();
-        all_sample_ids.insert(sample_id);
+        all_sample_ids.insert(sample_from_dais_id(&entry.sample_id).to_string());
     }
 
     // HA extraction
     for entry in dais_vars {
-        let hold_sample = entry.sample_id.clone();
-        let sample_ha = hold_sample[..hold_sample.len() - 2].to_string();
-
-        let ha = if entry.protein == "HA" {
-            match entry.positional_reference_id.as_str() {
-                "CALI07" => "H1",
-                "ANNARBOR60" => "H2",
-                "HK4801" => "H3",
-                "VT1203" => "H5",
-                "ANHUI01" => "H7",
-                "BGD0994" => "H9",
-                "BRISBANE60" => "BVIC",
-                "PHUKET3073" => "BYAM",
-                _ => "",
-            }
-        } else {
-            ""
-        };
+        let sample_ha = sample_from_dais_id(&entry.sample_id).to_string();
 
-        if !ha.is_empty() {
+        if entry.protein == "HA"
+            && let Some(ha) = subtype_map.subtype_of("HA", &entry.positional_reference_id)
+        {
             sample_hemagglutinin_map.insert(sample_ha, ha.to_string());
         }
     }
 
     // NA extraction
     for entry in dais_vars {
-        let hold_sample = entry.sample_id.clone();
-        let sample_na = hold_sample[..hold_sample.len() - 2].to_string();
-
-        let na = if entry.protein == "NA" {
-            match entry.positional_reference_id.as_str() {
-                "CALI07" => "N1",
-                "HK4801" => "N2",
-                "ONTARIO6118" => "N4",
-                "RU1526" | "ALASKA4733" => "N5",
-                "SICHUAN26221" => "N6",
-                "NL219" => "N7",
-                "ASTRAKHAN3212" => "N8",
-                "ANHUI01" => "N9",
-                _ => "",
-            }
-        } else {
-            ""
-        };
+        let sample_na = sample_from_dais_id(&entry.sample_id).to_string();
 
-        if !na.is_empty() {
+        if entry.protein == "NA"
+            && let Some(na) = subtype_map.subtype_of("NA", &entry.positional_reference_id)
+        {
             sample_neuraminidase_map.insert(sample_na, na.to_string());
         }
     }
@@ -650,8 +885,8 @@ pub fn extract_subtype_flu(
 
         // Downgrade if HA coverage < 100
         if subtype.as_str() == "BYAM" {
-            let ha_coverage = ha_coverage_map.get(&sample_id).copied().unwrap_or(0.0);
-            if ha_coverage < 100.0 {
+            let ha_coverage = ha_coverage_map.get(&sample_id).copied().unwrap_or_default();
+            if ha_coverage < Percent(100.0) {
                 subtype = "Undetermined".to_string();
             }
         }
@@ -691,6 +926,184 @@ pub fn extract_subtype_rsv(dais_vars: &[DaisVarsData]) -> Result<Vec<Subtype>, B
     Ok(subtype_data)
 }
 
+/// The 2-passQC read count of every sample, that other read records are reported against
+struct PassQcReads(HashMap<String, i32>);
+
+impl PassQcReads {
+    fn new(records: &[ReadsData]) -> Self {
+        let mut pass_qc_reads = HashMap::new();
+        for record in records {
+            if let Some(sample_id) = &record.sample_id
+                && record.record == "2-passQC"
+            {
+                pass_qc_reads.insert(sample_id.clone(), record.reads);
+            }
+        }
+        Self(pass_qc_reads)
+    }
+
+    /// `reads` as a percent of the sample's passed QC reads, rounded to two decimals. None when
+    /// the sample has no passed QC reads.
+    fn percent(&self, sample_id: &str, reads: i32) -> Option<f64> {
+        self.0
+            .get(sample_id)
+            .filter(|&&pass_qc| pass_qc > 0)
+            .map(|&pass_qc| (f64::from(reads) / f64::from(pass_qc) * 100.0 * 100.0).round() / 100.0)
+    }
+}
+
+/// Tidy table of where passed QC reads went for each sample: every 4- segment assignment plus
+/// nomatch, altmatch and chimeric, as a percent of the sample's 2-passQC reads.
+#[must_use]
+pub fn create_read_fate_vec(records: &[ReadsData]) -> Vec<ReadFateData> {
+    let pass_qc_reads = PassQcReads::new(records);
+
+    let mut read_fate = Vec::new();
+    for record in records {
+        let Some(sample_id) = &record.sample_id else {
+            continue;
+        };
+        let assignment = match record.record.as_str() {
+            "3-nomatch" => "nomatch",
+            "3-altmatch" => "altmatch",
+            "3-chimeric" => "chimeric",
+            r => match r.strip_prefix("4-") {
+                Some(segment) => segment,
+                None => continue,
+            },
+        };
+        let percent_of_pass_qc = pass_qc_reads.percent(sample_id, record.reads);
+
+        read_fate.push(ReadFateData {
+            sample_id: sample_id.clone(),
+            assignment: assignment.to_string(),
+            reads: record.reads,
+            percent_of_pass_qc,
+            runid: record.run_id.clone(),
+            instrument: record.instrument.clone(),
+        });
+    }
+
+    read_fate.sort_by(|a, b| {
+        a.sample_id
+            .cmp(&b.sample_id)
+            .then_with(|| a.assignment.cmp(&b.assignment))
+    });
+    read_fate
+}
+
+/// The secondary assemblies of every sample, from the `5-` records of IRMA's read counts, with
+/// their reads as a percent of the sample's 2-passQC reads
+#[must_use]
+pub fn create_secondary_assembly_vec(records: &[ReadsData]) -> Vec<SecondaryAssembly> {
+    let pass_qc_reads = PassQcReads::new(records);
+
+    let mut secondary = Vec::new();
+    for record in records {
+        let Some(sample_id) = &record.sample_id else {
+            continue;
+        };
+        let Some(reference) = record.record.strip_prefix("5-") else {
+            continue;
+        };
+        let (vtype, ref_type, subtype) = read_record2type(&record.record);
+        let secondary_subtype = if subtype.is_empty() || subtype == ref_type {
+            vtype
+        } else {
+            format!("{vtype}_{subtype}")
+        };
+        let percent_of_pass_qc = pass_qc_reads.percent(sample_id, record.reads);
+
+        secondary.push(SecondaryAssembly {
+            sample_id: sample_id.clone(),
+            reference: reference.to_string(),
+            secondary_subtype,
+            reads: record.reads,
+            percent_of_pass_qc,
+            runid: record.run_id.clone(),
+            instrument: record.instrument.clone(),
+        });
+    }
+
+    secondary.sort_by(|a, b| {
+        a.sample_id
+            .cmp(&b.sample_id)
+            .then_with(|| a.reference.cmp(&b.reference))
+    });
+    secondary
+}
+
+/// Fills in `secondary_subtypes` for every summary row with the distinct subtypes of the
+/// sample's secondary assemblies, left empty when the sample has none
+pub fn add_secondary_subtypes(irma_summary: &mut [IRMASummary], secondary: &[SecondaryAssembly]) {
+    for row in irma_summary {
+        let mut subtypes: Vec<&str> = secondary
+            .iter()
+            .filter(|s| s.sample_id == row.sample_id)
+            .map(|s| s.secondary_subtype.as_str())
+            .collect();
+        subtypes.sort_unstable();
+        subtypes.dedup();
+        row.secondary_subtypes = Some(subtypes.join(";"));
+    }
+}
+
+/// Positions where each segment's reference changed between the last two IRMA iterations it
+/// was refined in. Many changes late in the refinement point to an unstable consensus, e.g. a
+/// mixed sample. References are compared by position, so an indel shows up as a run of changes.
+#[must_use]
+pub fn compute_consensus_changes(
+    iteration_refs: &[IterationReference],
+    runid: &str,
+    instrument: &str,
+) -> Vec<ConsensusChange> {
+    let mut rounds: HashMap<(&str, &str), Vec<&IterationReference>> = HashMap::new();
+    for iteration_ref in iteration_refs {
+        rounds
+            .entry((
+                iteration_ref.sample_id.as_str(),
+                iteration_ref.reference.as_str(),
+            ))
+            .or_default()
+            .push(iteration_ref);
+    }
+
+    let mut changes = Vec::new();
+    for ((sample_id, reference), mut refs) in rounds {
+        if refs.len() < 2 {
+            continue;
+        }
+        refs.sort_by_key(|r| r.round);
+        let previous = refs[refs.len() - 2];
+        let last = refs[refs.len() - 1];
+
+        let previous_seq = previous.sequence.as_bytes();
+        let last_seq = last.sequence.as_bytes();
+        for position in 0..previous_seq.len().max(last_seq.len()) {
+            let from_base = previous_seq.get(position).map_or('-', |b| char::from(*b));
+            let to_base = last_seq.get(position).map_or('-', |b| char::from(*b));
+            if !from_base.eq_ignore_ascii_case(&to_base) {
+                changes.push(ConsensusChange {
+                    sample_id: sample_id.to_string(),
+                    reference: reference.to_string(),
+                    from_round: previous.round,
+                    to_round: last.round,
+                    position: position + 1,
+                    from_base,
+                    to_base,
+                    runid: runid.to_string(),
+                    instrument: instrument.to_string(),
+                });
+            }
+        }
+    }
+
+    changes.sort_by(|a, b| {
+        (&a.sample_id, &a.reference, a.position).cmp(&(&b.sample_id, &b.reference, b.position))
+    });
+    changes
+}
+
 //////////////// Functions used to create irma_summary ///////////////
 /// Flip orientation of the reads structs
 #[must_use]
@@ -770,12 +1183,12 @@ pub fn process_wgs_coverage_data<S: BuildHasher>(
         .into_iter()
         .map(|((sample, reference_name), maplen)| {
             let percent_reference_covered = ref_lens
-                .get(&reference_name)
+                .get(strip_reference_suffix(&reference_name))
                 .map(|&ref_len| (maplen as f64 / ref_len as f64) * 100.0);
             (
                 sample,
                 reference_name,
-                percent_reference_covered.map(|x| (x * 100.0).round() / 100.0),
+                percent_reference_covered.map(|x| Percent(x).rounded()),
             )
         })
         .collect();
@@ -806,12 +1219,12 @@ pub fn process_wgs_coverage_data<S: BuildHasher>(
         let percent_reference_covered = cov_ref_lens_processed
             .iter()
             .find(|(s, r, _)| s == sample && r == reference)
-            .map_or(Some(0.0), |(_, _, percent)| *percent); // Default value if not found
+            .map_or(Some(Percent(0.0)), |(_, _, percent)| *percent); // Default value if not found
 
         processed_coverage.push(ProcessedCoverage {
             sample: sample.clone(),
             reference: reference.clone(),
-            median_coverage, // Already an i32
+            median_coverage: Depth(median_coverage),
             percent_reference_covered,
         });
     }
@@ -819,6 +1232,7 @@ pub fn process_wgs_coverage_data<S: BuildHasher>(
     Ok(processed_coverage)
 }
 
+/// Coverage of the region between two positions (exclusive) of the reference
 pub fn process_position_coverage_data(
     coverage_vec: &[CoverageData],
     position_1: i32,
@@ -827,7 +1241,10 @@ pub fn process_position_coverage_data(
     // Filter rows where position is between position_1 and position_2
     let filtered_coverage: Vec<_> = coverage_vec
         .iter()
-        .filter(|row| row.position > position_1 && row.position < position_2)
+        .filter(|row| {
+            row.region_position()
+                .is_some_and(|position| position > position_1 && position < position_2)
+        })
         .collect();
 
     let filtered_coverage: Vec<_> = filtered_coverage
@@ -854,7 +1271,7 @@ pub fn process_position_coverage_data(
             (
                 sample,
                 reference_name,
-                Some((percent_reference_covered * 100.0).round() / 100.0),
+                Some(Percent(percent_reference_covered).rounded()),
             )
         })
         .collect();
@@ -886,12 +1303,12 @@ pub fn process_position_coverage_data(
         let percent_reference_covered = cov_ref_lens_processed
             .iter()
             .find(|(s, r, _)| s == sample && r == reference)
-            .map_or(Some(0.0), |(_, _, percent)| *percent); // Default value if not found
+            .map_or(Some(Percent(0.0)), |(_, _, percent)| *percent); // Default value if not found
 
         processed_coverage.push(ProcessedCoverage {
             sample: sample.clone(),
             reference: reference.clone(),
-            median_coverage, // Already an i32
+            median_coverage: Depth(median_coverage),
             percent_reference_covered,
         });
     }
@@ -998,10 +1415,12 @@ pub fn create_irma_summary_vec(
                     spike_median_coverage: None,
                     pass_fail_reason: None,
                     subtype: None,
+                    secondary_subtypes: None,
                     mira_module: Some(metadata.module.clone()),
                     runid: Some(metadata.runid.clone()),
                     instrument: Some(metadata.instrument.clone()),
                     di_ratios_5prime_3prime: None,
+                    metadata: SampleMetadata::default(),
                 });
             }
         }
@@ -1013,17 +1432,19 @@ pub fn create_irma_summary_vec(
                 total_reads: Some(0),
                 pass_qc: Some(0),
                 reads_mapped: Some(0),
-                percent_reference_coverage: Some(0.0),
-                median_coverage: Some(0),
+                percent_reference_coverage: Some(Percent(0.0)),
+                median_coverage: Some(Depth(0)),
                 count_minor_snv_at_or_over_5_pct: Some(0),
                 spike_percent_coverage: None,
                 spike_median_coverage: None,
                 pass_fail_reason: Some("Fail".to_owned()),
                 subtype: Some("Undetermined".to_owned()),
+                secondary_subtypes: None,
                 mira_module: Some(metadata.module.clone()),
                 runid: Some(metadata.runid.clone()),
                 instrument: Some(metadata.instrument.clone()),
                 di_ratios_5prime_3prime: None,
+                metadata: SampleMetadata::default(),
             });
         }
     }
@@ -1044,11 +1465,11 @@ pub fn create_irma_summary_vec(
                     && sample.reference == Some(entry.reference.clone())
             }) {
                 sample.spike_percent_coverage =
-                    Some(entry.percent_reference_covered.unwrap_or(0.0));
+                    Some(entry.percent_reference_covered.unwrap_or_default());
                 sample.spike_median_coverage = Some(entry.median_coverage);
             } else {
-                sample.spike_percent_coverage = Some(0.0);
-                sample.spike_median_coverage = Some(0);
+                sample.spike_percent_coverage = Some(Percent(0.0));
+                sample.spike_median_coverage = Some(Depth(0));
             }
         }
 
@@ -1084,43 +1505,84 @@ pub fn create_irma_summary_vec(
 /// Combine all vec to create IRMA summary
 #[allow(clippy::too_many_lines)]
 impl IRMASummary {
+    /// Proteins of this segment with a stop codon before the end, from the DAIS-ribosome
+    /// variants
+    fn proteins_with_premature_stop(&self, dais_vars: &[DaisVarsData], virus: &str) -> Vec<String> {
+        self.premature_stops(dais_vars, virus)
+            .into_iter()
+            .map(|(protein, _)| protein.to_string())
+            .collect()
+    }
+
+    /// Proteins of this segment with a stop codon before the end and those stops (`Q225*`)
+    fn premature_stops<'a>(
+        &self,
+        dais_vars: &'a [DaisVarsData],
+        virus: &str,
+    ) -> Vec<(&'a str, Vec<&'a str>)> {
+        dais_vars
+            .iter()
+            .filter(|entry| {
+                // Handle sample_id comparison based on virus type
+                let sample_match = if virus == "flu" {
+                    sample_from_dais_id(&entry.sample_id) == self.sample_id
+                } else {
+                    // Regular comparison
+                    self.sample_id == entry.sample_id
+                };
+
+                sample_match && self.reference == Some(entry.ctype.clone())
+            })
+            .map(|entry| (entry.protein.as_str(), stop_variants(&entry.aa_variants)))
+            .filter(|(_, stops)| !stops.is_empty())
+            .collect()
+    }
+
+    /// Translation QC flags of this segment that fail it, as `<flag> '<protein>'`
+    fn failed_translation_flags(
+        &self,
+        translation_qc: &[TranslationQC],
+        translation_qc_fail: &[String],
+    ) -> Vec<String> {
+        translation_qc
+            .iter()
+            .filter(|entry| {
+                entry.sample_id == self.sample_id
+                    && self.reference.as_deref() == Some(entry.reference.as_str())
+            })
+            .flat_map(|entry| {
+                entry
+                    .flags()
+                    .into_iter()
+                    .filter(|flag| translation_qc_fail.iter().any(|f| f == flag))
+                    .map(|flag| format!("{flag} '{}'", entry.protein))
+            })
+            .collect()
+    }
+
+    /// Positions where this segment's consensus changed in the last IRMA iteration
+    fn consensus_change_count(&self, consensus_changes: &[ConsensusChange]) -> usize {
+        consensus_changes
+            .iter()
+            .filter(|change| {
+                change.sample_id == self.sample_id
+                    && self.reference.as_deref() == Some(change.reference.as_str())
+            })
+            .count()
+    }
+
     pub fn add_pass_fail_qc(
         &mut self,
         dais_vars: &[DaisVarsData],
+        translation_qc: &[TranslationQC],
+        consensus_changes: &[ConsensusChange],
         virus: &str,
         qc_values: &QCSettings,
     ) -> Result<Vec<IRMASummary>, Box<dyn Error>> {
         let irma_summary: Vec<IRMASummary> = Vec::new();
 
         if !qc_values.stop_codon_restricted_proteins.is_empty() {
-            let proteins_with_stop: Vec<_> = dais_vars
-                .iter()
-                .filter(|entry| {
-                    // Handle sample_id comparison based on virus type
-                    let sample_match = if virus == "flu" {
-                        // Take the last two characters off entry.sample_id before comparing
-                        let entry_id = &entry.sample_id;
-
-                        if entry_id.len() > 2 {
-                            entry_id[..entry_id.len() - 2] == self.sample_id
-                        } else {
-                            false
-                        }
-                    } else {
-                        // Regular comparison
-                        self.sample_id == entry.sample_id
-                    };
-
-                    sample_match
-                        && self.reference == Some(entry.ctype.clone())
-                        && entry
-                            .aa_variants
-                            .chars()
-                            .zip(entry.aa_variants.chars().skip(1))
-                            .any(|(a, b)| a.is_ascii_digit() && b == '*')
-                })
-                .map(|entry| entry.protein.clone())
-                .collect();
+            let proteins_with_stop = self.proteins_with_premature_stop(dais_vars, virus);
 
             if !proteins_with_stop.is_empty() {
                 self.pass_fail_reason =
@@ -1129,7 +1591,7 @@ impl IRMASummary {
         }
 
         if let Some(coverage) = self.percent_reference_coverage
-            && coverage < qc_values.perc_ref_covered.into()
+            && coverage < qc_values.perc_ref_covered
         {
             let new_entry = format!(
                 "Less than {}% of reference covered",
@@ -1143,7 +1605,7 @@ impl IRMASummary {
         }
 
         if let Some(med_cov) = self.median_coverage
-            && med_cov < qc_values.med_cov.try_into().unwrap()
+            && med_cov < qc_values.med_cov
         {
             let new_entry = format!("Median coverage < {}", qc_values.med_cov);
             if let Some(ref mut pf_reason) = self.pass_fail_reason {
@@ -1167,18 +1629,43 @@ impl IRMASummary {
             }
         }
 
+        let translation_qc_fail = split_by_comma(&qc_values.translation_qc_fail);
+        if !translation_qc_fail.is_empty() {
+            let failed_flags = self.failed_translation_flags(translation_qc, &translation_qc_fail);
+            if !failed_flags.is_empty() {
+                let new_entry = format!("Translation QC {}", failed_flags.join(", "));
+                if let Some(ref mut pf_reason) = self.pass_fail_reason {
+                    append_with_delim(pf_reason, &new_entry, ';');
+                } else {
+                    self.pass_fail_reason = Some(new_entry);
+                }
+            }
+        }
+
+        if let Some(max_changes) = qc_values.max_consensus_iteration_changes {
+            let changes = self.consensus_change_count(consensus_changes);
+            if changes > max_changes as usize {
+                let new_entry = format!(
+                    "Consensus changed at {changes} positions in the last IRMA iteration > {max_changes}"
+                );
+                if let Some(ref mut pf_reason) = self.pass_fail_reason {
+                    append_with_delim(pf_reason, &new_entry, ';');
+                } else {
+                    self.pass_fail_reason = Some(new_entry);
+                }
+            }
+        }
+
         if self.pass_fail_reason.is_none() {
             self.pass_fail_reason = Some("Pass".to_string());
         }
 
         if let Some(spike_coverage) = self.spike_percent_coverage
             && let Some(perc_ref_spike_covered) = qc_values.perc_ref_spike_covered
-            && spike_coverage < f64::from(perc_ref_spike_covered)
+            && spike_coverage < perc_ref_spike_covered
         {
-            let new_entry = format!(
-                "Less than {}% of S gene reference covered",
-                qc_values.perc_ref_covered
-            );
+            let new_entry =
+                format!("Less than {perc_ref_spike_covered}% of S gene reference covered");
             if let Some(ref mut pf_reason) = self.pass_fail_reason {
                 append_with_delim(pf_reason, &new_entry, ';');
             } else {
@@ -1188,9 +1675,9 @@ impl IRMASummary {
 
         if let Some(spike_med_cov) = self.spike_median_coverage
             && let Some(spike_med_covered) = qc_values.med_spike_cov
-            && spike_med_cov < spike_med_covered.try_into().unwrap()
+            && spike_med_cov < spike_med_covered
         {
-            let new_entry = format!("Median coverage of S gene < {}", qc_values.med_cov);
+            let new_entry = format!("Median coverage of S gene < {spike_med_covered}");
             if let Some(ref mut pf_reason) = self.pass_fail_reason {
                 append_with_delim(pf_reason, &new_entry, ';');
             } else {
@@ -1206,6 +1693,192 @@ impl IRMASummary {
     }
 }
 
+// One QC criterion of a segment, before it is put in a `QCCriterion` row. `fail` is `None`
+// when the value needed to evaluate it is missing
+struct CriterionCheck {
+    criterion: &'static str,
+    observed: Option<String>,
+    threshold: String,
+    fail: Option<bool>,
+}
+
+// The coverage and minor variant criteria, which are always checked
+fn coverage_criteria(row: &IRMASummary, qc_values: &QCSettings) -> [CriterionCheck; 3] {
+    [
+        CriterionCheck {
+            criterion: "percent_reference_coverage",
+            observed: row.percent_reference_coverage.map(|v| format!("{v:.2}")),
+            threshold: format!(">= {}", qc_values.perc_ref_covered),
+            fail: row
+                .percent_reference_coverage
+                .map(|v| v < qc_values.perc_ref_covered),
+        },
+        CriterionCheck {
+            criterion: "median_coverage",
+            observed: row.median_coverage.map(|v| v.to_string()),
+            threshold: format!(">= {}", qc_values.med_cov),
+            fail: row.median_coverage.map(|v| v < qc_values.med_cov),
+        },
+        CriterionCheck {
+            criterion: "count_minor_snv_at_or_over_5_pct",
+            observed: row.count_minor_snv_at_or_over_5_pct.map(|v| v.to_string()),
+            threshold: format!("<= {}", qc_values.minor_vars),
+            fail: row
+                .count_minor_snv_at_or_over_5_pct
+                .map(|v| i64::from(v) > i64::from(qc_values.minor_vars)),
+        },
+    ]
+}
+
+// Only stops in the restricted proteins fail the consensus
+fn premature_stop_criterion(
+    row: &IRMASummary,
+    dais_vars: &[DaisVarsData],
+    virus: &str,
+    qc_values: &QCSettings,
+) -> Option<CriterionCheck> {
+    if qc_values.stop_codon_restricted_proteins.is_empty() {
+        return None;
+    }
+    let restricted = split_by_comma(&qc_values.stop_codon_restricted_proteins);
+    let stops = row.premature_stops(dais_vars, virus);
+    // The stops are given with their proteins, `HA Q225*`, to see where they truncate
+    let observed: Vec<String> = stops
+        .iter()
+        .map(|(protein, stops)| format!("{protein} {}", stops.join(" ")))
+        .collect();
+    Some(CriterionCheck {
+        criterion: "premature_stop_codon",
+        observed: Some(observed.join(", ")),
+        threshold: format!("none in {}", restricted.join(", ")),
+        fail: Some(
+            stops
+                .iter()
+                .any(|(protein, _)| restricted.iter().any(|r| r == protein)),
+        ),
+    })
+}
+
+fn translation_criterion(
+    row: &IRMASummary,
+    translation_qc: &[TranslationQC],
+    translation_qc_fail: &[String],
+) -> Option<CriterionCheck> {
+    if translation_qc_fail.is_empty() {
+        return None;
+    }
+    let flags = row.failed_translation_flags(translation_qc, translation_qc_fail);
+    Some(CriterionCheck {
+        criterion: "translation_qc",
+        observed: Some(flags.join(", ")),
+        threshold: format!("none of {}", translation_qc_fail.join(", ")),
+        fail: Some(!flags.is_empty()),
+    })
+}
+
+fn iteration_changes_criterion(
+    row: &IRMASummary,
+    consensus_changes: &[ConsensusChange],
+    qc_values: &QCSettings,
+) -> Option<CriterionCheck> {
+    let max_changes = qc_values.max_consensus_iteration_changes?;
+    let changes = row.consensus_change_count(consensus_changes);
+    Some(CriterionCheck {
+        criterion: "consensus_iteration_changes",
+        observed: Some(changes.to_string()),
+        threshold: format!("<= {max_changes}"),
+        fail: Some(changes > max_changes as usize),
+    })
+}
+
+// The spike criteria, for the QC yamls that set them
+fn spike_criteria(row: &IRMASummary, qc_values: &QCSettings) -> Vec<CriterionCheck> {
+    let mut checks = Vec::new();
+    if let Some(perc_ref_spike_covered) = qc_values.perc_ref_spike_covered {
+        checks.push(CriterionCheck {
+            criterion: "spike_percent_coverage",
+            observed: row.spike_percent_coverage.map(|v| format!("{v:.2}")),
+            threshold: format!(">= {perc_ref_spike_covered}"),
+            fail: row
+                .spike_percent_coverage
+                .map(|v| v < perc_ref_spike_covered),
+        });
+    }
+    if let Some(med_spike_cov) = qc_values.med_spike_cov {
+        checks.push(CriterionCheck {
+            criterion: "spike_median_coverage",
+            observed: row.spike_median_coverage.map(|v| v.to_string()),
+            threshold: format!(">= {med_spike_cov}"),
+            fail: row.spike_median_coverage.map(|v| v < med_spike_cov),
+        });
+    }
+    checks
+}
+
+/// Every QC criterion of every segment in long form, one row per segment and criterion, with
+/// the observed value and threshold behind `pass_fail_reason`. Criteria the QC yaml doesn't
+/// set are left out, and samples without an assembly get a single failed `assembly` row.
+#[must_use]
+pub fn create_qc_criteria_vec(
+    irma_summary: &[IRMASummary],
+    dais_vars: &[DaisVarsData],
+    translation_qc: &[TranslationQC],
+    consensus_changes: &[ConsensusChange],
+    virus: &str,
+    qc_values: &QCSettings,
+) -> Vec<QCCriterion> {
+    let translation_qc_fail = split_by_comma(&qc_values.translation_qc_fail);
+    let mut criteria = Vec::new();
+
+    for row in irma_summary {
+        let reference = row.reference.clone().unwrap_or_default();
+        let checks: Vec<CriterionCheck> = if reference == "Undetermined" {
+            vec![CriterionCheck {
+                criterion: "assembly",
+                observed: Some("none".to_string()),
+                threshold: "assembled".to_string(),
+                fail: Some(true),
+            }]
+        } else {
+            coverage_criteria(row, qc_values)
+                .into_iter()
+                .chain(premature_stop_criterion(row, dais_vars, virus, qc_values))
+                .chain(translation_criterion(
+                    row,
+                    translation_qc,
+                    &translation_qc_fail,
+                ))
+                .chain(iteration_changes_criterion(
+                    row,
+                    consensus_changes,
+                    qc_values,
+                ))
+                .chain(spike_criteria(row, qc_values))
+                .collect()
+        };
+
+        criteria.extend(checks.into_iter().map(|check| {
+            QCCriterion {
+                sample_id: row.sample_id.clone(),
+                reference: reference.clone(),
+                criterion: check.criterion.to_string(),
+                observed_value: check.observed,
+                threshold: check.threshold,
+                status: match check.fail {
+                    Some(true) => "fail",
+                    Some(false) => "pass",
+                    None => "not_evaluated",
+                }
+                .to_string(),
+                runid: row.runid.clone(),
+                instrument: row.instrument.clone(),
+            }
+        }));
+    }
+
+    criteria
+}
+
 /// Matching sequences to samples and references for `nt_seq_vec`
 pub fn create_nt_seq_vec(
     seq_data: &[SeqData],
@@ -1308,6 +1981,16 @@ pub fn create_nt_seq_vec(
     Ok(nt_seq_vec)
 }
 
+/// Whether sequences of the platform and virus can be divided into pass and fail: the built-in
+/// viruses and plugin viruses on Illumina, and every virus on ONT
+fn is_supported_virus(platform: &str, virus: &str) -> bool {
+    match platform {
+        "illumina" => matches!(virus, "flu" | "sc2-wgs" | "rsv") || virus_plugin(virus).is_some(),
+        "ont" => true,
+        _ => false,
+    }
+}
+
 //Take NTSequences and divide them into seqs that pass and seqs that fail
 //Pre step for printing the pass/fail amended concensus
 pub fn divide_nt_into_pass_fail_vec(
@@ -1327,20 +2010,14 @@ pub fn divide_nt_into_pass_fail_vec(
 
         let is_pass = if contains_forbidden_protein {
             false
+        } else if is_supported_virus(platform, virus) {
+            entry.qc_decision == "Pass"
+                || (entry.qc_decision.contains("Premature stop codon")
+                    && !entry.qc_decision.contains(';'))
         } else {
-            match (platform, virus) {
-                ("illumina", "flu" | "sc2-wgs" | "rsv") | ("ont", _) => {
-                    entry.qc_decision == "Pass"
-                        || (entry.qc_decision.contains("Premature stop codon")
-                            && !entry.qc_decision.contains(';'))
-                }
-                _ => {
-                    return Err(format!(
-                        "Unhandled case for platform '{platform}' and virus '{virus}'"
-                    )
-                    .into());
-                }
-            }
+            return Err(
+                format!("Unhandled case for platform '{platform}' and virus '{virus}'").into(),
+            );
         };
 
         if is_pass {
@@ -1367,6 +2044,216 @@ pub fn divide_nt_into_pass_fail_vec(
     })
 }
 
+/// Parse DAIS-ribosome nt coordinates (e.g. `21..1721` or `26..51,739..1008` for spliced
+/// proteins) into the outer 1-based start and end
+fn dais_coordinate_span(coordinates: &str) -> Option<(usize, usize)> {
+    let mut span: Option<(usize, usize)> = None;
+    for range in coordinates.split(',') {
+        let (start, end) = range.trim().split_once("..")?;
+        let (start, end) = (start.trim().parse().ok()?, end.trim().parse().ok()?);
+        let (start, end) = (usize::min(start, end), usize::max(start, end));
+        span = Some(span.map_or((start, end), |(s, e)| (s.min(start), e.max(end))));
+    }
+    span
+}
+
+/// The part of the 1-based `start..=end` span from its first to its last position covered by at
+/// least `min_depth` reads. None when no position of it is.
+fn covered_span(
+    depths: &[Option<u32>],
+    (start, end): (usize, usize),
+    min_depth: u32,
+) -> Option<(usize, usize)> {
+    let covered = |position: &usize| {
+        depths
+            .get(position - 1)
+            .copied()
+            .flatten()
+            .is_some_and(|depth| depth >= min_depth)
+    };
+    let first = (start..=end).find(covered)?;
+    let last = (first..=end).rev().find(covered)?;
+    Some((first, last))
+}
+
+/// Trim consensus sequences to the outermost CDS boundaries DAIS-ribosome found in them,
+/// removing the UTR and primer regions at the ends that are usually low confidence, and then
+/// the ends of that with fewer than `min_depth` reads in `depths`. Sequences without a DAIS
+/// annotation are only trimmed by coverage, and sequences without coverage only to the CDS.
+#[must_use]
+pub fn trim_nt_to_cds(
+    nt_seq_vec: &[NTSequences],
+    dais_seq_data: &[DaisSeqData],
+    depths: &CoverageDepths,
+    min_depth: u32,
+    virus: &str,
+) -> Vec<NTSequences> {
+    nt_seq_vec
+        .iter()
+        .map(|entry| {
+            let span = dais_seq_data
+                .iter()
+                .filter(|dais| {
+                    let dais_sample = if virus == "flu" {
+                        sample_from_dais_id(&dais.sample_id)
+                    } else {
+                        dais.sample_id.as_str()
+                    };
+                    dais_sample == entry.sample_id && dais.ctype == entry.reference
+                })
+                .filter_map(|dais| dais_coordinate_span(&dais.reference_nt_positions))
+                .reduce(|(s1, e1), (s2, e2)| (s1.min(s2), e1.max(e2)));
+
+            let span = match span {
+                Some((start, end)) if start >= 1 && end <= entry.sequence.len() => (start, end),
+                _ => (1, entry.sequence.len()),
+            };
+            let key = (entry.sample_id.clone(), entry.reference.clone());
+            let (start, end) = depths
+                .get(&key)
+                .and_then(|depths| covered_span(depths, span, min_depth))
+                .unwrap_or(span);
+            let sequence = entry
+                .sequence
+                .get(start.saturating_sub(1)..end)
+                .unwrap_or(&entry.sequence)
+                .to_string();
+
+            NTSequences {
+                sample_id: entry.sample_id.clone(),
+                sequence,
+                target_ref: entry.target_ref.clone(),
+                reference: entry.reference.clone(),
+                qc_decision: entry.qc_decision.clone(),
+                runid: entry.runid.clone(),
+                instrument: entry.instrument.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Translation QC for every protein DAIS-ribosome found: a CDS not starting with ATG, stop codons
+/// before the end of the translation, a CDS length not divisible by 3 and a CDS shorter than
+/// `min_cds_perc` percent of the reference CDS for the same protein.
+#[must_use]
+pub fn compute_translation_qc(
+    dais_seq_data: &[DaisSeqData],
+    dais_ref_data: &[DaisSeqData],
+    virus: &str,
+    min_cds_perc: Percent,
+    runid: &str,
+    instrument: &str,
+) -> Vec<TranslationQC> {
+    let cds_length = |cds: &str| cds.chars().filter(char::is_ascii_alphabetic).count();
+
+    dais_seq_data
+        .iter()
+        .map(|entry| {
+            let sample_id = if virus == "flu" {
+                sample_from_dais_id(&entry.sample_id)
+            } else {
+                entry.sample_id.as_str()
+            };
+            let length = cds_length(&entry.cds_sequence);
+            let reference_cds_length = dais_ref_data
+                .iter()
+                .find(|ref_entry| {
+                    ref_entry.reference == entry.reference && ref_entry.protein == entry.protein
+                })
+                .map(|ref_entry| cds_length(&ref_entry.cds_sequence));
+
+            TranslationQC {
+                sample_id: sample_id.to_string(),
+                reference: entry.ctype.clone(),
+                protein: entry.protein.clone(),
+                cds_length: length,
+                reference_cds_length,
+                missing_start: length > 0
+                    && !entry
+                        .cds_sequence
+                        .get(..3)
+                        .is_some_and(|start| start.eq_ignore_ascii_case("ATG")),
+                internal_stop_count: entry.aa_seq.trim_end_matches('*').matches('*').count(),
+                length_not_multiple_of_3: !length.is_multiple_of(3),
+                short_cds: reference_cds_length.is_some_and(|ref_len| {
+                    length as f64 * 100.0 < ref_len as f64 * f64::from(min_cds_perc)
+                }),
+                runid: runid.to_string(),
+                instrument: instrument.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Premature stop codons of every sample protein, from the same DAIS-ribosome variants as the
+/// `premature_stop_codon` QC, with how much of the reference protein is left before the first
+/// stop
+#[must_use]
+pub fn compute_premature_stops(
+    dais_vars: &[DaisVarsData],
+    dais_ref_data: &[DaisSeqData],
+    virus: &str,
+    restricted_proteins: &[String],
+    runid: &str,
+    instrument: &str,
+) -> Vec<PrematureStop> {
+    let mut premature_stops: Vec<PrematureStop> = dais_vars
+        .iter()
+        .filter_map(|entry| {
+            let stops = stop_variants(&entry.aa_variants);
+            let positions: Vec<usize> = stops
+                .iter()
+                .filter_map(|stop| {
+                    stop.trim_end_matches('*')
+                        .trim_start_matches(|c: char| !c.is_ascii_digit())
+                        .parse()
+                        .ok()
+                })
+                .collect();
+            let first_stop_position = *positions.iter().min()?;
+
+            let sample_id = if virus == "flu" {
+                sample_from_dais_id(&entry.sample_id)
+            } else {
+                entry.sample_id.as_str()
+            };
+            let reference_protein_length = dais_ref_data
+                .iter()
+                .find(|ref_entry| {
+                    ref_entry.reference == entry.positional_reference_id
+                        && ref_entry.protein == entry.protein
+                })
+                .map(|ref_entry| ref_entry.aa_seq.trim_end_matches('*').len());
+
+            Some(PrematureStop {
+                sample_id: sample_id.to_string(),
+                reference: entry.ctype.clone(),
+                protein: entry.protein.clone(),
+                first_stop_position,
+                stop_count: stops.len(),
+                stops: stops.join(","),
+                reference_protein_length,
+                percent_of_protein_retained: reference_protein_length
+                    .filter(|&length| length > 0)
+                    .map(|length| {
+                        Percent(
+                            first_stop_position.saturating_sub(1) as f64 * 100.0 / length as f64,
+                        )
+                        .rounded()
+                    }),
+                restricted: restricted_proteins.contains(&entry.protein),
+                runid: runid.to_string(),
+                instrument: instrument.to_string(),
+            })
+        })
+        .collect();
+
+    premature_stops.sort_by(|a, b| {
+        (&a.sample_id, &a.reference, &a.protein).cmp(&(&b.sample_id, &b.reference, &b.protein))
+    });
+    premature_stops
+}
+
 pub fn create_aa_seq_vec(
     aa_data: &[DaisSeqData],
     irma_summary_vec: &[IRMASummary],
@@ -1440,20 +2327,14 @@ pub fn divide_aa_into_pass_fail_vec(
 
         let is_pass = if contains_forbidden_protein {
             false
+        } else if is_supported_virus(platform, virus) {
+            entry.qc_decision == "Pass"
+                || (entry.qc_decision.contains("Premature stop codon")
+                    && !entry.qc_decision.contains(';'))
         } else {
-            match (platform, virus) {
-                ("illumina", "flu" | "sc2-wgs" | "rsv") | ("ont", _) => {
-                    entry.qc_decision == "Pass"
-                        || (entry.qc_decision.contains("Premature stop codon")
-                            && !entry.qc_decision.contains(';'))
-                }
-                _ => {
-                    return Err(format!(
-                        "Unhandled case for platform '{platform}' and virus '{virus}'"
-                    )
-                    .into());
-                }
-            }
+            return Err(
+                format!("Unhandled case for platform '{platform}' and virus '{virus}'").into(),
+            );
         };
 
         if is_pass {
@@ -1535,7 +2416,8 @@ pub fn divide_nt_into_nextclade_vec(
                             && !entry.reference.contains('F')
                             && !entry.reference.contains('G'))
                 }
-                ("ont", _) => {
+                // ONT runs and plugin viruses restrict stop codons only through the QC yaml
+                _ if is_supported_virus(platform, virus) => {
                     entry.qc_decision == "Pass"
                         || (entry.qc_decision.contains("Premature stop codon")
                             && !entry.qc_decision.contains(';'))
@@ -1575,6 +2457,42 @@ pub fn divide_nt_into_nextclade_vec(
     Ok(nextclade_seqs)
 }
 
+/// Subtypes of a plugin virus from its subtypes.tsv: the subtypes of every reference a sample
+/// assembled, joined with `/`, or Undetermined
+pub fn extract_subtype_plugin(
+    plugin: &VirusPlugin,
+    calc_cov_vec: &[ProcessedCoverage],
+) -> Result<Vec<Subtype>, Box<dyn Error>> {
+    let mut sample_subtypes: Vec<(String, Vec<String>)> = Vec::new();
+    for entry in calc_cov_vec {
+        let index =
+            if let Some(index) = sample_subtypes.iter().position(|(s, _)| *s == entry.sample) {
+                index
+            } else {
+                sample_subtypes.push((entry.sample.clone(), Vec::new()));
+                sample_subtypes.len() - 1
+            };
+        let subtypes = &mut sample_subtypes[index].1;
+        if let Some(subtype) = plugin.subtype_of(&entry.reference)
+            && !subtypes.iter().any(|s| s == subtype)
+        {
+            subtypes.push(subtype.to_string());
+        }
+    }
+
+    Ok(sample_subtypes
+        .into_iter()
+        .map(|(sample, subtypes)| Subtype {
+            sample_id: Some(sample),
+            subtype: if subtypes.is_empty() {
+                "Undetermined".to_string()
+            } else {
+                subtypes.join("/")
+            },
+        })
+        .collect())
+}
+
 ///////////////////////////////////////////////////////////////////////////////////////////////////////
 //////////////////////////////////////// Functions for Figures ////////////////////////////////////////
 ///////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -1591,7 +2509,10 @@ pub fn transform_coverage_to_heatmap(
     let filtered_data: Vec<&CoverageData> = if virus.to_lowercase() == "sc2-spike" {
         coverage_data
             .iter()
-            .filter(|row| row.position > position_1 && row.position < position_2)
+            .filter(|row| {
+                row.region_position()
+                    .is_some_and(|position| position > position_1 && position < position_2)
+            })
             .collect()
     } else {
         coverage_data.iter().collect()
@@ -1613,19 +2534,12 @@ pub fn transform_coverage_to_heatmap(
         median_data.push((sample_id, reference_name, median_depth));
     }
 
-    // Split Reference_Name into Subtype, Segment, and Group
+    // Reference_Name to the segment it is plotted as
     let mut transformed_data: Vec<TransformedData> = Vec::new();
     for (sample_id, reference_name, coverage_depth) in median_data {
-        let parts: Vec<&str> = reference_name.split('_').collect();
-        let segment = if parts.len() >= 2 {
-            parts[1].to_string()
-        } else {
-            reference_name.clone()
-        };
-
         transformed_data.push(TransformedData {
             sample_id,
-            ref_id: segment,
+            ref_id: reference_segment(&reference_name, virus),
             coverage_depth,
         });
     }
@@ -1,6 +1,14 @@
 use flate2::read::MultiGzDecoder;
-use std::{fs::File, io::Read, path::Path};
-use zoe::{define_whichever, prelude::FastQReader};
+use serde::Deserialize;
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+use zoe::{
+    define_whichever,
+    prelude::{FastQReader, Len},
+};
 
 define_whichever! {
     #[doc="An enum for the different acceptable input types"]
@@ -42,3 +50,172 @@ pub(crate) fn open_fastq_file<P: AsRef<Path>>(
         Ok(FastQReader::from_readable(ReadFileZip::File(file))?)
     }
 }
+
+/// Sequencing platform inferred from the reads themselves
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Platform {
+    Illumina,
+    ONT,
+}
+
+/// What could be inferred about a FASTQ from its headers and read lengths
+#[derive(Debug)]
+pub(crate) struct ReadLayout {
+    /// `None` if the reads gave no clear signal
+    pub platform: Option<Platform>,
+    /// Whether a mate file (`_R2` for an `_R1` file) exists next to this one
+    pub paired: bool,
+    pub median_len: usize,
+}
+
+// Casava 1.8+ (`@M00123:45:000000000-ABCDE:1:1101:15589:1331 1:N:0:1`) or older
+// (`@HWUSI-EAS100R:6:73:941:1973#0/1`) Illumina read names
+fn is_illumina_header(header: &str) -> bool {
+    let mut tokens = header.trim_start_matches('@').split_whitespace();
+    let name = tokens.next().unwrap_or("");
+    let casava = name.split(':').count() >= 7
+        || tokens.next().is_some_and(|comment| {
+            let mut fields = comment.split(':');
+            matches!(fields.next(), Some("1" | "2")) && matches!(fields.next(), Some("Y" | "N"))
+        });
+    let legacy = name.split(':').count() >= 5 && (name.ends_with("/1") || name.ends_with("/2"));
+    casava || legacy
+}
+
+// ONT read names are UUIDs, usually followed by `runid=`, `ch=` and `start_time=` fields
+fn is_ont_header(header: &str) -> bool {
+    let header = header.trim_start_matches('@');
+    if header.contains("runid=") || header.contains("start_time=") {
+        return true;
+    }
+    let name = header.split_whitespace().next().unwrap_or("");
+    let groups: Vec<usize> = name.split('-').map(str::len).collect();
+    groups == [8, 4, 4, 4, 12] && name.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
+/// Infers the platform and read layout from the first reads of a FASTQ. Headers are used when
+/// they are recognizable, otherwise long reads are taken to mean ONT.
+pub(crate) fn detect_read_layout<P: AsRef<Path>>(path: P) -> std::io::Result<ReadLayout> {
+    const SAMPLE_SIZE: usize = 1000;
+    const LONG_READ_LEN: usize = 500;
+
+    let fastq_reader = open_fastq_file(&path)?;
+
+    let mut illumina_votes = 0;
+    let mut ont_votes = 0;
+    let mut lengths = Vec::with_capacity(SAMPLE_SIZE);
+    for result in fastq_reader.take(SAMPLE_SIZE) {
+        let record = result?;
+        if is_illumina_header(&record.header) {
+            illumina_votes += 1;
+        } else if is_ont_header(&record.header) {
+            ont_votes += 1;
+        }
+        lengths.push(record.sequence.len());
+    }
+    lengths.sort_unstable();
+    let median_len = lengths.get(lengths.len() / 2).copied().unwrap_or(0);
+    let max_len = lengths.last().copied().unwrap_or(0);
+
+    let platform = if lengths.is_empty() {
+        None
+    } else if illumina_votes > ont_votes {
+        Some(Platform::Illumina)
+    } else if ont_votes > illumina_votes || max_len > LONG_READ_LEN {
+        Some(Platform::ONT)
+    } else {
+        Some(Platform::Illumina)
+    };
+
+    let paired = mate_path(&path).is_some();
+
+    Ok(ReadLayout {
+        platform,
+        paired,
+        median_len,
+    })
+}
+
+/// The `_R2` mate of an `_R1` FASTQ, if it exists next to it
+pub(crate) fn mate_path<P: AsRef<Path>>(path: P) -> Option<PathBuf> {
+    let path = path.as_ref();
+    let name = path.file_name()?.to_str()?;
+    if !name.contains("_R1") {
+        return None;
+    }
+    let mate = path.with_file_name(name.replacen("_R1", "_R2", 1));
+    mate.exists().then_some(mate)
+}
+
+/// Counts the records in a (possibly gzipped) FASTQ
+pub(crate) fn count_reads<P: AsRef<Path>>(path: P) -> std::io::Result<usize> {
+    let mut count = 0;
+    for result in open_fastq_file(path)? {
+        result?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// What was found comparing an `_R1` FASTQ with its `_R2` mate
+#[derive(Debug)]
+pub(crate) struct MateCheck {
+    pub r1_reads: usize,
+    pub r2_reads: usize,
+    /// Pairs whose read names were compared
+    pub names_checked: usize,
+    pub name_mismatches: usize,
+    /// The first pair of read names that didn't match
+    pub first_mismatch: Option<(String, String)>,
+}
+
+impl MateCheck {
+    pub fn is_consistent(&self) -> bool {
+        self.r1_reads == self.r2_reads && self.name_mismatches == 0
+    }
+}
+
+// Read name without the leading `@`, the comment, or a legacy `/1` `/2` mate suffix
+fn read_name(header: &str) -> &str {
+    let name = header
+        .trim_start_matches('@')
+        .split_whitespace()
+        .next()
+        .unwrap_or("");
+    name.strip_suffix("/1")
+        .or_else(|| name.strip_suffix("/2"))
+        .unwrap_or(name)
+}
+
+/// Counts the reads of both mates and compares the read names of the first `n_names` pairs.
+/// A truncated or corrupt file fails with the read error.
+pub(crate) fn check_mates<P: AsRef<Path>, Q: AsRef<Path>>(
+    r1: P,
+    r2: Q,
+    n_names: usize,
+) -> std::io::Result<MateCheck> {
+    let mut names_checked = 0;
+    let mut name_mismatches = 0;
+    let mut first_mismatch = None;
+    for (read1, read2) in open_fastq_file(&r1)?
+        .zip(open_fastq_file(&r2)?)
+        .take(n_names)
+    {
+        let (read1, read2) = (read1?, read2?);
+        let (name1, name2) = (read_name(&read1.header), read_name(&read2.header));
+        if name1 != name2 {
+            name_mismatches += 1;
+            first_mismatch.get_or_insert_with(|| (name1.to_string(), name2.to_string()));
+        }
+        names_checked += 1;
+    }
+
+    Ok(MateCheck {
+        r1_reads: count_reads(r1)?,
+        r2_reads: count_reads(r2)?,
+        names_checked,
+        name_mismatches,
+        first_mismatch,
+    })
+}
@@ -0,0 +1,166 @@
+/// Three letter code of an amino acid, `Ter` for a stop and `Xaa` for an unknown amino acid
+#[must_use]
+pub fn three_letter(aa: char) -> Option<&'static str> {
+    let code = match aa.to_ascii_uppercase() {
+        'A' => "Ala",
+        'R' => "Arg",
+        'N' => "Asn",
+        'D' => "Asp",
+        'C' => "Cys",
+        'Q' => "Gln",
+        'E' => "Glu",
+        'G' => "Gly",
+        'H' => "His",
+        'I' => "Ile",
+        'L' => "Leu",
+        'K' => "Lys",
+        'M' => "Met",
+        'F' => "Phe",
+        'P' => "Pro",
+        'S' => "Ser",
+        'T' => "Thr",
+        'W' => "Trp",
+        'Y' => "Tyr",
+        'V' => "Val",
+        'U' => "Sec",
+        'O' => "Pyl",
+        '*' => "Ter",
+        'X' => "Xaa",
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// HGVS protein notation of an amino acid change at a 1-based position: `p.Glu119Val`,
+/// `p.Glu119del` for a gap and `p.Glu119=` when unchanged. Partial and missing amino acids
+/// (`~`, `.`) have no notation
+#[must_use]
+pub fn protein_change(ref_aa: char, position: usize, alt_aa: char) -> Option<String> {
+    let reference = three_letter(ref_aa)?;
+    let alternate = match alt_aa {
+        '-' => "del",
+        alt if alt.eq_ignore_ascii_case(&ref_aa) => "=",
+        alt => three_letter(alt)?,
+    };
+    Some(format!("p.{reference}{position}{alternate}"))
+}
+
+/// HGVS coding notation of a codon change, from the 1-based CDS position of the codon's first
+/// base: `c.355G>A` for one base, `c.355_356delinsTT` for several and `c.355_357del` for a
+/// gapped codon. Codons with a gap in the reference or without a change have no notation
+#[must_use]
+pub fn coding_change(codon_start: usize, ref_codon: &[u8], alt_codon: &[u8]) -> Option<String> {
+    let differs = |i: &usize| !ref_codon[*i].eq_ignore_ascii_case(&alt_codon[*i]);
+    let length = ref_codon.len().min(alt_codon.len());
+    let first = (0..length).find(differs)?;
+    let last = (0..length).rev().find(differs)?;
+
+    let reference = &ref_codon[first..=last];
+    if reference.iter().any(|&base| matches!(base, b'-' | b'.')) {
+        return None;
+    }
+    let alternate: String = alt_codon[first..=last]
+        .iter()
+        .filter(|&&base| !matches!(base, b'-' | b'.'))
+        .map(|&base| char::from(base.to_ascii_uppercase()))
+        .collect();
+    let (start, end) = (codon_start + first, codon_start + last);
+    let range = if start == end {
+        start.to_string()
+    } else {
+        format!("{start}_{end}")
+    };
+
+    Some(match alternate.len() {
+        0 => format!("c.{range}del"),
+        1 if start == end => format!(
+            "c.{start}{}>{alternate}",
+            char::from(reference[0].to_ascii_uppercase())
+        ),
+        _ => format!("c.{range}delins{alternate}"),
+    })
+}
+
+/// HGVS protein and coding notations of every amino acid difference between a sample and its
+/// reference, aligned in the reference's coordinates, each comma separated. Codons are read
+/// from the aligned CDS, so the coding notation is left out where it is too short
+#[must_use]
+pub fn variant_notations(
+    ref_aa_aln: &str,
+    sample_aa_aln: &str,
+    ref_cds_aln: &str,
+    sample_cds_aln: &str,
+) -> (String, String) {
+    let (ref_cds, sample_cds) = (ref_cds_aln.as_bytes(), sample_cds_aln.as_bytes());
+    let mut protein = Vec::new();
+    let mut coding = Vec::new();
+    for (index, (sample_aa, ref_aa)) in sample_aa_aln.chars().zip(ref_aa_aln.chars()).enumerate() {
+        if sample_aa == ref_aa {
+            continue;
+        }
+        protein.extend(protein_change(ref_aa, index + 1, sample_aa));
+        let codon = index * 3..index * 3 + 3;
+        if let (Some(ref_codon), Some(sample_codon)) =
+            (ref_cds.get(codon.clone()), sample_cds.get(codon))
+        {
+            coding.extend(coding_change(index * 3 + 1, ref_codon, sample_codon));
+        }
+    }
+    (protein.join(","), coding.join(","))
+}
+
+/// HGVS protein notation of a deletion of the amino acids from `first` to `last`, each a
+/// reference amino acid and its 1-based position: `p.Glu119del` or `p.Glu119_Lys120del`
+#[must_use]
+pub fn protein_deletion(first: (char, usize), last: (char, usize)) -> Option<String> {
+    let start = format!("{}{}", three_letter(first.0)?, first.1);
+    if first.1 == last.1 {
+        return Some(format!("p.{start}del"));
+    }
+    Some(format!("p.{start}_{}{}del", three_letter(last.0)?, last.1))
+}
+
+/// HGVS protein notation of amino acids inserted between the reference amino acids `before`
+/// and `after`: `p.Lys120_Gly121insAlaSer`
+#[must_use]
+pub fn protein_insertion(
+    before: (char, usize),
+    after: (char, usize),
+    inserted: &str,
+) -> Option<String> {
+    let inserted = inserted
+        .chars()
+        .map(three_letter)
+        .collect::<Option<String>>()?;
+    Some(format!(
+        "p.{}{}_{}{}ins{inserted}",
+        three_letter(before.0)?,
+        before.1,
+        three_letter(after.0)?,
+        after.1
+    ))
+}
+
+/// HGVS protein notation of a frameshift starting at a reference amino acid: `p.Glu119fs`
+#[must_use]
+pub fn protein_frameshift(first: (char, usize)) -> Option<String> {
+    Some(format!("p.{}{}fs", three_letter(first.0)?, first.1))
+}
+
+/// HGVS coding notation of the deletion of the 1-based CDS bases `start` to `end`:
+/// `c.355del` or `c.355_357del`
+#[must_use]
+pub fn coding_deletion(start: usize, end: usize) -> String {
+    if start == end {
+        format!("c.{start}del")
+    } else {
+        format!("c.{start}_{end}del")
+    }
+}
+
+/// HGVS coding notation of bases inserted after the 1-based CDS base `after`:
+/// `c.357_358insAAA`
+#[must_use]
+pub fn coding_insertion(after: usize, bases: &str) -> String {
+    format!("c.{after}_{}ins{}", after + 1, bases.to_ascii_uppercase())
+}
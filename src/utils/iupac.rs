@@ -0,0 +1,23 @@
+/// Bases an IUPAC nucleotide code stands for, `U` being `T` and `N` any base. Gaps and other
+/// characters stand for none
+#[must_use]
+pub fn iupac_bases(base: u8) -> &'static [u8] {
+    match base.to_ascii_uppercase() {
+        b'A' => b"A",
+        b'C' => b"C",
+        b'G' => b"G",
+        b'T' | b'U' => b"T",
+        b'R' => b"AG",
+        b'Y' => b"CT",
+        b'S' => b"CG",
+        b'W' => b"AT",
+        b'K' => b"GT",
+        b'M' => b"AC",
+        b'B' => b"CGT",
+        b'D' => b"AGT",
+        b'H' => b"ACT",
+        b'V' => b"ACG",
+        b'N' => b"ACGT",
+        _ => b"",
+    }
+}
@@ -0,0 +1,8 @@
+/// Escapes text for HTML and SVG, in element content and in quoted attribute values
+#[must_use]
+pub fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -1,3 +1,18 @@
+pub mod adapter_screen;
 pub mod alignment;
+pub mod bam_read;
+pub mod cancel;
+pub mod catalogs;
+pub mod completeness;
+pub mod coordinates;
+pub mod coverage_depths;
+pub mod dais_tables;
 pub mod data_processing;
 pub mod fastq_read;
+pub mod hgvs;
+pub mod iupac;
+pub mod markup;
+pub mod output;
+pub mod read_duplication;
+pub mod rng;
+pub mod units;
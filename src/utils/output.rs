@@ -0,0 +1,118 @@
+// Keeps stdout for data. Subcommands write their tables and JSON through `output_writer`,
+// which only falls back to stdout when no output file is given, and report progress with
+// `status!` on stderr, so piped output is never interleaved with messages. `--quiet` silences
+// the progress messages too; warnings and errors are always printed to stderr.
+
+use either::Either;
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, IsTerminal, Stdout, Write, stderr, stdout},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Set once from the `--quiet` flag in `main`
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+#[must_use]
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Prints a progress message to stderr unless `--quiet` was given. Takes the same arguments
+/// as `eprintln!`.
+#[macro_export]
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !$crate::utils::output::is_quiet() {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Buffered writer to `path`, truncating it, or to stdout when no path is given
+pub fn output_writer(path: Option<&Path>) -> std::io::Result<BufWriter<Either<File, Stdout>>> {
+    if let Some(path) = path {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .map_err(|e| {
+                std::io::Error::new(
+                    e.kind(),
+                    format!("Could not create file '{}': {e}", path.display()),
+                )
+            })?;
+        Ok(BufWriter::new(Either::Left(file)))
+    } else {
+        Ok(BufWriter::new(Either::Right(stdout())))
+    }
+}
+
+/// Progress of a long step on stderr: a bar redrawn in place on a terminal, otherwise a line
+/// every 10%. Silent with `--quiet`
+pub struct Progress {
+    label: &'static str,
+    total: usize,
+    done: usize,
+    shown_percent: Option<usize>,
+    terminal: bool,
+}
+
+impl Progress {
+    const BAR_WIDTH: usize = 30;
+
+    #[must_use]
+    pub fn new(label: &'static str, total: usize) -> Self {
+        Progress {
+            label,
+            total,
+            done: 0,
+            shown_percent: None,
+            terminal: stderr().is_terminal(),
+        }
+    }
+
+    pub fn add(&mut self, count: usize) {
+        self.done = (self.done + count).min(self.total);
+        if is_quiet() {
+            return;
+        }
+        let percent = (self.done * 100).checked_div(self.total).unwrap_or(100);
+        if self.terminal {
+            if self.shown_percent != Some(percent) {
+                let filled = Self::BAR_WIDTH * percent / 100;
+                eprint!(
+                    "\r[{}{}] {percent:>3}% ({} of {} {})",
+                    "#".repeat(filled),
+                    "-".repeat(Self::BAR_WIDTH - filled),
+                    self.done,
+                    self.total,
+                    self.label
+                );
+                let _ = stderr().flush();
+            }
+        } else if self
+            .shown_percent
+            .is_none_or(|shown| percent / 10 > shown / 10)
+        {
+            eprintln!(
+                "{percent}% ({} of {} {})",
+                self.done, self.total, self.label
+            );
+        }
+        self.shown_percent = Some(percent);
+    }
+
+    /// Ends the bar's line
+    pub fn finish(&self) {
+        if self.terminal && self.shown_percent.is_some() && !is_quiet() {
+            eprintln!();
+        }
+    }
+}
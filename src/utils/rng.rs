@@ -0,0 +1,42 @@
+// Seeded random number generation for the di-stats bootstrap, so a rerun with the same
+// `--seed` gives bit-identical outputs.
+
+/// Seed used when the user does not give one. Runs are reproducible by default.
+pub const DEFAULT_SEED: u64 = 20_250_101;
+
+/// `SplitMix64` generator. Small, fast and fully determined by its seed, which is all the
+/// bootstrap resampling needs. Not suitable for anything security related.
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        SeededRng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `[0, n)`. Returns 0 when `n` is 0.
+    pub fn below(&mut self, n: u64) -> u64 {
+        if n == 0 {
+            return 0;
+        }
+        // Rejection sampling to avoid modulo bias
+        let zone = u64::MAX - (u64::MAX % n);
+        loop {
+            let x = self.next_u64();
+            if x < zone {
+                return x % n;
+            }
+        }
+    }
+}
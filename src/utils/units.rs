@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    num::{ParseFloatError, ParseIntError},
+    str::FromStr,
+};
+
+/// Read depth at a position, or the median of them across a reference. Serialized as a bare
+/// integer
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Depth(pub i32);
+
+/// Percent, 0 to 100. Serialized as a bare number
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Percent(pub f64);
+
+/// Fraction of reads, 0 to 1. Serialized as a bare number
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Frequency(pub f64);
+
+impl Frequency {
+    /// Minor variants at or over this frequency are counted against QC
+    pub const MINOR_VARIANT: Frequency = Frequency(0.05);
+
+    #[must_use]
+    pub fn as_percent(self) -> Percent {
+        Percent(self.0 * 100.0)
+    }
+}
+
+impl Percent {
+    /// Rounded to two decimals, as percents are reported
+    #[must_use]
+    pub fn rounded(self) -> Percent {
+        Percent((self.0 * 100.0).round() / 100.0)
+    }
+}
+
+// Display passes the formatter through, so `{:.2}` and friends work as on the bare number
+impl fmt::Display for Depth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for Percent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for Frequency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl From<Depth> for i32 {
+    fn from(depth: Depth) -> Self {
+        depth.0
+    }
+}
+
+impl From<Depth> for f64 {
+    fn from(depth: Depth) -> Self {
+        f64::from(depth.0)
+    }
+}
+
+impl From<Percent> for f64 {
+    fn from(percent: Percent) -> Self {
+        percent.0
+    }
+}
+
+impl From<Frequency> for f64 {
+    fn from(frequency: Frequency) -> Self {
+        frequency.0
+    }
+}
+
+// Parsed from bare numbers, so thresholds can be given on the command line
+impl FromStr for Depth {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim().parse().map(Depth)
+    }
+}
+
+impl FromStr for Percent {
+    type Err = ParseFloatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim().parse().map(Percent)
+    }
+}
@@ -0,0 +1,105 @@
+//! Subcommands writing to stdout must only write their data there, so it can be piped. Progress
+//! and status messages go to stderr, and `--quiet` silences them
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+const FASTA: &str = ">s1\nACGTACGTAC\n>s2\nACGTTCGTAC\n>s3\nACGAACGTRC\n";
+const FASTQ: &str = "@r1\nACGTACGTAC\n+\nIIIIIIIIII\n@r2\nACGTTCGTAC\n+\nIIIIIIIIII\n";
+
+// A scratch directory for one test, emptied first
+fn scratch_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mira-oxide-{test}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("Could not create the scratch directory");
+    dir
+}
+
+fn write_input(dir: &Path, name: &str, contents: &str) -> String {
+    let path = dir.join(name);
+    fs::write(&path, contents).expect("Could not write the test input");
+    path.to_string_lossy().into_owned()
+}
+
+fn mira_oxide(args: &[&str]) -> Output {
+    let output = Command::new(env!("CARGO_BIN_EXE_mira-oxide"))
+        .args(args)
+        .output()
+        .expect("Could not run mira-oxide");
+    assert!(
+        output.status.success(),
+        "mira-oxide {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+// Every stdout line is a delimited row with as many fields as the header
+fn assert_only_rows(stdout: &[u8], expected_rows: usize) {
+    let stdout = String::from_utf8(stdout.to_vec()).expect("stdout is not UTF-8");
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), expected_rows, "unexpected stdout:\n{stdout}");
+    let fields = lines[0].split(',').count();
+    for line in &lines {
+        assert_eq!(line.split(',').count(), fields, "not a data row: {line}");
+    }
+}
+
+#[test]
+fn hamming_writes_only_the_matrix_to_stdout() {
+    let dir = scratch_dir("hamming");
+    let fasta = write_input(&dir, "seqs.fa", FASTA);
+
+    let output = mira_oxide(&["hamming", "-i", &fasta]);
+    assert_only_rows(&output.stdout, 4);
+    assert!(!output.stderr.is_empty(), "progress should be on stderr");
+
+    let quiet = mira_oxide(&["--quiet", "hamming", "-i", &fasta]);
+    assert_eq!(quiet.stdout, output.stdout);
+    assert!(quiet.stderr.is_empty(), "--quiet should silence progress");
+}
+
+#[test]
+fn nt_diffs_writes_only_the_differences_to_stdout() {
+    let dir = scratch_dir("nt-diffs");
+    let fasta = write_input(&dir, "seqs.fa", FASTA);
+
+    let output = mira_oxide(&["nt-diffs", "-i", &fasta]);
+    assert_only_rows(&output.stdout, 7);
+}
+
+#[test]
+fn fastq_stats_writes_only_the_stats_to_stdout() {
+    let dir = scratch_dir("fastq-stats");
+    let fastq = write_input(&dir, "reads.fq", FASTQ);
+
+    let output = mira_oxide(&["fastq-stats", "-i", &fastq]);
+    assert_only_rows(&output.stdout, 2);
+    assert!(!output.stderr.is_empty(), "progress should be on stderr");
+
+    let json = mira_oxide(&["fastq-stats", "-i", &fastq, "--output-format", "json"]);
+    let parsed: serde_json::Value =
+        serde_json::from_slice(&json.stdout).expect("stdout is not only JSON");
+    assert_eq!(parsed[0]["reads"], 2);
+}
+
+#[test]
+fn check_mira_version_writes_nothing_to_stdout() {
+    let dir = scratch_dir("check-mira-version");
+    write_input(&dir, "DESCRIPTION", "Package: MIRA-NF\nVersion: 2.0.0\n");
+    let available = write_input(&dir, "available", "Package: MIRA-NF\nVersion: 2.1.0\n");
+
+    let output = mira_oxide(&[
+        "check-mira-version",
+        "-g",
+        &available,
+        "-l",
+        &dir.to_string_lossy(),
+    ]);
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("2.1.0"));
+}